@@ -0,0 +1,54 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use organize_core::{config::Config, file::File, synthetic::Shape};
+
+fn setup(shape: Shape) -> (tempfile::TempDir, Config, Vec<std::path::PathBuf>) {
+	let dir = tempfile::tempdir().unwrap();
+	let tree = dir.path().join("tree");
+	let files = shape.generate(&tree).unwrap();
+	let path = dir.path().join("organize.toml");
+	std::fs::write(&path, organize_core::synthetic::config(&tree, dir.path().join("out"))).unwrap();
+	let config = Config::parse(path).unwrap();
+	(dir, config, files)
+}
+
+fn scan(c: &mut Criterion) {
+	let mut group = c.benchmark_group("scan");
+	for shape in [Shape::Wide, Shape::Deep, Shape::ManySmall] {
+		let (_dir, config, files) = setup(shape);
+		group.throughput(Throughput::Elements(files.len() as u64));
+		group.bench_with_input(BenchmarkId::from_parameter(shape), &config, |b, config| {
+			b.iter(|| {
+				config
+					.path_to_recursive
+					.iter()
+					.map(|(path, recursive)| recursive.to_walker(path).into_iter().filter_map(|e| e.ok()).count())
+					.sum::<usize>()
+			})
+		});
+	}
+	group.finish();
+}
+
+fn filter(c: &mut Criterion) {
+	let mut group = c.benchmark_group("filter");
+	for shape in [Shape::Wide, Shape::Deep, Shape::ManySmall] {
+		let (_dir, config, files) = setup(shape);
+		group.throughput(Throughput::Elements(files.len() as u64));
+		group.bench_with_input(BenchmarkId::from_parameter(shape), &config, |b, config| {
+			b.iter(|| {
+				files
+					.iter()
+					.filter(|path| {
+						!File::new(*path, config, false)
+							.get_matching_rules(&config.path_to_rules)
+							.is_empty()
+					})
+					.count()
+			})
+		});
+	}
+	group.finish();
+}
+
+criterion_group!(benches, scan, filter);
+criterion_main!(benches);
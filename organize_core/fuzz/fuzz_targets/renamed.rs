@@ -0,0 +1,21 @@
+#![no_main]
+
+use std::path::Path;
+
+use libfuzzer_sys::fuzz_target;
+use organize_core::path::renamed;
+
+// any name, valid UTF-8 or not, gets a free name next to it or none at all
+fuzz_target!(|data: (&[u8], u8)| {
+	let (name, taken) = data;
+	#[cfg(unix)]
+	let name = <std::ffi::OsStr as std::os::unix::ffi::OsStrExt>::from_bytes(name);
+	#[cfg(not(unix))]
+	let name = String::from_utf8_lossy(name).to_string();
+	let path = Path::new("/dir").join(name);
+	let is_taken = |candidate: &Path| candidate == path || (1..=taken).any(|n| candidate.to_string_lossy().contains(&format!(" ({})", n)));
+	if let Some(renamed) = renamed(&path, is_taken) {
+		assert!(!is_taken(&renamed));
+		assert_eq!(renamed.parent(), path.parent());
+	}
+});
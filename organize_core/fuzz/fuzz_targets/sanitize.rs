@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use organize_core::config::actions::sanitize::{Filesystem, Sanitize};
+
+fuzz_target!(|data: (&str, &str, u8)| {
+	let (component, replacement, flags) = data;
+	let sanitize = Sanitize {
+		filesystem: match flags % 3 {
+			0 => Filesystem::Ntfs,
+			1 => Filesystem::Exfat,
+			_ => Filesystem::Ext4,
+		},
+		replacement: replacement.to_string(),
+		transliterate: flags & 4 != 0,
+		collapse_whitespace: flags & 8 != 0,
+	};
+	let sanitized = sanitize.sanitize_component(component);
+	assert!(!matches!(sanitized.as_str(), "" | "." | ".."));
+	assert!(!sanitized.contains('/'));
+});
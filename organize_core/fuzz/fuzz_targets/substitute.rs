@@ -0,0 +1,12 @@
+#![no_main]
+
+use std::ffi::OsString;
+
+use libfuzzer_sys::fuzz_target;
+use organize_core::string::substitute;
+
+// putting every placeholder back as it was gives back the template, so nothing is expanded twice
+fuzz_target!(|template: &str| {
+	let same = substitute(template, |name| Ok(format!("{{{}}}", name).into())).unwrap();
+	assert_eq!(same, OsString::from(template));
+});
@@ -0,0 +1,309 @@
+use std::{
+	fs,
+	io::{BufReader, Read, Seek, SeekFrom},
+	path::Path,
+};
+
+use anyhow::{Context, Result};
+use id3::TagLike;
+
+use crate::variables::Variable;
+
+/// Vorbis comments larger than this are skipped, they're mostly made of cover art
+const MAX_COMMENTS: usize = 16 * 1024 * 1024;
+
+/// The tags of a song, read from the ID3v2 tag of an MP3 or the Vorbis comments of a FLAC, Ogg Vorbis or Opus file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tags {
+	pub artist: Option<String>,
+	pub album_artist: Option<String>,
+	pub album: Option<String>,
+	pub title: Option<String>,
+	pub genre: Option<String>,
+	pub year: Option<String>,
+	/// two digits, e.g. `03`
+	pub track: Option<String>,
+}
+
+impl Tags {
+	/// The variables that hold the tags
+	pub(crate) const VARIABLES: [Variable; 7] = [
+		Variable::TagsArtist,
+		Variable::TagsAlbumArtist,
+		Variable::TagsAlbum,
+		Variable::TagsTitle,
+		Variable::TagsGenre,
+		Variable::TagsYear,
+		Variable::TagsTrack,
+	];
+
+	/// The tags of the file at `path`, or `None` if it has none in a format that's understood
+	pub fn read<T: AsRef<Path>>(path: T) -> Result<Option<Self>> {
+		let path = path.as_ref();
+		let file = fs::File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+		let mut file = BufReader::new(file);
+		let mut magic = [0; 4];
+		if file.read_exact(&mut magic).is_err() {
+			return Ok(None);
+		}
+		let tags = match &magic {
+			[b'I', b'D', b'3', _] => id3::no_tag_ok(id3::Tag::read_from_path(path))
+				.with_context(|| format!("could not read the ID3 tag of {}", path.display()))?
+				.map(|tag| Self::from_id3(&tag)),
+			b"fLaC" => flac_comments(&mut file)?.map(|comments| Self::from_comments(&comments)),
+			b"OggS" => {
+				file.seek(SeekFrom::Start(0))?;
+				ogg_comments(&mut file)?.map(|comments| Self::from_comments(&comments))
+			}
+			_ => None,
+		};
+		Ok(tags.filter(|tags| *tags != Self::default()))
+	}
+
+	/// The value of one of the [`VARIABLES`](Self::VARIABLES)
+	pub fn get(&self, variable: Variable) -> Option<&str> {
+		let value = match variable {
+			Variable::TagsArtist => &self.artist,
+			Variable::TagsAlbumArtist => &self.album_artist,
+			Variable::TagsAlbum => &self.album,
+			Variable::TagsTitle => &self.title,
+			Variable::TagsGenre => &self.genre,
+			Variable::TagsYear => &self.year,
+			Variable::TagsTrack => &self.track,
+			_ => return None,
+		};
+		value.as_deref()
+	}
+
+	fn from_id3(tag: &id3::Tag) -> Self {
+		let year = tag.year().or_else(|| tag.date_recorded().map(|date| date.year));
+		Self {
+			artist: tag.artist().map(str::to_string),
+			album_artist: tag.album_artist().map(str::to_string),
+			album: tag.album().map(str::to_string),
+			title: tag.title().map(str::to_string),
+			genre: tag.genre_parsed().map(|genre| genre.to_string()),
+			year: year.map(|year| year.to_string()),
+			track: tag.track().map(|track| format!("{:02}", track)),
+		}
+		.trimmed()
+	}
+
+	/// Reads `KEY=value` comments, keys being case-insensitive. The first value of a key wins.
+	fn from_comments(comments: &[String]) -> Self {
+		let get = |keys: &[&str]| {
+			comments.iter().find_map(|comment| {
+				let (key, value) = comment.split_once('=')?;
+				keys.iter().any(|k| key.eq_ignore_ascii_case(k)).then(|| value.to_string())
+			})
+		};
+		Self {
+			artist: get(&["ARTIST"]),
+			album_artist: get(&["ALBUMARTIST", "ALBUM ARTIST"]),
+			album: get(&["ALBUM"]),
+			title: get(&["TITLE"]),
+			genre: get(&["GENRE"]),
+			// dates can be full, e.g. `2021-03-14`
+			year: get(&["DATE", "YEAR"]).map(|date| date.chars().take(4).collect()),
+			// tracks can be written as `3/12`
+			track: get(&["TRACKNUMBER"]).and_then(|track| {
+				track
+					.split('/')
+					.next()?
+					.trim()
+					.parse::<u32>()
+					.ok()
+					.map(|track| format!("{:02}", track))
+			}),
+		}
+		.trimmed()
+	}
+
+	/// Drops the tags that are only whitespace
+	fn trimmed(self) -> Self {
+		let trim = |value: Option<String>| value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty());
+		Self {
+			artist: trim(self.artist),
+			album_artist: trim(self.album_artist),
+			album: trim(self.album),
+			title: trim(self.title),
+			genre: trim(self.genre),
+			year: trim(self.year),
+			track: trim(self.track),
+		}
+	}
+}
+
+/// The Vorbis comments in the metadata blocks of a FLAC file, read after its `fLaC` marker
+fn flac_comments<R: Read>(file: &mut R) -> Result<Option<Vec<String>>> {
+	const VORBIS_COMMENT: u8 = 4;
+	loop {
+		let mut header = [0; 4];
+		if file.read_exact(&mut header).is_err() {
+			return Ok(None);
+		}
+		let last = header[0] & 0x80 != 0;
+		let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+		if header[0] & 0x7f == VORBIS_COMMENT {
+			let mut block = vec![0; len.min(MAX_COMMENTS)];
+			file.read_exact(&mut block)?;
+			return Ok(parse_comments(&block));
+		}
+		std::io::copy(&mut file.take(len as u64), &mut std::io::sink())?;
+		if last {
+			return Ok(None);
+		}
+	}
+}
+
+/// The Vorbis comments of an Ogg Vorbis or Opus file, which make up its second packet
+fn ogg_comments<R: Read>(file: &mut R) -> Result<Option<Vec<String>>> {
+	let mut packets = 0;
+	let mut packet = Vec::new();
+	loop {
+		let mut header = [0; 27];
+		if file.read_exact(&mut header).is_err() || &header[..4] != b"OggS" {
+			return Ok(None);
+		}
+		let mut lacing = vec![0; header[26] as usize];
+		file.read_exact(&mut lacing)?;
+		for len in lacing {
+			let mut segment = vec![0; len as usize];
+			file.read_exact(&mut segment)?;
+			if packets == 1 {
+				packet.extend_from_slice(&segment);
+			}
+			// a segment shorter than 255 bytes ends its packet
+			if len < 255 {
+				packets += 1;
+				if packets == 2 {
+					let comments = packet
+						.strip_prefix(b"\x03vorbis")
+						.or_else(|| packet.strip_prefix(b"OpusTags"))
+						.and_then(parse_comments);
+					return Ok(comments);
+				}
+			}
+		}
+		if packet.len() > MAX_COMMENTS {
+			return Ok(None);
+		}
+	}
+}
+
+/// Parses a Vorbis comment header: the vendor, then the comments, each prefixed with its little-endian length
+fn parse_comments(data: &[u8]) -> Option<Vec<String>> {
+	fn take<'a>(rest: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+		if rest.len() < len {
+			return None;
+		}
+		let (head, tail) = rest.split_at(len);
+		*rest = tail;
+		Some(head)
+	}
+	fn length(rest: &mut &[u8]) -> Option<usize> {
+		take(rest, 4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+	}
+
+	let mut rest = data;
+	let vendor = length(&mut rest)?;
+	take(&mut rest, vendor)?;
+	let count = length(&mut rest)?;
+	let mut comments = Vec::with_capacity(count.min(1024));
+	for _ in 0..count {
+		let len = length(&mut rest)?;
+		comments.push(String::from_utf8_lossy(take(&mut rest, len)?).to_string());
+	}
+	Some(comments)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn comments(comments: &[&str]) -> Vec<u8> {
+		let field = |data: &mut Vec<u8>, field: &str| {
+			data.extend_from_slice(&(field.len() as u32).to_le_bytes());
+			data.extend_from_slice(field.as_bytes());
+		};
+		let mut data = Vec::new();
+		field(&mut data, "vendor");
+		data.extend_from_slice(&(comments.len() as u32).to_le_bytes());
+		for comment in comments {
+			field(&mut data, comment);
+		}
+		data
+	}
+
+	fn ogg_page(packet: &[u8]) -> Vec<u8> {
+		let mut lacing = vec![255; packet.len() / 255];
+		lacing.push((packet.len() % 255) as u8);
+		let mut page = b"OggS".to_vec();
+		page.extend_from_slice(&[0; 22]);
+		page.push(lacing.len() as u8);
+		page.extend_from_slice(&lacing);
+		page.extend_from_slice(packet);
+		page
+	}
+
+	#[test]
+	fn read_flac_tags() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("song.flac");
+		let block = comments(&[
+			"artist=Miles Davis",
+			"ALBUM=Kind of Blue",
+			"TRACKNUMBER=2/5",
+			"DATE=1959-08-17",
+			"TITLE= ",
+		]);
+		let mut data = b"fLaC".to_vec();
+		data.extend_from_slice(&[0, 0, 0, 34]);
+		data.extend_from_slice(&[0; 34]);
+		data.push(0x80 | 4);
+		data.extend_from_slice(&(block.len() as u32).to_be_bytes()[1..]);
+		data.extend_from_slice(&block);
+		fs::write(&path, data).unwrap();
+		let tags = Tags::read(&path).unwrap().unwrap();
+		assert_eq!(tags.artist.as_deref(), Some("Miles Davis"));
+		assert_eq!(tags.album.as_deref(), Some("Kind of Blue"));
+		assert_eq!(tags.track.as_deref(), Some("02"));
+		assert_eq!(tags.year.as_deref(), Some("1959"));
+		assert_eq!(tags.title, None);
+	}
+
+	#[test]
+	fn read_ogg_tags() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("song.ogg");
+		let mut packet = b"\x03vorbis".to_vec();
+		// long enough to span several segments
+		let title = format!("TITLE={}", "a".repeat(300));
+		packet.extend_from_slice(&comments(&["GENRE=Jazz", &title]));
+		let mut data = ogg_page(b"\x01vorbis identification");
+		data.extend_from_slice(&ogg_page(&packet));
+		fs::write(&path, data).unwrap();
+		let tags = Tags::read(&path).unwrap().unwrap();
+		assert_eq!(tags.genre.as_deref(), Some("Jazz"));
+		assert_eq!(tags.title.map(|title| title.len()), Some(300));
+	}
+
+	#[test]
+	fn read_id3_tags() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("song.mp3");
+		fs::write(&path, "").unwrap();
+		let mut tag = id3::Tag::new();
+		tag.set_artist("Nina Simone");
+		tag.set_track(7);
+		tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+		let tags = Tags::read(&path).unwrap().unwrap();
+		assert_eq!(tags.get(Variable::TagsArtist), Some("Nina Simone"));
+		assert_eq!(tags.get(Variable::TagsTrack), Some("07"));
+		assert_eq!(tags.get(Variable::TagsAlbum), None);
+
+		let other = dir.path().join("notes.txt");
+		fs::write(&other, "not a song").unwrap();
+		assert_eq!(Tags::read(&other).unwrap(), None);
+	}
+}
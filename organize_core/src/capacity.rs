@@ -0,0 +1,185 @@
+//! Checks, before a batch of files is copied or moved, that every destination filesystem has room for all of them,
+//! so that a run doesn't fill a disk halfway through.
+
+use std::{
+	collections::{BTreeMap, HashSet},
+	fmt::{self, Display, Formatter},
+	path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+use crate::{
+	config::Config,
+	events::{self, SkipReason},
+	file::File,
+	mount::Disks,
+};
+
+/// Where a copy or move action would send a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transfer {
+	pub to: PathBuf,
+	/// free space (in bytes) that must remain on the destination filesystem afterwards
+	pub margin: u64,
+	/// whether the file leaves its location, which takes up no space within the same filesystem
+	pub moving: bool,
+}
+
+/// A filesystem that doesn't have room for the files a batch would send to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortage {
+	pub mount_point: PathBuf,
+	/// the size of the files, plus the largest margin their actions ask for
+	pub needed: u64,
+	pub available: u64,
+	pub files: Vec<PathBuf>,
+}
+
+impl Display for Shortage {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"not enough space on {} for {} file(s): {} bytes needed, {} available",
+			self.mount_point.display(),
+			self.files.len(),
+			self.needed,
+			self.available
+		)
+	}
+}
+
+#[derive(Default)]
+struct Planned {
+	bytes: u64,
+	margin: u64,
+	files: Vec<PathBuf>,
+}
+
+/// The filesystems that can't hold what the rules of `config` would copy or move there from `paths`.
+/// The disks are only listed once, and nothing is done to the files.
+pub fn check(config: &Config, paths: &[PathBuf], is_watching: bool) -> Vec<Shortage> {
+	if !config.transfers_files() {
+		return Vec::new();
+	}
+	let disks = Disks::query();
+	let mut planned: BTreeMap<PathBuf, Planned> = BTreeMap::new();
+	for path in paths.iter().filter(|path| path.exists()) {
+		for transfer in File::new(path, config, is_watching).transfers(&config.path_to_rules) {
+			let dest = match disks.of(&transfer.to) {
+				Some(dest) => dest,
+				None => continue,
+			};
+			if transfer.moving && disks.of(path).is_some_and(|src| src.mount_point == dest.mount_point) {
+				continue;
+			}
+			let entry = planned.entry(dest.mount_point.clone()).or_default();
+			entry.bytes += size(path);
+			entry.margin = entry.margin.max(transfer.margin);
+			entry.files.push(path.clone());
+		}
+	}
+	planned
+		.into_iter()
+		.filter_map(|(mount_point, planned)| {
+			let available = disks.of(&mount_point)?.free_space;
+			let needed = planned.bytes.saturating_add(planned.margin);
+			(needed > available).then_some(Shortage {
+				mount_point,
+				needed,
+				available,
+				files: planned.files,
+			})
+		})
+		.collect()
+}
+
+/// Logs `shortages` and skips the files they hold back, returning them
+pub fn refuse(shortages: &[Shortage]) -> HashSet<PathBuf> {
+	let mut refused = HashSet::new();
+	for shortage in shortages {
+		log::error!("{}, leaving them in place", shortage);
+		for file in shortage.files.iter() {
+			if refused.insert(file.clone()) {
+				events::skip(file, SkipReason::Error);
+			}
+		}
+	}
+	refused
+}
+
+/// The size of a file, or of everything inside a directory
+fn size(path: &Path) -> u64 {
+	match path.is_dir() {
+		true => WalkDir::new(path)
+			.into_iter()
+			.filter_map(|entry| entry.ok()?.metadata().ok())
+			.filter(|metadata| metadata.is_file())
+			.map(|metadata| metadata.len())
+			.sum(),
+		false => std::fs::metadata(path).map(|metadata| metadata.len()).unwrap_or_default(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	fn config(dir: &Path, action: &str) -> Config {
+		let location = dir.join("in");
+		fs::create_dir_all(&location).unwrap();
+		fs::write(location.join("a.pdf"), "a").unwrap();
+		fs::write(location.join("b.pdf"), "bb").unwrap();
+		let config = format!("[[rules]]\nfolders = [{:?}]\nfilters = []\nactions = [{}]", location, action);
+		let path = dir.join("config.toml");
+		fs::write(&path, config).unwrap();
+		Config::parse(&path).unwrap()
+	}
+
+	fn paths(dir: &Path) -> Vec<PathBuf> {
+		let location = dir.join("in").canonicalize().unwrap();
+		vec![location.join("a.pdf"), location.join("b.pdf")]
+	}
+
+	#[test]
+	fn report_the_whole_batch() {
+		let dir = tempfile::tempdir().unwrap();
+		let margin = 1u64 << 60;
+		let action = format!(
+			"{{ type = \"copy\", to = \"{}/out/\", free_space_margin = {} }}",
+			dir.path().display(),
+			margin
+		);
+		let config = config(dir.path(), &action);
+		let shortages = check(&config, &paths(dir.path()), false);
+		assert_eq!(shortages.len(), 1);
+		assert_eq!(shortages[0].needed, margin + 3);
+		assert_eq!(shortages[0].files, paths(dir.path()));
+		assert_eq!(refuse(&shortages), paths(dir.path()).into_iter().collect());
+		assert!(!dir.path().join("out").exists());
+	}
+
+	#[test]
+	fn no_space_needed() {
+		let dir = tempfile::tempdir().unwrap();
+		for action in [
+			format!("{{ type = \"copy\", to = \"{}/out/\" }}", dir.path().display()),
+			// moves within a filesystem and dry runs don't take up any space
+			format!(
+				"{{ type = \"move\", to = \"{}/out/\", free_space_margin = {} }}",
+				dir.path().display(),
+				1u64 << 60
+			),
+			format!(
+				"{{ type = \"copy\", to = \"{}/out/\", free_space_margin = {}, dry_run = true }}",
+				dir.path().display(),
+				1u64 << 60
+			),
+		] {
+			let config = config(dir.path(), &action);
+			assert_eq!(check(&config, &paths(dir.path()), false), vec![], "{}", action);
+		}
+	}
+}
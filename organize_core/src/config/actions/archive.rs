@@ -0,0 +1,374 @@
+use std::{
+	collections::HashSet,
+	fs::{self, File, OpenOptions},
+	io::{self, Seek, SeekFrom},
+	path::{Path, PathBuf},
+	sync::Mutex,
+};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use chrono::{DateTime, Datelike, Local, Timelike};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use strum_macros::Display;
+use tempfile::NamedTempFile;
+
+use crate::{
+	config::actions::{Act, ActionType, AsAction},
+	events::{self, Event, SkipReason},
+	messages::{self, Message},
+	path::{sandbox, Expand, ValidateDestination},
+	string::{deserialize_placeholder_string, ExpandPlaceholder},
+};
+
+lazy_static! {
+	/// files are processed in parallel, but an archive can only be appended to by one of them at a time
+	static ref WRITING: Mutex<()> = Mutex::new(());
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+	Zip,
+	Tar,
+}
+
+impl Format {
+	fn of(archive: &Path) -> Result<Self> {
+		let extension = archive.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+		match extension.as_deref() {
+			Some("zip") => Ok(Self::Zip),
+			Some("tar") => Ok(Self::Tar),
+			_ => bail!(
+				"{} is neither a .zip nor a .tar, the only archives that can be appended to",
+				archive.display()
+			),
+		}
+	}
+}
+
+/// What to do when the archive already has an entry with the same name
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum EntryConflict {
+	/// leave the file out, so that running the same rule again doesn't add it twice
+	#[default]
+	Skip,
+	/// add it as `name (1).ext`
+	Rename,
+}
+
+/// Appends the file to a zip or tar archive, which is created if it doesn't exist yet,
+/// e.g. `~/logs/{modified.year}-{modified.month}.zip` to build monthly bundles over several runs.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Archive {
+	#[serde(deserialize_with = "deserialize_placeholder_string")]
+	to: String,
+	/// the path of the file inside the archive
+	#[serde(default = "Archive::default_entry", deserialize_with = "deserialize_placeholder_string")]
+	entry: String,
+	#[serde(default)]
+	if_exists: EntryConflict,
+	/// remove the file once it's in the archive
+	#[serde(default)]
+	remove: bool,
+}
+
+impl Archive {
+	fn default_entry() -> String {
+		"{filename}".into()
+	}
+
+	pub(crate) fn template(&self) -> &str {
+		&self.to
+	}
+
+	pub(crate) fn removes(&self) -> bool {
+		self.remove
+	}
+
+	fn archive(&self, path: &Path) -> Result<PathBuf> {
+		let archive = PathBuf::from(self.to.as_str().expand_placeholders(path)?)
+			.expand_user()?
+			.expand_vars()?;
+		archive.validate_destination(&self.to)?;
+		sandbox::confine(archive)
+	}
+
+	/// The name of the entry, with forward slashes and without a leading one, as archives expect
+	fn entry(&self, path: &Path) -> Result<String> {
+		let entry = self
+			.entry
+			.as_str()
+			.expand_placeholders(path)?
+			.to_string_lossy()
+			.replace('\\', "/");
+		let entry = entry.trim_start_matches('/');
+		if entry.is_empty() || entry.split('/').any(|component| component == "..") {
+			bail!("{} is not a valid entry name for {}", entry, path.display());
+		}
+		Ok(entry.to_string())
+	}
+
+	/// The name the file gets in an archive that already has `existing`, or `None` if it's left out
+	fn resolve(&self, entry: String, existing: &HashSet<String>) -> Option<String> {
+		if !existing.contains(&entry) {
+			return Some(entry);
+		}
+		match self.if_exists {
+			EntryConflict::Skip => None,
+			EntryConflict::Rename => {
+				let (dir, name) = entry.rsplit_once('/').map_or(("", entry.as_str()), |(dir, name)| (dir, name));
+				let (stem, extension) = match name.rsplit_once('.') {
+					Some((stem, extension)) if !stem.is_empty() => (stem, format!(".{}", extension)),
+					_ => (name, String::new()),
+				};
+				let prefix = if dir.is_empty() { String::new() } else { format!("{}/", dir) };
+				(1..)
+					.map(|n| format!("{}{} ({}){}", prefix, stem, n, extension))
+					.find(|candidate| !existing.contains(candidate))
+			}
+		}
+	}
+
+	/// Adds `path` to a copy of the archive written next to it, which only replaces it once the entry is complete,
+	/// so that a failure halfway through doesn't leave the entries of earlier runs unreadable
+	fn append(&self, format: Format, archive: &Path, path: &Path, entry: String) -> Result<Option<String>> {
+		let dir = archive.parent().unwrap_or_else(|| Path::new("."));
+		let staged = NamedTempFile::new_in(dir).with_context(|| format!("could not write in {}", dir.display()))?;
+		match archive.exists() {
+			true => fs::copy(archive, staged.path()).map(|_| ()),
+			// the temporary file is only readable by its owner
+			#[cfg(unix)]
+			false => fs::set_permissions(staged.path(), std::os::unix::fs::PermissionsExt::from_mode(0o644)),
+			#[cfg(not(unix))]
+			false => Ok(()),
+		}
+		.with_context(|| format!("could not copy {}", archive.display()))?;
+		let added = match format {
+			Format::Zip => self.append_zip(staged.path(), archive, path, entry)?,
+			Format::Tar => self.append_tar(staged.path(), archive, path, entry)?,
+		};
+		if added.is_some() {
+			staged
+				.persist(archive)
+				.with_context(|| format!("could not replace {}", archive.display()))?;
+		}
+		Ok(added)
+	}
+
+	/// Appends `path` to the zip archive at `staged`, a copy of `archive` that's empty if it doesn't exist yet
+	fn append_zip(&self, staged: &Path, archive: &Path, path: &Path, entry: String) -> Result<Option<String>> {
+		let exists = staged.metadata()?.len() > 0;
+		let existing = match exists {
+			true => zip::ZipArchive::new(File::open(staged)?)
+				.with_context(|| format!("could not read {}", archive.display()))?
+				.file_names()
+				.map(String::from)
+				.collect(),
+			false => HashSet::new(),
+		};
+		let entry = match self.resolve(entry, &existing) {
+			Some(entry) => entry,
+			None => return Ok(None),
+		};
+		let mut writer = match exists {
+			true => zip::ZipWriter::new_append(OpenOptions::new().read(true).write(true).open(staged)?)
+				.with_context(|| format!("could not open {}", archive.display()))?,
+			false => zip::ZipWriter::new(File::create(staged).with_context(|| format!("could not create {}", archive.display()))?),
+		};
+		let metadata = path.metadata()?;
+		let mut options = zip::write::FileOptions::default()
+			.compression_method(zip::CompressionMethod::Deflated)
+			.large_file(metadata.len() >= u32::MAX as u64);
+		if let Some(modified) = metadata.modified().ok().map(DateTime::<Local>::from) {
+			let time = zip::DateTime::from_date_and_time(
+				modified.year() as u16,
+				modified.month() as u8,
+				modified.day() as u8,
+				modified.hour() as u8,
+				modified.minute() as u8,
+				modified.second() as u8,
+			);
+			// zip can't represent dates before 1980
+			if let Ok(time) = time {
+				options = options.last_modified_time(time);
+			}
+		}
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			options = options.unix_permissions(metadata.permissions().mode());
+		}
+		writer.start_file(entry.as_str(), options)?;
+		io::copy(&mut File::open(path)?, &mut writer).with_context(|| format!("could not add {} to {}", path.display(), archive.display()))?;
+		writer.finish()?;
+		Ok(Some(entry))
+	}
+
+	/// Appends `path` to the tar archive at `staged`, a copy of `archive` that's empty if it doesn't exist yet
+	fn append_tar(&self, staged: &Path, archive: &Path, path: &Path, entry: String) -> Result<Option<String>> {
+		let mut file = OpenOptions::new()
+			.read(true)
+			.write(true)
+			.open(staged)
+			.with_context(|| format!("could not open {}", archive.display()))?;
+		// the archive ends with empty blocks, which the new entry replaces
+		let mut existing = HashSet::new();
+		let mut end = 0;
+		for tar_entry in tar::Archive::new(&file)
+			.entries()
+			.with_context(|| format!("could not read {}", archive.display()))?
+		{
+			let tar_entry = tar_entry.with_context(|| format!("could not read {}", archive.display()))?;
+			existing.insert(tar_entry.path()?.to_string_lossy().to_string());
+			end = tar_entry.raw_file_position() + tar_entry.size().div_ceil(512) * 512;
+		}
+		let entry = match self.resolve(entry, &existing) {
+			Some(entry) => entry,
+			None => return Ok(None),
+		};
+		file.set_len(end)?;
+		file.seek(SeekFrom::Start(end))?;
+		let mut builder = tar::Builder::new(file);
+		builder
+			.append_path_with_name(path, &entry)
+			.with_context(|| format!("could not add {} to {}", path.display(), archive.display()))?;
+		builder.finish()?;
+		Ok(Some(entry))
+	}
+}
+
+impl Act for Archive {
+	fn act<T, P>(&self, from: T, _to: Option<P>) -> Result<Option<PathBuf>>
+	where
+		Self: Sized,
+		T: AsRef<Path> + Into<PathBuf>,
+		P: AsRef<Path> + Into<PathBuf>,
+	{
+		let path = from.into();
+		if !path.is_file() {
+			bail!("{} is not a file, only files can be archived", path.display());
+		}
+		let archive = self.archive(&path)?;
+		let entry = self.entry(&path)?;
+		let format = Format::of(&archive)?;
+		if let Some(parent) = archive.parent() {
+			fs::create_dir_all(parent).with_context(|| format!("could not create {}", parent.display()))?;
+		}
+		let added = {
+			let _writing = WRITING.lock().unwrap_or_else(|e| e.into_inner());
+			self.append(format, &archive, &path, entry.clone())?
+		};
+		let entry = match added {
+			Some(entry) => entry,
+			None => {
+				log::info!("({}) {} already has {}, skipping {}", self.ty(), archive.display(), entry, path.display());
+				events::skip(&path, SkipReason::ConflictSkip);
+				return Ok(Some(path));
+			}
+		};
+		log::info!(
+			"{}",
+			messages::format(
+				Message::Acted,
+				&[
+					("action", &self.ty()),
+					("from", &path.display()),
+					("to", &format!("{}:{}", archive.display(), entry))
+				]
+			)
+		);
+		if self.remove {
+			fs::remove_file(&path).map_err(|e| anyhow!("{} was archived but could not be removed: {}", path.display(), e))?;
+		}
+		events::record(Event::Acted {
+			action: self.ty(),
+			from: path.clone(),
+			to: Some(archive),
+			output: None,
+		});
+		Ok((!self.remove).then_some(path))
+	}
+}
+
+impl AsAction for Archive {
+	fn process<T: Into<PathBuf> + AsRef<Path>>(&self, path: T) -> Option<PathBuf> {
+		let path = path.into();
+		let to: Option<T> = None;
+		match self.act(&path, to) {
+			Ok(path) => path,
+			Err(e) => {
+				log::error!("{:?}", e);
+				events::skip(&path, SkipReason::Error);
+				None
+			}
+		}
+	}
+
+	fn ty(&self) -> ActionType {
+		ActionType::Archive
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn names(archive: &Path) -> Vec<String> {
+		let mut names = match Format::of(archive).unwrap() {
+			Format::Zip => zip::ZipArchive::new(File::open(archive).unwrap())
+				.unwrap()
+				.file_names()
+				.map(String::from)
+				.collect::<Vec<_>>(),
+			Format::Tar => tar::Archive::new(File::open(archive).unwrap())
+				.entries()
+				.unwrap()
+				.map(|entry| entry.unwrap().path().unwrap().to_string_lossy().to_string())
+				.collect(),
+		};
+		names.sort();
+		names
+	}
+
+	#[test]
+	fn append_across_runs() {
+		let dir = tempfile::tempdir().unwrap();
+		for format in ["zip", "tar"] {
+			let archive = dir.path().join(format!("bundle.{}", format));
+			let action: Archive = toml::from_str(&format!("to = {:?}\nentry = \"logs/{{filename}}\"", archive)).unwrap();
+			let renaming = Archive {
+				if_exists: EntryConflict::Rename,
+				..action.clone()
+			};
+			let (first, second) = (dir.path().join("a.log"), dir.path().join("b.log"));
+			fs::write(&first, "first").unwrap();
+			fs::write(&second, "second").unwrap();
+			action.act(&first, None::<PathBuf>).unwrap();
+			action.act(&second, None::<PathBuf>).unwrap();
+			// the same file again
+			action.act(&first, None::<PathBuf>).unwrap();
+			renaming.act(&first, None::<PathBuf>).unwrap();
+			assert_eq!(names(&archive), ["logs/a (1).log", "logs/a.log", "logs/b.log"]);
+		}
+	}
+
+	#[test]
+	fn keep_archive_on_failure() {
+		let dir = tempfile::tempdir().unwrap();
+		let file = dir.path().join("a.log");
+		fs::write(&file, "first").unwrap();
+		for format in [Format::Zip, Format::Tar] {
+			let archive = dir.path().join(format!("bundle.{:?}", format).to_lowercase());
+			let action: Archive = toml::from_str(&format!("to = {:?}\nentry = \"logs/{{filename}}\"", archive)).unwrap();
+			action.act(&file, None::<PathBuf>).unwrap();
+			// the file is gone by the time it's added
+			let missing = dir.path().join("missing.log");
+			assert!(action.append(format, &archive, &missing, "logs/missing.log".into()).is_err());
+			assert_eq!(names(&archive), ["logs/a.log"]);
+		}
+		assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+	}
+}
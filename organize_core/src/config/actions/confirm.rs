@@ -0,0 +1,51 @@
+use std::{
+	io::{BufRead, IsTerminal, Write},
+	path::Path,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Mutex,
+	},
+};
+
+use lazy_static::lazy_static;
+
+use crate::config::actions::ActionType;
+
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+	// watch mode handles files in parallel, questions must not interleave
+	static ref PROMPT: Mutex<()> = Mutex::new(());
+}
+
+/// Runs actions marked with `confirm = true` without asking (`--yes`)
+pub fn assume_yes(yes: bool) {
+	ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+/// Asks whether `action` should run on `path`.
+/// Without a terminal to ask on, the action is refused unless `--yes` was passed.
+pub(crate) fn confirm(action: &ActionType, path: &Path) -> bool {
+	if ASSUME_YES.load(Ordering::Relaxed) {
+		return true;
+	}
+	let stdin = std::io::stdin();
+	if !stdin.is_terminal() {
+		log::warn!(
+			"({}) {} requires confirmation, pass --yes to run it non-interactively",
+			action,
+			path.display()
+		);
+		return false;
+	}
+	let _lock = PROMPT.lock().unwrap_or_else(|e| e.into_inner());
+	print!("({}) {}, proceed? [y/N] ", action, path.display());
+	if std::io::stdout().flush().is_err() {
+		return false;
+	}
+	let mut answer = String::new();
+	match stdin.lock().read_line(&mut answer) {
+		Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+		Err(_) => false,
+	}
+}
@@ -1,16 +1,105 @@
-use std::path::{Path, PathBuf};
+use std::{
+	fs::OpenOptions,
+	io::Write,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime},
+};
 
-use crate::config::actions::{Act, ActionType, AsAction};
-use anyhow::{Context, Result};
-use derive_more::Deref;
+use crate::{
+	config::{
+		actions::{Act, ActionType, AsAction},
+		filters::deserialize_duration,
+		trash,
+	},
+	elevation,
+	events::{self, Event, SkipReason},
+	in_use, layout,
+	messages::{self, Message},
+	path::sandbox,
+	settings,
+};
+use anyhow::{anyhow, bail, Context, Result};
 use serde::Deserialize;
 use std::str::FromStr;
 
-#[derive(Debug, Clone, Deref, Deserialize, Default, PartialEq, Eq)]
-pub struct Delete(bool);
+/// Limits on what a destructive action may remove, checked right before it runs.
+/// The ones that aren't set are taken from `[limits]` in the settings.
+#[derive(Debug, Clone, Deserialize, Default, PartialEq, Eq)]
+pub struct Guard {
+	/// refuse files that were modified more recently than this
+	#[serde(default, deserialize_with = "deserialize_duration")]
+	pub newer_than: Option<Duration>,
+	/// refuse files larger than this many bytes
+	#[serde(default)]
+	pub larger_than: Option<u64>,
+	/// refuse files that another process has open
+	#[serde(default)]
+	pub skip_open: bool,
+	/// ignore the limits above
+	#[serde(default)]
+	pub force: bool,
+}
 
-#[derive(Debug, Clone, Deref, Deserialize, Default, Eq, PartialEq)]
-pub struct Trash(bool);
+impl Guard {
+	/// Refuses to remove `path` if it's open, too recent or too large, unless `force` is set
+	pub(crate) fn check(&self, path: &Path) -> Result<()> {
+		if self.force {
+			return Ok(());
+		}
+		let limits = settings::get().limits;
+		let newer_than = self.newer_than.or(limits.newer_than);
+		let larger_than = self.larger_than.or(limits.larger_than);
+		let skip_open = self.skip_open || limits.skip_open;
+		if newer_than.is_none() && larger_than.is_none() && !skip_open {
+			return Ok(());
+		}
+		if skip_open && in_use::is_open(path) {
+			bail!(
+				"refusing to remove {}, another process has it open (set `force = true` to override)",
+				path.display()
+			);
+		}
+		let metadata = path
+			.metadata()
+			.with_context(|| format!("could not read the metadata of {}", path.display()))?;
+		if let Some(newer_than) = newer_than {
+			let modified = metadata
+				.modified()
+				.with_context(|| format!("could not read the modification time of {}", path.display()))?;
+			if SystemTime::now().duration_since(modified).unwrap_or_default() < newer_than {
+				bail!(
+					"refusing to remove {}, it was modified too recently (set `force = true` to override)",
+					path.display()
+				);
+			}
+		}
+		if let Some(larger_than) = larger_than {
+			if metadata.len() > larger_than {
+				bail!(
+					"refusing to remove {}, it's larger than {} bytes (set `force = true` to override)",
+					path.display(),
+					larger_than
+				);
+			}
+		}
+		Ok(())
+	}
+}
+
+#[derive(Debug, Clone, Deserialize, Default, PartialEq, Eq)]
+pub struct Delete {
+	#[serde(flatten)]
+	pub guard: Guard,
+	/// overwrite the contents of the file before removing it
+	#[serde(default)]
+	pub secure: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Default, Eq, PartialEq)]
+pub struct Trash {
+	#[serde(flatten)]
+	pub guard: Guard,
+}
 
 macro_rules! as_action {
 	($id:ty) => {
@@ -18,19 +107,29 @@ macro_rules! as_action {
 			fn process<T: Into<PathBuf> + AsRef<Path>>(&self, path: T) -> Option<PathBuf> {
 				let path = path.into();
 				let to: Option<T> = None;
-				if **self {
-					match self.act(&path, to) {
-						Ok(new_path) => {
-							log::info!("({}) {}", self.ty().to_string(), path.display());
-							new_path
-						}
-						Err(e) => {
-							log::error!("{:?}", e);
-							None
-						}
+				let acted = self.act(&path, to).or_else(|e| match self.elevates() {
+					true => elevation::retry(e, self.ty(), &path, None),
+					false => Err(e),
+				});
+				match acted {
+					Ok(new_path) => {
+						log::info!(
+							"{}",
+							messages::format(Message::ActedInPlace, &[("action", &self.ty()), ("from", &path.display())])
+						);
+						events::record(Event::Acted {
+							action: self.ty(),
+							from: path,
+							to: new_path.clone(),
+							output: None,
+						});
+						new_path
+					}
+					Err(e) => {
+						log::error!("{:?}", e);
+						events::skip(path, SkipReason::of(&e));
+						None
 					}
-				} else {
-					Some(path)
 				}
 			}
 
@@ -45,26 +144,63 @@ macro_rules! as_action {
 as_action!(Delete);
 as_action!(Trash);
 
+impl Delete {
+	/// A helper can only remove the file, so secure deletes are never elevated
+	fn elevates(&self) -> bool {
+		!self.secure
+	}
+
+	/// Overwrites the file with zeros and flushes it to disk, so its contents can't be recovered from the freed blocks
+	fn wipe(path: &Path) -> Result<()> {
+		let len = path.metadata()?.len();
+		let mut file = OpenOptions::new().write(true).open(path)?;
+		let zeros = [0u8; 64 * 1024];
+		let mut remaining = len;
+		while remaining > 0 {
+			let chunk = remaining.min(zeros.len() as u64) as usize;
+			file.write_all(&zeros[..chunk])?;
+			remaining -= chunk as u64;
+		}
+		file.sync_all()?;
+		Ok(())
+	}
+}
+
 impl Act for Delete {
 	fn act<T, P>(&self, from: T, _to: Option<P>) -> Result<Option<PathBuf>>
 	where
 		T: AsRef<Path> + Into<PathBuf>,
 		P: AsRef<Path> + Into<PathBuf>,
 	{
-		println!("te");
-		if **self {
-			std::fs::remove_file(&from)
-				.with_context(|| format!("could not delete {}", from.as_ref().display()))
-				.map(|_| None)
-		} else {
-			Ok(Some(from.into()))
+		let from = from.as_ref();
+		if from.is_dir() {
+			if self.secure {
+				bail!("refusing to remove {}, secure deletes only work on files", from.display());
+			}
+			return std::fs::remove_dir_all(from)
+				.with_context(|| format!("could not delete {}", from.display()))
+				.map(|_| None);
+		}
+		if self.secure {
+			Self::wipe(from).with_context(|| format!("could not overwrite {}", from.display()))?;
 		}
+		std::fs::remove_file(from)
+			.with_context(|| format!("could not delete {}", from.display()))
+			.map(|_| None)
 	}
 }
 
 impl Trash {
-	fn dir() -> Result<PathBuf> {
-		let dir = dirs_next::data_local_dir().unwrap().join("organize").join(".trash");
+	/// Items trashed by a helper would belong to another user, in another user's trash
+	fn elevates(&self) -> bool {
+		false
+	}
+
+	pub(crate) fn dir() -> Result<PathBuf> {
+		let dir = match sandbox::trash() {
+			Some(dir) => dir,
+			None => layout::data_dir()?.join(".trash"),
+		};
 		std::fs::create_dir_all(&dir)
 			.with_context(|| format!("Could not create trash directory at {}", &dir.display()))
 			.map(|_| dir)
@@ -77,30 +213,30 @@ impl Act for Trash {
 		T: AsRef<Path> + Into<PathBuf>,
 		P: AsRef<Path> + Into<PathBuf>,
 	{
-		if self.0 {
-			let to = Self::dir()?.join(from.as_ref().file_name().unwrap());
-			let from = from.as_ref();
-			std::fs::copy(from, &to).with_context(|| format!("Could not copy file ({} -> {})", from.display(), to.display()))?;
-			std::fs::remove_file(from)
-				.with_context(|| format!("could not move ({} -> {})", from.display(), to.display()))
-				.map(|_| None)
-		} else {
-			Ok(Some(from.into()))
-		}
+		let from = from.as_ref();
+		let name = from.file_name().ok_or_else(|| anyhow!("{} has no filename", from.display()))?;
+		let dir = Self::dir()?;
+		let to = dir.join(name);
+		std::fs::copy(from, &to).with_context(|| format!("Could not copy file ({} -> {})", from.display(), to.display()))?;
+		trash::record(&dir, name)?;
+		std::fs::remove_file(from)
+			.with_context(|| format!("could not move ({} -> {})", from.display(), to.display()))
+			.map(|_| None)
 	}
 }
 
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use crate::config::actions::{Action, Step};
 	use tempfile;
 
 	#[test]
-	fn test_delete_true() {
+	fn test_delete() {
 		let tmp_dir = tempfile::tempdir().expect("Couldn't create temporary directory");
 		let tmp_path = tmp_dir.path().to_owned();
 		let tmp_file = tmp_path.join("delete_me.txt");
-		let action = Delete(true);
+		let action = Delete::default();
 
 		std::fs::write(&tmp_file, "").expect("Could create target file");
 		assert!(tmp_file.exists());
@@ -112,27 +248,49 @@ mod tests {
 	}
 
 	#[test]
-	fn test_delete_false() {
+	fn test_delete_secure() {
+		let tmp_dir = tempfile::tempdir().expect("Couldn't create temporary directory");
+		let tmp_file = tmp_dir.path().join("delete_me.txt");
+		let action = Delete {
+			secure: true,
+			..Delete::default()
+		};
+
+		std::fs::write(&tmp_file, "secret").expect("Could create target file");
+		assert_eq!(action.process(&tmp_file), None);
+		assert!(!tmp_file.exists());
+	}
+
+	#[test]
+	fn test_delete_guarded() {
 		let tmp_dir = tempfile::tempdir().expect("Couldn't create temporary directory");
 		let tmp_path = tmp_dir.path().to_owned();
 		let tmp_file = tmp_path.join("delete_me.txt");
-		let action = Delete(false);
+		let mut action = Delete {
+			guard: Guard {
+				larger_than: Some(1),
+				..Guard::default()
+			},
+			..Delete::default()
+		};
 
-		std::fs::write(&tmp_file, "").expect("Could create target file");
+		std::fs::write(&tmp_file, "too large").expect("Could create target file");
 		assert!(tmp_file.exists());
 
-		action
-			.act::<&Path, &Path>(&tmp_file, None)
-			.expect("Could not `delete` target file");
+		assert_eq!(step(Action::Delete(action.clone())).process(tmp_file.clone()), Some(tmp_file.clone()));
 		assert!(tmp_file.exists());
+
+		action.guard.force = true;
+		assert_eq!(step(Action::Delete(action)).process(tmp_file.clone()), None);
+		assert!(!tmp_file.exists());
 	}
 
 	#[test]
-	fn test_trash_true() {
+	fn test_trash() {
 		let tmp_dir = tempfile::tempdir().expect("Couldn't create temporary directory");
 		let tmp_path = tmp_dir.path().to_owned();
 		let tmp_file = tmp_path.join("trash_me.txt");
-		let action = Trash(true);
+		let action = Trash::default();
 
 		std::fs::write(&tmp_file, "").expect("Could create target file");
 		assert!(tmp_file.exists());
@@ -144,18 +302,26 @@ mod tests {
 	}
 
 	#[test]
-	fn test_trash_false() {
+	fn test_trash_guarded() {
 		let tmp_dir = tempfile::tempdir().expect("Couldn't create temporary directory");
 		let tmp_path = tmp_dir.path().to_owned();
 		let tmp_file = tmp_path.join("trash_me.txt");
-		let action = Trash(false);
+		let action: Trash = toml::from_str("newer_than = \"1h\"").unwrap();
 
 		std::fs::write(&tmp_file, "").expect("Could create target file");
 		assert!(tmp_file.exists());
 
-		action
-			.act::<&Path, &Path>(&tmp_file, None)
-			.expect("Could not `delete` target file");
+		assert_eq!(step(Action::Trash(action)).process(tmp_file.clone()), Some(tmp_file.clone()));
 		assert!(tmp_file.exists());
 	}
+
+	/// The guards are checked by the middlewares around the action
+	fn step(action: Action) -> Step {
+		Step {
+			action,
+			when: None,
+			dry_run: false,
+			confirm: false,
+		}
+	}
 }
@@ -1,16 +1,96 @@
-use std::path::{Path, PathBuf};
+use std::{
+	fs::OpenOptions,
+	io::Write,
+	path::{Path, PathBuf},
+	result,
+	str::FromStr,
+};
 
-use derive_more::Deref;
-use serde::Deserialize;
+use log::Level;
+use notify_rust::Notification;
+use serde::{de::Error, Deserialize, Deserializer};
 
 use crate::{
 	config::actions::{Act, ActionType, AsAction},
+	messages::{self, Message},
+	path::Expand,
+	profile::{self, Stage},
+	settings::{self, NotificationBackend},
 	string::{deserialize_placeholder_string, ExpandPlaceholder},
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
+
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+pub struct Echo {
+	#[serde(deserialize_with = "deserialize_placeholder_string")]
+	message: String,
+	#[serde(default)]
+	to: Output,
+	/// only used when the output is the log
+	#[serde(default = "default_level", deserialize_with = "deserialize_level")]
+	level: Level,
+}
 
-#[derive(Debug, Clone, Deserialize, Deref, Default, Eq, PartialEq)]
-pub struct Echo(#[serde(deserialize_with = "deserialize_placeholder_string")] String);
+/// Where an echo's message ends up
+#[derive(Debug, Clone, Deserialize, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Output {
+	/// organize's own log, at the action's level
+	#[default]
+	Log,
+	Stdout,
+	Stderr,
+	/// appended to the given file, one message per line
+	File(PathBuf),
+	/// a desktop notification
+	Notification,
+}
+
+fn default_level() -> Level {
+	Level::Info
+}
+
+fn deserialize_level<'de, D>(deserializer: D) -> result::Result<Level, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let str = String::deserialize(deserializer)?;
+	Level::from_str(&str).map_err(D::Error::custom)
+}
+
+impl Echo {
+	fn write(&self, message: &str) -> Result<()> {
+		match &self.to {
+			Output::Log => log::log!(self.level, "({}) {}", self.ty(), message),
+			Output::Stdout => println!("{}", message),
+			Output::Stderr => eprintln!("{}", message),
+			Output::File(path) => {
+				let path = path.clone().expand_user()?.expand_vars()?;
+				let mut file = OpenOptions::new()
+					.create(true)
+					.append(true)
+					.open(&path)
+					.with_context(|| format!("could not open {}", path.display()))?;
+				writeln!(file, "{}", message).with_context(|| format!("could not write to {}", path.display()))?;
+			}
+			Output::Notification => {
+				for backend in settings::get().notifications.backends {
+					match backend {
+						NotificationBackend::Desktop => {
+							Notification::new()
+								.summary(&messages::format(Message::NotificationTitle, &[]))
+								.body(message)
+								.show()
+								.context("could not show notification")?;
+						}
+						NotificationBackend::Log => log::info!("({}) {}", self.ty(), message),
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+}
 
 impl Act for Echo {
 	fn act<T, P>(&self, from: T, _to: Option<P>) -> Result<Option<PathBuf>>
@@ -19,10 +99,13 @@ impl Act for Echo {
 		P: AsRef<Path> + Into<PathBuf>,
 	{
 		let from = from.into();
-		let expanded = self.as_str().expand_placeholders(&from);
+		let expanded = profile::time(|| Stage::Template, || self.message.as_str().expand_placeholders(&from));
 		match expanded {
 			Ok(str) => {
-				log::info!("({}) {:#?}", self.ty().to_string(), str);
+				// failing to report shouldn't stop the rest of the pipeline
+				if let Err(e) = self.write(&str.to_string_lossy()) {
+					log::error!("{:?}", e);
+				}
 				Ok(Some(from))
 			}
 			Err(e) => {
@@ -44,3 +127,38 @@ impl AsAction for Echo {
 		ActionType::Echo
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn deserialize_defaults() {
+		let echo: Echo = toml::from_str("message = \"{filename}\"").unwrap();
+		assert_eq!(echo.to, Output::Log);
+		assert_eq!(echo.level, Level::Info);
+	}
+
+	#[test]
+	fn deserialize_output() {
+		let echo: Echo = toml::from_str("message = \"{filename}\"\nto = \"stderr\"\nlevel = \"warn\"").unwrap();
+		assert_eq!(echo.to, Output::Stderr);
+		assert_eq!(echo.level, Level::Warn);
+		let echo: Echo = toml::from_str("message = \"{filename}\"\nto = { file = \"/tmp/report.txt\" }").unwrap();
+		assert_eq!(echo.to, Output::File(PathBuf::from("/tmp/report.txt")));
+	}
+
+	#[test]
+	fn append_to_file() {
+		let dir = tempfile::tempdir().unwrap();
+		let report = dir.path().join("report.txt");
+		let echo = Echo {
+			message: "seen {filename}".into(),
+			to: Output::File(report.clone()),
+			level: Level::Error,
+		};
+		echo.process(dir.path().join("a.txt"));
+		echo.process(dir.path().join("b.txt"));
+		assert_eq!(std::fs::read_to_string(report).unwrap(), "seen a.txt\nseen b.txt\n");
+	}
+}
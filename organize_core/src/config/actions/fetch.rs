@@ -0,0 +1,308 @@
+use std::{
+	collections::BTreeMap,
+	convert::TryInto,
+	fs::File,
+	io::{Read, Seek, SeekFrom},
+	path::{Path, PathBuf},
+	result,
+	str::FromStr,
+	time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use serde::{Deserialize, Deserializer};
+use strum_macros::Display;
+
+use crate::{
+	config::{
+		actions::{Act, ActionType, AsAction},
+		secret::Secret,
+	},
+	events::{self, Event, SkipReason},
+	media::{BRACKETS, RELEASE_TAGS},
+	messages::{self, Message},
+	path::{sandbox, Expand, ValidateDestination},
+	string::ExpandPlaceholder,
+};
+
+/// Responses larger than this are refused, no subtitle or NFO file comes close
+const MAX_SIZE: u64 = 10 * 1024 * 1024;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Kind {
+	/// written next to the video as `<stem>.<language>.srt`
+	#[default]
+	Subtitles,
+	/// written next to the video as `<stem>.nfo`, which media servers read the title, plot, cast... from
+	Nfo,
+}
+
+/// A header sent to the provider, which can be a reference to a secret so that API keys stay out of the config
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Header {
+	Plain(String),
+	Secret(Secret),
+}
+
+impl<'de> Deserialize<'de> for Header {
+	fn deserialize<D>(deserializer: D) -> result::Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let str = String::deserialize(deserializer)?;
+		Ok(match Secret::from_str(&str) {
+			Ok(secret) => Self::Secret(secret),
+			Err(_) => Self::Plain(str),
+		})
+	}
+}
+
+impl Header {
+	fn resolve(&self) -> Result<String> {
+		match self {
+			Self::Plain(value) => Ok(value.clone()),
+			Self::Secret(secret) => secret.resolve(),
+		}
+	}
+}
+
+/// Where subtitles or metadata are downloaded from.
+///
+/// The url can use `{query}` (the title guessed from the file name), `{hash}` (the OpenSubtitles hash of the video),
+/// `{lang}` and the usual placeholders. The provider must answer with the file itself, or with a 404 if it has nothing.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Provider {
+	url: String,
+	#[serde(default)]
+	headers: BTreeMap<String, Header>,
+}
+
+impl Provider {
+	fn url(&self, video: &Path, language: Option<&str>) -> Result<String> {
+		let mut url = self.url.clone();
+		if url.contains("{query}") {
+			url = url.replace("{query}", &encode(&query(video)));
+		}
+		if url.contains("{hash}") {
+			url = url.replace("{hash}", &hash(video)?);
+		}
+		if url.contains("{lang}") {
+			let language = language.ok_or_else(|| anyhow!("{} uses {{lang}} but no language is set", self.url))?;
+			url = url.replace("{lang}", &encode(language));
+		}
+		url.expand_placeholders(video)?
+			.into_string()
+			.map_err(|_| anyhow!("{} is not valid unicode once expanded", self.url))
+	}
+
+	/// The body of the response to `url`, or `None` if the provider has nothing for it
+	fn get(&self, url: &str) -> Result<Option<Vec<u8>>> {
+		let mut request = ureq::get(url).timeout(TIMEOUT);
+		for (name, value) in self.headers.iter() {
+			request = request.set(name, &value.resolve()?);
+		}
+		let response = match request.call() {
+			Ok(response) => response,
+			Err(ureq::Error::Status(404, _)) => return Ok(None),
+			Err(e) => return Err(e).with_context(|| format!("could not download {}", url)),
+		};
+		let mut content = Vec::new();
+		response
+			.into_reader()
+			.take(MAX_SIZE + 1)
+			.read_to_end(&mut content)
+			.with_context(|| format!("could not download {}", url))?;
+		if content.len() as u64 > MAX_SIZE {
+			bail!("{} is larger than {} bytes", url, MAX_SIZE);
+		}
+		Ok((!content.is_empty()).then_some(content))
+	}
+}
+
+/// Downloads subtitles or an NFO file for a video from a provider and writes it next to the video, or to `to`.
+/// The video itself is left alone, and files that were already fetched are kept unless `overwrite` is set.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Fetch {
+	#[serde(default)]
+	kind: Kind,
+	provider: Provider,
+	/// e.g. `en`, available to the url and `to` as `{lang}`
+	#[serde(default)]
+	language: Option<String>,
+	/// where the file is written, e.g. `{parent}/Subs/{stem}.{lang}.srt`
+	#[serde(default)]
+	to: Option<String>,
+	#[serde(default)]
+	overwrite: bool,
+}
+
+impl Fetch {
+	fn template(&self) -> String {
+		match (&self.to, self.kind, &self.language) {
+			(Some(to), ..) => to.clone(),
+			(None, Kind::Subtitles, Some(_)) => "{parent}/{stem}.{lang}.srt".into(),
+			(None, Kind::Subtitles, None) => "{parent}/{stem}.srt".into(),
+			(None, Kind::Nfo, _) => "{parent}/{stem}.nfo".into(),
+		}
+	}
+
+	fn destination(&self, video: &Path) -> Result<PathBuf> {
+		let template = self.template();
+		let mut rendered = template.clone();
+		if rendered.contains("{lang}") {
+			let language = self
+				.language
+				.as_deref()
+				.ok_or_else(|| anyhow!("{} uses {{lang}} but no language is set", template))?;
+			rendered = rendered.replace("{lang}", language);
+		}
+		let to = PathBuf::from(rendered.expand_placeholders(video)?)
+			.expand_user()?
+			.expand_vars()?;
+		to.validate_destination(&template)?;
+		sandbox::confine(to)
+	}
+}
+
+impl Act for Fetch {
+	fn act<T, P>(&self, from: T, _to: Option<P>) -> Result<Option<PathBuf>>
+	where
+		Self: Sized,
+		T: AsRef<Path> + Into<PathBuf>,
+		P: AsRef<Path> + Into<PathBuf>,
+	{
+		let video = from.into();
+		let to = self.destination(&video)?;
+		if to.exists() && !self.overwrite {
+			log::debug!("{} were already fetched for {}: {}", self.kind, video.display(), to.display());
+			return Ok(Some(video));
+		}
+		let url = self.provider.url(&video, self.language.as_deref())?;
+		let content = match self.provider.get(&url)? {
+			Some(content) => content,
+			None => {
+				log::info!("no {} were found for {}", self.kind, video.display());
+				return Ok(Some(video));
+			}
+		};
+		if let Some(parent) = to.parent() {
+			std::fs::create_dir_all(parent).with_context(|| format!("could not create {}", parent.display()))?;
+		}
+		std::fs::write(&to, content).with_context(|| format!("could not write {}", to.display()))?;
+		log::info!(
+			"{}",
+			messages::format(
+				Message::Acted,
+				&[("action", &self.ty()), ("from", &video.display()), ("to", &to.display())]
+			)
+		);
+		events::record(Event::Acted {
+			action: self.ty(),
+			from: video.clone(),
+			to: Some(to),
+			output: None,
+		});
+		Ok(Some(video))
+	}
+}
+
+impl AsAction for Fetch {
+	fn process<T: Into<PathBuf> + AsRef<Path>>(&self, path: T) -> Option<PathBuf> {
+		let path = path.into();
+		let to: Option<T> = None;
+		match self.act(&path, to) {
+			Ok(path) => path,
+			Err(e) => {
+				log::error!("{:?}", e);
+				events::skip(&path, SkipReason::Error);
+				// the video is still there, the next actions can carry on
+				Some(path)
+			}
+		}
+	}
+
+	fn ty(&self) -> ActionType {
+		ActionType::Fetch
+	}
+}
+
+/// The title of a video as guessed from its name, without the release tags that providers don't know about
+fn query(video: &Path) -> String {
+	let stem = video.file_stem().unwrap_or_default().to_string_lossy();
+	let stem = BRACKETS.replace_all(&stem, " ");
+	let stem = stem.replace(['.', '_'], " ");
+	let title = match RELEASE_TAGS.find(&stem) {
+		Some(tag) if tag.start() > 0 => &stem[..tag.start()],
+		_ => &stem,
+	};
+	title.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The hash used by OpenSubtitles and most subtitle providers: the size of the file plus the sum of
+/// the 64-bit words of its first and last 64KB
+fn hash(video: &Path) -> Result<String> {
+	const CHUNK: u64 = 64 * 1024;
+	let mut file = File::open(video).with_context(|| format!("could not open {}", video.display()))?;
+	let size = file.metadata()?.len();
+	let chunk = CHUNK.min(size);
+	let mut hash = size;
+	let mut buffer = vec![0; chunk as usize];
+	for offset in [0, size - chunk] {
+		file.seek(SeekFrom::Start(offset))?;
+		file.read_exact(&mut buffer)
+			.with_context(|| format!("could not read {}", video.display()))?;
+		for word in buffer.chunks_exact(8) {
+			hash = hash.wrapping_add(u64::from_le_bytes(word.try_into().unwrap()));
+		}
+	}
+	Ok(format!("{:016x}", hash))
+}
+
+/// Percent-encodes `value` for a url
+fn encode(value: &str) -> String {
+	value
+		.bytes()
+		.map(|byte| match byte {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (byte as char).to_string(),
+			_ => format!("%{:02X}", byte),
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn guess_the_title() {
+		assert_eq!(query(Path::new("/videos/The.Matrix.1999.1080p.BluRay.x264.mkv")), "The Matrix 1999");
+		assert_eq!(query(Path::new("[group] Some_Show_S01E02_720p.mp4")), "Some Show S01E02");
+		assert_eq!(query(Path::new("1080p.mkv")), "1080p");
+		assert_eq!(encode("Amélie 2001"), "Am%C3%A9lie%202001");
+	}
+
+	#[test]
+	fn render_the_request() {
+		let dir = tempfile::tempdir().unwrap();
+		let video = dir.path().join("Movie.2019.720p.mkv");
+		std::fs::write(&video, [1u8; 200_000]).unwrap();
+		let fetch: Fetch = toml::from_str(
+			"language = \"en\"\nprovider = { url = \"https://subs.example.com/{lang}?q={query}&hash={hash}\", headers = { Api-Key = 'env(\"SUBS_KEY\")', Accept = \"text/plain\" } }",
+		)
+		.unwrap();
+		assert_eq!(fetch.provider.headers["Api-Key"], Header::Secret(Secret::Env("SUBS_KEY".into())));
+		assert_eq!(fetch.provider.headers["Accept"], Header::Plain("text/plain".into()));
+		// 200000 + 2 * 8192 words of 0x0101010101010101
+		let words = 0x0101010101010101u64.wrapping_mul(2 * 8192).wrapping_add(200_000);
+		assert_eq!(
+			fetch.provider.url(&video, fetch.language.as_deref()).unwrap(),
+			format!("https://subs.example.com/en?q=Movie%202019&hash={:016x}", words)
+		);
+		assert_eq!(fetch.destination(&video).unwrap(), dir.path().join("Movie.2019.720p.en.srt"));
+	}
+}
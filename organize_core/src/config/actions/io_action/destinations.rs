@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+
+use serde::{de::Error, Deserialize, Deserializer};
+
+use super::ConflictOption;
+
+/// One of the paths a file is moved or copied to, with its own conflict policy if it needs one
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Destination {
+	pub to: PathBuf,
+	/// overrides the action's `if_exists` for this destination
+	#[serde(default)]
+	pub if_exists: Option<ConflictOption>,
+}
+
+/// Where an action sends a file: a single path, or several of them, e.g. `to = ["~/Archive/{extension}", "/mnt/nas/backups"]`.
+/// The file ends up in the first one, and a copy of it is left in each of the others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Destinations(Vec<Destination>);
+
+impl Destinations {
+	/// Where the file ends up
+	pub fn primary(&self) -> &Destination {
+		&self.0[0]
+	}
+
+	/// Where the copies go
+	pub fn replicas(&self) -> &[Destination] {
+		&self.0[1..]
+	}
+}
+
+impl Default for Destinations {
+	fn default() -> Self {
+		Self(vec![Destination::default()])
+	}
+}
+
+impl From<PathBuf> for Destinations {
+	fn from(to: PathBuf) -> Self {
+		Self(vec![Destination { to, if_exists: None }])
+	}
+}
+
+impl<'de> Deserialize<'de> for Destinations {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		#[derive(Deserialize)]
+		#[serde(untagged)]
+		enum Raw {
+			Path(PathBuf),
+			Destination(Destination),
+		}
+
+		#[derive(Deserialize)]
+		#[serde(untagged)]
+		enum OneOrMany {
+			One(Raw),
+			Many(Vec<Raw>),
+		}
+
+		let destinations = match OneOrMany::deserialize(deserializer)? {
+			OneOrMany::One(raw) => vec![raw],
+			OneOrMany::Many(raw) => raw,
+		};
+		if destinations.is_empty() {
+			return Err(D::Error::custom("`to` needs at least one destination"));
+		}
+		Ok(Self(
+			destinations
+				.into_iter()
+				.map(|raw| match raw {
+					Raw::Path(to) => Destination { to, if_exists: None },
+					Raw::Destination(destination) => destination,
+				})
+				.collect(),
+		))
+	}
+}
+
+/// What happens when the file can't be sent to every destination
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Replication {
+	/// the file is left where it was, and the copies that were made are removed
+	#[default]
+	AllOrNothing,
+	/// the file is sent wherever it can be, and the failures are logged
+	BestEffort,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Deserialize)]
+	struct Action {
+		to: Destinations,
+	}
+
+	#[test]
+	fn deserialize_destinations() {
+		let single: Action = toml::from_str("to = \"/docs\"").unwrap();
+		assert_eq!(single.to, Destinations::from(PathBuf::from("/docs")));
+		assert!(single.to.replicas().is_empty());
+		let many: Action = toml::from_str("to = [\"/docs\", { to = \"/backup\", if_exists = \"overwrite\" }]").unwrap();
+		assert_eq!(many.to.primary().to, PathBuf::from("/docs"));
+		assert_eq!(many.to.replicas()[0].if_exists, Some(ConflictOption::Overwrite));
+		assert!(toml::from_str::<Action>("to = []").is_err());
+	}
+}
@@ -1,5 +1,6 @@
 use std::{
 	convert::TryFrom,
+	io::{BufRead, BufReader},
 	path::{Path, PathBuf},
 	result,
 	str::FromStr,
@@ -7,26 +8,61 @@ use std::{
 
 use derive_more::Deref;
 use serde::{Deserialize, Serialize};
+use strum_macros::Display;
+use tempfile::TempDir;
+use walkdir::WalkDir;
 
 use crate::{
+	capacity::Transfer,
 	config::actions::{Act, ActionType, AsAction},
-	path::{Expand, ResolveConflict},
+	elevation,
+	events::{self, Event, SkipReason},
+	in_use,
+	messages::{self, Message},
+	path::{sandbox, Expand, Reservation, ResolveConflict, ValidateDestination, ZoneIdentifier},
+	profile::{self, Stage},
 	string::ExpandPlaceholder,
 	utils::UnwrapRef,
 	// DB,
 };
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 
-use regex::Regex;
-use serde::de::Error;
+use sanitize::Sanitize;
+
+pub use destinations::{Destination, Destinations, Replication};
+
+mod destinations;
+pub mod sanitize;
 
 #[derive(Deserialize, Debug, Clone, Eq, PartialEq, Default)]
 pub struct Inner {
-	pub to: PathBuf,
+	pub to: Destinations,
+	/// whether the file is still sent to the destinations it can be sent to when it can't be sent to all of them
+	#[serde(default)]
+	pub replication: Replication,
+	/// destinations tried in order when the first one in `to` cannot be rendered for a file
+	#[serde(default)]
+	pub fallback: Vec<PathBuf>,
 	#[serde(default)]
 	pub if_exists: ConflictOption,
 	#[serde(default)]
+	pub overwrite_if: Option<OverwriteIf>,
+	/// how to resolve conflicts between individual files when `if_exists = "merge"`
+	#[serde(default)]
+	pub merge_conflicts: ConflictOption,
+	/// free space (in bytes) that must remain on the destination filesystem after copying a file there
+	#[serde(default)]
+	pub free_space_margin: u64,
+	#[serde(default)]
+	pub sanitize: Option<Sanitize>,
+	/// whether to keep the `Zone.Identifier` stream of downloaded files when they are moved or copied (Windows only)
+	#[serde(default)]
+	pub zone_identifier: ZoneIdentifierOption,
+	#[serde(default)]
 	pub allow_cycles: bool,
+	/// leave files that another process has open where they are, e.g. downloads that are still being written
+	#[serde(default)]
+	pub skip_open: bool,
 }
 
 #[derive(Deserialize, Deref, Debug, Clone, PartialEq, Eq)]
@@ -36,11 +72,29 @@ pub struct Move(Inner);
 pub struct Copy(Inner);
 
 #[derive(Deserialize, Deref, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "Inner")]
 pub struct Hardlink(Inner);
 
 #[derive(Deserialize, Deref, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "Inner")]
 pub struct Symlink(Inner);
 
+impl TryFrom<Inner> for Hardlink {
+	type Error = anyhow::Error;
+
+	fn try_from(inner: Inner) -> Result<Self> {
+		inner.for_link().map(Self)
+	}
+}
+
+impl TryFrom<Inner> for Symlink {
+	type Error = anyhow::Error;
+
+	fn try_from(inner: Inner) -> Result<Self> {
+		inner.for_link().map(Self)
+	}
+}
+
 macro_rules! as_action {
 	($id:ty) => {
 		impl AsAction for $id {
@@ -48,7 +102,7 @@ macro_rules! as_action {
 				let path = path.into();
 				let to = self.0.prepare_path(&path);
 				if to.is_none() {
-					if self.0.if_exists == ConflictOption::Delete {
+					if *self.0.policy(&path) == ConflictOption::Delete {
 						if let Err(e) = std::fs::remove_file(&path).with_context(|| format!("could not delete {}", path.display())) {
 							log::error!("{:?}", e);
 						}
@@ -63,23 +117,62 @@ macro_rules! as_action {
 								.with_context(|| format!("could not create parent directory for {}", to.unwrap_ref().display()))
 							{
 								log::error!("{:?}", e);
+								events::skip(path, SkipReason::Error);
 								return None;
 							}
 						}
 					}
 					None => {
 						log::error!("{} has an invalid parent", to.unwrap().display());
+						events::skip(path, SkipReason::Error);
 						return None;
 					}
 				}
 
-				match self.act(&path, Some(to.unwrap_ref())) {
+				let replicas = match self.0.replicate(&path, self.ty()) {
+					Ok(replicas) => replicas,
+					Err(e) => {
+						log::error!("{:?}", e);
+						events::skip(path, SkipReason::of(&e));
+						return None;
+					}
+				};
+				let acted = self
+					.act(&path, Some(to.unwrap_ref().as_path()))
+					.or_else(|e| elevation::retry(e, self.ty(), &path, Some(to.unwrap_ref())));
+				// the copies are only put in place once the file has been sent, dropping them discards them
+				if acted.is_ok() || self.0.replication == Replication::BestEffort {
+					self.0.place_replicas(&path, replicas, self.ty());
+				}
+				match acted {
 					Ok(new_path) => {
-						log::info!("({}) {} -> {}", self.ty().to_string(), path.display(), to.unwrap().display());
+						if self.0.zone_identifier == ZoneIdentifierOption::Strip {
+							if let Err(e) = to.unwrap_ref().strip_zone_identifier() {
+								log::error!("{:?}", e);
+							}
+						}
+						log::info!(
+							"{}",
+							messages::format(
+								Message::Acted,
+								&[
+									("action", &self.ty()),
+									("from", &path.display()),
+									("to", &to.unwrap_ref().display())
+								]
+							)
+						);
+						events::record(Event::Acted {
+							action: self.ty(),
+							from: path,
+							to: new_path.clone(),
+							output: None,
+						});
 						new_path
 					}
 					Err(e) => {
 						log::error!("{:?}", e);
+						events::skip(path, SkipReason::of(&e));
 						None
 					}
 				}
@@ -113,9 +206,13 @@ impl Act for Move {
 				&to.display()
 			)
 		}
+		if *self.if_exists() == ConflictOption::Merge && from.is_dir() && to.is_dir() {
+			self.merge(from, &to, |from, to| std::fs::rename(from, to), true)?;
+			return Ok(Some(to));
+		}
 		std::fs::rename(from, &to)
-			.with_context(|| "Failed to move file")
-			.map_or(Ok(None), |_| Ok(Some(to)))
+			.with_context(|| format!("could not move {} to {}", from.display(), to.display()))
+			.map(|_| Some(to))
 	}
 }
 
@@ -135,9 +232,13 @@ impl Act for Copy {
 				&to.display()
 			)
 		}
-		std::fs::copy(from, to)
-			.with_context(|| "Failed to copy file")
-			.map_or(Ok(None), |_| Ok(Some(from.into())))
+		if *self.if_exists() == ConflictOption::Merge && from.is_dir() && to.is_dir() {
+			self.merge(from, &to, |from, to| std::fs::copy(from, to).map(|_| ()), false)?;
+			return Ok(Some(from.into()));
+		}
+		std::fs::copy(from, &to)
+			.with_context(|| format!("could not copy {} to {}", from.display(), to.display()))
+			.map(|_| Some(from.into()))
 	}
 }
 
@@ -149,16 +250,14 @@ impl Act for Hardlink {
 	{
 		let to = to.unwrap().into();
 		let from = from.as_ref();
-		if !self.allow_cycles {
-			if to.parent().unwrap() == from.parent().unwrap() {
-				bail!(
-					"Origin {} and target {} paths are inside the same folder, but cycles are not allowed",
-					from.display(),
-					to.display()
-				)
-			}
+		if !self.allow_cycles && to.parent().unwrap() == from.parent().unwrap() {
+			bail!(
+				"Origin {} and target {} paths are inside the same folder, but cycles are not allowed",
+				from.display(),
+				to.display()
+			)
 		}
-		std::fs::hard_link(&from, &to)
+		std::fs::hard_link(from, &to)
 			.with_context(|| format!("could not create hardlink ({} -> {})", from.display(), to.display()))
 			.map(|_| Some(from.into()))
 	}
@@ -173,14 +272,12 @@ impl Act for Symlink {
 	{
 		let to = to.unwrap().into();
 		let from = from.as_ref();
-		if !self.allow_cycles {
-			if to.parent().unwrap() == from.parent().unwrap() {
-				bail!(
-					"Origin {} and target {} paths are inside the same folder, but cycles are not allowed",
-					from.display(),
-					to.display()
-				)
-			}
+		if !self.allow_cycles && to.parent().unwrap() == from.parent().unwrap() {
+			bail!(
+				"Origin {} and target {} paths are inside the same folder, but cycles are not allowed",
+				from.display(),
+				to.display()
+			)
 		}
 		std::os::unix::fs::symlink(from, &to)
 			.with_context(|| format!("could not create symlink ({} -> {})", from.display(), to.display()))
@@ -189,28 +286,325 @@ impl Act for Symlink {
 }
 
 impl Inner {
-	fn prepare_path<T>(&self, path: T) -> Option<PathBuf>
+	/// Refuses the options that would change the source of a link, since both are the same file
+	fn for_link(self) -> Result<Self> {
+		if self.zone_identifier == ZoneIdentifierOption::Strip {
+			bail!("`zone_identifier = \"strip\"` can't be used to link files, it would strip it from the source too");
+		}
+		Ok(self)
+	}
+
+	fn prepare_path<T>(&self, path: T) -> Option<Reservation>
 	where
 		T: AsRef<Path>,
 	{
 		let path = path.as_ref();
-		let mut to = match self.to.to_string_lossy().expand_placeholders(path) {
-			Ok(str) => PathBuf::from(str),
-			Err(e) => {
+		let mut to = self.render(path)?;
+
+		if to.extension().is_none() || to.is_dir() {
+			to.push(path.file_name()?)
+		}
+
+		self.resolve(path, to, self.policy(path))
+	}
+
+	/// Refuses to send `path` anywhere while another process has it open, if `skip_open` is set
+	pub(crate) fn check_open(&self, path: &Path) -> Result<()> {
+		if self.skip_open && in_use::is_open(path) {
+			bail!("skipping {}, another process has it open", path.display());
+		}
+		Ok(())
+	}
+
+	/// Renders the first destination in the chain `to` -> `fallback` that expands to a non-empty path
+	fn render(&self, path: &Path) -> Option<PathBuf> {
+		match self.try_render(path) {
+			Ok(to) => Some(to),
+			Err((e, reason)) => {
 				log::error!("{:?}", e);
-				return None;
+				events::skip(path, reason);
+				None
 			}
+		}
+	}
+
+	/// Like [`render`](Self::render), returning why nothing could be rendered instead of reporting it
+	fn try_render(&self, path: &Path) -> result::Result<PathBuf, (anyhow::Error, SkipReason)> {
+		let mut last_error = None;
+		for template in std::iter::once(&self.to.primary().to).chain(self.fallback.iter()) {
+			let rendered = profile::time(|| Stage::Template, || template.to_string_lossy().expand_placeholders(path))
+				.and_then(Expand::expand_user)
+				.map(|to| match &self.sanitize {
+					Some(sanitize) => sanitize.sanitize_path(&to, template),
+					None => to,
+				});
+			match rendered {
+				Ok(to) if to.as_os_str().is_empty() => {
+					log::debug!("{} rendered an empty destination for {}", template.display(), path.display())
+				}
+				Ok(to) => match to.validate_destination(template).and_then(|()| sandbox::confine(to)) {
+					Ok(to) => return Ok(to),
+					Err(e) => {
+						log::debug!("refused to render {} for {}: {:?}", template.display(), path.display(), e);
+						last_error = Some((e, SkipReason::ProtectedPath));
+					}
+				},
+				Err(e) => {
+					log::debug!("could not render {} for {}: {:?}", template.display(), path.display(), e);
+					last_error = Some((e, SkipReason::Error));
+				}
+			}
+		}
+		Err(last_error.unwrap_or_else(|| (anyhow!("could not render a destination for {}", path.display()), SkipReason::Error)))
+	}
+
+	/// Where `path` would be sent, to the first destination and as a copy to the others, without sending it anywhere.
+	/// The destinations that can't be rendered are left out, the file won't be sent there either.
+	pub(crate) fn transfers(&self, path: &Path, moving: bool) -> Vec<Transfer> {
+		let transfer = |to, moving| Transfer {
+			to,
+			margin: self.free_space_margin,
+			moving,
+		};
+		self.try_render(path)
+			.ok()
+			.map(|to| transfer(to, moving))
+			.into_iter()
+			.chain(
+				self.to
+					.replicas()
+					.iter()
+					.filter_map(|destination| self.render_replica(path, &destination.to).ok())
+					.map(|to| transfer(to, false)),
+			)
+			.collect()
+	}
+
+	/// The conflict policy that applies to `from`.
+	/// Merging only makes sense for directories, so files fall back to `merge_conflicts`.
+	fn policy(&self, from: &Path) -> &ConflictOption {
+		match self.if_exists() {
+			ConflictOption::Merge if !from.is_dir() => &self.merge_conflicts,
+			if_exists => if_exists,
+		}
+	}
+
+	/// The conflict policy of the first destination
+	fn if_exists(&self) -> &ConflictOption {
+		self.to.primary().if_exists.as_ref().unwrap_or(&self.if_exists)
+	}
+
+	/// Prepares a copy of `from` next to every other destination, before it's sent to the first one.
+	/// Nothing is put in place yet, see [`place_replicas`](Self::place_replicas).
+	/// With `replication = "all_or_nothing"`, the copies are dropped as soon as one of them can't be made.
+	fn replicate(&self, from: &Path, ty: ActionType) -> Result<Vec<Replica>> {
+		let mut replicas = Vec::with_capacity(self.to.replicas().len());
+		for destination in self.to.replicas() {
+			match self.replicate_to(from, destination, ty) {
+				Ok(Some(replica)) => replicas.push(replica),
+				Ok(None) => {}
+				Err(e) if self.replication == Replication::BestEffort => log::error!("{:?}", e),
+				Err(e) => return Err(e.context(format!("{} was left in place, since it couldn't be sent everywhere", from.display()))),
+			}
+		}
+		Ok(replicas)
+	}
+
+	/// Where `template`, one of the other destinations, sends `from`
+	fn render_replica(&self, from: &Path, template: &Path) -> Result<PathBuf> {
+		let to = template.to_string_lossy().expand_placeholders(from)?.expand_user()?;
+		let to = match &self.sanitize {
+			Some(sanitize) => sanitize.sanitize_path(&to, template),
+			None => to,
 		};
+		to.validate_destination(template)?;
+		sandbox::confine(to)
+	}
 
+	/// Copies or links `from` next to `destination`, returning the copy, or `None` if nothing should be left there
+	fn replicate_to(&self, from: &Path, destination: &Destination, ty: ActionType) -> Result<Option<Replica>> {
+		let mut to = self.render_replica(from, &destination.to)?;
 		if to.extension().is_none() || to.is_dir() {
-			to.push(path.file_name()?)
+			to.push(from.file_name().unwrap_or_default());
 		}
+		let policy = destination.if_exists.as_ref().unwrap_or(&self.if_exists);
+		if let (ConflictOption::Overwrite, Some(condition), true) = (policy, &self.overwrite_if, to.exists()) {
+			if !condition.holds(from, &to)? {
+				log::info!(
+					"({}) {} does not satisfy `overwrite_if = {}` against {}, no copy was left there",
+					ty,
+					from.display(),
+					condition,
+					to.display()
+				);
+				return Ok(None);
+			}
+		}
+		let to = match to.clone().resolve_naming_conflict(policy) {
+			Some(to) => to,
+			None => {
+				log::info!("({}) {} already exists, no copy of {} was left there", ty, to.display(), from.display());
+				return Ok(None);
+			}
+		};
+		let parent = to.parent().unwrap_or_else(|| Path::new("."));
+		std::fs::create_dir_all(parent).with_context(|| format!("could not create {}", parent.display()))?;
+		let staging = tempfile::Builder::new()
+			.prefix(".organize-replica-")
+			.tempdir_in(parent)
+			.with_context(|| format!("could not prepare a copy of {} in {}", from.display(), parent.display()))?;
+		let replica = Replica { staging, to };
+		let staged = replica.staged();
+		let replicated = match ty {
+			ActionType::Hardlink => std::fs::hard_link(from, &staged),
+			#[cfg(unix)]
+			ActionType::Symlink => std::os::unix::fs::symlink(from, &staged),
+			_ if from.is_dir() => copy_dir(from, &staged),
+			_ => std::fs::copy(from, &staged).map(|_| ()),
+		};
+		replicated.with_context(|| format!("could not send {} to {}", from.display(), replica.to.display()))?;
+		Ok(Some(replica))
+	}
 
-		match to.exists() {
-			true => to.resolve_naming_conflict(&self.if_exists),
-			false => Some(to),
+	/// Puts the copies prepared by `replicate` in place, then logs and records them
+	fn place_replicas(&self, from: &Path, replicas: Vec<Replica>, ty: ActionType) {
+		// the file itself goes to the first destination, only copies are left in the others
+		let ty = match ty {
+			ActionType::Move => ActionType::Copy,
+			ty => ty,
+		};
+		for replica in replicas {
+			if let Err(e) = replica.place() {
+				log::error!("{:?}", e);
+				continue;
+			}
+			log::info!(
+				"{}",
+				messages::format(
+					Message::Acted,
+					&[("action", &ty), ("from", &from.display()), ("to", &replica.to.display())]
+				)
+			);
+			events::record(Event::Acted {
+				action: ty,
+				from: from.to_path_buf(),
+				to: Some(replica.to.to_path_buf()),
+				output: None,
+			});
 		}
 	}
+
+	fn resolve(&self, from: &Path, to: PathBuf, policy: &ConflictOption) -> Option<Reservation> {
+		if let (ConflictOption::Overwrite, Some(condition)) = (policy, &self.overwrite_if) {
+			if to.exists() {
+				match condition.holds(from, &to) {
+					Ok(true) => {}
+					Ok(false) => {
+						log::info!(
+							"(skip) {} does not satisfy `overwrite_if = {}` against {}",
+							from.display(),
+							condition,
+							to.display()
+						);
+						events::record(Event::Conflict {
+							from: from.to_path_buf(),
+							to,
+						});
+						events::skip(from, SkipReason::ConflictSkip);
+						return None;
+					}
+					Err(e) => {
+						log::error!("{:?}", e);
+						return None;
+					}
+				}
+			}
+		}
+
+		let reservation = to.clone().resolve_naming_conflict(policy);
+		if reservation.is_none() {
+			events::record(Event::Conflict {
+				from: from.to_path_buf(),
+				to,
+			});
+			events::skip(from, SkipReason::ConflictSkip);
+		}
+		reservation
+	}
+
+	/// Recursively merges the contents of `from` into the existing directory `to`,
+	/// resolving conflicts between files according to `merge_conflicts`. The emptied directories of `from` are removed if `moving`.
+	fn merge(&self, from: &Path, to: &Path, transfer: fn(&Path, &Path) -> std::io::Result<()>, moving: bool) -> Result<()> {
+		let create = |dir: &Path| std::fs::create_dir(dir).with_context(|| format!("could not create {}", dir.display()));
+		for entry in std::fs::read_dir(from).with_context(|| format!("could not read {}", from.display()))? {
+			let src = entry?.path();
+			let dest = to.join(src.file_name().unwrap());
+			if src.is_dir() && !dest.exists() {
+				create(&dest)?;
+			}
+			if src.is_dir() && dest.is_dir() {
+				self.merge(&src, &dest, transfer, moving)?;
+				continue;
+			}
+			match self.resolve(&src, dest, &self.merge_conflicts) {
+				// a file is in the way of the directory
+				Some(dest) if src.is_dir() => {
+					create(&dest)?;
+					self.merge(&src, &dest, transfer, moving)?
+				}
+				Some(dest) => transfer(&src, &dest).with_context(|| format!("could not merge {} into {}", src.display(), dest.display()))?,
+				None if self.merge_conflicts == ConflictOption::Delete && src.is_file() => {
+					std::fs::remove_file(&src).with_context(|| format!("could not delete {}", src.display()))?
+				}
+				None => {}
+			}
+		}
+		// anything left in the source is what was skipped, so it's only removed if it's empty
+		if moving && std::fs::read_dir(from).map(|mut dir| dir.next().is_none()).unwrap_or_default() {
+			let _ = std::fs::remove_dir(from);
+		}
+		Ok(())
+	}
+}
+
+/// A copy of a file waiting next to the destination it's meant for, in a directory that's removed when it's dropped.
+/// Whatever is already at the destination is only replaced once the copy is put in place.
+struct Replica {
+	staging: TempDir,
+	to: Reservation,
+}
+
+impl Replica {
+	fn staged(&self) -> PathBuf {
+		self.staging.path().join("copy")
+	}
+
+	fn place(&self) -> Result<()> {
+		let (staged, to) = (self.staged(), self.to.as_ref());
+		// a directory can't be renamed over something else or be replaced by it,
+		// so what it replaces is set aside and dropped with the staging directory
+		let replaced = self.staging.path().join("replaced");
+		let set_aside = (to.is_dir() || staged.is_dir()) && to.symlink_metadata().is_ok() && std::fs::rename(to, &replaced).is_ok();
+		std::fs::rename(&staged, to).or_else(|e| {
+			if set_aside {
+				let _ = std::fs::rename(&replaced, to);
+			}
+			Err(e).with_context(|| format!("could not put the copy in {}", to.display()))
+		})
+	}
+}
+
+/// Copies the directory `from` and everything inside it to `to`
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+	for entry in WalkDir::new(from) {
+		let entry = entry?;
+		let dest = to.join(entry.path().strip_prefix(from).unwrap_or(entry.path()));
+		match entry.file_type().is_dir() {
+			true => std::fs::create_dir_all(&dest)?,
+			false => std::fs::copy(entry.path(), &dest).map(|_| ())?,
+		}
+	}
+	Ok(())
 }
 
 impl TryFrom<PathBuf> for Inner {
@@ -218,9 +612,17 @@ impl TryFrom<PathBuf> for Inner {
 
 	fn try_from(value: PathBuf) -> result::Result<Self, Self::Error> {
 		let action = Self {
-			to: value.expand_user()?.expand_vars()?,
+			to: value.expand_user()?.expand_vars()?.into(),
+			replication: Replication::default(),
+			fallback: Vec::new(),
 			if_exists: Default::default(),
+			overwrite_if: None,
+			merge_conflicts: Default::default(),
+			free_space_margin: 0,
+			sanitize: None,
+			zone_identifier: Default::default(),
 			allow_cycles: false,
+			skip_open: false,
 		};
 		Ok(action)
 	}
@@ -245,6 +647,8 @@ pub enum ConflictOption {
 	#[default]
 	Rename,
 	Delete,
+	/// merge the contents of a directory into an existing one
+	Merge,
 }
 
 impl FromStr for ConflictOption {
@@ -255,9 +659,262 @@ impl FromStr for ConflictOption {
 			"delete" => Self::Delete,
 			"overwrite" => Self::Overwrite,
 			"skip" => Self::Skip,
+			"merge" => Self::Merge,
 			"rename" => Self::default(),
 			_ => panic!("Unknown option"),
 		};
 		Ok(variant)
 	}
 }
+
+#[derive(Eq, PartialEq, Default, Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "lowercase", deserialize = "lowercase"))]
+pub enum ZoneIdentifierOption {
+	#[default]
+	Preserve,
+	Strip,
+}
+
+/// Restricts `if_exists = "overwrite"` to the cases where the source is an improvement over the existing destination.
+/// If the condition does not hold, the file is skipped.
+#[derive(Eq, PartialEq, Debug, Clone, Deserialize, Serialize, Display)]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+#[strum(serialize_all = "snake_case")]
+pub enum OverwriteIf {
+	/// the source was modified more recently than the destination
+	Newer,
+	/// the source is bigger than the destination
+	Larger,
+	/// the source and the destination have different contents
+	DifferentHash,
+}
+
+impl OverwriteIf {
+	pub fn holds<T: AsRef<Path>, P: AsRef<Path>>(&self, from: T, to: P) -> Result<bool> {
+		let (from, to) = (from.as_ref(), to.as_ref());
+		let metadata = |path: &Path| std::fs::metadata(path).with_context(|| format!("could not read metadata of {}", path.display()));
+		let (src, dest) = (metadata(from)?, metadata(to)?);
+		match self {
+			Self::Newer => Ok(src.modified()? > dest.modified()?),
+			Self::Larger => Ok(src.len() > dest.len()),
+			Self::DifferentHash => {
+				if src.len() != dest.len() {
+					return Ok(true);
+				}
+				Ok(!same_contents(from, to)?)
+			}
+		}
+	}
+}
+
+/// Whether the files at `from` and `to` have the same contents, reading them a chunk at a time
+fn same_contents(from: &Path, to: &Path) -> Result<bool> {
+	let open = |path: &Path| {
+		std::fs::File::open(path)
+			.map(|file| BufReader::with_capacity(64 * 1024, file))
+			.with_context(|| format!("could not open {}", path.display()))
+	};
+	let (mut src, mut dest) = (open(from)?, open(to)?);
+	loop {
+		let a = src.fill_buf().with_context(|| format!("could not read {}", from.display()))?;
+		let b = dest.fill_buf().with_context(|| format!("could not read {}", to.display()))?;
+		let len = a.len().min(b.len());
+		if len == 0 {
+			return Ok(a.len() == b.len());
+		}
+		if a[..len] != b[..len] {
+			return Ok(false);
+		}
+		src.consume(len);
+		dest.consume(len);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn strip_zone_identifier_only_of_copies() {
+		let action = |ty: &str| {
+			toml::from_str::<crate::config::actions::Action>(&format!("type = \"{}\"\nto = \"/tmp/downloads/\"\nzone_identifier = \"strip\"", ty))
+		};
+		assert!(action("move").is_ok());
+		assert!(action("copy").is_ok());
+		assert!(action("hardlink").is_err());
+		assert!(action("symlink").is_err());
+	}
+
+	#[test]
+	fn render_fallback() {
+		let inner = Inner {
+			to: PathBuf::from("/tmp/{extension}").into(),
+			fallback: vec![PathBuf::from("/tmp/{parent.filename}"), PathBuf::from("/tmp/unsorted")],
+			..Inner::default()
+		};
+		assert_eq!(inner.render(Path::new("/home/test.pdf")), Some(PathBuf::from("/tmp/pdf")));
+		assert_eq!(inner.render(Path::new("/home/test")), Some(PathBuf::from("/tmp/home")));
+		assert_eq!(inner.render(Path::new("test")), Some(PathBuf::from("/tmp/unsorted")));
+	}
+
+	#[test]
+	fn replicate_all_or_nothing() {
+		let dir = tempfile::tempdir().unwrap();
+		let file = dir.path().join("in").join("a.txt");
+		std::fs::create_dir(dir.path().join("in")).unwrap();
+		std::fs::write(&file, "a").unwrap();
+		// a file can't have anything inside it
+		std::fs::write(dir.path().join("blocker"), "").unwrap();
+		let (docs, backup, unreachable) = (dir.path().join("docs"), dir.path().join("backup"), dir.path().join("blocker").join("nas"));
+		let action: Move = toml::from_str(&format!(
+			"to = [\"{}/\", \"{}/\", \"{}/\"]",
+			docs.display(),
+			backup.display(),
+			unreachable.display()
+		))
+		.unwrap();
+		assert_eq!(action.process(&file), None);
+		assert!(file.exists());
+		assert!(!backup.join("a.txt").exists());
+
+		let action = Move(Inner {
+			replication: Replication::BestEffort,
+			..action.0
+		});
+		assert_eq!(action.process(&file), Some(docs.join("a.txt")));
+		assert_eq!(std::fs::read_to_string(backup.join("a.txt")).unwrap(), "a");
+	}
+
+	#[test]
+	fn replicas_replace_only_once_sent() {
+		let dir = tempfile::tempdir().unwrap();
+		let (inbox, backup) = (dir.path().join("in"), dir.path().join("backup"));
+		std::fs::create_dir_all(&inbox).unwrap();
+		std::fs::create_dir_all(&backup).unwrap();
+		let file = inbox.join("a.txt");
+		std::fs::write(&file, "new").unwrap();
+		std::fs::write(backup.join("a.txt"), "old").unwrap();
+		// moving the file into its own folder is refused, after the copy was prepared
+		let action: Move = toml::from_str(&format!(
+			"to = [\"{}/\", \"{}/\"]\nif_exists = \"overwrite\"",
+			inbox.display(),
+			backup.display()
+		))
+		.unwrap();
+		assert_eq!(action.process(&file), None);
+		assert_eq!(std::fs::read_to_string(backup.join("a.txt")).unwrap(), "old");
+		assert_eq!(std::fs::read_dir(&backup).unwrap().count(), 1);
+
+		let action = Move(Inner {
+			allow_cycles: true,
+			..action.0
+		});
+		assert_eq!(action.process(&file), Some(file.clone()));
+		assert_eq!(std::fs::read_to_string(backup.join("a.txt")).unwrap(), "new");
+		assert_eq!(std::fs::read_dir(&backup).unwrap().count(), 1);
+	}
+
+	#[test]
+	fn replicas_overwrite_if() {
+		let dir = tempfile::tempdir().unwrap();
+		let (docs, backup) = (dir.path().join("docs"), dir.path().join("backup"));
+		std::fs::create_dir_all(dir.path().join("in")).unwrap();
+		std::fs::create_dir_all(&backup).unwrap();
+		let file = dir.path().join("in").join("a.txt");
+		std::fs::write(&file, "a").unwrap();
+		std::fs::write(backup.join("a.txt"), "larger").unwrap();
+		let action: Move = toml::from_str(&format!(
+			"to = [\"{}/\", \"{}/\"]\nif_exists = \"overwrite\"\noverwrite_if = \"larger\"",
+			docs.display(),
+			backup.display()
+		))
+		.unwrap();
+		assert_eq!(action.process(&file), Some(docs.join("a.txt")));
+		assert_eq!(std::fs::read_to_string(backup.join("a.txt")).unwrap(), "larger");
+	}
+
+	#[test]
+	fn replicate_directories() {
+		let dir = tempfile::tempdir().unwrap();
+		let (docs, backup) = (dir.path().join("docs"), dir.path().join("backup"));
+		let photos = dir.path().join("in").join("photos");
+		std::fs::create_dir_all(photos.join("2023")).unwrap();
+		std::fs::write(photos.join("2023").join("a.jpg"), "a").unwrap();
+		let action: Move = toml::from_str(&format!("to = [\"{}/\", \"{}/\"]", docs.display(), backup.display())).unwrap();
+		assert_eq!(action.process(&photos), Some(docs.join("photos")));
+		assert!(docs.join("photos").join("2023").join("a.jpg").exists());
+		assert_eq!(std::fs::read_to_string(backup.join("photos").join("2023").join("a.jpg")).unwrap(), "a");
+	}
+
+	#[test]
+	fn merge_directories() {
+		let dir = tempfile::tempdir().unwrap();
+		let (src, dest) = (dir.path().join("src"), dir.path().join("dest"));
+		std::fs::create_dir_all(src.join("nested")).unwrap();
+		std::fs::create_dir_all(dest.join("nested")).unwrap();
+		std::fs::create_dir_all(src.join("fresh").join("deeper")).unwrap();
+		std::fs::write(src.join("new.txt"), "").unwrap();
+		std::fs::write(src.join("fresh").join("deeper").join("inner.txt"), "").unwrap();
+		std::fs::write(src.join("nested").join("conflict.txt"), "src").unwrap();
+		std::fs::write(dest.join("nested").join("conflict.txt"), "dest").unwrap();
+
+		let action = Move(Inner {
+			if_exists: ConflictOption::Merge,
+			allow_cycles: true,
+			..Inner::default()
+		});
+		action.act(&src, Some(&dest)).unwrap();
+		assert!(!src.exists());
+		assert!(dest.join("new.txt").exists());
+		assert_eq!(std::fs::read_to_string(dest.join("nested").join("conflict.txt")).unwrap(), "dest");
+		assert_eq!(std::fs::read_to_string(dest.join("nested").join("conflict (1).txt")).unwrap(), "src");
+		assert!(dest.join("fresh").join("deeper").join("inner.txt").exists());
+	}
+
+	#[test]
+	fn merge_copies_keep_source() {
+		let dir = tempfile::tempdir().unwrap();
+		let (src, dest) = (dir.path().join("src"), dir.path().join("dest"));
+		std::fs::create_dir_all(src.join("fresh")).unwrap();
+		std::fs::create_dir_all(&dest).unwrap();
+		std::fs::write(src.join("fresh").join("inner.txt"), "src").unwrap();
+
+		let action = Copy(Inner {
+			if_exists: ConflictOption::Merge,
+			allow_cycles: true,
+			..Inner::default()
+		});
+		action.act(&src, Some(&dest)).unwrap();
+		assert_eq!(std::fs::read_to_string(dest.join("fresh").join("inner.txt")).unwrap(), "src");
+		assert_eq!(std::fs::read_to_string(src.join("fresh").join("inner.txt")).unwrap(), "src");
+	}
+
+	#[test]
+	fn overwrite_if_larger() {
+		let dir = tempfile::tempdir().unwrap();
+		let (small, large) = (dir.path().join("small.txt"), dir.path().join("large.txt"));
+		std::fs::write(&small, "a").unwrap();
+		std::fs::write(&large, "abc").unwrap();
+		assert!(OverwriteIf::Larger.holds(&large, &small).unwrap());
+		assert!(!OverwriteIf::Larger.holds(&small, &large).unwrap());
+	}
+
+	#[test]
+	fn overwrite_if_different_hash() {
+		let dir = tempfile::tempdir().unwrap();
+		let (first, second, third) = (dir.path().join("1.txt"), dir.path().join("2.txt"), dir.path().join("3.txt"));
+		std::fs::write(&first, "abc").unwrap();
+		std::fs::write(&second, "abc").unwrap();
+		std::fs::write(&third, "abd").unwrap();
+		assert!(!OverwriteIf::DifferentHash.holds(&first, &second).unwrap());
+		assert!(OverwriteIf::DifferentHash.holds(&first, &third).unwrap());
+		// the same size, with a difference past the first chunk
+		let (large, changed) = (dir.path().join("large.bin"), dir.path().join("changed.bin"));
+		let mut content = vec![7u8; 200 * 1024];
+		std::fs::write(&large, &content).unwrap();
+		assert!(!OverwriteIf::DifferentHash.holds(&large, &large).unwrap());
+		content[150 * 1024] = 8;
+		std::fs::write(&changed, &content).unwrap();
+		assert!(OverwriteIf::DifferentHash.holds(&large, &changed).unwrap());
+	}
+}
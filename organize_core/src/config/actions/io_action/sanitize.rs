@@ -0,0 +1,238 @@
+use std::{
+	ffi::{OsStr, OsString},
+	path::{Path, PathBuf},
+};
+
+use deunicode::deunicode;
+use serde::{Deserialize, Serialize};
+
+use crate::path::{is_reserved, static_prefix};
+
+/// Rewrites the parts of a rendered destination that came from placeholders,
+/// so that names built from metadata are always valid on the target filesystem
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq, Default)]
+#[serde(default)]
+pub struct Sanitize {
+	pub filesystem: Filesystem,
+	/// the string that replaces invalid characters
+	pub replacement: String,
+	/// transliterate non-ASCII characters into their closest ASCII representation
+	pub transliterate: bool,
+	/// collapse consecutive whitespace into a single space
+	pub collapse_whitespace: bool,
+}
+
+#[derive(Eq, PartialEq, Default, Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "lowercase", deserialize = "lowercase"))]
+pub enum Filesystem {
+	#[default]
+	Ntfs,
+	Exfat,
+	Ext4,
+}
+
+impl Filesystem {
+	fn is_invalid(&self, c: char) -> bool {
+		match self {
+			Self::Ntfs | Self::Exfat => c.is_control() || matches!(c, '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*'),
+			Self::Ext4 => c == '/' || c == '\0',
+		}
+	}
+}
+
+impl Sanitize {
+	pub fn sanitize_component(&self, component: &str) -> String {
+		let component = match self.transliterate {
+			true => deunicode(component),
+			false => component.to_string(),
+		};
+		let replacement = match self.replacement.chars().any(|c| self.filesystem.is_invalid(c)) {
+			true => "_",
+			false => self.replacement.as_str(),
+		};
+		let mut sanitized = String::with_capacity(component.len());
+		for c in component.chars() {
+			if self.filesystem.is_invalid(c) {
+				sanitized.push_str(replacement);
+			} else if self.collapse_whitespace && c.is_whitespace() {
+				if !sanitized.ends_with(' ') {
+					sanitized.push(' ');
+				}
+			} else {
+				sanitized.push(c);
+			}
+		}
+		if self.collapse_whitespace {
+			sanitized = sanitized.trim().to_string();
+		}
+		if self.filesystem == Filesystem::Ntfs {
+			// windows silently drops trailing dots and spaces
+			sanitized = sanitized.trim_end_matches(['.', ' ']).to_string();
+		}
+		// a name can't be empty, and `.` or `..` would point somewhere else entirely
+		if matches!(sanitized.as_str(), "" | "." | "..") {
+			sanitized = match replacement.trim_end_matches(['.', ' ']) {
+				"" => "_".into(),
+				replacement => replacement.to_string(),
+			};
+		}
+		// device names like `con` or `aux` can't be used on Windows volumes, whatever the system writing to them
+		if matches!(self.filesystem, Filesystem::Ntfs | Filesystem::Exfat) && is_reserved(&sanitized) {
+			let at = sanitized.find('.').unwrap_or(sanitized.len());
+			sanitized.insert_str(at, if replacement.is_empty() { "_" } else { replacement });
+		}
+		sanitized
+	}
+
+	/// Sanitizes a component that might not be valid UTF-8. ext4 accepts any bytes in a name so they're kept,
+	/// but NTFS and exFAT names are UTF-16, so the invalid bytes are replaced.
+	fn sanitize_os(&self, component: &OsStr) -> OsString {
+		match (component.to_str(), self.filesystem) {
+			(Some(component), _) => self.sanitize_component(component).into(),
+			(None, Filesystem::Ext4) => component.to_os_string(),
+			(None, _) => {
+				let component = component.to_string_lossy();
+				log::warn!("{} is not valid UTF-8, its invalid bytes were replaced", component);
+				self.sanitize_component(&component).into()
+			}
+		}
+	}
+
+	/// Sanitizes every component of `path` that comes after the static part of `template`
+	pub fn sanitize_path<T: AsRef<Path>>(&self, path: &Path, template: T) -> PathBuf {
+		let prefix = static_prefix(template.as_ref());
+		match path.strip_prefix(&prefix) {
+			Ok(rest) => rest
+				.components()
+				.map(|comp| self.sanitize_os(comp.as_os_str()))
+				.fold(prefix, |path, comp| path.join(comp)),
+			Err(_) => path.to_path_buf(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use proptest::prelude::*;
+
+	use super::*;
+
+	#[test]
+	fn sanitize_ntfs() {
+		let sanitize = Sanitize {
+			replacement: "_".into(),
+			collapse_whitespace: true,
+			..Sanitize::default()
+		};
+		assert_eq!(sanitize.sanitize_component("what?  a: file. "), "what_ a_ file");
+	}
+
+	#[test]
+	fn sanitize_reserved_names() {
+		let sanitize = Sanitize::default();
+		assert_eq!(sanitize.sanitize_component("con.txt"), "con_.txt");
+		assert_eq!(sanitize.sanitize_component("aux"), "aux_");
+		assert_eq!(sanitize.sanitize_component("console.txt"), "console.txt");
+		let ext4 = Sanitize {
+			filesystem: Filesystem::Ext4,
+			..Sanitize::default()
+		};
+		assert_eq!(ext4.sanitize_component("con.txt"), "con.txt");
+	}
+
+	#[test]
+	fn sanitize_ext4() {
+		let sanitize = Sanitize {
+			filesystem: Filesystem::Ext4,
+			..Sanitize::default()
+		};
+		assert_eq!(sanitize.sanitize_component("what?  a: file"), "what?  a: file");
+	}
+
+	#[test]
+	fn transliterate() {
+		let sanitize = Sanitize {
+			transliterate: true,
+			..Sanitize::default()
+		};
+		assert_eq!(sanitize.sanitize_component("Café Zürich"), "Cafe Zurich");
+	}
+
+	#[test]
+	fn sanitize_only_rendered_components() {
+		let sanitize = Sanitize {
+			replacement: "-".into(),
+			..Sanitize::default()
+		};
+		let path = Path::new("/home/a:b/Artist: Name/Song?.mp3");
+		assert_eq!(
+			sanitize.sanitize_path(path, "/home/a:b/{parent.filename}/{filename}"),
+			PathBuf::from("/home/a:b/Artist- Name/Song-.mp3")
+		);
+	}
+
+	#[test]
+	fn sanitize_dots() {
+		let sanitize = Sanitize {
+			filesystem: Filesystem::Ext4,
+			..Sanitize::default()
+		};
+		assert_eq!(sanitize.sanitize_component(".."), "_");
+		assert_eq!(Sanitize::default().sanitize_component("..."), "_");
+		assert_eq!(
+			sanitize.sanitize_path(Path::new("/music/../etc"), "/music/{filename}"),
+			PathBuf::from("/music/_/etc")
+		);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn sanitize_invalid_utf8() {
+		use std::os::unix::ffi::OsStrExt;
+
+		let path = Path::new(OsStr::from_bytes(b"/music/caf\xe9: live.mp3"));
+		let ext4 = Sanitize {
+			filesystem: Filesystem::Ext4,
+			..Sanitize::default()
+		};
+		assert_eq!(ext4.sanitize_path(path, "/music/{filename}"), path);
+		let ntfs = Sanitize {
+			replacement: "-".into(),
+			..Sanitize::default()
+		};
+		assert_eq!(
+			ntfs.sanitize_path(path, "/music/{filename}"),
+			PathBuf::from("/music/caf\u{fffd}- live.mp3")
+		);
+	}
+
+	fn sanitizers() -> impl Strategy<Value = Sanitize> {
+		let filesystem = prop_oneof![Just(Filesystem::Ntfs), Just(Filesystem::Exfat), Just(Filesystem::Ext4)];
+		let replacement = prop_oneof![Just(""), Just("_"), Just("."), Just(" "), Just("/"), Just(" - ")].prop_map(String::from);
+		(filesystem, replacement, any::<bool>(), any::<bool>()).prop_map(|(filesystem, replacement, transliterate, collapse_whitespace)| Sanitize {
+			filesystem,
+			replacement,
+			transliterate,
+			collapse_whitespace,
+		})
+	}
+
+	proptest! {
+		#[test]
+		fn sanitized_components_are_valid_names(sanitize in sanitizers(), component in "\\PC{0,16}") {
+			let sanitized = sanitize.sanitize_component(&component);
+			prop_assert!(!matches!(sanitized.as_str(), "" | "." | ".."));
+			prop_assert!(!sanitized.chars().any(|c| sanitize.filesystem.is_invalid(c)));
+		}
+
+		#[test]
+		fn sanitized_paths_stay_below_the_prefix(sanitize in sanitizers(), rest in "[a-z./ :?]{0,16}") {
+			let path = Path::new("/music").join(&rest);
+			// a rest starting with `/` replaces the whole path
+			prop_assume!(path.starts_with("/music"));
+			let sanitized = sanitize.sanitize_path(&path, "/music/{filename}");
+			prop_assert!(sanitized.starts_with("/music"));
+			prop_assert!(!sanitized.components().any(|c| c == std::path::Component::ParentDir));
+		}
+	}
+}
@@ -0,0 +1,284 @@
+#[cfg(any(test, feature = "test-util"))]
+use std::cell::RefCell;
+use std::{
+	path::PathBuf,
+	sync::{Arc, RwLock},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{
+	config::{
+		actions::{confirm, AsAction, Step},
+		options::apply::Apply,
+	},
+	elevation,
+	events::{self, SkipReason},
+	journal,
+	messages::{self, Message},
+	throttle,
+};
+
+lazy_static! {
+	/// the limits of the rule, the options of a step and what every action goes through, outermost first
+	static ref BUILT_IN: Vec<Arc<dyn Middleware>> = vec![
+		Arc::new(Throttle),
+		Arc::new(When),
+		Arc::new(DryRun),
+		Arc::new(Confirm),
+		Arc::new(Protect),
+		Arc::new(Journal),
+		Arc::new(Elevate),
+	];
+	/// the middlewares added with [`register`], outermost first
+	static ref REGISTERED: RwLock<Vec<Arc<dyn Middleware>>> = RwLock::new(Vec::new());
+}
+
+#[cfg(any(test, feature = "test-util"))]
+thread_local! {
+	// the middlewares added with [`register_scoped`], inside the registered ones
+	static SCOPED: RefCell<Vec<Arc<dyn Middleware>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Code that runs around every action, deciding whether and how the rest of the chain runs, e.g. to audit what's done:
+///
+/// ```ignore
+/// struct Audit;
+///
+/// impl Middleware for Audit {
+///     fn call(&self, step: &Step, path: PathBuf, next: Next) -> Option<PathBuf> {
+///         let from = path.clone();
+///         let to = next.run(path);
+///         audit(ActionType::from(&step.action), &from, to.as_deref());
+///         to
+///     }
+/// }
+///
+/// middleware::register(Audit);
+/// ```
+///
+/// Everything organize does around an action is a middleware too, and runs first: the rule's `max_concurrency` and
+/// `io_priority`, the options of the step (`when`, `dry_run` and `confirm`), the files protected from the action
+/// (`skip_open` and the guards of `delete` and `trash`), the journal and the retries through the `[elevation]` helper.
+/// The registered ones only see the actions that are carried out, and what they skip isn't written to the journal.
+pub trait Middleware: Send + Sync {
+	/// Returns where the file is afterwards, or `None` if nothing else should be done to it, like the action itself.
+	/// Returning without calling `next` skips the action.
+	fn call(&self, step: &Step, path: PathBuf, next: Next<'_>) -> Option<PathBuf>;
+}
+
+/// The rest of the chain, ending with the action
+pub struct Next<'a> {
+	step: &'a Step,
+	chain: &'a [Arc<dyn Middleware>],
+}
+
+impl Next<'_> {
+	pub fn run(self, path: PathBuf) -> Option<PathBuf> {
+		match self.chain.split_first() {
+			Some((middleware, chain)) => middleware.call(self.step, path, Next { step: self.step, chain }),
+			None => self.step.action.process(path),
+		}
+	}
+}
+
+/// Adds `middleware` around every action carried out from now on, inside the ones registered before it
+pub fn register<M: Middleware + 'static>(middleware: M) {
+	REGISTERED.write().unwrap_or_else(|e| e.into_inner()).push(Arc::new(middleware));
+}
+
+/// Like [`register`], for the actions carried out by this thread until the returned guard is dropped,
+/// so that tests running next to each other don't go through each other's middlewares
+#[cfg(any(test, feature = "test-util"))]
+pub fn register_scoped<M: Middleware + 'static>(middleware: M) -> Scoped {
+	SCOPED.with(|scoped| scoped.borrow_mut().push(Arc::new(middleware)));
+	Scoped
+}
+
+/// Removes the middleware added with [`register_scoped`] when it's dropped
+#[cfg(any(test, feature = "test-util"))]
+pub struct Scoped;
+
+#[cfg(any(test, feature = "test-util"))]
+impl Drop for Scoped {
+	fn drop(&mut self) {
+		SCOPED.with(|scoped| scoped.borrow_mut().pop());
+	}
+}
+
+/// Runs `step` on `path` through the whole chain
+pub(crate) fn run(step: &Step, path: PathBuf) -> Option<PathBuf> {
+	let registered = REGISTERED.read().unwrap_or_else(|e| e.into_inner());
+	let chain = BUILT_IN.iter().chain(registered.iter()).cloned();
+	#[cfg(any(test, feature = "test-util"))]
+	let chain = chain.chain(SCOPED.with(|scoped| scoped.borrow().clone()));
+	let chain = chain.collect::<Vec<_>>();
+	drop(registered);
+	Next { step, chain: &chain }.run(path)
+}
+
+/// Keeps the action within the limits of its rule
+struct Throttle;
+
+impl Middleware for Throttle {
+	fn call(&self, _step: &Step, path: PathBuf, next: Next<'_>) -> Option<PathBuf> {
+		throttle::within_current(|| next.run(path))
+	}
+}
+
+/// Leaves the file alone unless it matches the step's `when` filters
+struct When;
+
+impl Middleware for When {
+	fn call(&self, step: &Step, path: PathBuf, next: Next<'_>) -> Option<PathBuf> {
+		match &step.when {
+			Some(when) if !when.r#match(&path, &Apply::All) => Some(path),
+			_ => next.run(path),
+		}
+	}
+}
+
+/// Only logs what the action would do
+struct DryRun;
+
+impl Middleware for DryRun {
+	fn call(&self, step: &Step, path: PathBuf, next: Next<'_>) -> Option<PathBuf> {
+		if !step.dry_run {
+			return next.run(path);
+		}
+		log::info!(
+			"{}",
+			messages::format(Message::DryRun, &[("action", &step.action.ty()), ("from", &path.display())])
+		);
+		Some(path)
+	}
+}
+
+/// Asks before running the action, or requires `--yes`
+struct Confirm;
+
+impl Middleware for Confirm {
+	fn call(&self, step: &Step, path: PathBuf, next: Next<'_>) -> Option<PathBuf> {
+		let ty = step.action.ty();
+		if !step.confirm || confirm::confirm(&ty, &path) {
+			return next.run(path);
+		}
+		log::info!("{}", messages::format(Message::Declined, &[("action", &ty), ("from", &path.display())]));
+		Some(path)
+	}
+}
+
+/// Leaves the file alone if it's protected from the action
+struct Protect;
+
+impl Middleware for Protect {
+	fn call(&self, step: &Step, path: PathBuf, next: Next<'_>) -> Option<PathBuf> {
+		match step.action.guard(&path) {
+			Ok(()) => next.run(path),
+			Err(e) => {
+				log::warn!("({}) {:?}", step.action.ty(), e);
+				events::skip(&path, SkipReason::ProtectedPath);
+				Some(path)
+			}
+		}
+	}
+}
+
+/// Writes what the action did to the journal, once it's done
+struct Journal;
+
+impl Middleware for Journal {
+	fn call(&self, _step: &Step, path: PathBuf, next: Next<'_>) -> Option<PathBuf> {
+		let (path, entries) = events::acted(|| next.run(path));
+		entries.into_iter().for_each(journal::append);
+		path
+	}
+}
+
+/// Retries the operations the action is refused for lack of permissions through the `[elevation]` helper
+struct Elevate;
+
+impl Middleware for Elevate {
+	fn call(&self, _step: &Step, path: PathBuf, next: Next<'_>) -> Option<PathBuf> {
+		elevation::allowed(|| next.run(path))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::cell::Cell;
+
+	use super::*;
+	use crate::{config::actions::ActionType, events::Event, testing::Tree};
+
+	thread_local! {
+		static SEEN: Cell<usize> = const { Cell::new(0) };
+	}
+
+	/// Counts the actions on files named `audited.txt`, and keeps them from being deleted
+	struct Audit;
+
+	impl Middleware for Audit {
+		fn call(&self, step: &Step, path: PathBuf, next: Next<'_>) -> Option<PathBuf> {
+			if path.file_name().is_none_or(|name| name != "audited.txt") {
+				return next.run(path);
+			}
+			SEEN.with(|seen| seen.set(seen.get() + 1));
+			match step.action.ty() {
+				ActionType::Delete => Some(path),
+				_ => next.run(path),
+			}
+		}
+	}
+
+	#[test]
+	fn wrap_actions() {
+		let _audit = register_scoped(Audit);
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("audited.txt");
+		std::fs::write(&path, "").unwrap();
+		let step = |config: &str| toml::from_str::<Step>(config).unwrap();
+		assert_eq!(run(&step("type = \"delete\""), path.clone()), Some(path.clone()));
+		assert!(path.exists());
+		// dry runs never reach it
+		assert_eq!(run(&step("type = \"delete\"\ndry_run = true"), path.clone()), Some(path.clone()));
+		assert_eq!(SEEN.with(|seen| seen.get()), 1);
+
+		let other = dir.path().join("other.txt");
+		std::fs::write(&other, "").unwrap();
+		assert_eq!(run(&step("type = \"delete\""), other.clone()), None);
+		assert!(!other.exists());
+	}
+
+	#[test]
+	fn journal_what_is_carried_out() {
+		let tree = Tree::new()
+			.file("audited.txt", "")
+			.file("other.txt", "")
+			.file("large.txt", "too large");
+		let outcome = {
+			let _audit = register_scoped(Audit);
+			tree.run(
+				r#"
+				version = 2
+
+				[[rules]]
+				folders = ["{tree}"]
+				filters = []
+				actions = [{ type = "delete", larger_than = 1 }]
+				"#,
+			)
+			.unwrap()
+		};
+		tree.assert_layout(&["audited.txt", "large.txt"]);
+		assert_eq!(outcome.actions(), vec![(ActionType::Delete, "other.txt".to_string(), None)]);
+		assert!(outcome.events.contains(&Event::Skipped {
+			path: tree.path().join("large.txt"),
+			reason: SkipReason::ProtectedPath,
+		}));
+		// the scoped middleware is gone afterwards
+		let other = tree.path().join("audited.txt");
+		assert_eq!(run(&toml::from_str::<Step>("type = \"delete\"").unwrap(), other.clone()), None);
+		assert!(!other.exists());
+	}
+}
@@ -6,21 +6,40 @@ use strum_macros::{Display, EnumString};
 
 use crate::config::{
 	actions::{
+		archive::Archive,
 		delete::Delete,
 		echo::Echo,
+		fetch::Fetch,
 		io_action::{Copy, Hardlink, Move, Symlink},
+		recompress::Recompress,
 		script::Script,
 	},
+	filters::Filters,
 	options::apply::Apply,
 };
 
-use crate::config::actions::delete::Trash;
+#[cfg(feature = "thumbnails")]
+use crate::config::actions::thumbnail::Thumbnail;
+use crate::{
+	capacity::Transfer,
+	config::actions::delete::Trash,
+	profile::{self, Stage},
+};
 use anyhow::Result;
 
+pub(crate) mod archive;
+pub mod confirm;
 pub(crate) mod delete;
 pub(crate) mod echo;
+pub(crate) mod fetch;
 pub(crate) mod io_action;
+pub mod middleware;
+pub(crate) mod recompress;
 pub(crate) mod script;
+#[cfg(feature = "thumbnails")]
+pub(crate) mod thumbnail;
+
+pub use io_action::sanitize;
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 #[serde(tag = "type", rename_all(deserialize = "lowercase"))]
@@ -33,6 +52,11 @@ pub enum Action {
 	Echo(Echo),
 	Trash(Trash),
 	Script(Script),
+	Fetch(Fetch),
+	Archive(Archive),
+	Recompress(Recompress),
+	#[cfg(feature = "thumbnails")]
+	Thumbnail(Thumbnail),
 }
 
 impl Act for Action {
@@ -52,6 +76,11 @@ impl Act for Action {
 			Echo(echo) => echo.act(from, to),
 			Trash(trash) => trash.act(from, to),
 			Script(script) => script.act(from, to),
+			Fetch(fetch) => fetch.act(from, to),
+			Archive(archive) => archive.act(from, to),
+			Recompress(recompress) => recompress.act(from, to),
+			#[cfg(feature = "thumbnails")]
+			Thumbnail(thumbnail) => thumbnail.act(from, to),
 		}
 	}
 }
@@ -68,6 +97,11 @@ impl AsAction for Action {
 			Echo(echo) => echo.process(path),
 			Trash(trash) => trash.process(path),
 			Script(script) => script.process(path),
+			Fetch(fetch) => fetch.process(path),
+			Archive(archive) => archive.process(path),
+			Recompress(recompress) => recompress.process(path),
+			#[cfg(feature = "thumbnails")]
+			Thumbnail(thumbnail) => thumbnail.process(path),
 		}
 	}
 
@@ -82,6 +116,11 @@ impl AsAction for Action {
 			Echo(echo) => echo.ty(),
 			Trash(trash) => trash.ty(),
 			Script(script) => script.ty(),
+			Fetch(fetch) => fetch.ty(),
+			Archive(archive) => archive.ty(),
+			Recompress(recompress) => recompress.ty(),
+			#[cfg(feature = "thumbnails")]
+			Thumbnail(thumbnail) => thumbnail.ty(),
 		}
 	}
 }
@@ -103,7 +142,7 @@ pub trait Act {
 		U: AsRef<Path> + Into<PathBuf>;
 }
 
-#[derive(Eq, PartialEq, Display, EnumString)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Display, EnumString)]
 #[strum(serialize_all = "lowercase")]
 pub enum ActionType {
 	Copy,
@@ -114,6 +153,12 @@ pub enum ActionType {
 	Symlink,
 	Script,
 	Trash,
+	Thumbnail,
+	Fetch,
+	Archive,
+	Recompress,
+	/// not an action: a rule that was held back by its `defer_if`, as recorded in the journal
+	Defer,
 }
 
 impl From<&Action> for ActionType {
@@ -127,28 +172,115 @@ impl From<&Action> for ActionType {
 			Action::Echo(_) => Self::Echo,
 			Action::Trash(_) => Self::Trash,
 			Action::Script(_) => Self::Script,
+			Action::Fetch(_) => Self::Fetch,
+			Action::Archive(_) => Self::Archive,
+			Action::Recompress(_) => Self::Recompress,
+			#[cfg(feature = "thumbnails")]
+			Action::Thumbnail(_) => Self::Thumbnail,
+		}
+	}
+}
+
+impl Action {
+	/// Whether the file is no longer in its location afterwards, which requires writing to it
+	pub(crate) fn removes_source(&self) -> bool {
+		matches!(self, Self::Move(_) | Self::Delete(_) | Self::Trash(_) | Self::Recompress(_))
+			|| matches!(self, Self::Archive(archive) if archive.removes())
+	}
+
+	/// Refuses to act on `path` if it's protected from the action, e.g. open in another process with `skip_open` set,
+	/// or kept by a guard of `delete` and `trash`
+	pub(crate) fn guard(&self, path: &Path) -> Result<()> {
+		match self {
+			Self::Move(r#move) => r#move.check_open(path),
+			Self::Copy(copy) => copy.check_open(path),
+			Self::Hardlink(hardlink) => hardlink.check_open(path),
+			Self::Symlink(symlink) => symlink.check_open(path),
+			Self::Delete(delete) => delete.guard.check(path),
+			Self::Trash(trash) => trash.guard.check(path),
+			_ => Ok(()),
 		}
 	}
+
+	/// Where a copy or move would send `path`, for the space it takes up to be checked beforehand
+	pub(crate) fn transfers(&self, path: &Path) -> Vec<Transfer> {
+		match self {
+			Self::Move(r#move) => r#move.transfers(path, true),
+			Self::Copy(copy) => copy.transfers(path, false),
+			_ => vec![],
+		}
+	}
+
+	/// The templates of the paths the action writes to, if it writes outside the location
+	pub(crate) fn destinations(&self) -> Vec<PathBuf> {
+		let inner: &io_action::Inner = match self {
+			Self::Move(r#move) => r#move,
+			Self::Copy(copy) => copy,
+			Self::Hardlink(hardlink) => hardlink,
+			Self::Symlink(symlink) => symlink,
+			Self::Archive(archive) => return vec![archive.template().into()],
+			_ => return vec![],
+		};
+		std::iter::once(&inner.to.primary().to)
+			.chain(inner.to.replicas().iter().map(|destination| &destination.to))
+			.chain(inner.fallback.iter())
+			.cloned()
+			.collect()
+	}
+}
+
+/// An action along with the condition under which it runs
+#[derive(Debug, Clone, Deref, Deserialize, PartialEq, Eq)]
+pub struct Step {
+	#[deref]
+	#[serde(flatten)]
+	pub action: Action,
+	/// filters the file must match, as it is at this point of the pipeline, for the action to run
+	#[serde(default)]
+	pub when: Option<Filters>,
+	/// only log what the action would do
+	#[serde(default)]
+	pub dry_run: bool,
+	/// ask before running the action, or require `--yes`
+	#[serde(default)]
+	pub confirm: bool,
+}
+
+impl Step {
+	fn process(&self, path: PathBuf) -> Option<PathBuf> {
+		middleware::run(self, path)
+	}
+
+	/// Processes `path`, timing it as the action at `index` if the run is being profiled
+	fn timed(&self, index: usize, path: PathBuf) -> Option<PathBuf> {
+		profile::time(
+			|| Stage::Action {
+				index,
+				action: self.action.ty(),
+			},
+			|| self.process(path),
+		)
+	}
 }
 
 #[derive(Debug, Default, Deref, Clone, Deserialize, PartialEq, Eq)]
-pub struct Actions(pub Vec<Action>);
+pub struct Actions(pub Vec<Step>);
 
 impl Actions {
 	pub fn act<T: Into<PathBuf>>(&self, path: T, apply: &Apply) -> Option<PathBuf> {
 		match apply {
 			Apply::All => {
 				let mut path = path.into();
-				for action in self.iter() {
-					path = action.process(path)?;
+				for (i, step) in self.iter().enumerate() {
+					path = step.timed(i, path)?;
 				}
 				Some(path)
 			}
 			Apply::AllOf(indices) => {
 				let mut path = path.into();
 				for i in indices {
-					let action = self.0.get(*i)?;
-					path = action.process(path)?;
+					let step = self.0.get(*i)?;
+					path = step.timed(*i, path)?;
 				}
 				Some(path)
 			}
@@ -156,3 +288,51 @@ impl Actions {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn run_only_when_matching() {
+		let dir = tempfile::tempdir().unwrap();
+		let config = format!(
+			r#"
+			actions = [
+				{{ type = "echo", message = "{{filename}}" }},
+				{{ type = "move", to = "{}/pdfs/", when = [{{ type = "extension", extensions = ["pdf"] }}] }},
+			]
+			"#,
+			dir.path().display()
+		);
+		let actions: Actions = toml::from_str::<toml::Value>(&config).unwrap()["actions"]
+			.clone()
+			.try_into()
+			.unwrap();
+		let txt = dir.path().join("test.txt");
+		let pdf = dir.path().join("test.pdf");
+		std::fs::write(&txt, "").unwrap();
+		std::fs::write(&pdf, "").unwrap();
+		assert_eq!(actions.act(&txt, &Apply::All), Some(txt.clone()));
+		assert_eq!(actions.act(&pdf, &Apply::All), Some(dir.path().join("pdfs").join("test.pdf")));
+	}
+
+	#[test]
+	fn dry_run_does_nothing() {
+		let dir = tempfile::tempdir().unwrap();
+		let config = format!(
+			r#"
+			actions = [{{ type = "move", to = "{}/dry/", dry_run = true }}]
+			"#,
+			dir.path().display()
+		);
+		let actions: Actions = toml::from_str::<toml::Value>(&config).unwrap()["actions"]
+			.clone()
+			.try_into()
+			.unwrap();
+		let file = dir.path().join("test.txt");
+		std::fs::write(&file, "").unwrap();
+		assert_eq!(actions.act(&file, &Apply::All), Some(file.clone()));
+		assert!(file.exists());
+	}
+}
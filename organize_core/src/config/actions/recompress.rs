@@ -0,0 +1,244 @@
+use std::{
+	fs::{self, File},
+	io::{self, Read, Write},
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Context as _, Result};
+use serde::Deserialize;
+use strum_macros::Display;
+use tempfile::NamedTempFile;
+
+use crate::{
+	config::actions::{Act, ActionType, AsAction},
+	events::{self, Event, SkipReason},
+	messages::{self, Message},
+	summary,
+};
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Codec {
+	Gzip,
+	Zstd,
+	/// a PNG optimized with oxipng, which stays a PNG that any viewer opens
+	Png,
+}
+
+impl Codec {
+	/// What the file at `path` is compressed with, from its first bytes
+	fn detect(path: &Path) -> Result<Option<Self>> {
+		let mut magic = [0; 8];
+		let read = File::open(path)?.read(&mut magic)?;
+		let magic = &magic[..read];
+		Ok(if magic.starts_with(&[0x1f, 0x8b]) {
+			Some(Self::Gzip)
+		} else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+			Some(Self::Zstd)
+		} else if magic.starts_with(b"\x89PNG\r\n\x1a\n") {
+			Some(Self::Png)
+		} else {
+			None
+		})
+	}
+
+	/// Whether a file compressed with `self` can be recompressed with `other`
+	fn converts_to(&self, other: Self) -> bool {
+		matches!((self, other), (Self::Gzip | Self::Zstd, Self::Gzip | Self::Zstd) | (Self::Png, Self::Png))
+	}
+
+	fn extension(&self) -> &'static str {
+		match self {
+			Self::Gzip => "gz",
+			Self::Zstd => "zst",
+			Self::Png => "png",
+		}
+	}
+
+	fn decoder(&self, file: File) -> Result<Box<dyn Read>> {
+		Ok(match self {
+			Self::Gzip => Box::new(flate2::read::MultiGzDecoder::new(file)),
+			Self::Zstd => Box::new(zstd::Decoder::new(file)?),
+			Self::Png => Box::new(file),
+		})
+	}
+}
+
+/// Recompresses the file with another codec, e.g. `.gz` logs with zstd or PNGs with oxipng, if that makes it smaller.
+/// The file is replaced atomically, and renamed when its extension changes (`app.log.gz` becomes `app.log.zst`).
+/// Files that aren't compressed with a codec that converts to `to` are left alone.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Recompress {
+	to: Codec,
+	/// how hard to compress, from 1 to 9 for gzip, 1 to 22 for zstd and 0 to 6 for png, the highest sensible level by default
+	#[serde(default)]
+	level: Option<u8>,
+}
+
+impl Recompress {
+	/// Where the recompressed file goes: the same path, with the extension of the new codec
+	fn destination(&self, path: &Path, from: Codec) -> PathBuf {
+		if from == self.to {
+			return path.to_path_buf();
+		}
+		let name = path.file_name().unwrap_or_default().to_string_lossy();
+		let stem = match name.rsplit_once('.') {
+			Some((stem, "tgz")) => format!("{}.tar", stem),
+			Some((stem, extension)) if extension.eq_ignore_ascii_case(from.extension()) => stem.to_string(),
+			_ => name.to_string(),
+		};
+		path.with_file_name(format!("{}.{}", stem, self.to.extension()))
+	}
+
+	fn recompress(&self, path: &Path, from: Codec, out: &mut File) -> Result<()> {
+		match self.to {
+			Codec::Png => {
+				let data = fs::read(path)?;
+				let options = oxipng::Options::from_preset(self.level.unwrap_or(4).min(6));
+				out.write_all(&oxipng::optimize_from_memory(&data, &options)?)?;
+			}
+			Codec::Gzip => {
+				let level = flate2::Compression::new(self.level.unwrap_or(9).clamp(1, 9) as u32);
+				let mut encoder = flate2::write::GzEncoder::new(out, level);
+				io::copy(&mut from.decoder(File::open(path)?)?, &mut encoder)?;
+				encoder.finish()?;
+			}
+			Codec::Zstd => {
+				let mut encoder = zstd::Encoder::new(out, self.level.unwrap_or(19).clamp(1, 22) as i32)?;
+				io::copy(&mut from.decoder(File::open(path)?)?, &mut encoder)?;
+				encoder.finish()?;
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Act for Recompress {
+	fn act<T, P>(&self, from: T, _to: Option<P>) -> Result<Option<PathBuf>>
+	where
+		Self: Sized,
+		T: AsRef<Path> + Into<PathBuf>,
+		P: AsRef<Path> + Into<PathBuf>,
+	{
+		let path = from.into();
+		let codec = match Codec::detect(&path).with_context(|| format!("could not read {}", path.display()))? {
+			Some(codec) if codec.converts_to(self.to) => codec,
+			_ => {
+				log::debug!("({}) {} can't be recompressed as {}", self.ty(), path.display(), self.to);
+				return Ok(Some(path));
+			}
+		};
+		let to = self.destination(&path, codec);
+		if to != path && to.exists() {
+			log::warn!("({}) {} already exists, leaving {} alone", self.ty(), to.display(), path.display());
+			events::record(Event::Conflict { from: path.clone(), to });
+			return Ok(Some(path));
+		}
+		let metadata = path.metadata()?;
+		// written next to the file, so that it can replace it atomically
+		let dir = path.parent().unwrap_or_else(|| Path::new("."));
+		let mut temp = NamedTempFile::new_in(dir).with_context(|| format!("could not write in {}", dir.display()))?;
+		self.recompress(&path, codec, temp.as_file_mut())
+			.with_context(|| format!("could not recompress {}", path.display()))?;
+		let size = temp.as_file().metadata()?.len();
+		if size >= metadata.len() {
+			log::info!("({}) {} would not be smaller as {}, leaving it alone", self.ty(), path.display(), self.to);
+			return Ok(Some(path));
+		}
+		fs::set_permissions(temp.path(), metadata.permissions())?;
+		if let Ok(modified) = metadata.modified() {
+			temp.as_file().set_modified(modified)?;
+		}
+		match to == path {
+			true => temp.persist(&to),
+			false => temp.persist_noclobber(&to),
+		}
+		.with_context(|| format!("could not replace {}", path.display()))?;
+		if to != path {
+			fs::remove_file(&path).with_context(|| format!("could not remove {} after recompressing it", path.display()))?;
+		}
+		let saved = metadata.len() - size;
+		log::info!(
+			"{} ({} saved)",
+			messages::format(
+				Message::Acted,
+				&[("action", &self.ty()), ("from", &path.display()), ("to", &to.display())]
+			),
+			summary::bytes(saved)
+		);
+		events::record(Event::Acted {
+			action: self.ty(),
+			from: path,
+			to: Some(to.clone()),
+			output: None,
+		});
+		events::record(Event::Saved {
+			path: to.clone(),
+			bytes: saved,
+		});
+		Ok(Some(to))
+	}
+}
+
+impl AsAction for Recompress {
+	fn process<T: Into<PathBuf> + AsRef<Path>>(&self, path: T) -> Option<PathBuf> {
+		let path = path.into();
+		let to: Option<T> = None;
+		match self.act(&path, to) {
+			Ok(path) => path,
+			Err(e) => {
+				log::error!("{:?}", e);
+				events::skip(&path, SkipReason::Error);
+				// the file is left as it was
+				Some(path)
+			}
+		}
+	}
+
+	fn ty(&self) -> ActionType {
+		ActionType::Recompress
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn decompressed(path: &Path) -> Vec<u8> {
+		let codec = Codec::detect(path).unwrap().unwrap();
+		let mut content = Vec::new();
+		codec
+			.decoder(File::open(path).unwrap())
+			.unwrap()
+			.read_to_end(&mut content)
+			.unwrap();
+		content
+	}
+
+	#[test]
+	fn gzip_to_zstd() {
+		let dir = tempfile::tempdir().unwrap();
+		let content = "GET /index.html 200\n".repeat(10_000).into_bytes();
+		let path = dir.path().join("access.log.gz");
+		let mut encoder = flate2::write::GzEncoder::new(File::create(&path).unwrap(), flate2::Compression::fast());
+		encoder.write_all(&content).unwrap();
+		encoder.finish().unwrap();
+
+		let action: Recompress = toml::from_str("to = \"zstd\"").unwrap();
+		let to = action.act(&path, None::<PathBuf>).unwrap().unwrap();
+		assert_eq!(to, dir.path().join("access.log.zst"));
+		assert!(!path.exists());
+		assert_eq!(decompressed(&to), content);
+
+		// plain files are left alone
+		let plain = dir.path().join("notes.txt");
+		fs::write(&plain, "notes").unwrap();
+		assert_eq!(action.act(&plain, None::<PathBuf>).unwrap(), Some(plain));
+		assert_eq!(
+			action.destination(Path::new("/logs/backup.tgz"), Codec::Gzip),
+			PathBuf::from("/logs/backup.tar.zst")
+		);
+	}
+}
@@ -1,4 +1,5 @@
 use std::{
+	collections::BTreeMap,
 	path::{Path, PathBuf},
 	process::{Command, Output, Stdio},
 	result,
@@ -14,17 +15,28 @@ use crate::{
 	config::{
 		actions::{Act, ActionType, AsAction},
 		filters::AsFilter,
+		secret::Secret,
 	},
-	string::{deserialize_placeholder_string, ExpandPlaceholder},
+	events::{self, Event},
+	string::{deserialize_placeholder_string, into_bytes, ExpandPlaceholder},
 };
 use anyhow::Result;
 
+/// How much of the output of a script is kept in the journal
+const MAX_OUTPUT: usize = 1000;
+
 #[derive(Deserialize, Debug, Clone, Default, Eq, PartialEq)]
 pub struct Script {
 	#[serde(deserialize_with = "deserialize_exec")]
 	exec: String,
 	#[serde(deserialize_with = "deserialize_placeholder_string")]
 	content: String,
+	/// environment variables passed to the script, so that credentials don't have to be written into it
+	#[serde(default)]
+	env: BTreeMap<String, Secret>,
+	/// keep what the script printed before the new path in the journal, e.g. the id of the ticket it filed the document under
+	#[serde(default)]
+	capture: bool,
 }
 
 impl Act for Script {
@@ -46,6 +58,14 @@ impl AsAction for Script {
 				let output = String::from_utf8_lossy(&output.stdout);
 				let new_path = output.lines().last().map(|last| PathBuf::from(&last.trim())).unwrap();
 				info!("({}) {} -> {}", self.exec.bold(), path.display(), new_path.display());
+				if self.capture {
+					events::record(Event::Acted {
+						action: self.ty(),
+						from: path,
+						to: Some(new_path.clone()),
+						output: captured(&output),
+					});
+				}
 				Some(new_path)
 			})
 			.ok()?
@@ -93,23 +113,29 @@ impl Script {
 		Self {
 			exec: exec.into(),
 			content: content.into(),
+			env: BTreeMap::new(),
+			capture: false,
 		}
 	}
 
 	fn write(&self, path: &Path) -> anyhow::Result<PathBuf> {
 		let script = tempfile::NamedTempFile::new()?;
 		let script_path = script.into_temp_path().to_path_buf();
-		let content = self.content.as_str().expand_placeholders(path)?.into_string();
-		if let Ok(content) = content {
-			std::fs::write(&script_path, content)?;
-		}
+		// a filename that isn't valid UTF-8 is passed to the script as it is
+		let content = self.content.as_str().expand_placeholders(path)?;
+		std::fs::write(&script_path, into_bytes(content))?;
 		Ok(script_path)
 	}
 
 	fn run<T: AsRef<Path>>(&self, path: T) -> anyhow::Result<Output> {
 		let script = self.write(path.as_ref())?;
+		let mut env = Vec::with_capacity(self.env.len());
+		for (key, secret) in self.env.iter() {
+			env.push((key, secret.resolve()?));
+		}
 		let output = Command::new(&self.exec)
 			.arg(&script)
+			.envs(env)
 			.stdout(Stdio::piped())
 			.spawn()?
 			.wait_with_output()?;
@@ -117,6 +143,18 @@ impl Script {
 	}
 }
 
+/// The output of a script without its last line, which is the new path, shortened to [`MAX_OUTPUT`] characters
+fn captured(output: &str) -> Option<String> {
+	let lines = output.trim_end().lines().collect::<Vec<_>>();
+	let captured = lines[..lines.len().saturating_sub(1)].join("\n");
+	let captured = captured.trim();
+	match captured.char_indices().nth(MAX_OUTPUT) {
+		_ if captured.is_empty() => None,
+		Some((end, _)) => Some(format!("{}…", &captured[..end])),
+		None => Some(captured.to_string()),
+	}
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -133,4 +171,21 @@ mod tests {
 		});
 		assert!(script.matches(path))
 	}
+
+	#[test]
+	fn deserialize_env() {
+		let script: Script = toml::from_str("exec = \"sh\"\ncontent = \"echo $TOKEN\"\nenv = { TOKEN = 'env(\"WEBHOOK_TOKEN\")' }").unwrap();
+		assert_eq!(script.env.get("TOKEN"), Some(&Secret::Env("WEBHOOK_TOKEN".into())));
+		assert!(toml::from_str::<Script>("exec = \"sh\"\ncontent = \"echo $TOKEN\"\nenv = { TOKEN = \"hunter2\" }").is_err());
+	}
+
+	#[test]
+	fn capture_output() {
+		assert_eq!(captured("filed as TICKET-42\n/docs/a.pdf\n").as_deref(), Some("filed as TICKET-42"));
+		assert_eq!(captured("/docs/a.pdf\n"), None);
+		assert_eq!(
+			captured(&format!("{}\n/docs/a.pdf", "x".repeat(2000))).unwrap().chars().count(),
+			MAX_OUTPUT + 1
+		);
+	}
 }
@@ -0,0 +1,217 @@
+use std::{
+	path::{Path, PathBuf},
+	process::Command,
+	result,
+};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use serde::{de::Error, Deserialize, Deserializer};
+
+use crate::{
+	config::actions::{Act, ActionType, AsAction},
+	context::Context,
+	events::{self, Event, SkipReason},
+	messages::{self, Message},
+	path::{sandbox, Expand},
+	thumbnails::{self, Thumbnail as Mapping},
+};
+
+/// Makes a thumbnail or a contact sheet of a video with ffmpeg, in a `.thumbs` tree that mirrors the location of the video.
+/// The video itself is left alone. The thumbnail follows the video when organize moves it, and is removed with it.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Thumbnail {
+	/// the root of the tree, `.thumbs` inside the location of the video by default
+	#[serde(default)]
+	dir: Option<PathBuf>,
+	/// the width of the image in pixels
+	#[serde(default = "Thumbnail::default_width")]
+	width: u32,
+	/// how far into the video the thumbnail is taken, in percent of its length
+	#[serde(default = "Thumbnail::default_at")]
+	at: u8,
+	/// a grid of frames spread over the whole video, e.g. `4x3`, instead of a single one
+	#[serde(default, deserialize_with = "deserialize_grid")]
+	sheet: Option<(u32, u32)>,
+}
+
+fn deserialize_grid<'de, D>(deserializer: D) -> result::Result<Option<(u32, u32)>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let str = String::deserialize(deserializer)?;
+	let (columns, rows) = str
+		.split_once('x')
+		.ok_or_else(|| D::Error::custom(format!("expected a grid such as `4x3`, found `{}`", str)))?;
+	let parse = |n: &str| n.trim().parse::<u32>().ok().filter(|n| *n > 0);
+	match (parse(columns), parse(rows)) {
+		(Some(columns), Some(rows)) => Ok(Some((columns, rows))),
+		_ => Err(D::Error::custom(format!("expected a grid such as `4x3`, found `{}`", str))),
+	}
+}
+
+impl Thumbnail {
+	fn default_width() -> u32 {
+		320
+	}
+
+	fn default_at() -> u8 {
+		10
+	}
+
+	/// Where the thumbnail of `video` goes, along with the directory its tree mirrors
+	fn mapping(&self, video: &Path) -> Result<Mapping> {
+		let base = match Context::root() {
+			Some(root) => root,
+			None => video
+				.parent()
+				.ok_or_else(|| anyhow!("{} has no parent directory", video.display()))?
+				.to_path_buf(),
+		};
+		let tree = match &self.dir {
+			Some(dir) => sandbox::confine(dir.clone().expand_user()?.expand_vars()?)?,
+			None => base.join(".thumbs"),
+		};
+		let path = Mapping::path_in(&tree, &base, video).ok_or_else(|| anyhow!("{} is not inside {}", video.display(), base.display()))?;
+		Ok(Mapping {
+			source: video.to_path_buf(),
+			path,
+			base,
+			tree,
+		})
+	}
+
+	/// The length of the video in seconds
+	fn duration(video: &Path) -> Result<f64> {
+		let output = Command::new("ffprobe")
+			.args([
+				"-v",
+				"error",
+				"-show_entries",
+				"format=duration",
+				"-of",
+				"default=noprint_wrappers=1:nokey=1",
+			])
+			.arg(video)
+			.output()
+			.context("could not run ffprobe, is ffmpeg installed?")?;
+		let duration = String::from_utf8_lossy(&output.stdout);
+		duration.trim().parse().map_err(|_| {
+			anyhow!(
+				"could not read the length of {}: {}",
+				video.display(),
+				String::from_utf8_lossy(&output.stderr).trim()
+			)
+		})
+	}
+
+	fn render(&self, video: &Path, image: &Path) -> Result<()> {
+		let duration = Self::duration(video)?;
+		let mut command = Command::new("ffmpeg");
+		command.args(["-v", "error", "-y"]);
+		match self.sheet {
+			Some((columns, rows)) => {
+				let frames = (columns * rows) as f64;
+				let filter = format!(
+					"fps={}/{},scale={}:-2,tile={}x{}",
+					frames,
+					duration.max(1.0),
+					self.width / columns,
+					columns,
+					rows
+				);
+				command.arg("-i").arg(video).args(["-vf", &filter]);
+			}
+			None => {
+				let at = duration * self.at.min(100) as f64 / 100.0;
+				command
+					.args(["-ss", &format!("{:.3}", at)])
+					.arg("-i")
+					.arg(video)
+					.args(["-vf", &format!("scale={}:-2", self.width)]);
+			}
+		}
+		let output = command
+			.args(["-frames:v", "1"])
+			.arg(image)
+			.output()
+			.context("could not run ffmpeg, is it installed?")?;
+		if !output.status.success() {
+			bail!("ffmpeg failed on {}: {}", video.display(), String::from_utf8_lossy(&output.stderr).trim());
+		}
+		Ok(())
+	}
+}
+
+impl Act for Thumbnail {
+	fn act<T, P>(&self, from: T, _to: Option<P>) -> Result<Option<PathBuf>>
+	where
+		Self: Sized,
+		T: AsRef<Path> + Into<PathBuf>,
+		P: AsRef<Path> + Into<PathBuf>,
+	{
+		let video = from.into();
+		let mapping = self.mapping(&video)?;
+		let modified = |path: &Path| path.metadata().and_then(|metadata| metadata.modified()).ok();
+		// a thumbnail newer than the video is up to date
+		if modified(&mapping.path).is_some_and(|thumbnail| modified(&video).is_some_and(|video| thumbnail >= video)) {
+			return Ok(Some(video));
+		}
+		if let Some(parent) = mapping.path.parent() {
+			std::fs::create_dir_all(parent).with_context(|| format!("could not create {}", parent.display()))?;
+		}
+		self.render(&video, &mapping.path)?;
+		thumbnails::record(&mapping)?;
+		log::info!(
+			"{}",
+			messages::format(
+				Message::Acted,
+				&[("action", &self.ty()), ("from", &video.display()), ("to", &mapping.path.display())]
+			)
+		);
+		events::record(Event::Acted {
+			action: self.ty(),
+			from: video.clone(),
+			to: Some(mapping.path),
+			output: None,
+		});
+		Ok(Some(video))
+	}
+}
+
+impl AsAction for Thumbnail {
+	fn process<T: Into<PathBuf> + AsRef<Path>>(&self, path: T) -> Option<PathBuf> {
+		let path = path.into();
+		let to: Option<T> = None;
+		match self.act(&path, to) {
+			Ok(path) => path,
+			Err(e) => {
+				log::error!("{:?}", e);
+				events::skip(&path, SkipReason::Error);
+				// the video is still there, the next actions can carry on
+				Some(path)
+			}
+		}
+	}
+
+	fn ty(&self) -> ActionType {
+		ActionType::Thumbnail
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_thumbnail() {
+		let thumbnail: Thumbnail = toml::from_str("sheet = \"4x3\"\nwidth = 1280").unwrap();
+		assert_eq!(thumbnail.sheet, Some((4, 3)));
+		assert_eq!(thumbnail.at, 10);
+		assert!(toml::from_str::<Thumbnail>("sheet = \"4\"").is_err());
+
+		let _context = Context::enter("/videos/2023/trip.mp4", Some(PathBuf::from("/videos")));
+		let mapping = thumbnail.mapping(Path::new("/videos/2023/trip.mp4")).unwrap();
+		assert_eq!(mapping.path, PathBuf::from("/videos/.thumbs/2023/trip.mp4.jpg"));
+	}
+}
@@ -0,0 +1,189 @@
+use std::{env, fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use serde::{de::Error, Deserialize, Deserializer};
+
+/// Restricts a rule, or one of its locations, to the machines it's meant for, so that one config can be shared between
+/// them, e.g. `only_if = { host = "work-laptop", os = "macos", env = ['ONBATTERY != "1"'] }`.
+/// Every condition that is set has to hold. They're checked once, when the config is read.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+	/// the hostname of the machine, or a list of them
+	#[serde(default, deserialize_with = "one_or_many")]
+	pub host: Vec<String>,
+	/// `linux`, `macos`, `windows`..., or a list of them
+	#[serde(default, deserialize_with = "one_or_many")]
+	pub os: Vec<String>,
+	#[serde(default)]
+	pub env: Vec<EnvCondition>,
+}
+
+/// A condition on an environment variable: `NAME` (it's set), `!NAME` (it isn't), `NAME == "value"` or `NAME != "value"`.
+/// An empty variable counts as unset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvCondition {
+	Set(String),
+	Unset(String),
+	Equals(String, String),
+	Differs(String, String),
+}
+
+impl Condition {
+	/// Whether this machine meets the condition
+	pub fn holds(&self) -> bool {
+		self.holds_on(hostname().as_deref(), env::consts::OS, |name| env::var(name).ok())
+	}
+
+	fn holds_on(&self, host: Option<&str>, os: &str, var: impl Fn(&str) -> Option<String>) -> bool {
+		let host_matches = self.host.is_empty() || host.is_some_and(|host| self.host.iter().any(|expected| expected.eq_ignore_ascii_case(host)));
+		host_matches && (self.os.is_empty() || self.os.iter().any(|expected| expected == os)) && self.env.iter().all(|env| env.holds(&var))
+	}
+}
+
+impl EnvCondition {
+	fn holds(&self, var: impl Fn(&str) -> Option<String>) -> bool {
+		let value = |name: &str| var(name).filter(|value| !value.is_empty());
+		match self {
+			Self::Set(name) => value(name).is_some(),
+			Self::Unset(name) => value(name).is_none(),
+			Self::Equals(name, expected) => value(name).as_deref() == Some(expected.as_str()),
+			Self::Differs(name, expected) => value(name).as_deref() != Some(expected.as_str()),
+		}
+	}
+}
+
+impl FromStr for EnvCondition {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let valid = |name: &str| !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+		// `env.NAME` reads like the rest of the config, but it's the same as `NAME`
+		let variable = |name: &str| {
+			let name = name.trim();
+			name.strip_prefix("env.").unwrap_or(name).to_string()
+		};
+		let comparison = ["==", "!="]
+			.iter()
+			.find_map(|op| s.split_once(op).map(|(name, value)| (*op, name, value)));
+		let condition = match comparison {
+			Some((op, name, value)) => {
+				let (name, value) = (variable(name), value.trim().trim_matches('"').to_string());
+				match op {
+					"==" => Self::Equals(name, value),
+					_ => Self::Differs(name, value),
+				}
+			}
+			None => match s.trim().strip_prefix('!') {
+				Some(unset) => Self::Unset(variable(unset)),
+				None => Self::Set(variable(s)),
+			},
+		};
+		let name = match &condition {
+			Self::Set(name) | Self::Unset(name) | Self::Equals(name, _) | Self::Differs(name, _) => name,
+		};
+		match valid(name) {
+			true => Ok(condition),
+			false => Err(anyhow!("expected NAME, !NAME, NAME == \"value\" or NAME != \"value\", found {:?}", s)),
+		}
+	}
+}
+
+impl<'de> Deserialize<'de> for EnvCondition {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let str = String::deserialize(deserializer)?;
+		Self::from_str(&str).map_err(D::Error::custom)
+	}
+}
+
+impl fmt::Display for Condition {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let mut parts = Vec::new();
+		if !self.host.is_empty() {
+			parts.push(format!("host is {}", self.host.join(" or ")));
+		}
+		if !self.os.is_empty() {
+			parts.push(format!("os is {}", self.os.join(" or ")));
+		}
+		for env in self.env.iter() {
+			parts.push(match env {
+				EnvCondition::Set(name) => format!("{} is set", name),
+				EnvCondition::Unset(name) => format!("{} is unset", name),
+				EnvCondition::Equals(name, value) => format!("{} == {:?}", name, value),
+				EnvCondition::Differs(name, value) => format!("{} != {:?}", name, value),
+			});
+		}
+		write!(f, "{}", parts.join(", "))
+	}
+}
+
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum OneOrMany {
+		One(String),
+		Many(Vec<String>),
+	}
+
+	Ok(match OneOrMany::deserialize(deserializer)? {
+		OneOrMany::One(str) => vec![str],
+		OneOrMany::Many(strs) => strs,
+	})
+}
+
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+	let mut buf = [0u8; 256];
+	// SAFETY: the buffer is valid for its whole length, which is what the call is told
+	if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+		return None;
+	}
+	let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+	String::from_utf8(buf[..len].to_vec()).ok()
+}
+
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+	env::var("COMPUTERNAME").ok()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Deserialize)]
+	struct Rule {
+		only_if: Condition,
+	}
+
+	#[test]
+	fn conditions() {
+		let rule: Rule = toml::from_str("only_if = { host = \"Work-Laptop\", os = [\"macos\", \"linux\"], env = ['ONBATTERY != \"1\"'] }").unwrap();
+		let env = |battery: &'static str| move |name: &str| (name == "ONBATTERY").then(|| battery.to_string());
+		assert!(rule.only_if.holds_on(Some("work-laptop"), "linux", env("0")));
+		assert!(!rule.only_if.holds_on(Some("work-laptop"), "linux", env("1")));
+		assert!(!rule.only_if.holds_on(Some("home-desktop"), "linux", env("0")));
+		assert!(!rule.only_if.holds_on(None, "macos", env("0")));
+		assert!(!rule.only_if.holds_on(Some("work-laptop"), "windows", env("0")));
+		assert!(Condition::default().holds_on(None, "windows", |_| None));
+	}
+
+	#[test]
+	fn env_conditions() {
+		assert_eq!(EnvCondition::from_str("WORK").unwrap(), EnvCondition::Set("WORK".into()));
+		assert_eq!(EnvCondition::from_str("!WORK").unwrap(), EnvCondition::Unset("WORK".into()));
+		assert_eq!(
+			EnvCondition::from_str("PROFILE == \"work\"").unwrap(),
+			EnvCondition::Equals("PROFILE".into(), "work".into())
+		);
+		assert_eq!(EnvCondition::from_str("!env.WORK").unwrap(), EnvCondition::Unset("WORK".into()));
+		assert!(EnvCondition::from_str("WORK MODE").is_err());
+		assert!(EnvCondition::Unset("WORK".into()).holds(|_| Some(String::new())));
+	}
+}
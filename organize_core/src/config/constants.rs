@@ -0,0 +1,93 @@
+use std::{cell::RefCell, collections::BTreeMap, env};
+
+use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+
+lazy_static! {
+	static ref CONSTANT_REGEX: Regex = Regex::new(r"\{const\.(\w+)}").unwrap(); // a panic here indicates a compile-time bug
+}
+
+thread_local! {
+	// the constants of the config this thread is reading or running, see [`Config::enter`](crate::config::Config::enter)
+	static CONSTANTS: RefCell<Constants> = const { RefCell::new(Constants::new()) };
+}
+
+/// Values set under `[constants]`, e.g. `archive_root = "/mnt/nas/archive"`, that templates and paths can use as
+/// `{const.archive_root}`, so that common prefixes are only written once.
+/// Each one can be overridden with an `ORGANIZE_CONST_<NAME>` environment variable, e.g. `ORGANIZE_CONST_ARCHIVE_ROOT`.
+pub type Constants = BTreeMap<String, String>;
+
+/// Makes `constants` the ones `{const.<name>}` refers to on this thread, until the guard is dropped
+pub(crate) fn enter(constants: &Constants) -> Guard {
+	Guard(CONSTANTS.with(|current| current.replace(constants.clone())))
+}
+
+/// Puts back the constants that were in effect before [`enter`] when it's dropped
+pub(crate) struct Guard(Constants);
+
+impl Drop for Guard {
+	fn drop(&mut self) {
+		CONSTANTS.with(|current| current.replace(std::mem::take(&mut self.0)));
+	}
+}
+
+/// The name of the constant `{const.<name>}` refers to
+pub(crate) fn name(placeholder: &str) -> Option<&str> {
+	placeholder.strip_prefix("const.").filter(|name| !name.contains('.'))
+}
+
+/// The value of the constant called `name`, taking the environment into account
+pub(crate) fn value(name: &str) -> Result<String> {
+	CONSTANTS.with(|constants| value_with(name, &constants.borrow(), |var| env::var(var).ok()))
+}
+
+/// `s` with every `{const.<name>}` in it replaced by the constant's value
+pub(crate) fn expand(s: &str) -> Result<String> {
+	if !s.contains("{const.") {
+		return Ok(s.to_string());
+	}
+	CONSTANTS.with(|constants| expand_with(s, &constants.borrow(), |var| env::var(var).ok()))
+}
+
+fn value_with(name: &str, constants: &Constants, env: impl Fn(&str) -> Option<String>) -> Result<String> {
+	if !constants.contains_key(name) {
+		return Err(anyhow!("unknown constant {} (constants are defined under [constants])", name));
+	}
+	Ok(env(&format!("ORGANIZE_CONST_{}", name.to_uppercase())).unwrap_or_else(|| constants[name].clone()))
+}
+
+fn expand_with(s: &str, constants: &Constants, env: impl Fn(&str) -> Option<String>) -> Result<String> {
+	let mut error = None;
+	let expanded = CONSTANT_REGEX.replace_all(s, |captures: &Captures| match value_with(&captures[1], constants, &env) {
+		Ok(value) => value,
+		Err(e) => {
+			error.get_or_insert(e);
+			String::new()
+		}
+	});
+	match error {
+		Some(e) => Err(e),
+		None => Ok(expanded.into_owned()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn expand_constants() {
+		let constants = Constants::from([("archive_root".to_string(), "/mnt/nas/archive".to_string())]);
+		let no_env = |_: &str| None;
+		assert_eq!(
+			expand_with("{const.archive_root}/{extension}", &constants, no_env).unwrap(),
+			"/mnt/nas/archive/{extension}"
+		);
+		assert!(expand_with("{const.missing}/docs", &constants, no_env).is_err());
+		let env = |var: &str| (var == "ORGANIZE_CONST_ARCHIVE_ROOT").then(|| "/srv/archive".to_string());
+		assert_eq!(expand_with("{const.archive_root}", &constants, env).unwrap(), "/srv/archive");
+		assert_eq!(name("const.archive_root"), Some("archive_root"));
+		assert_eq!(name("const.archive_root.parent"), None);
+	}
+}
@@ -0,0 +1,143 @@
+use std::{fmt, path::PathBuf};
+
+use chrono::Local;
+use serde::{de::Error, Deserialize, Deserializer};
+
+use crate::{
+	config::{actions::ActionType, Config},
+	journal::{self, Entry},
+	resources::Resources,
+};
+
+/// Holds a heavy rule back while the machine can't spare what it needs, e.g.
+/// `defer_if = { on_battery = true, metered = true, load_above = 4.0 }`.
+/// `organize run` skips the rule, and the daemon waits until none of the conditions hold anymore and then catches up on its locations.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct DeferIf {
+	/// while the machine runs off its battery
+	#[serde(default)]
+	pub on_battery: bool,
+	/// while the network connection is metered
+	#[serde(default)]
+	pub metered: bool,
+	/// while the load average over the last minute is above this
+	#[serde(default)]
+	pub load_above: Option<Load>,
+}
+
+/// A load average, which can't be negative or NaN
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Load(f64);
+
+// NaN is refused when deserializing
+impl Eq for Load {}
+
+impl<'de> Deserialize<'de> for Load {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let load = f64::deserialize(deserializer)?;
+		match load.is_finite() && load >= 0.0 {
+			true => Ok(Self(load)),
+			false => Err(D::Error::custom(format!("expected a positive load average, found {}", load))),
+		}
+	}
+}
+
+impl fmt::Display for Load {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+impl DeferIf {
+	/// Why the rule has to wait, if it does
+	pub fn reason(&self, resources: &Resources) -> Option<String> {
+		if self.on_battery && resources.on_battery() {
+			return Some("the machine is on battery".into());
+		}
+		if self.metered && resources.metered() {
+			return Some("the connection is metered".into());
+		}
+		match self.load_above {
+			Some(Load(max)) if resources.load() > max => Some(format!("the load is {:.2}, above {}", resources.load(), max)),
+			_ => None,
+		}
+	}
+}
+
+impl Config {
+	/// The rules that have to wait right now, either because they're outside their active hours or because of their `defer_if`,
+	/// with the reason why
+	pub fn deferred(&self) -> Vec<(usize, String)> {
+		let (resources, now) = (Resources::default(), Local::now().naive_local());
+		self.rules
+			.iter()
+			.enumerate()
+			.filter_map(|(i, rule)| match &rule.active {
+				Some(window) if !window.contains(now) => Some((i, format!("it's outside its active hours ({})", window))),
+				_ => Some((i, rule.defer_if.as_ref()?.reason(&resources)?)),
+			})
+			.collect()
+	}
+
+	/// Whether some rules may have to wait
+	pub fn defers(&self) -> bool {
+		self.rules.iter().any(|rule| rule.defer_if.is_some() || rule.active.is_some())
+	}
+
+	/// A copy of this config where the given rules have neither locations nor volumes to run on
+	pub fn without(&self, rules: &[usize]) -> Self {
+		let mut kept = self.rules.clone();
+		for i in rules.iter() {
+			kept[*i].folders.clear();
+			kept[*i].on_mount = None;
+		}
+		self.with_rules(kept)
+	}
+}
+
+/// Logs that the rules in `deferred` were held back, and records it in the journal for each of their locations
+pub fn record(config: &Config, deferred: &[(usize, String)]) {
+	for (i, reason) in deferred.iter() {
+		let rule = &config.rules[*i];
+		log::info!("deferring rule {}: {}", rule.name(*i), reason);
+		let locations = match rule.folders.is_empty() {
+			true => vec![config.path.clone()],
+			false => rule.folders.iter().map(|folder| folder.path.clone()).collect::<Vec<PathBuf>>(),
+		};
+		for location in locations {
+			let mut entry = Entry::new(ActionType::Defer, location, None);
+			entry.rule = Some(rule.name(*i));
+			entry.output = Some(reason.clone());
+			journal::append(entry);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Deserialize)]
+	struct Rule {
+		defer_if: DeferIf,
+	}
+
+	#[test]
+	fn reasons() {
+		let rule: Rule = toml::from_str("defer_if = { on_battery = true, load_above = 4 }").unwrap();
+		assert_eq!(rule.defer_if.reason(&Resources::fixed(false, true, 1.0)), None);
+		assert_eq!(
+			rule.defer_if.reason(&Resources::fixed(true, false, 1.0)),
+			Some("the machine is on battery".into())
+		);
+		assert_eq!(
+			rule.defer_if.reason(&Resources::fixed(false, false, 6.5)),
+			Some("the load is 6.50, above 4".into())
+		);
+		assert!(toml::from_str::<Rule>("defer_if = { load_above = -1.0 }").is_err());
+	}
+}
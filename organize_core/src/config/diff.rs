@@ -0,0 +1,256 @@
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt,
+	path::PathBuf,
+};
+
+use colored::Colorize;
+
+use crate::config::{options::Options, Config, Rule};
+
+/// A single semantic difference between two configs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+	RuleAdded(String),
+	RuleRemoved(String),
+	/// a rule that exists in both configs, with a description of each thing that changed in it
+	RuleChanged(String, Vec<String>),
+	/// an option in `[defaults]`, with its old and new value
+	DefaultChanged(String, String, String),
+}
+
+/// What changed between two configs, and which locations are affected by it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigDiff {
+	pub changes: Vec<Change>,
+	/// locations whose rules changed, or whose defaults changed
+	pub affected: BTreeSet<PathBuf>,
+}
+
+impl ConfigDiff {
+	/// Rules are matched by id, and by position when they don't have one
+	pub fn new(old: &Config, new: &Config) -> Self {
+		let old_rules: BTreeMap<String, &Rule> = old.rules.iter().enumerate().map(|(i, rule)| (rule.name(i), rule)).collect();
+		let new_rules: BTreeMap<String, &Rule> = new.rules.iter().enumerate().map(|(i, rule)| (rule.name(i), rule)).collect();
+		let mut changes = Vec::new();
+		let mut affected = BTreeSet::new();
+		let locations = |rule: &Rule| rule.folders.iter().map(|folder| folder.path.clone()).collect::<Vec<_>>();
+
+		for (name, rule) in old_rules.iter() {
+			if !new_rules.contains_key(name) {
+				changes.push(Change::RuleRemoved(name.clone()));
+				affected.extend(locations(rule));
+			}
+		}
+		for (name, rule) in new_rules.iter() {
+			match old_rules.get(name) {
+				None => {
+					changes.push(Change::RuleAdded(name.clone()));
+					affected.extend(locations(rule));
+				}
+				Some(old_rule) => {
+					let what = rule_changes(old_rule, rule);
+					if !what.is_empty() {
+						changes.push(Change::RuleChanged(name.clone(), what));
+						affected.extend(locations(old_rule));
+						affected.extend(locations(rule));
+					}
+				}
+			}
+		}
+
+		// unset defaults fall back to the built-in ones, so those are what need to be compared
+		let defaults = |config: &Config| {
+			let mut defaults = flatten(&config.global_defaults);
+			defaults.extend(flatten(&config.local_defaults));
+			defaults
+		};
+		let defaults = value_changes(&defaults(old), &defaults(new));
+		if !defaults.is_empty() {
+			// defaults can apply to any rule
+			affected.extend(new.rules.iter().flat_map(locations));
+		}
+		changes.extend(
+			defaults
+				.into_iter()
+				.map(|(option, old, new)| Change::DefaultChanged(option, old, new)),
+		);
+		Self { changes, affected }
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.changes.is_empty()
+	}
+}
+
+fn rule_changes(old: &Rule, new: &Rule) -> Vec<String> {
+	let mut what = Vec::new();
+	let paths = |rule: &Rule| rule.folders.iter().map(|folder| folder.path.clone()).collect::<BTreeSet<_>>();
+	let (old_paths, new_paths) = (paths(old), paths(new));
+	for path in new_paths.difference(&old_paths) {
+		what.push(format!("location added: {}", path.display()));
+	}
+	for path in old_paths.difference(&new_paths) {
+		what.push(format!("location removed: {}", path.display()));
+	}
+	for folder in new.folders.iter() {
+		if let Some(old_folder) = old.folders.iter().find(|old_folder| old_folder.path == folder.path) {
+			for (option, before, after) in option_changes(&old_folder.options, &folder.options) {
+				what.push(format!("{} of {}: {} -> {}", option, folder.path.display(), before, after));
+			}
+		}
+	}
+	if old.filters != new.filters {
+		what.push(format!("filters changed ({} -> {})", old.filters.len(), new.filters.len()));
+	}
+	if old.actions != new.actions {
+		what.push(format!("actions changed ({} -> {})", old.actions.len(), new.actions.len()));
+	}
+	for (option, before, after) in option_changes(&old.options, &new.options) {
+		what.push(format!("{}: {} -> {}", option, before, after));
+	}
+	if old.phase != new.phase {
+		what.push(format!("phase: {} -> {}", old.phase, new.phase));
+	}
+	if old.after != new.after {
+		what.push(format!("after: [{}] -> [{}]", old.after.join(", "), new.after.join(", ")));
+	}
+	if old.locked != new.locked {
+		what.push(format!("locked: {} -> {}", old.locked, new.locked));
+	}
+	if old.on_mount != new.on_mount {
+		what.push("on_mount changed".into());
+	}
+	what
+}
+
+/// The options that are set, keyed by their dotted name
+fn flatten(options: &Options) -> BTreeMap<String, String> {
+	let mut flat = BTreeMap::new();
+	if let Ok(toml::Value::Table(table)) = toml::Value::try_from(options) {
+		for (key, value) in table {
+			match value {
+				toml::Value::Table(nested) => flat.extend(nested.into_iter().map(|(k, v)| (format!("{}.{}", key, k), v.to_string()))),
+				value => {
+					flat.insert(key, value.to_string());
+				}
+			}
+		}
+	}
+	flat
+}
+
+/// The options that differ between `old` and `new`, with their old and new values
+fn option_changes(old: &Options, new: &Options) -> Vec<(String, String, String)> {
+	value_changes(&flatten(old), &flatten(new))
+}
+
+fn value_changes(old: &BTreeMap<String, String>, new: &BTreeMap<String, String>) -> Vec<(String, String, String)> {
+	let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+	keys.into_iter()
+		.filter(|key| old.get(*key) != new.get(*key))
+		.map(|key| {
+			let value = |map: &BTreeMap<String, String>| map.get(key).cloned().unwrap_or_else(|| "unset".into());
+			(key.clone(), value(old), value(new))
+		})
+		.collect()
+}
+
+impl fmt::Display for ConfigDiff {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		if self.is_empty() {
+			return write!(f, "no changes");
+		}
+		for change in self.changes.iter() {
+			match change {
+				Change::RuleAdded(name) => writeln!(f, "{} rule {}", "+".green(), name.bold())?,
+				Change::RuleRemoved(name) => writeln!(f, "{} rule {}", "-".red(), name.bold())?,
+				Change::RuleChanged(name, what) => {
+					writeln!(f, "{} rule {}", "~".yellow(), name.bold())?;
+					for line in what {
+						writeln!(f, "    {}", line)?;
+					}
+				}
+				Change::DefaultChanged(option, old, new) => writeln!(f, "{} default {}: {} -> {}", "~".yellow(), option.bold(), old, new)?,
+			}
+		}
+		writeln!(f, "{}", "affected locations".bold())?;
+		for path in self.affected.iter() {
+			writeln!(f, "    {}", path.display())?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse(dir: &std::path::Path, name: &str, content: &str) -> Config {
+		let path = dir.join(name);
+		std::fs::write(&path, content).unwrap();
+		Config::parse(path).unwrap()
+	}
+
+	#[test]
+	fn diff_rules_and_defaults() {
+		let dir = tempfile::tempdir().unwrap();
+		let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+		std::fs::create_dir_all(&a).unwrap();
+		std::fs::create_dir_all(&b).unwrap();
+		let old = parse(
+			dir.path(),
+			"old.toml",
+			&format!(
+				r#"
+[[rules]]
+id = "docs"
+folders = ["{0}"]
+filters = []
+actions = []
+
+[[rules]]
+id = "gone"
+folders = ["{1}"]
+filters = []
+actions = []
+"#,
+				a.display(),
+				b.display()
+			),
+		);
+		let new = parse(
+			dir.path(),
+			"new.toml",
+			&format!(
+				r#"
+[defaults]
+hidden_files = true
+
+[[rules]]
+id = "docs"
+folders = ["{0}", "{1}"]
+filters = [{{ type = "extension", extensions = ["pdf"] }}]
+actions = []
+"#,
+				a.display(),
+				b.display()
+			),
+		);
+		let diff = ConfigDiff::new(&old, &new);
+		let (a, b) = (a.canonicalize().unwrap(), b.canonicalize().unwrap());
+		assert_eq!(
+			diff.changes,
+			vec![
+				Change::RuleRemoved("gone".into()),
+				Change::RuleChanged(
+					"docs".into(),
+					vec![format!("location added: {}", b.display()), "filters changed (0 -> 1)".into()]
+				),
+				Change::DefaultChanged("hidden_files".into(), "false".into(), "true".into()),
+			]
+		);
+		assert_eq!(diff.affected, BTreeSet::from([a, b]));
+		assert!(ConfigDiff::new(&new, &new).is_empty());
+	}
+}
@@ -0,0 +1,242 @@
+use std::{
+	fs::Metadata,
+	path::Path,
+	str::FromStr,
+	time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{Local, NaiveDate, NaiveDateTime, TimeZone};
+use serde::{de::Error, Deserialize, Deserializer};
+
+/// Matches files by one of their dates, either relative to now or to a fixed date, e.g.
+/// `{ type = "created", older_than = "90d" }`, `{ type = "last_modified", newer_than = "2024-01-31" }`
+/// or `{ type = "last_accessed", when = "older than 30d and newer than 1y" }`.
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct Age {
+	pub newer_than: Option<Moment>,
+	pub older_than: Option<Moment>,
+}
+
+/// A point in time: a duration ago (`30d`), or a date (`2024-01-31`, `2024-01-31 18:00`) in the local timezone
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Moment {
+	Ago(Duration),
+	At(NaiveDateTime),
+}
+
+/// The date of a file that an [`Age`] is compared with
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Timestamp {
+	Created,
+	Modified,
+	Accessed,
+}
+
+impl Moment {
+	pub fn time(&self) -> Option<SystemTime> {
+		match self {
+			Self::Ago(duration) => SystemTime::now().checked_sub(*duration),
+			Self::At(date) => Local.from_local_datetime(date).earliest().map(SystemTime::from),
+		}
+	}
+}
+
+impl FromStr for Moment {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+		if let Ok(duration) = parse_duration(s) {
+			return Ok(Self::Ago(duration));
+		}
+		if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+			return Ok(Self::At(date.and_hms_opt(0, 0, 0).unwrap()));
+		}
+		["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"]
+			.iter()
+			.find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+			.map(Self::At)
+			.ok_or_else(|| anyhow!("expected a duration like `30d` or a date like `2024-01-31`, found `{}`", s))
+	}
+}
+
+impl Timestamp {
+	fn of(&self, metadata: &Metadata) -> std::io::Result<SystemTime> {
+		match self {
+			Self::Created => metadata.created(),
+			Self::Modified => metadata.modified(),
+			Self::Accessed => metadata.accessed(),
+		}
+	}
+}
+
+impl Age {
+	/// The oldest time a matching date can be
+	pub fn cutoff(&self) -> Option<SystemTime> {
+		self.newer_than.and_then(|newer_than| newer_than.time())
+	}
+
+	/// Whether the `timestamp` of the file at `path` is within the bounds. Files whose date can't be read, e.g. creation dates
+	/// on filesystems that don't keep them, never match.
+	pub fn matches<T: AsRef<Path>>(&self, path: T, timestamp: Timestamp) -> bool {
+		match path.as_ref().metadata().and_then(|metadata| timestamp.of(&metadata)) {
+			Ok(time) => self.contains(time),
+			Err(_) => false,
+		}
+	}
+
+	fn contains(&self, time: SystemTime) -> bool {
+		let bound = |moment: &Option<Moment>, within: fn(SystemTime, SystemTime) -> bool| match moment {
+			Some(moment) => moment.time().is_some_and(|bound| within(time, bound)),
+			None => true,
+		};
+		bound(&self.newer_than, |time, bound| time > bound) && bound(&self.older_than, |time, bound| time < bound)
+	}
+}
+
+/// Reads expressions such as `older than 30d`, `newer than 2h`, `before 2024-01-01` or `after 2024-01-01`, joined with `and`
+impl FromStr for Age {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut age = Self::default();
+		for part in s.split(" and ") {
+			let part = part.trim();
+			let (bound, moment) = match ["older than", "before", "newer than", "after"]
+				.iter()
+				.find_map(|prefix| part.strip_prefix(prefix).map(|moment| (*prefix, moment)))
+			{
+				Some(("older than" | "before", moment)) => (&mut age.older_than, moment),
+				Some((_, moment)) => (&mut age.newer_than, moment),
+				None => bail!("expected `older than`, `newer than`, `before` or `after`, found `{}`", part),
+			};
+			if bound.is_some() {
+				bail!("`{}` sets the same bound twice", s);
+			}
+			*bound = Some(moment.parse().with_context(|| format!("invalid age `{}`", part))?);
+		}
+		Ok(age)
+	}
+}
+
+impl<'de> Deserialize<'de> for Age {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		#[derive(Deserialize)]
+		struct Raw {
+			#[serde(default)]
+			newer_than: Option<String>,
+			#[serde(default)]
+			older_than: Option<String>,
+			#[serde(default)]
+			when: Option<String>,
+		}
+
+		let raw = Raw::deserialize(deserializer)?;
+		let mut age = match raw.when {
+			Some(when) => Age::from_str(&when).map_err(D::Error::custom)?,
+			None => Age::default(),
+		};
+		for (bound, moment) in [(&mut age.newer_than, raw.newer_than), (&mut age.older_than, raw.older_than)] {
+			if let Some(moment) = moment {
+				if bound.is_some() {
+					return Err(D::Error::custom("a bound is set both in `when` and on its own"));
+				}
+				*bound = Some(Moment::from_str(&moment).map_err(D::Error::custom)?);
+			}
+		}
+		Ok(age)
+	}
+}
+
+/// Parses durations such as `30s`, `15m`, `12h`, `7d`, `2w` or `1y`
+pub fn parse_duration(s: &str) -> Result<Duration> {
+	let s = s.trim();
+	let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+	let (amount, unit) = s.split_at(split);
+	let amount: u64 = amount.parse().with_context(|| format!("invalid duration {}", s))?;
+	let seconds = match unit.trim() {
+		"s" => 1,
+		"m" => 60,
+		"h" => 60 * 60,
+		"d" => 60 * 60 * 24,
+		"w" => 60 * 60 * 24 * 7,
+		"y" => 60 * 60 * 24 * 365,
+		unit => return Err(anyhow!("unknown unit `{}` in duration {} (expected s, m, h, d, w or y)", unit, s)),
+	};
+	Ok(Duration::from_secs(amount * seconds))
+}
+
+pub(crate) fn deserialize_duration<'de, D>(deserializer: D) -> Result<Option<Duration>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	let str = String::deserialize(deserializer)?;
+	parse_duration(&str).map(Some).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_durations() {
+		assert_eq!(parse_duration("30s").unwrap(), Duration::from_secs(30));
+		assert_eq!(parse_duration("2w").unwrap(), Duration::from_secs(2 * 7 * 24 * 60 * 60));
+		assert!(parse_duration("2x").is_err());
+		assert!(parse_duration("d").is_err());
+	}
+
+	#[test]
+	fn match_recent_file() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("recent.txt");
+		std::fs::write(&path, "").unwrap();
+		let newer = Age {
+			newer_than: Some(Moment::Ago(Duration::from_secs(60))),
+			..Age::default()
+		};
+		let older = Age {
+			older_than: Some(Moment::Ago(Duration::from_secs(60))),
+			..Age::default()
+		};
+		assert!(newer.matches(&path, Timestamp::Modified));
+		assert!(!older.matches(&path, Timestamp::Modified));
+		assert!(newer.matches(&path, Timestamp::Accessed));
+	}
+
+	#[test]
+	fn parse_ages() {
+		let date = |s: &str| Moment::At(NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap());
+		assert_eq!(
+			Age::from_str("older than 30d and after 2024-01-31").unwrap(),
+			Age {
+				newer_than: Some(date("2024-01-31 00:00")),
+				older_than: Some(Moment::Ago(Duration::from_secs(30 * 24 * 60 * 60))),
+			}
+		);
+		let age: Age = toml::from_str("older_than = \"2024-01-31 18:30\"").unwrap();
+		assert_eq!(age.older_than, Some(date("2024-01-31 18:30")));
+		assert!(Age::from_str("older than 1d and before 2d").is_err());
+		assert!(Age::from_str("around 2024-01-31").is_err());
+		assert!(toml::from_str::<Age>("older_than = \"1d\"\nwhen = \"older than 2d\"").is_err());
+	}
+
+	#[test]
+	fn compare_with_dates() {
+		let age = Age::from_str("after 2024-01-01 and before 2024-02-01").unwrap();
+		let at = |s: &str| {
+			SystemTime::from(
+				Local
+					.from_local_datetime(&NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap())
+					.unwrap(),
+			)
+		};
+		assert!(age.contains(at("2024-01-15 12:00")));
+		assert!(!age.contains(at("2023-12-31 23:00")));
+		assert!(!age.contains(at("2024-02-02 00:00")));
+	}
+}
@@ -0,0 +1,98 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{audio::Tags, config::filters::AsFilter, context::Context};
+
+/// Matches songs with tags, or only those whose tags have the given values (ignoring case), e.g. `{ type = "audio_tags", genre = "Jazz" }`.
+/// The tags are then available to templates as `{tags.artist}`, `{tags.album_artist}`, `{tags.album}`, `{tags.title}`, `{tags.genre}`,
+/// `{tags.year}` and `{tags.track}`. A tag the song doesn't have is replaced by `fallback` if there's one, e.g. `Unknown`;
+/// otherwise it fails the template, so that the destination falls back to the next one.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct AudioTags {
+	#[serde(default)]
+	pub artist: Option<String>,
+	#[serde(default)]
+	pub album: Option<String>,
+	#[serde(default)]
+	pub genre: Option<String>,
+	#[serde(default)]
+	pub fallback: Option<String>,
+}
+
+impl AudioTags {
+	fn accepts(&self, tags: &Tags) -> bool {
+		[(&self.artist, &tags.artist), (&self.album, &tags.album), (&self.genre, &tags.genre)]
+			.iter()
+			.all(|(expected, found)| match (expected, found) {
+				(Some(expected), Some(found)) => expected.to_lowercase() == found.to_lowercase(),
+				(Some(_), None) => false,
+				(None, _) => true,
+			})
+	}
+}
+
+impl AsFilter for AudioTags {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		let path = path.as_ref();
+		let tags = match Tags::read(path) {
+			Ok(Some(tags)) => tags,
+			Ok(None) => return false,
+			Err(e) => {
+				log::debug!("could not read the tags of {}: {:?}", path.display(), e);
+				return false;
+			}
+		};
+		if !self.accepts(&tags) {
+			return false;
+		}
+		for variable in Tags::VARIABLES {
+			if let Some(value) = tags.get(variable).or(self.fallback.as_deref()) {
+				Context::set_variable(variable, value);
+			}
+		}
+		true
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use id3::TagLike;
+
+	use super::*;
+	use crate::variables::Variable;
+
+	#[test]
+	fn set_tags_with_fallback() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("song.mp3");
+		fs::write(&path, "").unwrap();
+		let mut tag = id3::Tag::new();
+		tag.set_artist("Nina Simone");
+		tag.set_genre("Jazz");
+		tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+
+		let _guard = Context::enter(&path, None);
+		let filter: AudioTags = toml::from_str("genre = \"jazz\"\nfallback = \"Unknown\"").unwrap();
+		assert!(filter.matches(&path));
+		assert_eq!(Variable::TagsArtist.value(&path).unwrap(), "Nina Simone");
+		assert_eq!(Variable::TagsAlbum.value(&path).unwrap(), "Unknown");
+		assert!(!toml::from_str::<AudioTags>("artist = \"Miles Davis\"").unwrap().matches(&path));
+	}
+
+	#[test]
+	fn fail_without_fallback() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("song.mp3");
+		fs::write(&path, "").unwrap();
+		let mut tag = id3::Tag::new();
+		tag.set_title("Feeling Good");
+		tag.write_to_path(&path, id3::Version::Id3v24).unwrap();
+		assert_eq!(Variable::TagsTitle.value(&path).unwrap(), "Feeling Good");
+		assert!(Variable::TagsArtist.value(&path).is_err());
+		assert!(!AudioTags::default().matches(dir.path().join("missing.mp3")));
+	}
+}
@@ -0,0 +1,232 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	convert::TryFrom,
+	path::{Path, PathBuf},
+	process::Command,
+	sync::Mutex,
+	time::SystemTime,
+};
+
+use anyhow::{anyhow, bail, Context as _, Result};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use crate::{
+	config::{filters::AsFilter, secret::Secret},
+	context::Context,
+	layout,
+	string::{visit_placeholder_string, ExpandPlaceholder},
+	PROJECT_NAME,
+};
+
+type Labels = BTreeMap<String, String>;
+/// the labels of a file by classifier, along with when the file was last modified
+type Cache = Mutex<HashMap<(Vec<String>, PathBuf), (Option<SystemTime>, Option<Labels>)>>;
+
+lazy_static! {
+	/// the labels every classifier gave to every file so far, so that several rules can share a classifier
+	static ref LABELS: Cache = Mutex::new(HashMap::new());
+}
+
+/// Asks a classifier which labels a document has, e.g. its correspondent, type or tags, the way paperless-ngx does.
+///
+/// The classifier is a command that gets the path of the file and prints the labels, either as a JSON object
+/// (`{"correspondent": "ACME", "tags": ["invoice", "2023"]}`) or as `name=value` lines. Lists are joined with commas.
+/// The labels are available to templates as `{label.<name>}`, and the filter matches when the classifier succeeded
+/// and every label in `require` has the required value (or, for lists, contains it).
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "RawClassify")]
+pub struct Classify {
+	/// the command and its arguments
+	command: Vec<String>,
+	env: BTreeMap<String, Secret>,
+	require: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawClassify {
+	exec: Option<String>,
+	/// `organize-classify-<plugin>`, from the `plugins` directory of organize or from the PATH
+	plugin: Option<String>,
+	/// the path of the file by default
+	#[serde(default = "RawClassify::default_args")]
+	args: Vec<String>,
+	#[serde(default)]
+	env: BTreeMap<String, Secret>,
+	#[serde(default)]
+	require: BTreeMap<String, String>,
+}
+
+impl RawClassify {
+	fn default_args() -> Vec<String> {
+		vec!["{path}".into()]
+	}
+}
+
+impl TryFrom<RawClassify> for Classify {
+	type Error = anyhow::Error;
+
+	fn try_from(raw: RawClassify) -> Result<Self> {
+		let exec = match (raw.exec, raw.plugin) {
+			(Some(exec), None) => exec,
+			(None, Some(plugin)) => plugin_path(&plugin),
+			_ => bail!("a classifier needs either `exec` or `plugin`"),
+		};
+		for arg in raw.args.iter() {
+			visit_placeholder_string(arg).with_context(|| format!("{} contains an invalid placeholder", arg))?;
+		}
+		Ok(Self {
+			command: std::iter::once(exec).chain(raw.args).collect(),
+			env: raw.env,
+			require: raw.require,
+		})
+	}
+}
+
+/// Where the executable of a classifier plugin is
+fn plugin_path(plugin: &str) -> String {
+	let name = format!("{}-classify-{}", PROJECT_NAME, plugin);
+	layout::data_dir()
+		.map(|dir| dir.join("plugins").join(&name))
+		.ok()
+		.filter(|path| path.is_file())
+		.map(|path| path.to_string_lossy().to_string())
+		.unwrap_or(name)
+}
+
+impl Classify {
+	fn run(&self, path: &Path) -> Result<Labels> {
+		let mut args = Vec::with_capacity(self.command.len() - 1);
+		for arg in self.command[1..].iter() {
+			args.push(arg.expand_placeholders(path)?);
+		}
+		let mut env = Vec::with_capacity(self.env.len());
+		for (key, secret) in self.env.iter() {
+			env.push((key, secret.resolve()?));
+		}
+		let output = Command::new(&self.command[0])
+			.args(args)
+			.envs(env)
+			.output()
+			.with_context(|| format!("could not run {}", self.command[0]))?;
+		if !output.status.success() {
+			bail!(
+				"{} failed on {}: {}",
+				self.command[0],
+				path.display(),
+				String::from_utf8_lossy(&output.stderr).trim()
+			);
+		}
+		parse(&String::from_utf8_lossy(&output.stdout))
+	}
+
+	/// The labels of the file at `path`, asking the classifier only once for as long as the file isn't modified
+	fn labels(&self, path: &Path) -> Option<Labels> {
+		let key = (self.command.clone(), path.to_path_buf());
+		let modified = path.metadata().and_then(|metadata| metadata.modified()).ok();
+		if let Some((when, labels)) = LABELS.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+			if *when == modified {
+				return labels.clone();
+			}
+		}
+		let labels = self
+			.run(path)
+			.map_err(|e| log::warn!("could not classify {}: {:?}", path.display(), e))
+			.ok();
+		LABELS
+			.lock()
+			.unwrap_or_else(|e| e.into_inner())
+			.insert(key, (modified, labels.clone()));
+		labels
+	}
+}
+
+impl AsFilter for Classify {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		let labels = match self.labels(path.as_ref()) {
+			Some(labels) => labels,
+			None => return false,
+		};
+		let matches = self.require.iter().all(|(name, required)| {
+			labels
+				.get(name)
+				.is_some_and(|value| value.eq_ignore_ascii_case(required) || value.split(',').any(|item| item.trim().eq_ignore_ascii_case(required)))
+		});
+		if matches {
+			for (name, value) in labels {
+				Context::set_label(name, value);
+			}
+		}
+		matches
+	}
+}
+
+/// Reads the labels printed by a classifier, as a JSON object or as `name=value` lines.
+/// Names are reduced to letters, digits and underscores so that templates can refer to them.
+fn parse(output: &str) -> Result<Labels> {
+	let name = |name: &str| -> String { name.trim().chars().map(|c| if c.is_alphanumeric() { c } else { '_' }).collect() };
+	let output = output.trim();
+	if output.starts_with('{') {
+		let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(output).context("invalid JSON")?;
+		let text = |value: &serde_json::Value| match value {
+			serde_json::Value::String(value) => Some(value.clone()),
+			serde_json::Value::Number(_) | serde_json::Value::Bool(_) => Some(value.to_string()),
+			_ => None,
+		};
+		return Ok(object
+			.iter()
+			.filter_map(|(key, value)| {
+				let value = match value {
+					serde_json::Value::Array(values) => Some(values.iter().filter_map(text).collect::<Vec<_>>().join(",")),
+					value => text(value),
+				}?;
+				Some((name(key), value))
+			})
+			.collect());
+	}
+	output
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.map(|line| {
+			let (key, value) = line
+				.split_once('=')
+				.ok_or_else(|| anyhow!("expected name=value, found {:?}", line))?;
+			Ok((name(key), value.trim().to_string()))
+		})
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_labels() {
+		let labels = parse(r#"{"correspondent": "ACME Corp", "tags": ["invoice", "2023"], "document type": "Invoice", "notes": null}"#).unwrap();
+		assert_eq!(labels["correspondent"], "ACME Corp");
+		assert_eq!(labels["tags"], "invoice,2023");
+		assert_eq!(labels["document_type"], "Invoice");
+		assert!(!labels.contains_key("notes"));
+		let labels = parse("# a comment\ncorrespondent = ACME\ntags=a,b\n").unwrap();
+		assert_eq!(labels["correspondent"], "ACME");
+		assert!(parse("not a label").is_err());
+	}
+
+	#[test]
+	fn classify_with_a_command() {
+		let dir = tempfile::tempdir().unwrap();
+		let file = dir.path().join("scan.pdf");
+		std::fs::write(&file, "").unwrap();
+		let classify: Classify =
+			toml::from_str("exec = \"sh\"\nargs = [\"-c\", \"echo correspondent=ACME; echo tags=invoice,paid\"]\nrequire = { tags = \"paid\" }")
+				.unwrap();
+		let _context = Context::enter(&file, None);
+		assert!(classify.matches(&file));
+		assert_eq!(Context::label("correspondent").as_deref(), Some("ACME"));
+		let other: Classify = toml::from_str("exec = \"sh\"\nargs = [\"-c\", \"echo tags=invoice\"]\nrequire = { tags = \"paid\" }").unwrap();
+		assert!(!other.matches(&file));
+		assert!(toml::from_str::<Classify>("args = []").is_err());
+	}
+}
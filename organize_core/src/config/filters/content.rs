@@ -0,0 +1,124 @@
+use std::{convert::TryFrom, fs, io::Read, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use encoding_rs::Encoding;
+use serde::Deserialize;
+
+use crate::config::filters::{deserialize_size, AsFilter};
+
+/// Matches text files whose beginning matches any of `patterns`, e.g. bank statements or receipts:
+/// `{ type = "content", patterns = ["(?i)statement of account", "IBAN: DE\\d+"] }`.
+/// Only the first `max_size` bytes are read (`64KB` by default), and files with a NUL byte in them are binary and never match.
+/// The text is decoded as UTF-8, or as what its byte order mark says, unless `encoding` names another one, e.g. `windows-1252`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawContent")]
+pub struct Content {
+	set: regex::RegexSet,
+	max_size: u64,
+	encoding: &'static Encoding,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawContent {
+	patterns: Vec<String>,
+	#[serde(default, deserialize_with = "deserialize_size")]
+	max_size: Option<u64>,
+	#[serde(default)]
+	encoding: Option<String>,
+}
+
+impl TryFrom<RawContent> for Content {
+	type Error = anyhow::Error;
+
+	fn try_from(raw: RawContent) -> Result<Self> {
+		let set = regex::RegexSet::new(&raw.patterns).context("invalid pattern")?;
+		let encoding = match raw.encoding {
+			Some(label) => Encoding::for_label(label.as_bytes()).ok_or_else(|| anyhow!("unknown encoding `{}`", label))?,
+			None => encoding_rs::UTF_8,
+		};
+		Ok(Self {
+			set,
+			max_size: raw.max_size.unwrap_or(64_000),
+			encoding,
+		})
+	}
+}
+
+impl PartialEq for Content {
+	fn eq(&self, other: &Self) -> bool {
+		self.set.patterns() == other.set.patterns() && self.max_size == other.max_size && self.encoding == other.encoding
+	}
+}
+impl Eq for Content {}
+
+impl Content {
+	/// The beginning of the file at `path`, or `None` if it's binary
+	fn text(&self, path: &Path) -> Result<Option<String>> {
+		let file = fs::File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+		let mut bytes = Vec::new();
+		file.take(self.max_size)
+			.read_to_end(&mut bytes)
+			.with_context(|| format!("could not read {}", path.display()))?;
+		let (text, _, _) = self.encoding.decode(&bytes);
+		// UTF-16 text is full of NUL bytes, so they're looked for once it's decoded
+		Ok((!text.contains('\0')).then(|| text.into_owned()))
+	}
+}
+
+impl AsFilter for Content {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		let path = path.as_ref();
+		if !path.is_file() {
+			return false;
+		}
+		match self.text(path) {
+			Ok(Some(text)) => self.set.is_match(&text),
+			Ok(None) => {
+				log::trace!("(content) {} is binary", path.display());
+				false
+			}
+			Err(e) => {
+				log::debug!("{:?}", e);
+				false
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn match_contents() {
+		let dir = tempfile::tempdir().unwrap();
+		let (statement, binary, late) = (dir.path().join("a.txt"), dir.path().join("b.bin"), dir.path().join("c.txt"));
+		fs::write(&statement, "Bank\nStatement of account\nIBAN: DE123").unwrap();
+		fs::write(&binary, b"\x00\x01Statement of account").unwrap();
+		fs::write(&late, format!("{}Statement of account", " ".repeat(100))).unwrap();
+		let content: Content = toml::from_str("patterns = [\"(?i)statement of account\"]\nmax_size = 50").unwrap();
+		assert!(content.matches(&statement));
+		assert!(!content.matches(&binary));
+		assert!(!content.matches(&late));
+		assert!(!content.matches(dir.path()));
+	}
+
+	#[test]
+	fn decode_encodings() {
+		let dir = tempfile::tempdir().unwrap();
+		let (latin, utf16) = (dir.path().join("latin.txt"), dir.path().join("utf16.txt"));
+		fs::write(&latin, b"Caf\xe9").unwrap();
+		let mut bytes = vec![0xff, 0xfe];
+		bytes.extend("Café".encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+		fs::write(&utf16, bytes).unwrap();
+		let latin1: Content = toml::from_str("patterns = [\"Café\"]\nencoding = \"windows-1252\"").unwrap();
+		let utf8: Content = toml::from_str("patterns = [\"Café\"]").unwrap();
+		assert!(latin1.matches(&latin));
+		assert!(!utf8.matches(&latin));
+		// the byte order mark wins
+		assert!(utf8.matches(&utf16));
+		assert!(toml::from_str::<Content>("patterns = [\"(\"]").is_err());
+		assert!(toml::from_str::<Content>("patterns = [\"a\"]\nencoding = \"klingon\"").is_err());
+	}
+}
@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{
+	config::filters::AsFilter,
+	context::Context,
+	hash_index::{self, HashAlgorithm},
+	variables::Variable,
+};
+
+/// Matches files with the same contents as another one seen before, e.g. `{ type = "duplicate", algorithm = "sha256" }`.
+/// The first copy found doesn't match, so that a rule can set the others aside and keep it; it's available to templates as `{duplicate}`.
+/// Files are compared with the ones seen since organize started or, with `persistent`, with every file hashed by an earlier run too.
+/// Empty files are never duplicates.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Duplicate {
+	#[serde(default)]
+	pub algorithm: HashAlgorithm,
+	#[serde(default)]
+	pub persistent: bool,
+}
+
+impl AsFilter for Duplicate {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		let path = path.as_ref();
+		if path.metadata().map_or(true, |metadata| metadata.len() == 0) {
+			return false;
+		}
+		match hash_index::original(path, self.algorithm, self.persistent) {
+			Ok(Some(original)) => {
+				Context::set_variable(Variable::Duplicate, original.to_string_lossy());
+				true
+			}
+			Ok(None) => false,
+			Err(e) => {
+				log::debug!("could not look for copies of {}: {:?}", path.display(), e);
+				false
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	#[test]
+	fn match_later_copies() {
+		let dir = tempfile::tempdir().unwrap();
+		let contents = dir.path().to_string_lossy().to_string();
+		let (first, copy, empty, other) = (dir.path().join("a"), dir.path().join("b"), dir.path().join("c"), dir.path().join("d"));
+		fs::write(&first, &contents).unwrap();
+		fs::write(&copy, &contents).unwrap();
+		fs::write(&empty, "").unwrap();
+		fs::write(&other, "").unwrap();
+		let duplicate: Duplicate = toml::from_str("algorithm = \"sha256\"").unwrap();
+		assert!(!duplicate.matches(&first));
+		let _guard = Context::enter(&copy, None);
+		assert!(duplicate.matches(&copy));
+		assert_eq!(Context::variable(Variable::Duplicate), Some(first.to_string_lossy().to_string()));
+		assert!(!duplicate.matches(&empty));
+		assert!(!duplicate.matches(&other));
+		assert!(toml::from_str::<Duplicate>("algorithm = \"md5\"").is_err());
+	}
+}
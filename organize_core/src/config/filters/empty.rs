@@ -0,0 +1,79 @@
+use std::{fs, path::Path};
+
+use serde::Deserialize;
+
+use crate::{config::filters::AsFilter, path::IsHidden};
+
+/// Matches empty files and, in rules with `targets = "dirs"`, directories without entries, e.g. to remove leftover folders:
+/// `{ type = "empty", ignore_hidden = true }`. With `ignore_hidden`, directories holding nothing but hidden files
+/// (`.DS_Store`, `Thumbs.db`...) are empty too.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Empty {
+	#[serde(default)]
+	pub ignore_hidden: bool,
+}
+
+impl AsFilter for Empty {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		let path = path.as_ref();
+		if path.is_file() {
+			return path.metadata().is_ok_and(|metadata| metadata.len() == 0);
+		}
+		match fs::read_dir(path) {
+			Ok(mut entries) => !entries.any(|entry| entry.map_or(true, |entry| !self.ignore_hidden || !entry.path().is_hidden())),
+			Err(e) => {
+				log::debug!("could not list {}: {}", path.display(), e);
+				false
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::testing::Tree;
+
+	#[test]
+	fn match_empty_files_and_dirs() {
+		let tree = Tree::new()
+			.file("empty.txt", "")
+			.file("full.txt", "contents")
+			.file("hidden/.DS_Store", "")
+			.dir("nothing");
+		let (strict, lenient) = (Empty::default(), Empty { ignore_hidden: true });
+		assert!(strict.matches(tree.path().join("empty.txt")));
+		assert!(!strict.matches(tree.path().join("full.txt")));
+		assert!(strict.matches(tree.path().join("nothing")));
+		assert!(!strict.matches(tree.path().join("hidden")));
+		assert!(lenient.matches(tree.path().join("hidden")));
+		assert!(!lenient.matches(tree.path()));
+	}
+
+	#[test]
+	fn remove_empty_dirs() {
+		let tree = Tree::new()
+			.file("downloads/kept/file.txt", "")
+			.file("downloads/junk/.DS_Store", "")
+			.dir("downloads/nested/empty");
+		let outcome = tree
+			.run(
+				r#"
+				version = 2
+
+				[[rules]]
+				folders = [{ path = "{tree}/downloads", options = { recursive = 0, hidden_files = true } }]
+				options = { targets = "dirs" }
+				filters = [{ type = "empty", ignore_hidden = true }]
+				actions = [{ type = "delete" }]
+				"#,
+			)
+			.unwrap();
+		assert!(outcome.errors().is_empty());
+		tree.assert_layout(&["downloads/kept/file.txt"]);
+		// emptying `nested/empty` left `nested` empty too
+		assert!(!tree.path().join("downloads/nested").exists());
+		assert!(!tree.path().join("downloads/junk").exists());
+	}
+}
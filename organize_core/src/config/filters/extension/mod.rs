@@ -4,18 +4,73 @@ use crate::config::filters::AsFilter;
 use derive_more::Deref;
 use serde::Deserialize;
 
+/// Matches files by their extension.
+/// Extensions can have several parts (`tar.gz`), and split archives such as `backup.7z.001` match the extension before the part number.
 #[derive(Debug, Deserialize, Deref, Clone, Eq, PartialEq)]
+#[serde(from = "RawExtension")]
 pub struct Extension {
+	#[deref]
 	extensions: Vec<String>,
+	case_sensitive: bool,
+}
+
+#[derive(Deserialize)]
+struct RawExtension {
+	extensions: Vec<String>,
+	#[serde(default)]
+	case_sensitive: bool,
+}
+
+impl From<RawExtension> for Extension {
+	fn from(raw: RawExtension) -> Self {
+		Self::new(raw.extensions, raw.case_sensitive)
+	}
+}
+
+impl Extension {
+	pub fn new(extensions: Vec<String>, case_sensitive: bool) -> Self {
+		let extensions = extensions
+			.into_iter()
+			.map(|extension| {
+				let extension = extension.trim_start_matches('.');
+				match case_sensitive {
+					true => extension.to_string(),
+					false => extension.to_lowercase(),
+				}
+			})
+			.collect();
+		Self { extensions, case_sensitive }
+	}
+
+	/// Whether `filename` ends with `.extension`, with something before it
+	fn has(filename: &str, extension: &str) -> bool {
+		filename.len() > extension.len() + 1 && filename.ends_with(extension) && filename[..filename.len() - extension.len()].ends_with('.')
+	}
+
+	/// `filename` without a trailing part number, such as the `.001` of `backup.7z.001`
+	fn without_part_number(filename: &str) -> Option<&str> {
+		let (rest, number) = filename.rsplit_once('.')?;
+		match !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+			true => Some(rest),
+			false => None,
+		}
+	}
 }
 
 impl AsFilter for Extension {
 	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
-		path.as_ref()
-			.extension()
-			.and_then(|ext| ext.to_str())
-			.map(|s| self.extensions.contains(&s.to_string()))
-			.unwrap_or(false)
+		let filename = match path.as_ref().file_name() {
+			Some(filename) => filename.to_string_lossy(),
+			None => return false,
+		};
+		let filename = match self.case_sensitive {
+			true => filename.to_string(),
+			false => filename.to_lowercase(),
+		};
+		let unsplit = Self::without_part_number(&filename);
+		self.extensions
+			.iter()
+			.any(|extension| Self::has(&filename, extension) || unsplit.is_some_and(|unsplit| Self::has(unsplit, extension)))
 	}
 }
 
@@ -29,27 +84,41 @@ pub mod tests {
 
 	#[test]
 	fn single_match_pdf() {
-		let extension = Extension {
-			extensions: vec!["pdf".into()],
-		};
+		let extension = Extension::new(vec!["pdf".into()], false);
 		let path = PathBuf::from("$HOME/Downloads/test.pdf");
 		assert!(extension.matches(&path))
 	}
 	#[test]
 	fn multiple_match_pdf() {
-		let extension = Extension {
-			extensions: vec!["pdf".into(), "doc".into(), "docx".into()],
-		};
+		let extension = Extension::new(vec!["pdf".into(), "doc".into(), "docx".into()], false);
 		let path = PathBuf::from("$HOME/Downloads/test.pdf");
 		assert!(extension.matches(&path))
 	}
 
 	#[test]
 	fn no_match() {
-		let extension = Extension {
-			extensions: vec!["pdf".into(), "doc".into(), "docx".into()],
-		};
+		let extension = Extension::new(vec!["pdf".into(), "doc".into(), "docx".into()], false);
 		let path = PathBuf::from("$HOME/Downloads/test.jpg");
 		assert!(!extension.matches(&path))
 	}
+
+	#[test]
+	fn match_compound_and_split() {
+		let extension = Extension::new(vec!["tar.gz".into(), ".7z".into()], false);
+		assert!(extension.matches("$HOME/Downloads/foo.tar.gz"));
+		assert!(extension.matches("$HOME/Downloads/archive.7z.001"));
+		assert!(extension.matches("$HOME/Downloads/archive.7z"));
+		assert!(!extension.matches("$HOME/Downloads/foo.gz"));
+		assert!(!extension.matches("$HOME/Downloads/.tar.gz"));
+		assert!(!extension.matches("$HOME/Downloads/foo.targz"));
+	}
+
+	#[test]
+	fn match_case() {
+		let path = PathBuf::from("$HOME/Downloads/IMG_0001.JPG");
+		assert!(Extension::new(vec!["jpg".into()], false).matches(&path));
+		assert!(!Extension::new(vec!["jpg".into()], true).matches(&path));
+		let extension: Extension = toml::from_str("extensions = [\"JPG\"]").unwrap();
+		assert!(extension.matches("$HOME/Downloads/img.jpg"));
+	}
 }
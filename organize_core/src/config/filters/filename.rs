@@ -1,44 +1,75 @@
-use std::path::Path;
+use std::{borrow::Cow, path::Path};
 
-use crate::config::filters::AsFilter;
+use crate::config::filters::{AsFilter, Target};
 use serde::Deserialize;
 
 #[derive(Eq, PartialEq, Deserialize, Debug, Clone, Default)]
+#[serde(from = "RawFilename")]
 pub struct Filename {
 	pub startswith: Option<String>,
 	pub endswith: Option<String>,
 	pub contains: Option<String>,
-	#[serde(default)]
 	pub case_sensitive: bool,
+	/// what the patterns are compared with
+	pub target: Target,
 }
 
-impl AsFilter for Filename {
-	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
-		let mut filename = path.as_ref().file_name().unwrap().to_str().unwrap().to_string();
-		let mut filter = self.clone();
-		if !self.case_sensitive {
-			filename = filename.to_lowercase();
-			if let Some(startswith) = &filter.startswith {
-				filter.startswith = Some(startswith.to_lowercase())
-			}
-			if let Some(endswith) = &filter.endswith {
-				filter.endswith = Some(endswith.to_lowercase())
-			}
-			if let Some(contains) = &filter.contains {
-				filter.contains = Some(contains.to_lowercase())
-			}
-		}
-		let mut matches = true;
-		if let Some(startswith) = &filter.startswith {
-			matches = matches && filename.starts_with(startswith);
-		}
-		if let Some(endswith) = &filter.endswith {
-			matches = matches && filename.ends_with(endswith)
+#[derive(Deserialize)]
+struct RawFilename {
+	startswith: Option<String>,
+	endswith: Option<String>,
+	contains: Option<String>,
+	#[serde(default)]
+	case_sensitive: bool,
+	#[serde(default)]
+	target: Target,
+}
+
+impl From<RawFilename> for Filename {
+	// lowercase the patterns once when the config is loaded, instead of once per file
+	fn from(raw: RawFilename) -> Self {
+		let case_sensitive = raw.case_sensitive;
+		let lower = |s: Option<String>| match case_sensitive {
+			true => s,
+			false => s.map(|s| s.to_lowercase()),
+		};
+		Self {
+			startswith: lower(raw.startswith),
+			endswith: lower(raw.endswith),
+			contains: lower(raw.contains),
+			case_sensitive,
+			target: raw.target,
 		}
-		if let Some(contains) = &filter.contains {
-			matches = matches && filename.contains(contains);
+	}
+}
+
+impl Filename {
+	fn normalize<'a>(&self, s: &'a str) -> Cow<'a, str> {
+		match !self.case_sensitive && s.chars().any(char::is_uppercase) {
+			true => Cow::Owned(s.to_lowercase()),
+			false => Cow::Borrowed(s),
 		}
-		matches
+	}
+}
+
+impl AsFilter for Filename {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		let filename = match self.target.of(path.as_ref()) {
+			Some(filename) => filename,
+			None => return false,
+		};
+		let filename = self.normalize(&filename);
+		self.startswith
+			.as_deref()
+			.is_none_or(|startswith| filename.starts_with(&*self.normalize(startswith)))
+			&& self
+				.endswith
+				.as_deref()
+				.is_none_or(|endswith| filename.ends_with(&*self.normalize(endswith)))
+			&& self
+				.contains
+				.as_deref()
+				.is_none_or(|contains| filename.contains(&*self.normalize(contains)))
 	}
 }
 
@@ -48,6 +79,18 @@ mod tests {
 
 	use super::*;
 
+	#[test]
+	fn match_stem() {
+		let path = PathBuf::from("$HOME/Downloads/invoice_final.pdf");
+		let filename: Filename = toml::from_str("endswith = \"_final\"\ntarget = \"stem\"").unwrap();
+		assert!(filename.matches(&path));
+		let filename = Filename {
+			target: Target::Filename,
+			..filename
+		};
+		assert!(!filename.matches(&path))
+	}
+
 	#[test]
 	fn match_beginning_case_insensitive() {
 		let path = PathBuf::from("$HOME/Downloads/test.pdf");
@@ -110,6 +153,14 @@ mod tests {
 		};
 		assert!(!filename.matches(path))
 	}
+	#[test]
+	fn deserialize_lowers_patterns() {
+		let filename: Filename = toml::from_str("startswith = \"TE\"").unwrap();
+		assert_eq!(filename.startswith.as_deref(), Some("te"));
+		let filename: Filename = toml::from_str("startswith = \"TE\"\ncase_sensitive = true").unwrap();
+		assert_eq!(filename.startswith.as_deref(), Some("TE"));
+	}
+
 	#[test]
 	fn match_containing_case_sensitive() {
 		let path = PathBuf::from("$HOME/Downloads/tESt.pdf");
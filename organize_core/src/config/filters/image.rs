@@ -0,0 +1,143 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use serde::{de::Error, Deserialize, Deserializer};
+
+use crate::{
+	config::filters::AsFilter,
+	context::Context,
+	dimensions::{Dimensions, Orientation},
+};
+
+/// Matches pictures by their dimensions, e.g. wallpapers: `{ type = "image", min_width = 1920, aspect_ratio = "16:9" }`,
+/// or phone screenshots: `{ type = "image", orientation = "portrait", max_megapixels = 4 }`. Bounds are inclusive,
+/// and the aspect ratio (`16:9`, `16/9` or `1.78`) matches give or take 1%.
+/// The dimensions are then available to templates as `{image.width}`, `{image.height}`, `{image.megapixels}`,
+/// `{image.aspect_ratio}` and `{image.orientation}`.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Image {
+	#[serde(default)]
+	pub min_width: Option<u32>,
+	#[serde(default)]
+	pub max_width: Option<u32>,
+	#[serde(default)]
+	pub min_height: Option<u32>,
+	#[serde(default)]
+	pub max_height: Option<u32>,
+	#[serde(default)]
+	pub min_megapixels: Option<f64>,
+	#[serde(default)]
+	pub max_megapixels: Option<f64>,
+	#[serde(default, deserialize_with = "deserialize_ratio")]
+	pub aspect_ratio: Option<f64>,
+	/// `portrait`, `landscape` or `square`
+	#[serde(default)]
+	pub orientation: Option<Orientation>,
+}
+
+// the bounds are never NaN, `parse_ratio` and TOML don't produce it
+impl Eq for Image {}
+
+impl Image {
+	fn accepts(&self, dimensions: &Dimensions) -> bool {
+		let within = |value: f64, min: Option<f64>, max: Option<f64>| min.is_none_or(|min| value >= min) && max.is_none_or(|max| value <= max);
+		let (width, height) = (f64::from(dimensions.width), f64::from(dimensions.height));
+		within(width, self.min_width.map(f64::from), self.max_width.map(f64::from))
+			&& within(height, self.min_height.map(f64::from), self.max_height.map(f64::from))
+			&& within(dimensions.megapixels(), self.min_megapixels, self.max_megapixels)
+			&& self.aspect_ratio.is_none_or(|ratio| dimensions.has_aspect_ratio(ratio))
+			&& self
+				.orientation
+				.is_none_or(|orientation| dimensions.orientation() == orientation)
+	}
+}
+
+impl AsFilter for Image {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		let path = path.as_ref();
+		if !path.is_file() {
+			return false;
+		}
+		let dimensions = match Dimensions::read(path) {
+			Ok(dimensions) => dimensions,
+			Err(e) => {
+				log::trace!("(image) {:?}", e);
+				return false;
+			}
+		};
+		if !self.accepts(&dimensions) {
+			return false;
+		}
+		for variable in Dimensions::VARIABLES {
+			if let Some(value) = dimensions.get(variable) {
+				Context::set_variable(variable, value);
+			}
+		}
+		true
+	}
+}
+
+/// Parses aspect ratios such as `16:9`, `16/9` or `1.78`
+fn parse_ratio(s: &str) -> Result<f64> {
+	let invalid = || anyhow!("invalid aspect ratio {} (expected e.g. 16:9 or 1.78)", s);
+	let number = |n: &str| n.trim().parse::<f64>().map_err(|_| invalid());
+	let ratio = match s.split_once([':', '/']) {
+		Some((width, height)) => number(width)? / number(height)?,
+		None => number(s)?,
+	};
+	match ratio.is_finite() && ratio > 0.0 {
+		true => Ok(ratio),
+		false => Err(invalid()),
+	}
+}
+
+fn deserialize_ratio<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum Raw {
+		Number(f64),
+		Ratio(String),
+	}
+
+	match Raw::deserialize(deserializer)? {
+		Raw::Number(ratio) => parse_ratio(&ratio.to_string()).map(Some).map_err(D::Error::custom),
+		Raw::Ratio(str) => parse_ratio(&str).map(Some).map_err(D::Error::custom),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_ratios() {
+		assert_eq!(parse_ratio("16:9").unwrap(), 16.0 / 9.0);
+		assert_eq!(parse_ratio("4 / 3").unwrap(), 4.0 / 3.0);
+		assert_eq!(parse_ratio("1.5").unwrap(), 1.5);
+		assert!(parse_ratio("16:0").is_err());
+		assert!(parse_ratio("wide").is_err());
+		assert!(toml::from_str::<Image>("aspect_ratio = \"wide\"").is_err());
+		assert_eq!(toml::from_str::<Image>("aspect_ratio = 2").unwrap().aspect_ratio, Some(2.0));
+	}
+
+	#[test]
+	fn match_dimensions() {
+		let dir = tempfile::tempdir().unwrap();
+		let (wallpaper, screenshot, text) = (dir.path().join("a.png"), dir.path().join("b.png"), dir.path().join("c.txt"));
+		image::RgbImage::new(160, 90).save(&wallpaper).unwrap();
+		image::RgbImage::new(90, 195).save(&screenshot).unwrap();
+		std::fs::write(&text, "not a picture").unwrap();
+		let wide: Image = toml::from_str("min_width = 100\naspect_ratio = \"16:9\"").unwrap();
+		let portrait: Image = toml::from_str("orientation = \"portrait\"\nmax_megapixels = 0.1").unwrap();
+		assert!(wide.matches(&wallpaper));
+		assert!(!wide.matches(&screenshot));
+		assert!(portrait.matches(&screenshot));
+		assert!(!portrait.matches(&wallpaper));
+		assert!(!Image::default().matches(&text));
+		assert!(Image::default().matches(&wallpaper));
+	}
+}
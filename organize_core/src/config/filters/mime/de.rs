@@ -1,11 +1,9 @@
 use crate::config::filters::mime::{Mime, MimeWrapper};
 use serde::{
 	de::MapAccess,
-
 	de::{Error, Visitor},
 	Deserialize, Deserializer,
 };
-use itertools::Itertools;
 
 use std::{fmt, str::FromStr};
 
@@ -35,20 +33,30 @@ impl<'de> Deserialize<'de> for MimeWrapper {
 			where
 				M: MapAccess<'de>,
 			{
-				let mut types = Vec::new();
+				#[derive(Deserialize)]
+				#[serde(untagged)]
+				enum OneOrMany {
+					One(String),
+					Many(Vec<String>),
+				}
+
+				let mut wrapper = MimeWrapper::new(vec![]);
 				while let Some(key) = map.next_key::<String>()? {
 					match key.as_str() {
 						"types" => {
-							let value = map.next_value::<Vec<String>>()?;
-							types = value
-								.into_iter()
-								.map(|s| Mime::from_str(&s).map_err(M::Error::custom))
-								.try_collect()?;
+							let types = match map.next_value::<OneOrMany>()? {
+								OneOrMany::One(str) => vec![str],
+								OneOrMany::Many(types) => types,
+							};
+							for str in types.iter() {
+								wrapper.push(str).map_err(M::Error::custom)?;
+							}
 						}
-						key => return Err(M::Error::unknown_field(key, &["types"])),
+						"deep" => wrapper.deep = map.next_value()?,
+						key => return Err(M::Error::unknown_field(key, &["types", "deep"])),
 					}
 				}
-				Ok(MimeWrapper { types })
+				Ok(wrapper)
 			}
 		}
 		deserializer.deserialize_any(WrapperVisitor)
@@ -0,0 +1,123 @@
+use std::{path::Path, str::FromStr};
+
+use anyhow::anyhow;
+use strum_macros::{Display, EnumString};
+
+/// Common kinds of files that can be used in place of a list of MIME types, e.g. `types = ["image", "video"]`.
+/// A file belongs to a group if its guessed MIME type or its extension is in the group's sets,
+/// since guessing by type alone misses things such as RAW photos or most source code.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Group {
+	Image,
+	Video,
+	Audio,
+	Document,
+	Archive,
+	Code,
+}
+
+impl Group {
+	pub fn parse(s: &str) -> anyhow::Result<Self> {
+		Self::from_str(&s.to_lowercase()).map_err(|_| anyhow!("unknown group `{}`", s))
+	}
+
+	/// MIME types, where `*` stands for any subtype
+	fn mimes(&self) -> &'static [&'static str] {
+		match self {
+			Self::Image => &["image/*"],
+			Self::Video => &["video/*"],
+			Self::Audio => &["audio/*"],
+			Self::Document => &[
+				"application/pdf",
+				"application/msword",
+				"application/rtf",
+				"application/epub+zip",
+				"application/vnd.ms-excel",
+				"application/vnd.ms-powerpoint",
+				"application/vnd.oasis.opendocument.*",
+				"application/vnd.openxmlformats-officedocument.*",
+			],
+			Self::Archive => &[
+				"application/zip",
+				"application/gzip",
+				"application/x-tar",
+				"application/x-bzip2",
+				"application/x-xz",
+				"application/x-7z-compressed",
+				"application/vnd.rar",
+				"application/x-rar-compressed",
+				"application/zstd",
+			],
+			Self::Code => &[],
+		}
+	}
+
+	/// Lowercase extensions, without the leading dot
+	fn extensions(&self) -> &'static [&'static str] {
+		match self {
+			Self::Image => &[
+				"jpg", "jpeg", "png", "gif", "bmp", "tif", "tiff", "webp", "heic", "heif", "avif", "svg", "ico", "psd", "raw", "dng", "cr2", "cr3",
+				"nef", "arw", "orf", "rw2",
+			],
+			Self::Video => &[
+				"mp4", "m4v", "mkv", "mov", "avi", "wmv", "flv", "webm", "mpg", "mpeg", "3gp", "mts", "m2ts",
+			],
+			Self::Audio => &[
+				"mp3", "flac", "wav", "ogg", "oga", "opus", "m4a", "aac", "wma", "aiff", "alac", "mid", "midi",
+			],
+			Self::Document => &[
+				"pdf", "doc", "docx", "odt", "rtf", "txt", "md", "tex", "epub", "xls", "xlsx", "ods", "csv", "ppt", "pptx", "odp", "pages",
+				"numbers", "key",
+			],
+			Self::Archive => &[
+				"zip", "tar", "gz", "tgz", "bz2", "tbz2", "xz", "txz", "zst", "lz4", "7z", "rar", "cab", "iso", "dmg",
+			],
+			Self::Code => &[
+				"rs", "py", "js", "mjs", "ts", "jsx", "tsx", "c", "h", "cc", "cpp", "hpp", "cs", "java", "kt", "go", "rb", "php", "swift", "scala",
+				"lua", "pl", "r", "sh", "bash", "zsh", "fish", "ps1", "sql", "html", "css", "scss", "json", "yaml", "yml", "toml", "xml", "ini",
+				"ipynb",
+			],
+		}
+	}
+
+	pub fn matches(&self, path: &Path, guess: &mime::Mime) -> bool {
+		let extension = path.extension().map(|extension| extension.to_string_lossy().to_lowercase());
+		if let Some(extension) = extension {
+			if self.extensions().contains(&extension.as_str()) {
+				return true;
+			}
+		}
+		self.matches_mime(guess)
+	}
+
+	/// Whether `mime` is in the group, regardless of the file's extension
+	pub fn matches_mime(&self, mime: &mime::Mime) -> bool {
+		self.mimes().iter().any(|pattern| match pattern.strip_suffix('*') {
+			Some(prefix) => mime.essence_str().starts_with(prefix),
+			None => mime.essence_str() == *pattern,
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn matches(group: Group, path: &str) -> bool {
+		let path = Path::new(path);
+		group.matches(path, &mime_guess::from_path(path).first_or_octet_stream())
+	}
+
+	#[test]
+	fn match_groups() {
+		assert!(matches(Group::Image, "photo.CR2"));
+		assert!(matches(Group::Image, "photo.jpg"));
+		assert!(matches(Group::Document, "report.docx"));
+		assert!(matches(Group::Archive, "backup.tar.gz"));
+		assert!(matches(Group::Code, "main.rs"));
+		assert!(!matches(Group::Video, "main.rs"));
+		assert!(Group::parse("Video").is_ok());
+		assert!(Group::parse("spreadsheet").is_err());
+	}
+}
@@ -1,6 +1,9 @@
 mod de;
+mod groups;
 
-use crate::config::filters::AsFilter;
+pub use groups::Group;
+
+use crate::{config::filters::AsFilter, context::Context, variables::Variable};
 use derive_more::Deref;
 use mime::FromStrError;
 use std::{convert::TryFrom, path::Path, str::FromStr};
@@ -17,8 +20,13 @@ impl FromStr for Mime {
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Deref)]
-pub struct MimeWrapper{
-	types: Vec<Mime>
+pub struct MimeWrapper {
+	#[deref]
+	types: Vec<Mime>,
+	groups: Vec<Group>,
+	/// whether the type is sniffed from the first bytes of the file, so that renamed files or files without an extension
+	/// are recognized, instead of guessed from the extension. Files whose contents aren't recognized fall back to their extension.
+	deep: bool,
 }
 
 impl From<Mime> for MimeWrapper {
@@ -31,34 +39,71 @@ impl TryFrom<Vec<&str>> for MimeWrapper {
 	type Error = FromStrError;
 
 	fn try_from(value: Vec<&str>) -> Result<Self, Self::Error> {
-		let mut vec = Vec::with_capacity(value.len());
+		let mut wrapper = MimeWrapper::new(Vec::with_capacity(value.len()));
 		for str in value {
-			match Mime::from_str(str) {
-				Ok(mime) => vec.push(mime),
-				Err(e) => return Err(e),
-			}
+			wrapper.push(str)?;
 		}
-		Ok(MimeWrapper::new(vec))
+		Ok(wrapper)
 	}
 }
 
 impl AsFilter for MimeWrapper {
 	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
-		let guess = mime_guess::from_path(path.as_ref()).first_or_octet_stream();
-		self.iter().any(|mime| match (mime.type_(), mime.subtype()) {
+		let sniffed = match self.deep {
+			true => sniff(path.as_ref()),
+			false => None,
+		};
+		let guess = sniffed
+			.clone()
+			.unwrap_or_else(|| mime_guess::from_path(path.as_ref()).first_or_octet_stream());
+		let matches = self.groups.iter().any(|group| match sniffed {
+			// the contents are known, the extension might be lying
+			Some(_) => group.matches_mime(&guess),
+			None => group.matches(path.as_ref(), &guess),
+		}) || self.iter().any(|mime| match (mime.type_(), mime.subtype()) {
 			(mime::STAR, subtype) => subtype == guess.subtype(),
 			(type_, mime::STAR) => type_ == guess.type_(),
 			(type_, subtype) => type_ == guess.type_() && subtype == guess.subtype(),
-		})
+		});
+		if matches {
+			Context::set_variable(Variable::MimeType, guess.type_().as_str());
+			Context::set_variable(Variable::MimeSubtype, guess.subtype().as_str());
+			if sniffed.is_some() {
+				Context::set_variable(Variable::MimeDeepType, guess.type_().as_str());
+				Context::set_variable(Variable::MimeDeepSubtype, guess.subtype().as_str());
+			}
+		}
+		matches
 	}
 }
 
 impl MimeWrapper {
 	pub fn new(vec: Vec<Mime>) -> Self {
-		Self{types: vec}
+		Self {
+			types: vec,
+			groups: vec![],
+			deep: false,
+		}
+	}
+
+	/// Adds a MIME type, or one of the [`Group`]s when `str` is the name of one
+	fn push(&mut self, str: &str) -> Result<(), FromStrError> {
+		match Group::parse(str) {
+			Ok(group) => self.groups.push(group),
+			Err(_) => self.types.push(Mime::from_str(str)?),
+		}
+		Ok(())
 	}
 }
 
+/// The MIME type of the file at `path` according to its first bytes, if they're recognized
+pub(crate) fn sniff(path: &Path) -> Option<mime::Mime> {
+	infer::get_from_path(path)
+		.ok()
+		.flatten()
+		.and_then(|kind| kind.mime_type().parse().ok())
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -67,7 +112,34 @@ mod tests {
 		let types = MimeWrapper::try_from(vec!["image/*", "audio/*"]).unwrap();
 		let img = "test.jpg";
 		let audio = "test.ogg";
-		assert!(types.matches(&img));
-		assert!(types.matches(&audio));
+		assert!(types.matches(img));
+		assert!(types.matches(audio));
+	}
+
+	#[test]
+	fn test_match_groups() {
+		let types = MimeWrapper::try_from(vec!["document", "text/csv"]).unwrap();
+		assert!(types.matches("report.pdf"));
+		assert!(types.matches("table.csv"));
+		assert!(!types.matches("photo.jpg"));
+		assert!(MimeWrapper::try_from(vec!["spreadsheet"]).is_err());
+		let types: MimeWrapper = toml::from_str("types = \"image\"").unwrap();
+		assert!(types.matches("photo.NEF"));
+	}
+
+	#[test]
+	fn test_match_deep() {
+		let dir = tempfile::tempdir().unwrap();
+		let png = dir.path().join("screenshot");
+		std::fs::write(&png, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+		let fake = dir.path().join("fake.jpg");
+		std::fs::write(&fake, "just text").unwrap();
+		let shallow: MimeWrapper = toml::from_str("types = \"image\"").unwrap();
+		let deep: MimeWrapper = toml::from_str("types = \"image/*\"\ndeep = true").unwrap();
+		assert!(!shallow.matches(&png));
+		assert!(deep.matches(&png));
+		// unrecognized contents fall back to the extension
+		assert!(deep.matches(&fake));
+		assert_eq!(sniff(&png).unwrap().essence_str(), "image/png");
 	}
 }
@@ -1,27 +1,88 @@
-use std::path::Path;
+use std::{iter::FromIterator, path::Path, str::FromStr, sync::Arc, time::SystemTime};
 
+use anyhow::{anyhow, bail, Context};
 use derive_more::Deref;
 use serde::Deserialize;
+use strum_macros::IntoStaticStr;
 
 use extension::Extension;
 use filename::Filename;
 
+mod age;
+mod audio_tags;
+mod classify;
+mod content;
+mod duplicate;
+mod empty;
 mod extension;
 mod filename;
-mod mime;
+mod image;
+pub(crate) mod mime;
+mod owner;
+mod permissions;
+mod predicate;
+mod processed;
 mod regex;
+mod similar;
+mod size;
+mod target;
+mod zone;
 
-use crate::config::filters::mime::MimeWrapper;
+pub use self::image::Image;
+pub use audio_tags::AudioTags;
+pub use classify::Classify;
+pub use content::Content;
+pub use duplicate::Duplicate;
+pub use empty::Empty;
+pub use owner::Owner;
+pub use permissions::{Mode, Permissions};
+pub use predicate::{Check, Predicate};
+pub use processed::Processed;
+pub use similar::{Algorithm, Similar};
+pub use zone::Zone;
+
+pub(crate) use age::deserialize_duration;
+pub use age::{parse_duration, Age, Moment, Timestamp};
+pub(crate) use size::deserialize_size;
+pub use size::parse_size;
+pub use target::Target;
+
+use crate::config::filters::{mime::MimeWrapper, size::Size, zone::Zones};
 use crate::config::{actions::script::Script, filters::regex::Regex, options::apply::Apply};
+use crate::in_use;
 
-#[derive(Debug, Clone, Deserialize, Eq, PartialEq)]
+#[derive(Debug, Clone, Deserialize, Eq, PartialEq, IntoStaticStr)]
 #[serde(tag = "type", rename_all(deserialize = "lowercase"))]
+#[strum(serialize_all = "lowercase")]
 pub enum Filter {
 	Regex(Regex),
 	Filename(Filename),
 	Extension(Extension),
 	Script(Script),
 	Mime(MimeWrapper),
+	Zone(Zones),
+	#[serde(alias = "last_modified")]
+	Modified(Age),
+	Created(Age),
+	#[serde(alias = "last_accessed")]
+	Accessed(Age),
+	Size(Size),
+	Similar(Similar),
+	Classify(Classify),
+	Duplicate(Duplicate),
+	/// files that another process has open, e.g. downloads that are still being written
+	Open,
+	/// files that no other process has open
+	Closed,
+	Processed(Processed),
+	#[serde(rename = "audio_tags")]
+	#[strum(serialize = "audio_tags")]
+	AudioTags(AudioTags),
+	Content(Content),
+	Empty(Empty),
+	Owner(Owner),
+	Permissions(Permissions),
+	Image(Image),
 }
 
 pub trait AsFilter {
@@ -36,28 +97,168 @@ impl AsFilter for Filter {
 			Filter::Extension(extension) => extension.matches(path),
 			Filter::Script(script) => script.matches(path),
 			Filter::Mime(mime) => mime.matches(path),
+			Filter::Zone(zones) => zones.matches(path),
+			Filter::Modified(age) => age.matches(path, Timestamp::Modified),
+			Filter::Created(age) => age.matches(path, Timestamp::Created),
+			Filter::Accessed(age) => age.matches(path, Timestamp::Accessed),
+			Filter::Size(size) => size.matches(path),
+			Filter::Similar(similar) => similar.matches(path),
+			Filter::Classify(classify) => classify.matches(path),
+			Filter::Duplicate(duplicate) => duplicate.matches(path),
+			Filter::Open => in_use::is_open(path),
+			Filter::Closed => !in_use::is_open(path),
+			Filter::Processed(processed) => processed.matches(path),
+			Filter::AudioTags(tags) => tags.matches(path),
+			Filter::Content(content) => content.matches(path),
+			Filter::Empty(empty) => empty.matches(path),
+			Filter::Owner(owner) => owner.matches(path),
+			Filter::Permissions(permissions) => permissions.matches(path),
+			Filter::Image(image) => image.matches(path),
+		}
+	}
+}
+
+impl Filter {
+	/// The function that tests files against this filter, see [`Predicate`]
+	pub fn compile(&self) -> Check {
+		match self.clone() {
+			Filter::Regex(regex) => Arc::new(move |path| regex.matches(path)),
+			Filter::Filename(filename) => Arc::new(move |path| filename.matches(path)),
+			Filter::Extension(extension) => Arc::new(move |path| extension.matches(path)),
+			Filter::Script(script) => Arc::new(move |path| script.matches(path)),
+			Filter::Mime(mime) => Arc::new(move |path| mime.matches(path)),
+			Filter::Zone(zones) => Arc::new(move |path| zones.matches(path)),
+			Filter::Modified(age) => Arc::new(move |path| age.matches(path, Timestamp::Modified)),
+			Filter::Created(age) => Arc::new(move |path| age.matches(path, Timestamp::Created)),
+			Filter::Accessed(age) => Arc::new(move |path| age.matches(path, Timestamp::Accessed)),
+			Filter::Size(size) => Arc::new(move |path| size.matches(path)),
+			Filter::Similar(similar) => Arc::new(move |path| similar.matches(path)),
+			Filter::Classify(classify) => Arc::new(move |path| classify.matches(path)),
+			Filter::Duplicate(duplicate) => Arc::new(move |path| duplicate.matches(path)),
+			Filter::Open => Arc::new(|path| in_use::is_open(path)),
+			Filter::Closed => Arc::new(|path| !in_use::is_open(path)),
+			Filter::Processed(processed) => Arc::new(move |path| processed.matches(path)),
+			Filter::AudioTags(tags) => Arc::new(move |path| tags.matches(path)),
+			Filter::Content(content) => Arc::new(move |path| content.matches(path)),
+			Filter::Empty(empty) => Arc::new(move |path| empty.matches(path)),
+			Filter::Owner(owner) => Arc::new(move |path| owner.matches(path)),
+			Filter::Permissions(permissions) => Arc::new(move |path| permissions.matches(path)),
+			Filter::Image(image) => Arc::new(move |path| image.matches(path)),
+		}
+	}
+
+	/// How much testing a file against this filter costs, roughly: the path, the metadata, the contents or other processes
+	fn cost(&self) -> u8 {
+		match self {
+			Filter::Regex(_) | Filter::Filename(_) | Filter::Extension(_) => 0,
+			Filter::Modified(_)
+			| Filter::Created(_)
+			| Filter::Accessed(_)
+			| Filter::Size(_)
+			| Filter::Owner(_)
+			| Filter::Permissions(_)
+			| Filter::Empty(_) => 1,
+			Filter::Mime(_)
+			| Filter::Zone(_)
+			| Filter::Processed(_)
+			| Filter::AudioTags(_)
+			| Filter::Content(_)
+			| Filter::Image(_)
+			| Filter::Duplicate(_)
+			| Filter::Similar(_)
+			| Filter::Classify(_) => 2,
+			Filter::Script(_) | Filter::Open | Filter::Closed => 3,
+		}
+	}
+}
+
+/// Parses the short form of a filter used on the command line, e.g. `extension=pdf,docx`, `size>10MB`, `modified<7d`, `created<2024-01-31`,
+/// `user=alice`, `permissions=o+w` or `orientation=portrait`.
+/// Any other filter can be written as an inline table, like in the config: `{ type = "filename", startswith = "IMG" }`.
+impl FromStr for Filter {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let s = s.trim();
+		if s.starts_with('{') {
+			let table: toml::Value = toml::from_str(&format!("filter = {}", s)).context("invalid filter table")?;
+			let filter = table.get("filter").cloned().ok_or_else(|| anyhow!("invalid filter table"))?;
+			return filter.try_into().context("invalid filter");
 		}
+		let split = s
+			.find(['=', '<', '>'])
+			.ok_or_else(|| anyhow!("expected a filter such as `extension=pdf` or `size>10MB`, found `{}`", s))?;
+		let (key, value) = (s[..split].trim(), s[split + 1..].trim());
+		let list = || toml::Value::Array(value.split(',').map(|item| toml::Value::String(item.trim().into())).collect());
+		let id = || {
+			value
+				.parse()
+				.map_or_else(|_| toml::Value::String(value.into()), toml::Value::Integer)
+		};
+		let (ty, field, value) = match (key, &s[split..=split]) {
+			("extension", "=") => ("extension", "extensions", list()),
+			("mime", "=") => ("mime", "types", list()),
+			("regex", "=") => ("regex", "patterns", list()),
+			(field @ ("startswith" | "endswith" | "contains"), "=") => ("filename", field, toml::Value::String(value.into())),
+			(field @ ("user" | "group"), "=") => ("owner", field, id()),
+			("permissions", "=") => ("permissions", "mode", toml::Value::String(value.into())),
+			("orientation", "=") => ("image", "orientation", toml::Value::String(value.into())),
+			("size", ">") => ("size", "larger_than", toml::Value::String(value.into())),
+			("size", "<") => ("size", "smaller_than", toml::Value::String(value.into())),
+			// the age of the file, not its date: `>` is older
+			(ty @ ("modified" | "created" | "accessed"), ">") => (ty, "older_than", toml::Value::String(value.into())),
+			(ty @ ("modified" | "created" | "accessed"), "<") => (ty, "newer_than", toml::Value::String(value.into())),
+			(key, op) => bail!("unsupported filter `{}{}`", key, op),
+		};
+		let mut table = toml::map::Map::new();
+		table.insert("type".into(), toml::Value::String(ty.into()));
+		table.insert(field.into(), value);
+		toml::Value::Table(table)
+			.try_into()
+			.with_context(|| format!("invalid filter `{}`", s))
 	}
 }
 
 #[derive(Debug, Clone, Deserialize, Deref, Eq, PartialEq)]
 pub struct Filters(pub(crate) Vec<Filter>);
 
+impl FromIterator<Filter> for Filters {
+	fn from_iter<T: IntoIterator<Item = Filter>>(iter: T) -> Self {
+		Self(iter.into_iter().collect())
+	}
+}
+
 impl Filters {
+	/// Whether `path` matches the filters selected by `apply`. The filters are compiled for each call, see [`Predicate`] to test many files.
 	pub fn r#match<T: AsRef<Path>>(&self, path: T, apply: &Apply) -> bool {
+		Predicate::new(self, apply).matches(path)
+	}
+}
+
+impl Filters {
+	/// The oldest creation time a file can have to match these filters, if every possible match is bounded by one
+	pub fn cutoff(&self, apply: &Apply) -> Option<SystemTime> {
+		let cutoff = |filter: &Filter| match filter {
+			Filter::Created(created) => created.cutoff(),
+			_ => None,
+		};
+		let selected = |i: &usize| match apply {
+			Apply::AllOf(indices) | Apply::AnyOf(indices) => indices.contains(i),
+			Apply::All | Apply::Any => true,
+		};
+		let mut cutoffs = self
+			.iter()
+			.enumerate()
+			.filter(|(i, _)| selected(i))
+			.map(|(_, filter)| cutoff(filter));
 		match apply {
-			Apply::All => self.iter().all(|filter| filter.matches(&path)),
-			Apply::Any => self.iter().any(|filter| filter.matches(&path)),
-			Apply::AllOf(filters) => self
-				.iter()
-				.enumerate()
-				.filter(|(i, _)| filters.contains(i))
-				.all(|(_, filter)| filter.matches(&path)),
-			Apply::AnyOf(filters) => self
-				.iter()
-				.enumerate()
-				.filter(|(i, _)| filters.contains(i))
-				.any(|(_, filter)| filter.matches(&path)),
+			// every filter must match, so the most recent cutoff applies
+			Apply::All | Apply::AllOf(_) => cutoffs.flatten().max(),
+			// any filter can match, so all of them need a cutoff and the oldest one applies
+			Apply::Any | Apply::AnyOf(_) => cutoffs.try_fold(None, |acc: Option<SystemTime>, cutoff| {
+				let cutoff = cutoff?;
+				Some(Some(acc.map_or(cutoff, |acc| acc.min(cutoff))))
+			})?,
 		}
 	}
 }
@@ -101,9 +302,9 @@ mod tests {
 			}),
 		]);
 		let path = "$HOME/Downloads/unsplash.jpg";
-		assert!(filters.r#match(&path, &Apply::AnyOf(vec![0, 1])));
-		assert!(!filters.r#match(&path, &Apply::AnyOf(vec![1, 2])));
-		assert!(filters.r#match(&path, &Apply::AnyOf(vec![0, 2])));
+		assert!(filters.r#match(path, &Apply::AnyOf(vec![0, 1])));
+		assert!(!filters.r#match(path, &Apply::AnyOf(vec![1, 2])));
+		assert!(filters.r#match(path, &Apply::AnyOf(vec![0, 2])));
 	}
 
 	#[test]
@@ -119,11 +320,78 @@ mod tests {
 			Filter::Regex(Regex::from_str(".*\\.jpg").unwrap()),
 		]);
 		let path = "$HOME/Downloads/unsplash.jpg";
-		assert!(!filters.r#match(&path, &Apply::AllOf(vec![0, 1])));
-		assert!(!filters.r#match(&path, &Apply::AllOf(vec![1, 2])));
-		assert!(!filters.r#match(&path, &Apply::AllOf(vec![1, 3])));
-		assert!(!filters.r#match(&path, &Apply::AllOf(vec![2, 3])));
-		assert!(!filters.r#match(&path, &Apply::AllOf(vec![0, 2])));
-		assert!(filters.r#match(&path, &Apply::AllOf(vec![0, 3])));
+		assert!(!filters.r#match(path, &Apply::AllOf(vec![0, 1])));
+		assert!(!filters.r#match(path, &Apply::AllOf(vec![1, 2])));
+		assert!(!filters.r#match(path, &Apply::AllOf(vec![1, 3])));
+		assert!(!filters.r#match(path, &Apply::AllOf(vec![2, 3])));
+		assert!(!filters.r#match(path, &Apply::AllOf(vec![0, 2])));
+		assert!(filters.r#match(path, &Apply::AllOf(vec![0, 3])));
+	}
+
+	#[test]
+	fn parse_short_form() {
+		assert_eq!(
+			Filter::from_str("extension=pdf, docx").unwrap(),
+			Filter::Extension(toml::from_str("extensions = [\"pdf\", \"docx\"]").unwrap())
+		);
+		assert_eq!(
+			Filter::from_str("size>10MB").unwrap(),
+			Filter::Size(Size {
+				larger_than: Some(10_000_000),
+				smaller_than: None
+			})
+		);
+		assert_eq!(
+			Filter::from_str("modified<1h").unwrap(),
+			Filter::Modified(Age {
+				newer_than: Some(Moment::Ago(std::time::Duration::from_secs(3600))),
+				older_than: None
+			})
+		);
+		assert!(matches!(
+			Filter::from_str("{ type = \"filename\", startswith = \"IMG\" }").unwrap(),
+			Filter::Filename(_)
+		));
+		assert!(matches!(
+			Filter::from_str("created>2024-01-31").unwrap(),
+			Filter::Created(Age {
+				older_than: Some(Moment::At(_)),
+				..
+			})
+		));
+		assert!(matches!(
+			Filter::from_str("{ type = \"last_accessed\", when = \"older than 90d\" }").unwrap(),
+			Filter::Accessed(_)
+		));
+		assert_eq!(Filter::from_str("{ type = \"closed\" }").unwrap(), Filter::Closed);
+		assert_eq!(Filter::from_str("user=0").unwrap(), Filter::Owner(Owner { uid: Some(0), gid: None }));
+		assert!(matches!(Filter::from_str("permissions=o+w").unwrap(), Filter::Permissions(_)));
+		assert!(matches!(
+			Filter::from_str("orientation=landscape").unwrap(),
+			Filter::Image(Image { orientation: Some(_), .. })
+		));
+		assert!(Filter::from_str("orientation=sideways").is_err());
+		assert!(Filter::from_str("size=10MB").is_err());
+		assert!(Filter::from_str("pdf").is_err());
+	}
+
+	#[test]
+	fn cutoff() {
+		let created = |secs| {
+			Filter::Created(Age {
+				newer_than: Some(Moment::Ago(std::time::Duration::from_secs(secs))),
+				..Default::default()
+			})
+		};
+		let filters = Filters(vec![created(60), Filter::Regex(Regex::from_str(".*").unwrap()), created(3600)]);
+		let (all, any) = (filters.cutoff(&Apply::All).unwrap(), filters.cutoff(&Apply::AnyOf(vec![0, 2])).unwrap());
+		assert!(all > any);
+		assert!(filters.cutoff(&Apply::Any).is_none());
+		// editing a file doesn't modify its directory
+		let modified = Filters(vec![Filter::Modified(Age {
+			newer_than: Some(Moment::Ago(std::time::Duration::from_secs(60))),
+			..Default::default()
+		})]);
+		assert!(modified.cutoff(&Apply::All).is_none());
 	}
 }
@@ -0,0 +1,148 @@
+use std::{convert::TryFrom, path::Path};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+
+use crate::config::filters::AsFilter;
+
+/// Matches files owned by a user and/or a group, given by name or by id, e.g. `{ type = "owner", user = "alice", group = 100 }`.
+/// Names are looked up when the config is read. Only supported on unix, where nothing else matches.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "RawOwner")]
+pub struct Owner {
+	pub uid: Option<u32>,
+	pub gid: Option<u32>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawOwner {
+	#[serde(default)]
+	user: Option<Id>,
+	#[serde(default)]
+	group: Option<Id>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Id {
+	Number(u32),
+	Name(String),
+}
+
+impl TryFrom<RawOwner> for Owner {
+	type Error = anyhow::Error;
+
+	fn try_from(raw: RawOwner) -> Result<Self> {
+		if raw.user.is_none() && raw.group.is_none() {
+			bail!("expected a user or a group");
+		}
+		let uid = match raw.user {
+			Some(Id::Number(uid)) => Some(uid),
+			Some(Id::Name(name)) => Some(lookup::user(&name).ok_or_else(|| anyhow!("unknown user `{}`", name))?),
+			None => None,
+		};
+		let gid = match raw.group {
+			Some(Id::Number(gid)) => Some(gid),
+			Some(Id::Name(name)) => Some(lookup::group(&name).ok_or_else(|| anyhow!("unknown group `{}`", name))?),
+			None => None,
+		};
+		Ok(Self { uid, gid })
+	}
+}
+
+#[cfg(unix)]
+impl AsFilter for Owner {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		use std::os::unix::fs::MetadataExt;
+
+		match path.as_ref().metadata() {
+			Ok(metadata) => self.uid.is_none_or(|uid| metadata.uid() == uid) && self.gid.is_none_or(|gid| metadata.gid() == gid),
+			Err(_) => false,
+		}
+	}
+}
+
+#[cfg(not(unix))]
+impl AsFilter for Owner {
+	fn matches<T: AsRef<Path>>(&self, _path: T) -> bool {
+		false
+	}
+}
+
+#[cfg(unix)]
+mod lookup {
+	use std::{ffi::CString, mem::MaybeUninit};
+
+	/// Calls one of the reentrant `get*nam_r` functions with a buffer large enough for the entry, and reads the id out of it
+	fn lookup<E, F, G>(name: &str, call: F, id: G) -> Option<u32>
+	where
+		F: Fn(*const libc::c_char, *mut E, *mut libc::c_char, usize, *mut *mut E) -> libc::c_int,
+		G: Fn(&E) -> u32,
+	{
+		let name = CString::new(name).ok()?;
+		let mut size = 4096;
+		loop {
+			let mut entry = MaybeUninit::<E>::uninit();
+			let mut buf = vec![0 as libc::c_char; size];
+			let mut result = std::ptr::null_mut();
+			match call(name.as_ptr(), entry.as_mut_ptr(), buf.as_mut_ptr(), buf.len(), &mut result) {
+				libc::ERANGE if size < 1 << 20 => size *= 2,
+				// SAFETY: `result` points to `entry` once it was filled, and `buf`, which its strings point to, is still alive
+				0 if !result.is_null() => return Some(id(unsafe { &*result })),
+				_ => return None,
+			}
+		}
+	}
+
+	pub fn user(name: &str) -> Option<u32> {
+		// SAFETY: the pointers are valid for the call, and `len` is the length of the buffer
+		lookup(
+			name,
+			|name, entry, buf, len, result| unsafe { libc::getpwnam_r(name, entry, buf, len, result) },
+			|entry: &libc::passwd| entry.pw_uid,
+		)
+	}
+
+	pub fn group(name: &str) -> Option<u32> {
+		// SAFETY: the pointers are valid for the call, and `len` is the length of the buffer
+		lookup(
+			name,
+			|name, entry, buf, len, result| unsafe { libc::getgrnam_r(name, entry, buf, len, result) },
+			|entry: &libc::group| entry.gr_gid,
+		)
+	}
+}
+
+#[cfg(not(unix))]
+mod lookup {
+	pub fn user(_name: &str) -> Option<u32> {
+		None
+	}
+
+	pub fn group(_name: &str) -> Option<u32> {
+		None
+	}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	use std::{fs, os::unix::fs::MetadataExt};
+
+	use super::*;
+
+	#[test]
+	fn match_owners() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("a");
+		fs::write(&path, "").unwrap();
+		let metadata = path.metadata().unwrap();
+		let owner = |config: &str| toml::from_str::<Owner>(config).unwrap();
+		assert!(owner(&format!("user = {}", metadata.uid())).matches(&path));
+		assert!(owner(&format!("user = {}\ngroup = {}", metadata.uid(), metadata.gid())).matches(&path));
+		assert!(!owner(&format!("group = {}", metadata.gid() + 1)).matches(&path));
+		assert_eq!(owner("user = \"root\""), Owner { uid: Some(0), gid: None });
+		assert!(toml::from_str::<Owner>("user = \"no-such-user-here\"").is_err());
+		assert!(toml::from_str::<Owner>("").is_err());
+	}
+}
@@ -0,0 +1,153 @@
+use std::{convert::TryFrom, path::Path, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+
+use crate::config::filters::AsFilter;
+
+/// Matches files by their mode bits, e.g. world-writable ones with `{ type = "permissions", mode = "o+w" }`.
+/// The mode is either octal (`644`), which has to match exactly, or comma-separated clauses like `chmod`'s:
+/// `u+x` (the owner can run it), `o-w` (others can't write to it), `g=r` (the group can read it and nothing else).
+/// Only supported on unix, where nothing else matches.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Permissions {
+	#[serde(alias = "permissions")]
+	pub mode: Mode,
+}
+
+/// Mode bits that have to be set (`expected`) or unset among the ones in `mask`, for each clause
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "String")]
+pub struct Mode(Vec<(u32, u32)>);
+
+impl TryFrom<String> for Mode {
+	type Error = anyhow::Error;
+
+	fn try_from(s: String) -> Result<Self> {
+		s.parse()
+	}
+}
+
+impl FromStr for Mode {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self> {
+		let s = s.trim();
+		if !s.is_empty() && s.chars().all(|c| c.is_digit(8)) {
+			let mode = u32::from_str_radix(s, 8).map_err(|_| anyhow!("invalid mode `{}`", s))?;
+			if mode > 0o7777 {
+				bail!("invalid mode `{}`", s);
+			}
+			return Ok(Self(vec![(0o7777, mode)]));
+		}
+		s.split(',').map(clause).collect::<Result<_>>().map(Self)
+	}
+}
+
+/// Parses a clause such as `go-w` into its mask and the bits expected in it
+fn clause(clause: &str) -> Result<(u32, u32)> {
+	let clause = clause.trim();
+	let split = clause
+		.find(['+', '-', '='])
+		.ok_or_else(|| anyhow!("expected a mode such as `o+w` or `644`, found `{}`", clause))?;
+	let (who, op, what) = (&clause[..split], &clause[split..=split], &clause[split + 1..]);
+	// read, write and execute, shifted to each class
+	let mut classes = 0;
+	for c in who.chars() {
+		classes |= match c {
+			'u' => 0o100,
+			'g' => 0o010,
+			'o' => 0o001,
+			'a' => 0o111,
+			c => bail!("unknown class `{}` in `{}` (expected u, g, o or a)", c, clause),
+		};
+	}
+	if classes == 0 {
+		classes = 0o111;
+	}
+	let mut bits = 0;
+	for c in what.chars() {
+		bits |= match c {
+			'r' => classes * 4,
+			'w' => classes * 2,
+			'x' => classes,
+			// setuid for the owner, setgid for the group
+			's' => ((classes & 0o100) * 0o40) | ((classes & 0o010) * 0o200),
+			't' => 0o1000,
+			c => bail!("unknown permission `{}` in `{}` (expected r, w, x, s or t)", c, clause),
+		};
+	}
+	Ok(match op {
+		"+" => (bits, bits),
+		"-" => (bits, 0),
+		_ => (classes * 7, bits),
+	})
+}
+
+impl Mode {
+	pub fn matches(&self, mode: u32) -> bool {
+		self.0.iter().all(|(mask, expected)| mode & mask == *expected)
+	}
+}
+
+#[cfg(unix)]
+impl AsFilter for Permissions {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		use std::os::unix::fs::PermissionsExt;
+
+		path.as_ref()
+			.metadata()
+			.is_ok_and(|metadata| self.mode.matches(metadata.permissions().mode()))
+	}
+}
+
+#[cfg(not(unix))]
+impl AsFilter for Permissions {
+	fn matches<T: AsRef<Path>>(&self, _path: T) -> bool {
+		false
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_modes() {
+		let mode = |s: &str| s.parse::<Mode>().unwrap();
+		assert!(mode("o+w").matches(0o666));
+		assert!(!mode("o+w").matches(0o644));
+		assert!(mode("go-w").matches(0o644));
+		assert!(!mode("go-w").matches(0o664));
+		assert!(mode("u=rw").matches(0o644));
+		assert!(!mode("u=rw").matches(0o744));
+		assert!(mode("+x").matches(0o755));
+		assert!(!mode("a+x").matches(0o744));
+		assert!(mode("u+x,o-rwx").matches(0o750));
+		assert!(mode("644").matches(0o644));
+		assert!(!mode("644").matches(0o4644));
+		assert!(mode("u+s").matches(0o4755));
+		assert!(mode("+t").matches(0o1777));
+		for invalid in ["", "o*w", "z+w", "o+q", "99999"] {
+			assert!(invalid.parse::<Mode>().is_err(), "{}", invalid);
+		}
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn match_world_writable_files() {
+		use std::{fs, os::unix::fs::PermissionsExt};
+
+		let dir = tempfile::tempdir().unwrap();
+		let (open, closed) = (dir.path().join("open"), dir.path().join("closed"));
+		fs::write(&open, "").unwrap();
+		fs::write(&closed, "").unwrap();
+		fs::set_permissions(&open, fs::Permissions::from_mode(0o666)).unwrap();
+		fs::set_permissions(&closed, fs::Permissions::from_mode(0o640)).unwrap();
+		let filter: Permissions = toml::from_str("permissions = \"o+w\"").unwrap();
+		assert!(filter.matches(&open));
+		assert!(!filter.matches(&closed));
+		assert!(toml::from_str::<Permissions>("mode = \"o+q\"").is_err());
+	}
+}
@@ -0,0 +1,117 @@
+use std::{fmt, path::Path, sync::Arc};
+
+use crate::config::{filters::Filters, options::apply::Apply};
+
+/// A filter turned into the function that tests a file against it
+pub type Check = Arc<dyn Fn(&Path) -> bool + Send + Sync>;
+
+/// The filters of a rule compiled into a single test, once when the config is built, instead of going through [`Filter`](super::Filter) for each file.
+/// Only the filters selected by `apply` are kept, and the cheapest ones run first so that the others are only reached by the files that passed them:
+/// the ones that only look at the path, then those that read the metadata, then those that read the contents and last those that start processes.
+#[derive(Clone)]
+pub struct Predicate {
+	/// the checks in the order they run, with the index and the name of the filter they come from
+	checks: Vec<(usize, &'static str, Check)>,
+	any: bool,
+}
+
+impl Predicate {
+	pub fn new(filters: &Filters, apply: &Apply) -> Self {
+		let mut selected = filters
+			.iter()
+			.enumerate()
+			.filter(|(i, _)| match apply {
+				Apply::All | Apply::Any => true,
+				Apply::AllOf(filters) | Apply::AnyOf(filters) => filters.contains(i),
+			})
+			.collect::<Vec<_>>();
+		selected.sort_by_key(|(_, filter)| filter.cost()); // stable, so that filters that cost the same keep the order of the config
+		Self {
+			checks: selected
+				.into_iter()
+				.map(|(i, filter)| (i, filter.into(), filter.compile()))
+				.collect(),
+			any: matches!(apply, Apply::Any | Apply::AnyOf(_)),
+		}
+	}
+
+	pub fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		self.matches_by(|_, _, check| check(path.as_ref()))
+	}
+
+	/// Combines the checks, with `run` running each of them along with the index and the name of its filter
+	pub fn matches_by<F: FnMut(usize, &'static str, &Check) -> bool>(&self, mut run: F) -> bool {
+		let mut checks = self.checks.iter();
+		match self.any {
+			true => checks.any(|(i, name, check)| run(*i, name, check)),
+			false => checks.all(|(i, name, check)| run(*i, name, check)),
+		}
+	}
+}
+
+impl fmt::Debug for Predicate {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let checks = self
+			.checks
+			.iter()
+			.map(|(i, name, _)| format!("#{} ({})", i, name))
+			.collect::<Vec<_>>();
+		write!(f, "{}({})", if self.any { "any" } else { "all" }, checks.join(", "))
+	}
+}
+
+// predicates compiled from the same rule are the same
+impl PartialEq for Predicate {
+	fn eq(&self, other: &Self) -> bool {
+		self.any == other.any
+			&& self.checks.len() == other.checks.len()
+			&& self.checks.iter().zip(other.checks.iter()).all(|(lhs, rhs)| lhs.0 == rhs.0)
+	}
+}
+impl Eq for Predicate {}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+
+	use super::*;
+	use crate::config::filters::{regex::Regex, Filter};
+
+	#[test]
+	fn cheapest_filters_first() {
+		let filters = Filters(vec![
+			Filter::from_str("size>1MB").unwrap(),
+			Filter::Closed,
+			Filter::Regex(Regex::from_str(".*\\.pdf").unwrap()),
+			Filter::from_str("extension=pdf").unwrap(),
+		]);
+		let order = |apply: &Apply| {
+			let mut ran = vec![];
+			Predicate::new(&filters, apply).matches_by(|i, _, _| {
+				ran.push(i);
+				true
+			});
+			ran
+		};
+		assert_eq!(order(&Apply::All), vec![2, 3, 0, 1]);
+		assert_eq!(order(&Apply::AllOf(vec![0, 1, 3])), vec![3, 0, 1]);
+		// the first match is enough
+		assert_eq!(order(&Apply::Any), vec![2]);
+	}
+
+	#[test]
+	fn skip_expensive_filters() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("notes.txt");
+		std::fs::write(&path, "").unwrap();
+		let filters = Filters(vec![Filter::Closed, Filter::from_str("extension=pdf").unwrap()]);
+		let mut ran = vec![];
+		let matched = Predicate::new(&filters, &Apply::All).matches_by(|i, _, check| {
+			ran.push(i);
+			check(&path)
+		});
+		assert!(!matched);
+		assert_eq!(ran, vec![1]);
+		assert!(Predicate::new(&filters, &Apply::Any).matches(&path));
+	}
+}
@@ -0,0 +1,62 @@
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::{config::filters::AsFilter, journal};
+
+/// Matches files that organize already acted on according to the journal, i.e. that an action put where they are or left there,
+/// e.g. `{ type = "processed", processed = false }` to only import files that were never imported before.
+/// With `rule`, only what the rule with that `id` did counts.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Processed {
+	/// whether to match the files that were processed, or those that weren't
+	#[serde(default = "Processed::default_processed")]
+	pub processed: bool,
+	#[serde(default)]
+	pub rule: Option<String>,
+}
+
+impl Processed {
+	fn default_processed() -> bool {
+		true
+	}
+}
+
+impl AsFilter for Processed {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		let path = path.as_ref();
+		match journal::processed(path, self.rule.as_deref()) {
+			Ok(processed) => processed == self.processed,
+			// a file that can't be told apart is left alone either way
+			Err(e) => {
+				log::warn!("could not tell whether {} was processed: {:?}", path.display(), e);
+				false
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use crate::testing::Tree;
+
+	#[test]
+	fn match_unprocessed_files() {
+		let tree = Tree::new().file("inbox/a.txt", "").file("inbox/b.txt", "");
+		let outcome = tree
+			.run(
+				r#"
+				version = 2
+
+				[[rules]]
+				folders = ["{tree}/inbox"]
+				filters = [{ type = "processed", processed = false }]
+				actions = [{ type = "move", to = "{tree}/imported/" }]
+				"#,
+			)
+			.unwrap();
+		assert!(outcome.errors().is_empty());
+		tree.assert_layout(&["imported/a.txt", "imported/b.txt"]);
+	}
+}
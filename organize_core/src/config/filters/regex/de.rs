@@ -1,11 +1,10 @@
-use crate::config::filters::regex::Regex;
+use crate::config::filters::{regex::Regex, Target};
 use itertools::Itertools;
 use serde::{
-	de::MapAccess,
-	de::{Error, Visitor},
+	de::{Error, MapAccess, SeqAccess, Visitor},
 	Deserialize, Deserializer,
 };
-use std::{fmt, fmt::Formatter};
+use std::{fmt, fmt::Formatter, str::FromStr};
 
 impl<'de> Deserialize<'de> for Regex {
 	fn deserialize<D>(deserializer: D) -> Result<Self, <D as Deserializer<'de>>::Error>
@@ -18,7 +17,25 @@ impl<'de> Deserialize<'de> for Regex {
 			type Value = Regex;
 
 			fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
-				formatter.write_str("map")
+				formatter.write_str("string, seq or map")
+			}
+
+			fn visit_str<E>(self, v: &str) -> Result<Regex, E>
+			where
+				E: Error,
+			{
+				Regex::from_str(v).map_err(E::custom)
+			}
+
+			fn visit_seq<A>(self, mut seq: A) -> Result<Regex, A::Error>
+			where
+				A: SeqAccess<'de>,
+			{
+				let mut patterns = Vec::new();
+				while let Some(val) = seq.next_element::<String>()? {
+					patterns.push(regex::Regex::new(&val).map_err(A::Error::custom)?)
+				}
+				Regex::new(patterns).map_err(A::Error::custom)
 			}
 
 			fn visit_map<M>(self, mut map: M) -> Result<Regex, M::Error>
@@ -26,8 +43,10 @@ impl<'de> Deserialize<'de> for Regex {
 				M: MapAccess<'de>,
 			{
 				let mut patterns = Vec::new();
+				let mut target = Target::default();
 				while let Some(key) = map.next_key::<String>()? {
 					match key.as_str() {
+						"target" => target = map.next_value()?,
 						"patterns" => {
 							let value = map.next_value::<Vec<String>>()?;
 							patterns = value
@@ -35,10 +54,10 @@ impl<'de> Deserialize<'de> for Regex {
 								.map(|s| regex::Regex::new(&s).map_err(M::Error::custom))
 								.try_collect()?;
 						}
-						key => return Err(M::Error::unknown_field(key, &["patterns"])),
+						key => return Err(M::Error::unknown_field(key, &["patterns", "target"])),
 					}
 				}
-				Ok(Regex { patterns })
+				Regex::with_target(patterns, target).map_err(M::Error::custom)
 			}
 		}
 
@@ -48,13 +67,13 @@ impl<'de> Deserialize<'de> for Regex {
 
 #[cfg(test)]
 mod tests {
-	use serde_test::{assert_de_tokens, Token};
+	use serde_test::{assert_de_tokens, assert_de_tokens_error, Token};
 
 	use super::*;
 	#[test]
 	fn deserialize_single() {
 		let re = regex::Regex::new(".*").unwrap();
-		let value = Regex { patterns: vec![re] };
+		let value = Regex::new(vec![re]).unwrap();
 		assert_de_tokens(&value, &[Token::Str(".*")])
 	}
 
@@ -62,7 +81,35 @@ mod tests {
 	fn deserialize_mult() {
 		let first = regex::Regex::new(".*").unwrap();
 		let sec = regex::Regex::new(".+").unwrap();
-		let value = Regex { patterns: vec![first, sec] };
+		let value = Regex::new(vec![first, sec]).unwrap();
 		assert_de_tokens(&value, &[Token::Seq { len: Some(2) }, Token::Str(".*"), Token::Str(".+"), Token::SeqEnd])
 	}
+
+	#[test]
+	fn deserialize_invalid() {
+		let error = Regex::from_str("(").unwrap_err().to_string();
+		assert_de_tokens_error::<Regex>(&[Token::Str("(")], &error);
+		assert_de_tokens_error::<Regex>(&[Token::Seq { len: Some(2) }, Token::Str(".*"), Token::Str("(")], &error);
+	}
+
+	#[test]
+	fn deserialize_target() {
+		let value = Regex::with_target(vec![regex::Regex::new(".*").unwrap()], Target::Path).unwrap();
+		assert_de_tokens(
+			&value,
+			&[
+				Token::Map { len: Some(2) },
+				Token::Str("patterns"),
+				Token::Seq { len: Some(1) },
+				Token::Str(".*"),
+				Token::SeqEnd,
+				Token::Str("target"),
+				Token::UnitVariant {
+					name: "Target",
+					variant: "path",
+				},
+				Token::MapEnd,
+			],
+		)
+	}
 }
@@ -2,29 +2,75 @@ mod de;
 
 use std::{path::Path, str::FromStr};
 
-use crate::config::filters::AsFilter;
+use crate::config::filters::{AsFilter, Target};
 use derive_more::Deref;
 use std::convert::TryFrom;
 
 #[derive(Debug, Deref, Clone)]
 pub struct Regex {
+	#[deref]
 	patterns: Vec<regex::Regex>,
+	// all patterns compiled into a single automaton, so that a filename is scanned only once
+	set: regex::RegexSet,
+	target: Target,
+}
+
+impl Regex {
+	pub fn new(patterns: Vec<regex::Regex>) -> Result<Self, regex::Error> {
+		Self::with_target(patterns, Target::default())
+	}
+
+	/// Fails if the patterns, which compiled on their own, are too large to be compiled together
+	pub fn with_target(patterns: Vec<regex::Regex>, target: Target) -> Result<Self, regex::Error> {
+		let set = regex::RegexSet::new(patterns.iter().map(|re| re.as_str()))?;
+		Ok(Self { patterns, set, target })
+	}
+
+	/// Logs what each pattern was tested against and what it captured,
+	/// since a pattern written for the full path silently fails against the filename and vice versa
+	fn trace(&self, path: &Path, tested: &str) {
+		log::trace!("(regex) testing the {} of {}: {}", self.target, path.display(), tested);
+		for pattern in self.patterns.iter() {
+			match pattern.captures(tested) {
+				Some(captures) => {
+					let groups = captures
+						.iter()
+						.enumerate()
+						.skip(1)
+						.map(|(i, group)| {
+							let name = pattern
+								.capture_names()
+								.nth(i)
+								.flatten()
+								.map_or_else(|| i.to_string(), String::from);
+							format!("{} = {:?}", name, group.map(|group| group.as_str()))
+						})
+						.collect::<Vec<_>>();
+					log::trace!("(regex) `{}` matched {:?}, captures: [{}]", pattern, &captures[0], groups.join(", "));
+				}
+				None => log::trace!("(regex) `{}` did not match", pattern),
+			}
+		}
+	}
 }
 
 impl PartialEq for Regex {
 	fn eq(&self, other: &Self) -> bool {
-		self.iter().zip(other.iter()).all(|(lhs, rhs)| lhs.as_str() == rhs.as_str())
+		self.target == other.target && self.len() == other.len() && self.iter().zip(other.iter()).all(|(lhs, rhs)| lhs.as_str() == rhs.as_str())
 	}
 }
 impl Eq for Regex {}
 
 impl AsFilter for Regex {
 	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
-		match path.as_ref().file_name() {
+		let path = path.as_ref();
+		match self.target.of(path) {
 			None => false,
-			Some(filename) => {
-				let filename = filename.to_string_lossy();
-				self.iter().any(|re| re.is_match(&filename))
+			Some(tested) => {
+				if log::log_enabled!(log::Level::Trace) {
+					self.trace(path, &tested);
+				}
+				self.set.is_match(&tested)
 			}
 		}
 	}
@@ -39,7 +85,7 @@ impl TryFrom<Vec<&str>> for Regex {
 			let re = regex::Regex::new(str)?;
 			vec.push(re)
 		}
-		Ok(Self { patterns: vec })
+		Self::new(vec)
 	}
 }
 
@@ -48,7 +94,7 @@ impl FromStr for Regex {
 
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
 		match regex::Regex::new(s) {
-			Ok(regex) => Ok(Regex { patterns: vec![regex] }),
+			Ok(regex) => Regex::new(vec![regex]),
 			Err(e) => Err(e),
 		}
 	}
@@ -63,20 +109,50 @@ mod tests {
 	fn match_single() {
 		let regex = Regex::from_str(r".*unsplash.*").unwrap();
 		let path = "$HOME/Pictures/test_unsplash_img.jpg";
-		assert!(regex.matches(&path))
+		assert!(regex.matches(path))
 	}
 
 	#[test]
 	fn match_multiple() {
 		let regex = Regex::try_from(vec![r".*unsplash.*", r"\w"]).unwrap();
 		let path = "$HOME/Pictures/test_unsplash_img.jpg";
-		assert!(regex.matches(&path))
+		assert!(regex.matches(path))
+	}
+
+	#[test]
+	fn match_full_path() {
+		let patterns = vec![regex::Regex::new(r"^\$HOME/Pictures/").unwrap()];
+		let path = "$HOME/Pictures/test_unsplash_img.jpg";
+		assert!(!Regex::new(patterns.clone()).unwrap().matches(path));
+		assert!(Regex::with_target(patterns, Target::Path).unwrap().matches(path));
+	}
+
+	#[test]
+	fn too_large_together() {
+		// each pattern compiles on its own, but not in a single set
+		let pattern = r"\w{50}";
+		assert!(Regex::from_str(pattern).is_ok());
+		assert!(Regex::try_from(vec![pattern, pattern]).is_err());
+		let filter = |patterns: &str| toml::from_str::<crate::config::filters::Filter>(&format!("type = \"regex\"\npatterns = {}", patterns));
+		assert!(filter(r"['\w{50}']").is_ok());
+		assert!(filter(r"['\w{50}', '\w{50}']").is_err());
 	}
 
 	#[test]
 	fn no_match_multiple() {
 		let regex = Regex::try_from(vec![r".*unsplash.*", r"\d"]).unwrap();
 		let path = "$HOME/Documents/deep_learning.pdf";
-		assert!(!regex.matches(&path))
+		assert!(!regex.matches(path))
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn match_invalid_utf8() {
+		use std::os::unix::ffi::OsStrExt;
+
+		// the bytes that aren't valid UTF-8 are replaced, and the rest of the name is matched as usual
+		let path = Path::new(std::ffi::OsStr::from_bytes(b"/photos/IMG_\xff\xfe_2021.jpg"));
+		assert!(Regex::from_str(r"^IMG_.*_2021\.jpg$").unwrap().matches(path));
+		assert!(!Regex::from_str(r"^IMG_\d+\.jpg$").unwrap().matches(path));
 	}
 }
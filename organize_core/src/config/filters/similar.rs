@@ -0,0 +1,271 @@
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	sync::Mutex,
+	time::SystemTime,
+};
+
+use image::{imageops::FilterType, GenericImageView};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use strum_macros::Display;
+
+use crate::{config::filters::AsFilter, context::Context, path::Expand, variables::Variable};
+
+/// values along with when the file they were computed from was last modified
+type Cache<K, V> = Mutex<HashMap<K, (Option<SystemTime>, V)>>;
+
+lazy_static! {
+	/// the hash of every image seen so far
+	static ref HASHES: Cache<(PathBuf, Algorithm), Option<Image>> = Mutex::new(HashMap::new());
+	/// the images in the directories compared against so far
+	static ref DIRS: Cache<(PathBuf, bool), Vec<PathBuf>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, Hash, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Algorithm {
+	/// compares the brightness of neighboring pixels, fast and good at resized or recompressed copies
+	#[default]
+	DHash,
+	/// compares the low frequencies of the image, slower but more robust to small edits
+	PHash,
+}
+
+/// Matches images that look like another one: resized, recompressed or slightly edited copies of the same shot.
+/// With `in`, the file is compared with the images in that directory and its subdirectories, e.g. a photo library.
+/// Otherwise it's compared with the images next to it, and every image of a group matches except the best one
+/// (the largest, then the heaviest), so that a rule can set the copies aside and keep the original.
+/// The most similar image is available to templates as `{similar}`.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Similar {
+	#[serde(default)]
+	pub algorithm: Algorithm,
+	/// how many of the 64 bits of the hashes may differ, lower is stricter
+	#[serde(default = "Similar::default_distance")]
+	pub distance: u32,
+	#[serde(default, rename = "in")]
+	pub within: Option<PathBuf>,
+}
+
+/// What's compared of an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Image {
+	hash: u64,
+	pixels: u64,
+	bytes: u64,
+}
+
+impl Image {
+	/// Whether this image is a better copy than `other`, so that exactly one image of a group is kept
+	fn is_better(&self, path: &Path, other: &Self, other_path: &Path) -> bool {
+		(self.pixels, self.bytes)
+			.cmp(&(other.pixels, other.bytes))
+			.then_with(|| other_path.cmp(path))
+			.is_gt()
+	}
+}
+
+impl Similar {
+	fn default_distance() -> u32 {
+		10
+	}
+
+	/// The image closest to the one at `path` within the allowed distance, if any
+	fn closest(&self, path: &Path, image: &Image) -> Option<PathBuf> {
+		let (dir, recursive) = match &self.within {
+			Some(dir) => (dir.clone().expand_user().ok()?.expand_vars().ok()?, true),
+			None => (path.parent()?.to_path_buf(), false),
+		};
+		images(&dir, recursive)
+			.into_iter()
+			.filter(|other| other != path)
+			.filter_map(|other| {
+				let other_image = hash(&other, self.algorithm)?;
+				let distance = (image.hash ^ other_image.hash).count_ones();
+				let candidate = distance <= self.distance && (self.within.is_some() || other_image.is_better(&other, image, path));
+				candidate.then_some((distance, other))
+			})
+			// the listing may be outdated
+			.filter(|(_, other)| other.exists())
+			.min()
+			.map(|(_, other)| other)
+	}
+}
+
+impl AsFilter for Similar {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		let path = path.as_ref();
+		let closest = hash(path, self.algorithm).and_then(|image| self.closest(path, &image));
+		match closest {
+			Some(closest) => {
+				Context::set_variable(Variable::Similar, closest.to_string_lossy());
+				true
+			}
+			None => false,
+		}
+	}
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+	path.metadata().and_then(|metadata| metadata.modified()).ok()
+}
+
+/// The images in `dir`, reusing the last listing while the directory is unchanged
+fn images(dir: &Path, recursive: bool) -> Vec<PathBuf> {
+	let key = (dir.to_path_buf(), recursive);
+	let modified = modified(dir);
+	if let Some((when, images)) = DIRS.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+		if *when == modified {
+			return images.clone();
+		}
+	}
+	let depth = if recursive { usize::MAX } else { 1 };
+	let images = walkdir::WalkDir::new(dir)
+		.max_depth(depth)
+		.into_iter()
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.file_type().is_file() && image::ImageFormat::from_path(entry.path()).is_ok())
+		.map(|entry| entry.into_path())
+		.collect::<Vec<_>>();
+	DIRS.lock()
+		.unwrap_or_else(|e| e.into_inner())
+		.insert(key, (modified, images.clone()));
+	images
+}
+
+/// The hash of the image at `path`, or `None` if it isn't an image that can be read.
+/// It's computed once for as long as the file isn't modified.
+fn hash(path: &Path, algorithm: Algorithm) -> Option<Image> {
+	let key = (path.to_path_buf(), algorithm);
+	let modified = modified(path);
+	if let Some((when, image)) = HASHES.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+		if *when == modified {
+			return *image;
+		}
+	}
+	let image = image::open(path)
+		.map_err(|e| log::debug!("could not read {} as an image: {}", path.display(), e))
+		.ok()
+		.map(|decoded| {
+			let (width, height) = decoded.dimensions();
+			Image {
+				hash: match algorithm {
+					Algorithm::DHash => dhash(&decoded),
+					Algorithm::PHash => phash(&decoded),
+				},
+				pixels: width as u64 * height as u64,
+				bytes: path.metadata().map(|metadata| metadata.len()).unwrap_or_default(),
+			}
+		});
+	HASHES.lock().unwrap_or_else(|e| e.into_inner()).insert(key, (modified, image));
+	image
+}
+
+/// Whether each pixel of a 9x8 thumbnail is brighter than its right neighbor
+fn dhash(image: &image::DynamicImage) -> u64 {
+	let thumbnail = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+	let mut hash = 0;
+	for y in 0..8 {
+		for x in 0..8 {
+			hash <<= 1;
+			hash |= (thumbnail.get_pixel(x, y)[0] > thumbnail.get_pixel(x + 1, y)[0]) as u64;
+		}
+	}
+	hash
+}
+
+/// Whether each of the 8x8 lowest frequencies of the DCT of a 32x32 thumbnail is above their median
+fn phash(image: &image::DynamicImage) -> u64 {
+	const SIZE: usize = 32;
+	let thumbnail = image.resize_exact(SIZE as u32, SIZE as u32, FilterType::Triangle).to_luma8();
+	let pixels = (0..SIZE)
+		.map(|y| {
+			(0..SIZE)
+				.map(|x| thumbnail.get_pixel(x as u32, y as u32)[0] as f64)
+				.collect::<Vec<_>>()
+		})
+		.collect::<Vec<_>>();
+	let cosines = (0..8)
+		.map(|u| {
+			(0..SIZE)
+				.map(|x| ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2 * SIZE) as f64).cos())
+				.collect::<Vec<_>>()
+		})
+		.collect::<Vec<_>>();
+	let mut coefficients = Vec::with_capacity(64);
+	for v in 0..8 {
+		for u in 0..8 {
+			let mut sum = 0.0;
+			for (y, row) in pixels.iter().enumerate() {
+				for (x, pixel) in row.iter().enumerate() {
+					sum += pixel * cosines[u][x] * cosines[v][y];
+				}
+			}
+			coefficients.push(sum);
+		}
+	}
+	// the first coefficient is the average brightness, which says nothing about the picture
+	let mut sorted = coefficients[1..].to_vec();
+	sorted.sort_by(|a, b| a.total_cmp(b));
+	let median = sorted[sorted.len() / 2];
+	coefficients
+		.iter()
+		.fold(0, |hash, coefficient| (hash << 1) | (*coefficient > median) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+	use image::{ImageBuffer, Rgb};
+
+	use super::*;
+
+	/// A gradient with a bright square, which looks the same at any size
+	fn picture(size: u32, square: (u32, u32)) -> image::DynamicImage {
+		image::DynamicImage::ImageRgb8(ImageBuffer::from_fn(size, size, |x, y| {
+			let (sx, sy) = (square.0 * size / 100, square.1 * size / 100);
+			match x >= sx && x < sx + size / 4 && y >= sy && y < sy + size / 4 {
+				true => Rgb([255, 255, 255]),
+				false => Rgb([(x * 200 / size) as u8, (y * 100 / size) as u8, 50]),
+			}
+		}))
+	}
+
+	#[test]
+	fn resized_copies_are_similar() {
+		for algorithm in [Algorithm::DHash, Algorithm::PHash] {
+			let hash = |image: &image::DynamicImage| match algorithm {
+				Algorithm::DHash => dhash(image),
+				Algorithm::PHash => phash(image),
+			};
+			let original = hash(&picture(400, (10, 10)));
+			let resized = hash(&picture(120, (10, 10)));
+			let other = hash(&picture(400, (60, 55)));
+			assert!((original ^ resized).count_ones() <= Similar::default_distance(), "{}", algorithm);
+			assert!((original ^ other).count_ones() > Similar::default_distance(), "{}", algorithm);
+		}
+	}
+
+	#[test]
+	fn keep_the_best_copy() {
+		let dir = tempfile::tempdir().unwrap();
+		let (large, small, different) = (dir.path().join("large.png"), dir.path().join("small.jpg"), dir.path().join("other.png"));
+		picture(400, (10, 10)).save(&large).unwrap();
+		picture(120, (10, 10)).save(&small).unwrap();
+		picture(400, (60, 55)).save(&different).unwrap();
+		let filter: Similar = toml::from_str("distance = 10").unwrap();
+		assert!(!filter.matches(&large));
+		assert!(!filter.matches(&different));
+		let _context = Context::enter(&small, None);
+		assert!(filter.matches(&small));
+		assert_eq!(Context::variable(Variable::Similar), Some(large.to_string_lossy().to_string()));
+
+		let library: Similar = toml::from_str(&format!("in = {:?}", dir.path())).unwrap();
+		let elsewhere = tempfile::tempdir().unwrap();
+		let copy = elsewhere.path().join("copy.png");
+		picture(200, (10, 10)).save(&copy).unwrap();
+		assert!(library.matches(&copy));
+	}
+}
@@ -0,0 +1,102 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{de::Error, Deserialize, Deserializer};
+
+use crate::{config::filters::AsFilter, context, variables::Variable};
+
+/// Matches files by their size in bytes
+#[derive(Debug, Deserialize, Clone, Eq, PartialEq, Default)]
+pub struct Size {
+	#[serde(default, deserialize_with = "deserialize_size")]
+	pub larger_than: Option<u64>,
+	#[serde(default, deserialize_with = "deserialize_size")]
+	pub smaller_than: Option<u64>,
+}
+
+impl AsFilter for Size {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		let size = match path.as_ref().metadata() {
+			Ok(metadata) => metadata.len(),
+			Err(_) => return false,
+		};
+		let matches =
+			self.larger_than.is_none_or(|larger_than| size > larger_than) && self.smaller_than.is_none_or(|smaller_than| size < smaller_than);
+		if matches {
+			context::Context::set_variable(Variable::SizeBytes, size.to_string());
+			context::Context::set_variable(Variable::SizeBucket, Variable::bucket(size));
+		}
+		matches
+	}
+}
+
+/// Parses sizes such as `512`, `100KB`, `10MB` or `2GiB`.
+/// Units without an `i` are powers of 1000, the others powers of 1024.
+pub fn parse_size(s: &str) -> Result<u64> {
+	let s = s.trim();
+	let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+	let (amount, unit) = s.split_at(split);
+	let amount: f64 = amount.parse().with_context(|| format!("invalid size {}", s))?;
+	let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+		"" | "b" => 1,
+		"kb" | "k" => 1000,
+		"mb" | "m" => 1000_u64.pow(2),
+		"gb" | "g" => 1000_u64.pow(3),
+		"tb" | "t" => 1000_u64.pow(4),
+		"kib" => 1 << 10,
+		"mib" => 1 << 20,
+		"gib" => 1 << 30,
+		"tib" => 1 << 40,
+		unit => {
+			return Err(anyhow!(
+				"unknown unit `{}` in size {} (expected B, KB, MB, GB, TB or KiB, MiB, GiB, TiB)",
+				unit,
+				s
+			))
+		}
+	};
+	Ok((amount * multiplier as f64) as u64)
+}
+
+pub(crate) fn deserialize_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+	D: Deserializer<'de>,
+{
+	#[derive(Deserialize)]
+	#[serde(untagged)]
+	enum Raw {
+		Bytes(u64),
+		Human(String),
+	}
+
+	match Raw::deserialize(deserializer)? {
+		Raw::Bytes(bytes) => Ok(Some(bytes)),
+		Raw::Human(str) => parse_size(&str).map(Some).map_err(D::Error::custom),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_sizes() {
+		assert_eq!(parse_size("512").unwrap(), 512);
+		assert_eq!(parse_size("10MB").unwrap(), 10_000_000);
+		assert_eq!(parse_size("1.5 KiB").unwrap(), 1536);
+		assert!(parse_size("10 parsecs").is_err());
+		assert!(parse_size("MB").is_err());
+	}
+
+	#[test]
+	fn match_size() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("file.txt");
+		std::fs::write(&path, [0; 100]).unwrap();
+		let size = |larger_than, smaller_than| Size { larger_than, smaller_than };
+		assert!(size(Some(50), Some(150)).matches(&path));
+		assert!(!size(Some(100), None).matches(&path));
+		assert!(!size(None, Some(100)).matches(&path));
+		assert!(!size(None, None).matches(dir.path().join("missing.txt")));
+	}
+}
@@ -0,0 +1,48 @@
+use std::{borrow::Cow, path::Path};
+
+use serde::Deserialize;
+use strum_macros::Display;
+
+/// The part of a path a filter looks at
+#[derive(Debug, Clone, Copy, Default, Deserialize, Eq, PartialEq, Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Target {
+	#[default]
+	#[serde(alias = "name")]
+	Filename,
+	/// the filename without its extension
+	Stem,
+	/// the full path
+	Path,
+	/// the full path of the directory the file is in
+	Parent,
+}
+
+impl Target {
+	/// The part of `path` this target refers to, if it has one
+	pub fn of<'a>(&self, path: &'a Path) -> Option<Cow<'a, str>> {
+		match self {
+			Self::Filename => path.file_name().map(|name| name.to_string_lossy()),
+			Self::Stem => path.file_stem().map(|stem| stem.to_string_lossy()),
+			Self::Path => Some(path.to_string_lossy()),
+			Self::Parent => path.parent().map(|parent| parent.to_string_lossy()),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parts_of_path() {
+		let path = Path::new("/home/user/Downloads/report.tar.gz");
+		let of = |target: Target| target.of(path).unwrap().to_string();
+		assert_eq!(of(Target::Filename), "report.tar.gz");
+		assert_eq!(of(Target::Stem), "report.tar");
+		assert_eq!(of(Target::Path), "/home/user/Downloads/report.tar.gz");
+		assert_eq!(of(Target::Parent), "/home/user/Downloads");
+		assert_eq!(Target::Filename.of(Path::new("/")), None);
+	}
+}
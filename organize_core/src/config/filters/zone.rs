@@ -0,0 +1,64 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{config::filters::AsFilter, path::ZoneIdentifier};
+
+/// The security zones Windows records in the `Zone.Identifier` stream of downloaded files
+#[derive(Eq, PartialEq, Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all(serialize = "snake_case", deserialize = "snake_case"))]
+pub enum Zone {
+	LocalMachine,
+	Intranet,
+	Trusted,
+	Internet,
+	Untrusted,
+}
+
+impl Zone {
+	/// Extracts the zone from the contents of a `Zone.Identifier` stream
+	pub fn parse(content: &str) -> Option<Self> {
+		let id = content
+			.lines()
+			.find_map(|line| line.trim().strip_prefix("ZoneId="))?
+			.trim()
+			.parse::<u8>()
+			.ok()?;
+		match id {
+			0 => Some(Self::LocalMachine),
+			1 => Some(Self::Intranet),
+			2 => Some(Self::Trusted),
+			3 => Some(Self::Internet),
+			4 => Some(Self::Untrusted),
+			_ => None,
+		}
+	}
+}
+
+#[derive(Debug, Deserialize, Clone, Eq, PartialEq)]
+pub struct Zones {
+	zones: Vec<Zone>,
+}
+
+impl AsFilter for Zones {
+	fn matches<T: AsRef<Path>>(&self, path: T) -> bool {
+		path.as_ref().zone().map(|zone| self.zones.contains(&zone)).unwrap_or_default()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_internet_zone() {
+		let content = "[ZoneTransfer]\r\nZoneId=3\r\nHostUrl=https://example.com/file.zip\r\n";
+		assert_eq!(Zone::parse(content), Some(Zone::Internet));
+	}
+
+	#[test]
+	fn parse_invalid_zone() {
+		assert_eq!(Zone::parse("[ZoneTransfer]\r\n"), None);
+		assert_eq!(Zone::parse("[ZoneTransfer]\r\nZoneId=9\r\n"), None);
+	}
+}
@@ -1,7 +1,7 @@
 use std::{fmt, result, str::FromStr};
 
 use crate::{
-	config::{folders::Folder, options::Options},
+	config::{condition::Condition, folders::Folder, options::Options},
 	utils::UnwrapOrDefaultOpt,
 };
 use serde::{
@@ -38,6 +38,7 @@ impl<'de> Deserialize<'de> for Folder {
 			{
 				let mut path: Option<PathBuf> = None;
 				let mut options: Option<Options> = None;
+				let mut only_if: Option<Condition> = None;
 				while let Some(key) = map.next_key::<String>()? {
 					match key.as_str() {
 						"path" => {
@@ -56,12 +57,19 @@ impl<'de> Deserialize<'de> for Folder {
 								false => Some(map.next_value()?),
 							};
 						}
-						_ => return Err(M::Error::unknown_field(key.as_str(), &["path", "options"])),
+						"only_if" => {
+							only_if = match only_if.is_some() {
+								true => return Err(M::Error::duplicate_field("only_if")),
+								false => Some(map.next_value()?),
+							};
+						}
+						_ => return Err(M::Error::unknown_field(key.as_str(), &["path", "options", "only_if"])),
 					}
 				}
 				let folder = Folder {
 					path: path.ok_or_else(|| M::Error::missing_field("path"))?,
 					options: options.unwrap_or_default_none(),
+					only_if,
 				};
 				Ok(folder)
 			}
@@ -180,7 +188,7 @@ mod tests {
 				Token::Str("unknown"),
 				Token::MapEnd,
 			],
-			&Error::unknown_field("unknown", &["path", "options"]).to_string(),
+			&Error::unknown_field("unknown", &["path", "options", "only_if"]).to_string(),
 		)
 	}
 	#[test]
@@ -189,10 +197,16 @@ mod tests {
 		value.options = Options {
 			recursive: Recursive { depth: None },
 			watch: Some(true),
+			watch_strategy: None,
+			poll_interval: None,
+			priority: None,
 			ignored_dirs: None,
 			hidden_files: None,
 			r#match: None,
 			partial_files: None,
+			prune_empty_dirs: None,
+			allow_deep_home: None,
+			targets: None,
 			apply: ApplyWrapper::from(Apply::All),
 		};
 		assert_de_tokens(
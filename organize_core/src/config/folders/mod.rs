@@ -2,13 +2,19 @@ mod de;
 
 use std::{path::PathBuf, str::FromStr};
 
-use crate::{config::options::Options, path::Expand, utils::DefaultOpt};
+use crate::{
+	config::{condition::Condition, options::Options},
+	path::Expand,
+	utils::DefaultOpt,
+};
 use std::convert::TryFrom;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Folder {
 	pub path: PathBuf,
 	pub options: Options,
+	/// the machines this location is watched on, if not all of them
+	pub only_if: Option<Condition>,
 }
 
 impl TryFrom<PathBuf> for Folder {
@@ -21,6 +27,7 @@ impl TryFrom<PathBuf> for Folder {
 			.map(|path| Self {
 				path,
 				options: DefaultOpt::default_none(),
+				only_if: None,
 			})
 			.map_err(anyhow::Error::new)
 	}
@@ -0,0 +1,93 @@
+use std::{cell::RefCell, collections::BTreeMap, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::path::Expand;
+
+thread_local! {
+	// the locations of the config this thread is reading or running, see [`Config::enter`](crate::config::Config::enter)
+	static LOCATIONS: RefCell<Locations> = const { RefCell::new(Locations::new()) };
+}
+
+/// A directory named under `[locations]`, e.g. `[locations.downloads] path = "~/Downloads"`.
+/// Paths in folders and destinations can then start with `@downloads` instead, so that rules don't depend on
+/// where a machine keeps its files, and only the `[locations]` table has to change when the config is shared.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Location {
+	pub path: PathBuf,
+}
+
+pub type Locations = BTreeMap<String, Location>;
+
+/// Makes `locations` the ones `@name` refers to on this thread, until the guard is dropped
+pub(crate) fn enter(locations: &Locations) -> Guard {
+	Guard(LOCATIONS.with(|current| current.replace(locations.clone())))
+}
+
+/// Puts back the locations that were in effect before [`enter`] when it's dropped
+pub(crate) struct Guard(Locations);
+
+impl Drop for Guard {
+	fn drop(&mut self) {
+		LOCATIONS.with(|current| current.replace(std::mem::take(&mut self.0)));
+	}
+}
+
+/// `path` with the location it starts with, if it starts with `@name`, replaced by that location's path
+pub(crate) fn expand(path: PathBuf) -> Result<PathBuf> {
+	if !path.as_os_str().to_str().is_some_and(|path| path.starts_with('@')) {
+		return Ok(path);
+	}
+	// expanding the location's own path borrows them again
+	let locations = LOCATIONS.with(|locations| locations.borrow().clone());
+	expand_with(path, &locations)
+}
+
+fn expand_with(path: PathBuf, locations: &Locations) -> Result<PathBuf> {
+	let mut components = path.components();
+	let name = match components
+		.next()
+		.and_then(|component| component.as_os_str().to_str()?.strip_prefix('@'))
+	{
+		Some(name) => name.to_string(),
+		None => return Ok(path),
+	};
+	let location = match locations.get(&name) {
+		Some(location) => location.path.clone(),
+		None => bail!("unknown location @{} (locations are defined under [locations])", name),
+	};
+	if location.to_string_lossy().starts_with('@') {
+		bail!("location @{} can't refer to another location", name);
+	}
+	let mut expanded = location
+		.expand_user()
+		.and_then(Expand::expand_vars)
+		.with_context(|| format!("could not expand the path of location @{}", name))?;
+	expanded.extend(components);
+	Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn resolve_locations() {
+		let mut locations = Locations::new();
+		locations.insert("archive".into(), Location { path: "/mnt/archive".into() });
+		locations.insert(
+			"loop".into(),
+			Location {
+				path: "@archive/loop".into(),
+			},
+		);
+		let expand = |path: &str| expand_with(PathBuf::from(path), &locations);
+		assert_eq!(expand("@archive").unwrap(), PathBuf::from("/mnt/archive"));
+		assert_eq!(expand("@archive/2023/report.pdf").unwrap(), PathBuf::from("/mnt/archive/2023/report.pdf"));
+		assert_eq!(expand("/home/user@host").unwrap(), PathBuf::from("/home/user@host"));
+		assert!(expand("@loop").is_err());
+		assert!(expand("@missing/report.pdf").is_err());
+	}
+}
@@ -1,43 +1,104 @@
 use std::{
 	collections::HashMap,
 	fs,
+	num::NonZeroUsize,
 	path::{Path, PathBuf},
+	time::{Duration, SystemTime},
 };
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use serde::Deserialize;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+use walkdir::DirEntry;
 
 use crate::{
+	elevation::Elevation,
+	journal::Retention,
+	messages::Messages,
+	mount::{OnMount, Volume},
+	path::Expand,
+	priority::IoPriority,
 	utils::{DefaultOpt, UnwrapRef},
 	PROJECT_NAME,
 };
 
 use self::{
-	actions::Actions,
-	filters::Filters,
-	folders::Folders,
-	options::{apply::Apply, r#match::Match, recursive::Recursive, Options},
+	actions::{Action, Actions},
+	condition::Condition,
+	constants::Constants,
+	defer::DeferIf,
+	filters::{Filters, Predicate},
+	folders::{Folder, Folders},
+	locations::Locations,
+	options::{apply::Apply, r#match::Match, recursive::Recursive, strategy::WatchStrategy, targets::Targets, Options},
+	trash::TrashRetention,
+	window::Window,
 };
 
 pub mod actions;
+pub mod condition;
+pub mod constants;
+pub mod defer;
+pub mod diff;
 pub mod filters;
 pub mod folders;
+pub mod locations;
 pub mod options;
+pub mod remote;
+pub mod secret;
+pub mod system;
+pub mod trash;
+pub mod version;
+pub mod window;
 
 #[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct ConfigBuilder {
+	/// the schema version the config was written for
+	#[serde(default)]
+	pub version: Option<u32>,
+	/// named directories that paths can refer to as `@name`
+	#[serde(default)]
+	pub locations: Locations,
+	/// values that templates and paths can refer to as `{const.<name>}`
+	#[serde(default)]
+	pub constants: Constants,
 	pub rules: Vec<Rule>,
-	#[serde(rename = "defaults", default = "Options::default_some")]
+	/// unset defaults fall back to the system config's, and then to the built-in ones
+	#[serde(rename = "defaults", default = "Options::default_none")]
 	pub local_defaults: Options,
 	#[serde(skip)]
 	pub global_defaults: Options,
+	#[serde(default)]
+	pub journal: Retention,
+	#[serde(default)]
+	pub trash: Option<TrashRetention>,
+	#[serde(default)]
+	pub elevation: Option<Elevation>,
+	#[serde(default)]
+	pub messages: Messages,
 }
 
 impl ConfigBuilder {
 	pub fn parse<T: AsRef<Path>>(path: T) -> Result<Self> {
 		let path = path.as_ref();
 		let s = fs::read_to_string(path)?;
-		toml::from_str(&s).context("Could not deserialize config")
+		// the locations and constants have to be known before the folders that refer to them are read
+		#[derive(Deserialize)]
+		struct Header {
+			#[serde(default)]
+			locations: Locations,
+			#[serde(default)]
+			constants: Constants,
+		}
+		let header: Header = toml::from_str(&s).context("Could not deserialize config")?;
+		let _definitions = Definitions::enter(&header.locations, &header.constants);
+		let mut builder: Self = toml::from_str(&s).context("Could not deserialize config")?;
+		version::check(builder.version, path)?;
+		for (i, rule) in builder.rules.iter_mut().enumerate() {
+			rule.apply_conditions(i);
+		}
+		Ok(builder)
 	}
 	pub fn path_to_rules(&self) -> HashMap<PathBuf, Vec<(usize, usize)>> {
 		let mut map = HashMap::with_capacity(self.rules.len()); // there will be at least one folder per rule
@@ -50,11 +111,32 @@ impl ConfigBuilder {
 		map
 	}
 
+	/// The filters of each rule compiled for each of its locations, which can apply them differently
+	pub fn predicates(&self) -> Vec<Vec<Predicate>> {
+		self.rules
+			.iter()
+			.enumerate()
+			.map(|(i, rule)| {
+				(0..rule.folders.len())
+					.map(|j| Predicate::new(&rule.filters, self.get_apply_filters(i, j)))
+					.collect()
+			})
+			.collect()
+	}
+
 	pub fn path_to_recursive(&self) -> HashMap<PathBuf, Recursive> {
 		let mut map = HashMap::with_capacity(self.rules.len());
 		self.rules.iter().enumerate().for_each(|(i, rule)| {
 			rule.folders.iter().enumerate().for_each(|(j, folder)| {
 				let depth = *self.get_recursive_depth(i, j);
+				let clamped = Recursive::clamp_home(&folder.path, depth, *self.allows_deep_home(i, j));
+				if clamped != depth {
+					log::warn!(
+						"{} is the home directory, only its top level will be scanned because `allow_deep_home` is turned off",
+						folder.path.display()
+					);
+				}
+				let depth = clamped;
 				map.entry(folder.path.to_path_buf())
 					.and_modify(|entry: &mut Recursive| {
 						if let Some(curr_depth) = entry.depth {
@@ -75,11 +157,41 @@ impl ConfigBuilder {
 #[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct Config {
 	pub rules: Vec<Rule>,
+	/// named directories that paths can refer to as `@name`
+	pub locations: Locations,
+	/// values that templates and paths can refer to as `{const.<name>}`
+	pub constants: Constants,
 	pub path: PathBuf,
 	pub local_defaults: Options,
 	pub global_defaults: Options,
 	pub path_to_rules: HashMap<PathBuf, Vec<(usize, usize)>>,
 	pub path_to_recursive: HashMap<PathBuf, Recursive>,
+	/// the filters of each rule for each of its locations, indexed like them, see [`ConfigBuilder::predicates`]
+	#[serde(skip)]
+	pub predicates: Vec<Vec<Predicate>>,
+	/// how much of the journal of past actions to keep
+	pub journal: Retention,
+	/// how much of the trash to keep, if it should be pruned at all
+	pub trash: Option<TrashRetention>,
+	/// how to retry the operations that were refused for lack of permissions, if at all
+	pub elevation: Option<Elevation>,
+	/// what organize says about the files it handles
+	pub messages: Messages,
+}
+
+/// Puts back the locations and constants that were in effect before [`Config::enter`] when it's dropped
+pub struct Definitions {
+	_locations: locations::Guard,
+	_constants: constants::Guard,
+}
+
+impl Definitions {
+	fn enter(locations: &Locations, constants: &Constants) -> Self {
+		Self {
+			_locations: locations::enter(locations),
+			_constants: constants::enter(constants),
+		}
+	}
 }
 
 macro_rules! getters {
@@ -173,6 +285,24 @@ getters! {
 	pub fn allows_hidden_files(&self, rule: usize, folder: usize) -> bool {
 		hidden_files
 	}
+	pub fn prunes_empty_dirs(&self, rule: usize, folder: usize) -> bool {
+		prune_empty_dirs
+	}
+	pub fn allows_deep_home(&self, rule: usize, folder: usize) -> bool {
+		allow_deep_home
+	}
+	pub fn get_targets(&self, rule: usize, folder: usize) -> Targets {
+		targets
+	}
+	pub fn get_watch_strategy(&self, rule: usize, folder: usize) -> WatchStrategy {
+		watch_strategy
+	}
+	pub fn get_poll_interval(&self, rule: usize, folder: usize) -> Duration {
+		poll_interval
+	}
+	pub fn get_priority(&self, rule: usize, folder: usize) -> u16 {
+		priority
+	}
 }
 
 getters! {
@@ -204,17 +334,300 @@ impl Config {
 		Self::default_dir().join("config.toml")
 	}
 
+	/// Parses the config at `path`, which can also be a remote source (see [`remote::Remote`])
 	pub fn parse<T: AsRef<Path>>(path: T) -> Result<Self> {
-		let path = path.as_ref();
-		let builder = ConfigBuilder::parse(path)?;
-		Ok(Self {
+		let path = &remote::resolve(path)?;
+		let builder = system::apply(ConfigBuilder::parse(path)?, path)?;
+		Self::build(builder, path)
+	}
+
+	/// The config read from `path` into `builder`, once its rules were checked
+	pub(crate) fn build(builder: ConfigBuilder, path: &Path) -> Result<Self> {
+		let _definitions = Definitions::enter(&builder.locations, &builder.constants);
+		let config = Self {
 			rules: builder.rules.clone(),
+			locations: builder.locations.clone(),
+			constants: builder.constants.clone(),
 			local_defaults: builder.local_defaults.clone(),
 			path: path.to_path_buf(),
 			global_defaults: builder.global_defaults.clone(),
 			path_to_rules: builder.path_to_rules(),
 			path_to_recursive: builder.path_to_recursive(),
-		})
+			predicates: builder.predicates(),
+			journal: builder.journal,
+			trash: builder.trash,
+			elevation: builder.elevation,
+			messages: builder.messages,
+		};
+		config.stages()?;
+		Ok(config)
+	}
+
+	/// Makes the `[locations]` and `[constants]` of this config the ones `@name` and `{const.<name>}` refer to on this thread,
+	/// until the guard is dropped. Each config keeps its own, so that several of them can run side by side.
+	pub fn enter(&self) -> Definitions {
+		Definitions::enter(&self.locations, &self.constants)
+	}
+
+	/// Groups the rules into stages that have to run one after the other.
+	/// Phases run in order, and within a phase every rule runs after the ones listed in its `after`.
+	/// Rules within a stage don't depend on each other.
+	pub fn stages(&self) -> Result<Vec<Vec<usize>>> {
+		let mut ids = HashMap::new();
+		for (i, rule) in self.rules.iter().enumerate() {
+			if let Some(id) = &rule.id {
+				if ids.insert(id.as_str(), i).is_some() {
+					bail!("there is more than one rule with id `{}`", id);
+				}
+			}
+		}
+		let mut dependencies = Vec::with_capacity(self.rules.len());
+		for rule in self.rules.iter() {
+			let mut indices = Vec::with_capacity(rule.after.len());
+			for id in rule.after.iter() {
+				let dependency = *ids
+					.get(id.as_str())
+					.ok_or_else(|| anyhow!("unknown rule `{}` in `after`", id))?;
+				let phase = self.rules[dependency].phase;
+				if phase > rule.phase {
+					bail!(
+						"rules in the {} phase can't run after `{}`, which is in the later {} phase",
+						rule.phase,
+						id,
+						phase
+					);
+				}
+				indices.push(dependency);
+			}
+			dependencies.push(indices);
+		}
+
+		let mut done = vec![false; self.rules.len()];
+		let mut stages: Vec<Vec<usize>> = Vec::new();
+		for phase in Phase::iter() {
+			let mut pending: Vec<usize> = (0..self.rules.len()).filter(|i| self.rules[*i].phase == phase).collect();
+			while !pending.is_empty() {
+				let (ready, blocked): (Vec<usize>, Vec<usize>) = pending
+					.iter()
+					.partition(|i| dependencies[**i].iter().all(|dependency| done[*dependency]));
+				if ready.is_empty() {
+					let cycle = blocked.iter().map(|i| self.rules[*i].name(*i)).collect::<Vec<_>>();
+					bail!("the `after` lists of these rules form a cycle: {}", cycle.join(", "));
+				}
+				for i in ready.iter() {
+					done[*i] = true;
+				}
+				stages.push(ready);
+				pending = blocked;
+			}
+		}
+		Ok(stages)
+	}
+
+	/// A copy of this config where only the given rules have locations
+	pub fn restrict(&self, rules: &[usize]) -> Self {
+		let mut restricted = self.rules.clone();
+		for (i, rule) in restricted.iter_mut().enumerate() {
+			if !rules.contains(&i) {
+				rule.folders.clear();
+			}
+		}
+		self.with_rules(restricted)
+	}
+
+	fn with_rules(&self, rules: Vec<Rule>) -> Self {
+		let builder = ConfigBuilder {
+			version: Some(version::CONFIG_VERSION),
+			locations: self.locations.clone(),
+			constants: self.constants.clone(),
+			rules,
+			local_defaults: self.local_defaults.clone(),
+			global_defaults: self.global_defaults.clone(),
+			journal: self.journal.clone(),
+			trash: self.trash.clone(),
+			elevation: self.elevation.clone(),
+			messages: self.messages.clone(),
+		};
+		Self {
+			path_to_rules: builder.path_to_rules(),
+			path_to_recursive: builder.path_to_recursive(),
+			predicates: builder.predicates(),
+			rules: builder.rules,
+			locations: builder.locations,
+			constants: builder.constants,
+			local_defaults: builder.local_defaults,
+			global_defaults: builder.global_defaults,
+			path: self.path.clone(),
+			journal: builder.journal,
+			trash: builder.trash,
+			elevation: builder.elevation,
+			messages: builder.messages,
+		}
+	}
+
+	/// The directories that need to be walked to reach every location, with the depth needed to cover them.
+	/// Locations nested inside another one are merged into it, so that each directory is only scanned once per run.
+	pub fn scan_roots(&self) -> HashMap<PathBuf, Recursive> {
+		let mut locations: Vec<(&PathBuf, &Recursive)> = self.path_to_recursive.iter().collect();
+		locations.sort_by_key(|(path, _)| path.components().count()); // ancestors come first
+		let mut roots: HashMap<PathBuf, Recursive> = HashMap::with_capacity(locations.len());
+		for (path, recursive) in locations {
+			match roots.iter_mut().find(|(root, _)| path.starts_with(root)) {
+				Some((root, root_recursive)) => {
+					let current = root_recursive.depth.unwrap_or(1);
+					let needed = match recursive.depth.unwrap_or(1) {
+						0 => 0,
+						depth => (path.components().count() - root.components().count()) as u16 + depth,
+					};
+					if current != 0 && (needed == 0 || needed > current) {
+						root_recursive.depth = Some(needed);
+					}
+				}
+				None => {
+					roots.insert(path.clone(), recursive.clone());
+				}
+			}
+		}
+		roots
+	}
+
+	/// Whether some rule applies to directories, so that they have to be walked too
+	pub fn targets_dirs(&self) -> bool {
+		self.rules
+			.iter()
+			.enumerate()
+			.any(|(i, rule)| (0..rule.folders.len()).any(|j| *self.get_targets(i, j) == Targets::Dirs))
+	}
+
+	/// Whether some rule copies or moves files, which needs space on their destination
+	pub fn transfers_files(&self) -> bool {
+		self.rules
+			.iter()
+			.flat_map(|rule| rule.actions.iter())
+			.any(|step| !step.dry_run && matches!(step.action, Action::Copy(_) | Action::Move(_)))
+	}
+
+	/// The scan roots that `watch` has to poll, with how often, because some location in them uses the `poll` strategy.
+	/// A root shared by several polled locations is polled as often as the most frequent of them.
+	pub fn poll_roots(&self) -> HashMap<PathBuf, Duration> {
+		let roots = self.scan_roots();
+		let mut polls: HashMap<PathBuf, Duration> = HashMap::new();
+		for (path, locations) in self.path_to_rules.iter() {
+			for (rule, folder) in locations.iter() {
+				if !*self.allows_watching(*rule, *folder) || *self.get_watch_strategy(*rule, *folder) != WatchStrategy::Poll {
+					continue;
+				}
+				if let Some(root) = roots.keys().find(|root| path.starts_with(root)) {
+					let interval = *self.get_poll_interval(*rule, *folder);
+					polls
+						.entry(root.clone())
+						.and_modify(|current| *current = (*current).min(interval))
+						.or_insert(interval);
+				}
+			}
+		}
+		polls
+	}
+
+	/// The most specific location `path` is in, with the highest priority any rule gives it
+	pub fn location_of<T: AsRef<Path>>(&self, path: T) -> Option<(&Path, u16)> {
+		let path = path.as_ref();
+		let (location, rules) = self
+			.path_to_rules
+			.iter()
+			.filter(|(location, _)| path.starts_with(location))
+			.max_by_key(|(location, _)| location.components().count())?;
+		let priority = rules
+			.iter()
+			.map(|(rule, folder)| *self.get_priority(*rule, *folder))
+			.max()
+			.unwrap_or(1);
+		Some((location.as_path(), priority.max(1)))
+	}
+
+	/// The oldest creation time a file inside `root` can have to match any of the rules whose locations are in it.
+	/// Creating a file, or moving one in, modifies its directory, so a directory that hasn't been modified since then
+	/// can't hold anything those rules would match. Editing a file in place doesn't modify its directory,
+	/// which is why `modified` filters can't be used to skip anything.
+	pub fn cutoff<T: AsRef<Path>>(&self, root: T) -> Option<SystemTime> {
+		let root = root.as_ref();
+		self.path_to_rules
+			.iter()
+			.filter(|(location, _)| location.starts_with(root))
+			.flat_map(|(_, rules)| rules.iter())
+			.map(|(rule, folder)| self.rules[*rule].filters.cutoff(self.get_apply_filters(*rule, *folder)))
+			.try_fold(None, |acc: Option<SystemTime>, cutoff| {
+				let cutoff = cutoff?;
+				Some(Some(acc.map_or(cutoff, |acc| acc.min(cutoff))))
+			})?
+	}
+
+	/// Which of the files found while walking `root` can be skipped, see [`cutoff`](Self::cutoff)
+	pub fn stale<T: AsRef<Path>>(&self, root: T) -> Stale {
+		Stale {
+			cutoff: self.cutoff(root),
+			dirs: HashMap::new(),
+		}
+	}
+
+	/// A copy of this config where the locations inside each `from` are moved to the matching `to`, keeping their relative path.
+	/// Locations that aren't inside any of them are left out.
+	pub fn with_roots(&self, roots: &[(PathBuf, PathBuf)]) -> Result<Self> {
+		let _definitions = self.enter();
+		let mut resolved = Vec::with_capacity(roots.len());
+		for (from, to) in roots.iter() {
+			let resolve = |path: &PathBuf| {
+				path.clone()
+					.expand_user()?
+					.expand_vars()?
+					.canonicalize()
+					.with_context(|| format!("could not find {}", path.display()))
+			};
+			resolved.push((resolve(from)?, resolve(to)?));
+		}
+		let mut rules = self.rules.clone();
+		for rule in rules.iter_mut() {
+			rule.folders = rule
+				.folders
+				.drain(..)
+				.filter_map(|mut folder| {
+					let (from, to) = resolved.iter().find(|(from, _)| folder.path.starts_with(from))?;
+					folder.path = to.join(folder.path.strip_prefix(from).ok()?);
+					Some(folder)
+				})
+				.collect();
+		}
+		let config = self.with_rules(rules);
+		if config.path_to_rules.is_empty() {
+			bail!("none of the configured locations are inside the given roots");
+		}
+		Ok(config)
+	}
+
+	/// A copy of this config where `path` is an additional location of the given rule
+	pub fn with_location<T: Into<PathBuf>>(&self, rule: usize, path: T) -> Self {
+		let mut rules = self.rules.clone();
+		rules[rule].folders.push(Folder {
+			path: path.into(),
+			options: Options::default_none(),
+			only_if: None,
+		});
+		self.with_rules(rules)
+	}
+
+	/// The config each rule triggered by mounting `volume` should run with
+	pub fn on_mount(&self, volume: &Volume) -> Vec<Self> {
+		self.rules
+			.iter()
+			.enumerate()
+			.filter(|(_, rule)| {
+				rule.on_mount
+					.as_ref()
+					.map(|on_mount| volume.matches(on_mount))
+					.unwrap_or_default()
+			})
+			.map(|(i, _)| self.with_location(i, &volume.mount_point))
+			.collect()
 	}
 
 	pub fn path() -> Result<PathBuf> {
@@ -256,20 +669,372 @@ impl Config {
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 pub struct Rule {
+	/// lets other rules refer to this one
+	#[serde(default)]
+	pub id: Option<String>,
+	/// rules that must be done before this one starts
+	#[serde(default)]
+	pub after: Vec<String>,
+	#[serde(default)]
+	pub phase: Phase,
+	/// in the system config, prevents users from replacing this rule or changing its options through their defaults
+	#[serde(default)]
+	pub locked: bool,
 	pub actions: Actions,
 	pub filters: Filters,
+	#[serde(default)]
 	pub folders: Folders,
 	#[serde(default = "Options::default_none")]
 	pub options: Options,
+	#[serde(default)]
+	pub on_mount: Option<OnMount>,
+	/// the machines this rule runs on, if not all of them
+	#[serde(default)]
+	pub only_if: Option<Condition>,
+	/// when the rule should wait for a better time
+	#[serde(default)]
+	pub defer_if: Option<DeferIf>,
+	/// when the rule is allowed to run, if not all the time
+	#[serde(default)]
+	pub active: Option<Window>,
+	/// how many files the rule handles at the same time at most, whatever the number of workers
+	#[serde(default)]
+	pub max_concurrency: Option<NonZeroUsize>,
+	/// the IO priority of the threads handling the rule's files
+	#[serde(default)]
+	pub io_priority: Option<IoPriority>,
+}
+
+/// Rules run in phases, so that e.g. cleanup rules see the results of the rules that organized files in the same run
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, PartialOrd, Ord, Display, EnumIter)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Phase {
+	Ingest,
+	#[default]
+	Organize,
+	Cleanup,
+}
+
+impl Rule {
+	/// How the rule at position `i` is referred to in messages
+	pub fn name(&self, i: usize) -> String {
+		self.id.clone().unwrap_or_else(|| format!("#{}", i))
+	}
+
+	/// Leaves out the locations of the rule at position `i` that aren't meant for this machine, or all of them if the rule isn't.
+	/// The rule itself is kept, so that the rules that come after it still can.
+	fn apply_conditions(&mut self, i: usize) {
+		if let Some(condition) = self.only_if.as_ref().filter(|condition| !condition.holds()) {
+			log::debug!("rule {} doesn't apply to this machine ({})", self.name(i), condition);
+			self.folders.clear();
+			return;
+		}
+		let name = self.name(i);
+		self.folders
+			.retain(|folder| match folder.only_if.as_ref().filter(|condition| !condition.holds()) {
+				Some(condition) => {
+					log::debug!("{} isn't watched by rule {} on this machine ({})", folder.path.display(), name, condition);
+					false
+				}
+				None => true,
+			});
+	}
 }
 
 impl Default for Rule {
 	fn default() -> Self {
 		Self {
+			id: None,
+			after: vec![],
+			phase: Phase::default(),
+			locked: false,
 			actions: Actions(vec![]),
 			filters: Filters(vec![]),
 			folders: vec![],
 			options: Options::default_none(),
+			on_mount: None,
+			only_if: None,
+			defer_if: None,
+			active: None,
+			max_concurrency: None,
+			io_priority: None,
 		}
 	}
 }
+
+/// Skips the files of the directories that weren't modified since the [cutoff](Config::cutoff) of a walk
+pub struct Stale {
+	cutoff: Option<SystemTime>,
+	dirs: HashMap<PathBuf, bool>,
+}
+
+impl Stale {
+	/// Whether `entry` is a file in a directory below the root that wasn't modified since the cutoff.
+	/// A directory's mtime only changes when its own entries do, so a stale one says nothing about its subdirectories.
+	pub fn contains(&mut self, entry: &DirEntry) -> bool {
+		match (self.cutoff, entry.path().parent()) {
+			(Some(cutoff), Some(parent)) if entry.depth() > 1 && !entry.file_type().is_dir() => *self
+				.dirs
+				.entry(parent.to_path_buf())
+				.or_insert_with(|| parent.metadata().and_then(|m| m.modified()).is_ok_and(|m| m < cutoff)),
+			_ => false,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn scan_roots_merge_nested_locations() {
+		let dir = tempfile::tempdir().unwrap();
+		let (root, nested, other) = (
+			dir.path().join("root"),
+			dir.path().join("root").join("a").join("b"),
+			dir.path().join("other"),
+		);
+		for path in [&nested, &other] {
+			fs::create_dir_all(path).unwrap();
+		}
+		let config = format!(
+			r#"
+[[rules]]
+folders = ["{}", "{}"]
+filters = []
+actions = []
+
+[[rules]]
+folders = [{{ path = "{}", options = {{ recursive = 2 }} }}]
+filters = []
+actions = []
+"#,
+			root.display(),
+			other.display(),
+			nested.display()
+		);
+		let path = dir.path().join("organize.toml");
+		fs::write(&path, config).unwrap();
+		let roots = Config::parse(path).unwrap().scan_roots();
+		assert_eq!(roots.len(), 2);
+		assert_eq!(roots[&root.canonicalize().unwrap()].depth, Some(4));
+		assert_eq!(roots[&other.canonicalize().unwrap()].depth, Some(1));
+	}
+
+	#[test]
+	fn poll_roots_use_shortest_interval() {
+		let dir = tempfile::tempdir().unwrap();
+		let (share, nested, local) = (dir.path().join("share"), dir.path().join("share").join("a"), dir.path().join("local"));
+		for path in [&nested, &local] {
+			fs::create_dir_all(path).unwrap();
+		}
+		let config = format!(
+			r#"
+[defaults]
+watch_strategy = "poll"
+poll_interval = "5m"
+
+[[rules]]
+folders = ["{}", {{ path = "{}", options = {{ poll_interval = "30s" }} }}, {{ path = "{}", options = {{ watch_strategy = "native" }} }}]
+filters = []
+actions = []
+"#,
+			share.display(),
+			nested.display(),
+			local.display()
+		);
+		let path = dir.path().join("organize.toml");
+		fs::write(&path, config).unwrap();
+		let polls = Config::parse(path).unwrap().poll_roots();
+		assert_eq!(polls.len(), 1);
+		assert_eq!(polls[&share.canonicalize().unwrap()], Duration::from_secs(30));
+	}
+
+	#[test]
+	fn location_of_nested_path() {
+		let dir = tempfile::tempdir().unwrap();
+		let (home, desktop) = (dir.path().join("home"), dir.path().join("home").join("desktop"));
+		fs::create_dir_all(&desktop).unwrap();
+		let config = format!(
+			r#"
+[[rules]]
+folders = ["{}", {{ path = "{}", options = {{ priority = 5 }} }}]
+filters = []
+actions = []
+"#,
+			home.display(),
+			desktop.display()
+		);
+		let path = dir.path().join("organize.toml");
+		fs::write(&path, config).unwrap();
+		let config = Config::parse(path).unwrap();
+		let (home, desktop) = (home.canonicalize().unwrap(), desktop.canonicalize().unwrap());
+		assert_eq!(config.location_of(desktop.join("a.txt")), Some((desktop.as_path(), 5)));
+		assert_eq!(config.location_of(home.join("b.txt")), Some((home.as_path(), 1)));
+		assert_eq!(config.location_of(dir.path().join("c.txt")), None);
+	}
+
+	#[test]
+	fn resolve_definitions_per_config() {
+		let dir = tempfile::tempdir().unwrap();
+		let parse = |name: &str| {
+			let inbox = dir.path().join(name);
+			fs::create_dir_all(&inbox).unwrap();
+			let config = format!(
+				r#"
+[locations.inbox]
+path = "{}"
+
+[constants]
+archive = "{}"
+
+[[rules]]
+folders = ["@inbox"]
+filters = []
+actions = []
+"#,
+				inbox.display(),
+				name
+			);
+			let path = dir.path().join(format!("{}.toml", name));
+			fs::write(&path, config).unwrap();
+			(Config::parse(path).unwrap(), inbox)
+		};
+		let ((personal, personal_inbox), (work, work_inbox)) = (parse("personal"), parse("work"));
+		assert!(personal.path_to_rules.contains_key(&personal_inbox));
+		assert!(work.path_to_rules.contains_key(&work_inbox));
+		let expand = || PathBuf::from("@inbox/{const.archive}").expand_user();
+		for (config, inbox, archive) in [(&personal, &personal_inbox, "personal"), (&work, &work_inbox, "work")] {
+			let _definitions = config.enter();
+			assert_eq!(expand().unwrap(), inbox.join(archive));
+		}
+		// nothing is left in effect once they're done
+		assert!(expand().is_err());
+	}
+
+	#[test]
+	fn with_roots_substitutes_locations() {
+		let dir = tempfile::tempdir().unwrap();
+		let (downloads, documents, usb) = (dir.path().join("downloads"), dir.path().join("documents"), dir.path().join("usb"));
+		for path in [&downloads.join("pdfs"), &documents, &usb.join("pdfs")] {
+			fs::create_dir_all(path).unwrap();
+		}
+		let config = format!(
+			r#"
+[[rules]]
+folders = ["{}", "{}"]
+filters = []
+actions = []
+"#,
+			downloads.join("pdfs").display(),
+			documents.display(),
+		);
+		let path = dir.path().join("organize.toml");
+		fs::write(&path, config).unwrap();
+		let config = Config::parse(path).unwrap();
+		let substituted = config.with_roots(&[(downloads.clone(), usb.clone())]).unwrap();
+		let locations: Vec<&PathBuf> = substituted.path_to_rules.keys().collect();
+		assert_eq!(locations, vec![&usb.join("pdfs").canonicalize().unwrap()]);
+		assert!(config.with_roots(&[(usb.clone(), downloads)]).is_err());
+	}
+
+	fn with_after(rules: &[(Option<&str>, &[&str])]) -> Config {
+		with_phases(
+			&rules
+				.iter()
+				.map(|(id, after)| (*id, *after, Phase::default()))
+				.collect::<Vec<_>>(),
+		)
+	}
+
+	fn with_phases(rules: &[(Option<&str>, &[&str], Phase)]) -> Config {
+		Config {
+			rules: rules
+				.iter()
+				.map(|(id, after, phase)| Rule {
+					id: id.map(String::from),
+					after: after.iter().map(|s| s.to_string()).collect(),
+					phase: *phase,
+					..Rule::default()
+				})
+				.collect(),
+			path: PathBuf::new(),
+			locations: Locations::new(),
+			constants: Constants::new(),
+			local_defaults: Options::default_none(),
+			global_defaults: Options::default_some(),
+			path_to_rules: HashMap::new(),
+			path_to_recursive: HashMap::new(),
+			predicates: vec![],
+			journal: Retention::default(),
+			trash: None,
+			elevation: None,
+			messages: Messages::default(),
+		}
+	}
+
+	#[test]
+	fn stages_follow_after() {
+		let config = with_after(&[
+			(Some("sort"), &["extract"]),
+			(Some("extract"), &[]),
+			(None, &[]),
+			(None, &["sort", "extract"]),
+		]);
+		assert_eq!(config.stages().unwrap(), vec![vec![1, 2], vec![0], vec![3]]);
+	}
+
+	#[test]
+	fn stages_reject_cycles_and_unknown_ids() {
+		assert!(with_after(&[(Some("a"), &["b"]), (Some("b"), &["a"])]).stages().is_err());
+		assert!(with_after(&[(Some("a"), &["c"])]).stages().is_err());
+		assert!(with_after(&[(Some("a"), &[]), (Some("a"), &[])]).stages().is_err());
+	}
+
+	#[test]
+	fn stages_follow_phases() {
+		let config = with_phases(&[
+			(Some("prune"), &[], Phase::Cleanup),
+			(Some("sort"), &[], Phase::Organize),
+			(Some("extract"), &[], Phase::Ingest),
+			(None, &["extract"], Phase::Cleanup),
+		]);
+		assert_eq!(config.stages().unwrap(), vec![vec![2], vec![1], vec![0, 3]]);
+		assert!(with_phases(&[(Some("a"), &["b"], Phase::Ingest), (Some("b"), &[], Phase::Cleanup)])
+			.stages()
+			.is_err());
+	}
+
+	#[test]
+	fn keep_files_edited_in_stale_directories() {
+		let dir = tempfile::tempdir().unwrap();
+		let root = dir.path().join("root");
+		let nested = root.join("a");
+		fs::create_dir_all(&nested).unwrap();
+		fs::write(nested.join("notes.txt"), "edited").unwrap();
+		// the directory hasn't changed in a day, but the file in it was just edited
+		let yesterday = SystemTime::now() - Duration::from_secs(86_400);
+		fs::File::open(&nested).unwrap().set_modified(yesterday).unwrap();
+		let stale = |filter: &str| {
+			let path = dir.path().join("organize.toml");
+			let config = format!(
+				"[[rules]]\nfolders = [{{ path = {:?}, options = {{ recursive = 0 }} }}]\nfilters = [{}]\nactions = []",
+				root, filter
+			);
+			fs::write(&path, config).unwrap();
+			let config = Config::parse(path).unwrap();
+			let root = root.canonicalize().unwrap();
+			let mut stale = config.stale(&root);
+			let entry = walkdir::WalkDir::new(&root)
+				.into_iter()
+				.filter_map(|entry| entry.ok())
+				.find(|entry| entry.file_name() == "notes.txt")
+				.unwrap();
+			stale.contains(&entry)
+		};
+		assert!(!stale("{ type = \"modified\", newer_than = \"1h\" }"));
+		// nothing can have been created in it within the last hour
+		assert!(stale("{ type = \"created\", newer_than = \"1h\" }"));
+	}
+}
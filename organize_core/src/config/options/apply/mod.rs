@@ -2,23 +2,18 @@ mod de;
 pub mod wrapper;
 
 use serde::{de::Error, Serialize};
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
-#[derive(Serialize, Debug, Clone, Eq, PartialEq)]
+#[derive(Serialize, Debug, Clone, Eq, PartialEq, Default)]
 #[serde(rename_all(serialize = "lowercase", deserialize = "lowercase"))]
 pub enum Apply {
+	#[default]
 	All,
 	Any,
 	AllOf(Vec<usize>),
 	AnyOf(Vec<usize>),
 }
 
-impl Default for Apply {
-	fn default() -> Self {
-		Self::All
-	}
-}
-
 impl FromStr for Apply {
 	type Err = serde::de::value::Error;
 
@@ -37,13 +32,13 @@ impl AsRef<Self> for Apply {
 	}
 }
 
-impl ToString for Apply {
-	fn to_string(&self) -> String {
+impl fmt::Display for Apply {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
-			Apply::All => "all".into(),
-			Apply::Any => "any".into(),
-			Apply::AllOf(_) => "all_of".into(),
-			Apply::AnyOf(_) => "any_of".into(),
+			Apply::All => f.write_str("all"),
+			Apply::Any => f.write_str("any"),
+			Apply::AllOf(_) => f.write_str("all_of"),
+			Apply::AnyOf(_) => f.write_str("any_of"),
 		}
 	}
 }
@@ -1,16 +1,11 @@
 use serde::{Deserialize, Serialize};
 use strum_macros::Display;
 
-#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Display)]
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq, Display, Default)]
 #[strum(serialize_all = "snake_case")]
 #[serde(rename_all(serialize = "lowercase", deserialize = "lowercase"))]
 pub enum Match {
 	All,
+	#[default]
 	First,
 }
-
-impl Default for Match {
-	fn default() -> Self {
-		Self::First
-	}
-}
@@ -1,26 +1,47 @@
 pub mod apply;
 pub(crate) mod r#match;
 pub mod recursive;
+pub mod strategy;
+pub mod targets;
 
 use crate::config::options::r#match::Match;
 
 use crate::{config::options::apply::wrapper::ApplyWrapper, utils::DefaultOpt};
 
-use crate::config::options::recursive::Recursive;
+use crate::config::{
+	filters::deserialize_duration,
+	options::{recursive::Recursive, strategy::WatchStrategy, targets::Targets},
+};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::{
+	path::{Path, PathBuf},
+	time::Duration,
+};
 
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
 pub struct Options {
 	/// defines whether or not subdirectories must be scanned
 	pub recursive: Recursive,
 	pub watch: Option<bool>,
+	pub watch_strategy: Option<WatchStrategy>,
+	/// how often locations watched with the `poll` strategy are rescanned
+	#[serde(default, deserialize_with = "deserialize_duration")]
+	pub poll_interval: Option<Duration>,
+	/// how many new files from this location `watch` handles per turn while other locations also have some waiting
+	pub priority: Option<u16>,
 	pub ignored_dirs: Option<Vec<PathBuf>>,
 	pub hidden_files: Option<bool>,
 	pub r#match: Option<Match>,
 	pub partial_files: Option<bool>,
+	/// remove directories left empty after their files were moved away, up to the location itself
+	pub prune_empty_dirs: Option<bool>,
+	/// scan the home directory past its top level when it's used as a location, which can be turned off to guard against
+	/// recursive rules on `~`
+	pub allow_deep_home: Option<bool>,
+	/// whether the rule applies to files or to directories
+	pub targets: Option<Targets>,
 	#[serde(default = "DefaultOpt::default_none")]
 	pub apply: ApplyWrapper,
 }
@@ -30,6 +51,30 @@ impl Options {
 		let path = path.as_ref();
 		fs::read_to_string(path).map(|s| toml::from_str(&s).with_context(|| format!("could not deserialize {}", path.display())))?
 	}
+
+	/// These options, with the ones that aren't set taken from `fallback`
+	pub fn or(&self, fallback: &Self) -> Self {
+		Self {
+			recursive: Recursive {
+				depth: self.recursive.depth.or(fallback.recursive.depth),
+			},
+			watch: self.watch.or(fallback.watch),
+			watch_strategy: self.watch_strategy.or(fallback.watch_strategy),
+			poll_interval: self.poll_interval.or(fallback.poll_interval),
+			priority: self.priority.or(fallback.priority),
+			ignored_dirs: self.ignored_dirs.clone().or_else(|| fallback.ignored_dirs.clone()),
+			hidden_files: self.hidden_files.or(fallback.hidden_files),
+			r#match: self.r#match.clone().or_else(|| fallback.r#match.clone()),
+			partial_files: self.partial_files.or(fallback.partial_files),
+			prune_empty_dirs: self.prune_empty_dirs.or(fallback.prune_empty_dirs),
+			allow_deep_home: self.allow_deep_home.or(fallback.allow_deep_home),
+			targets: self.targets.or(fallback.targets),
+			apply: ApplyWrapper {
+				actions: self.apply.actions.clone().or_else(|| fallback.apply.actions.clone()),
+				filters: self.apply.filters.clone().or_else(|| fallback.apply.filters.clone()),
+			},
+		}
+	}
 }
 
 impl Default for Options {
@@ -43,9 +88,15 @@ impl DefaultOpt for Options {
 		Self {
 			recursive: DefaultOpt::default_none(),
 			watch: None,
+			watch_strategy: None,
+			poll_interval: None,
+			priority: None,
 			ignored_dirs: None,
 			hidden_files: None,
 			partial_files: None,
+			prune_empty_dirs: None,
+			allow_deep_home: None,
+			targets: None,
 			r#match: None,
 			apply: DefaultOpt::default_none(),
 		}
@@ -55,9 +106,15 @@ impl DefaultOpt for Options {
 		Self {
 			recursive: DefaultOpt::default_some(),
 			watch: Some(true),
+			watch_strategy: Some(WatchStrategy::Native),
+			poll_interval: Some(Duration::from_secs(60)),
+			priority: Some(1),
 			ignored_dirs: Some(Vec::new()),
 			hidden_files: Some(false),
 			partial_files: Some(false),
+			prune_empty_dirs: Some(false),
+			allow_deep_home: Some(true),
+			targets: Some(Targets::Files),
 			apply: DefaultOpt::default_some(),
 			r#match: Some(Match::default()),
 		}
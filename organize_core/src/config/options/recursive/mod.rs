@@ -20,9 +20,26 @@ impl DefaultOpt for Recursive {
 }
 
 impl Recursive {
+	/// The depth `path` is actually scanned at, which is only the top level of the home directory if `allow_deep_home` is turned off
+	pub fn clamp_home<T: AsRef<Path>>(path: T, depth: u16, allow_deep_home: bool) -> u16 {
+		match dirs_next::home_dir().and_then(|home| home.canonicalize().ok()) {
+			Some(home) => Self::clamp(&home, path.as_ref(), depth, allow_deep_home),
+			None => depth,
+		}
+	}
+
+	fn clamp(home: &Path, path: &Path, depth: u16, allow_deep_home: bool) -> u16 {
+		match home == path && !allow_deep_home {
+			true => 1,
+			false => depth,
+		}
+	}
+
+	/// Lists what's inside `path` down to the depth of this option: `0` walks the whole tree, and a missing depth only the top level
 	pub fn to_walker<T: AsRef<Path>>(&self, path: T) -> WalkDir {
 		match self.depth {
-			None | Some(1) => WalkDir::new(path).min_depth(1),
+			Some(0) => WalkDir::new(path).min_depth(1),
+			None => WalkDir::new(path).min_depth(1).max_depth(1),
 			Some(other) => WalkDir::new(path).min_depth(1).max_depth(other as usize),
 		}
 	}
@@ -50,4 +67,24 @@ mod tests {
 		assert!(Recursive { depth: Some(0) }.is_recursive());
 		assert!(Recursive { depth: Some(3) }.is_recursive());
 	}
+
+	#[test]
+	fn clamp_home() {
+		let home = tempfile::tempdir().unwrap();
+		let home = home.path();
+		assert_eq!(Recursive::clamp(home, home, 0, false), 1);
+		assert_eq!(Recursive::clamp(home, home, 0, true), 0);
+		assert_eq!(Recursive::clamp(home, &home.join("Downloads"), 0, false), 0);
+	}
+
+	#[test]
+	fn walker_depth() {
+		let dir = tempfile::tempdir().unwrap();
+		std::fs::create_dir_all(dir.path().join("a").join("b")).unwrap();
+		let count = |depth| Recursive { depth }.to_walker(dir.path()).into_iter().count();
+		assert_eq!(count(None), 1);
+		assert_eq!(count(Some(1)), 1);
+		assert_eq!(count(Some(2)), 2);
+		assert_eq!(count(Some(0)), 2);
+	}
 }
@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// How `watch` notices new files in a location
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchStrategy {
+	/// filesystem events, which most local filesystems support
+	#[default]
+	Native,
+	/// rescanning the location every `poll_interval`, for network and FUSE mounts (NFS, SMB, rclone...) where events are unreliable
+	Poll,
+}
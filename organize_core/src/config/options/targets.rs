@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// What a rule is applied to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Targets {
+	#[default]
+	#[serde(alias = "file")]
+	Files,
+	/// the directories inside the rule's locations, deepest first, so that emptying one can leave its parent empty too.
+	/// Only `run` visits them.
+	#[serde(alias = "dir")]
+	Dirs,
+}
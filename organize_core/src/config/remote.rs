@@ -0,0 +1,288 @@
+use std::{
+	collections::HashMap,
+	io::Read,
+	path::{Path, PathBuf},
+	process::Command,
+};
+
+use anyhow::{bail, Context, Result};
+use sha2::{Digest, Sha256};
+
+use crate::PROJECT_NAME;
+
+/// Remote configs larger than this are refused
+const MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// A config that lives somewhere else, e.g. a team's shared policies.
+///
+/// - `https://example.com/config.toml`
+/// - `git+https://example.com/policies.git#path=config.toml&ref=main`
+///
+/// Either can be pinned by adding `sha256=<hex digest of the file>` to the fragment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remote {
+	url: String,
+	kind: Kind,
+	/// the file inside the repository, for git sources
+	path: Option<PathBuf>,
+	/// the branch or tag to check out, for git sources
+	reference: Option<String>,
+	sha256: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+	Https,
+	Git,
+}
+
+impl Remote {
+	/// Parses `source` if it refers to a remote config
+	pub fn parse(source: &str) -> Option<Self> {
+		let (kind, rest) = if let Some(rest) = source.strip_prefix("git+") {
+			(Kind::Git, rest)
+		} else if source.starts_with("https://") {
+			(Kind::Https, source)
+		} else {
+			return None;
+		};
+		let (url, fragment) = rest.split_once('#').unwrap_or((rest, ""));
+		let params: HashMap<&str, &str> = fragment.split('&').filter_map(|pair| pair.split_once('=')).collect();
+		Some(Self {
+			url: url.to_string(),
+			kind,
+			path: params.get("path").map(PathBuf::from),
+			reference: params.get("ref").map(|s| s.to_string()),
+			sha256: params.get("sha256").map(|s| s.to_lowercase()),
+		})
+	}
+
+	fn cache_dir() -> Result<PathBuf> {
+		let dir = dirs_next::cache_dir()
+			.context("could not find the cache directory")?
+			.join(PROJECT_NAME)
+			.join("remote");
+		std::fs::create_dir_all(&dir).with_context(|| format!("could not create {}", dir.display()))?;
+		Ok(dir)
+	}
+
+	/// Where the latest copy of this source is kept
+	fn cached(&self) -> Result<PathBuf> {
+		Ok(self.cached_in(&Self::cache_dir()?))
+	}
+
+	/// Sources that check out something different, like another ref of the same repository, get their own copy.
+	/// The pinned digest is left out, it only decides whether a copy can be used.
+	fn cached_in(&self, cache: &Path) -> PathBuf {
+		let key = format!(
+			"{:?}\n{}\n{}\n{}",
+			self.kind,
+			self.url,
+			self.reference.as_deref().unwrap_or_default(),
+			self.path.as_deref().unwrap_or(Path::new("")).display()
+		);
+		let name = digest(key.as_bytes());
+		match self.kind {
+			Kind::Https => cache.join(format!("{}.toml", name)),
+			Kind::Git => cache.join(name),
+		}
+	}
+
+	/// Downloads the config, verifies it and returns the path of the local copy.
+	/// If the source can't be reached, the last copy that was fetched is used instead.
+	pub fn fetch(&self) -> Result<PathBuf> {
+		let fetched = match self.kind {
+			Kind::Https => self.fetch_https(),
+			Kind::Git => Self::cache_dir().and_then(|cache| self.fetch_git(&cache)),
+		};
+		let path = match fetched {
+			Ok(path) => path,
+			Err(e) => {
+				let path = self.file()?;
+				if !path.exists() {
+					return Err(e);
+				}
+				log::warn!("could not fetch {}, using the cached copy: {:?}", self.url, e);
+				path
+			}
+		};
+		self.verify(&path)?;
+		Ok(path)
+	}
+
+	/// The config file inside the cache
+	fn file(&self) -> Result<PathBuf> {
+		Ok(self.file_in(&self.cached()?))
+	}
+
+	/// The config file inside `cached`, a copy of this source
+	fn file_in(&self, cached: &Path) -> PathBuf {
+		match (self.kind, &self.path) {
+			(Kind::Git, Some(path)) => cached.join(path),
+			(Kind::Git, None) => cached.join("config.toml"),
+			(Kind::Https, _) => cached.to_path_buf(),
+		}
+	}
+
+	fn fetch_https(&self) -> Result<PathBuf> {
+		let response = ureq::get(&self.url)
+			.call()
+			.with_context(|| format!("could not download {}", self.url))?;
+		let mut content = Vec::new();
+		response
+			.into_reader()
+			.take(MAX_SIZE + 1)
+			.read_to_end(&mut content)
+			.with_context(|| format!("could not download {}", self.url))?;
+		if content.len() as u64 > MAX_SIZE {
+			bail!("{} is larger than {} bytes", self.url, MAX_SIZE);
+		}
+		// the cached copy must never be replaced by one that doesn't pass verification
+		self.check(&content)?;
+		let path = self.file()?;
+		std::fs::write(&path, content).with_context(|| format!("could not cache {}", self.url))?;
+		Ok(path)
+	}
+
+	/// Clones the repository next to the cached copy and only replaces it once the new one passes verification
+	fn fetch_git(&self, cache: &Path) -> Result<PathBuf> {
+		let dir = self.cached_in(cache);
+		let staging = tempfile::Builder::new()
+			.prefix(".fetching-")
+			.tempdir_in(cache)
+			.with_context(|| format!("could not create a directory in {}", cache.display()))?;
+		let clone = staging.path().join("clone");
+		let target = clone.to_string_lossy().to_string();
+		let mut args = vec!["clone", "--depth", "1"];
+		if let Some(reference) = &self.reference {
+			args.extend(["--branch", reference.as_str()]);
+		}
+		// a url starting with `-` would be taken for an option
+		args.extend(["--", self.url.as_str(), target.as_str()]);
+		let status = Command::new("git")
+			.args(&args)
+			.current_dir(cache)
+			.status()
+			.context("could not run git")?;
+		if !status.success() {
+			bail!("`git {}` failed for {}", args.join(" "), self.url);
+		}
+		self.verify(&self.file_in(&clone))?;
+		// the previous copy is moved aside rather than removed, so that it can be put back if the new one can't take its place
+		let previous = staging.path().join("previous");
+		if dir.exists() {
+			std::fs::rename(&dir, &previous).with_context(|| format!("could not replace {}", dir.display()))?;
+		}
+		if let Err(e) = std::fs::rename(&clone, &dir) {
+			if previous.exists() {
+				std::fs::rename(&previous, &dir).ok();
+			}
+			return Err(e).with_context(|| format!("could not replace {}", dir.display()));
+		}
+		Ok(self.file_in(&dir))
+	}
+
+	fn check(&self, content: &[u8]) -> Result<()> {
+		if let Some(expected) = &self.sha256 {
+			let actual = digest(content);
+			if &actual != expected {
+				bail!("{} does not match its pinned sha256 (expected {}, got {})", self.url, expected, actual);
+			}
+		}
+		Ok(())
+	}
+
+	fn verify(&self, path: &Path) -> Result<()> {
+		let content = std::fs::read(path).with_context(|| format!("could not read {}", path.display()))?;
+		self.check(&content)
+	}
+}
+
+fn digest(content: &[u8]) -> String {
+	Sha256::digest(content).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The local path of the config referred to by `path`, fetching it first if it's remote
+pub fn resolve<T: AsRef<Path>>(path: T) -> Result<PathBuf> {
+	let path = path.as_ref();
+	match Remote::parse(&path.to_string_lossy()) {
+		Some(remote) => remote.fetch(),
+		None => Ok(path.to_path_buf()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_sources() {
+		assert_eq!(Remote::parse("/home/user/config.toml"), None);
+		let remote = Remote::parse("git+https://example.com/policies.git#path=team/config.toml&ref=main&sha256=ABC").unwrap();
+		assert_eq!(remote.kind, Kind::Git);
+		assert_eq!(remote.url, "https://example.com/policies.git");
+		assert_eq!(remote.path, Some(PathBuf::from("team/config.toml")));
+		assert_eq!(remote.reference.as_deref(), Some("main"));
+		assert_eq!(remote.sha256.as_deref(), Some("abc"));
+		let remote = Remote::parse("https://example.com/config.toml").unwrap();
+		assert_eq!(remote.kind, Kind::Https);
+		assert_eq!(remote.sha256, None);
+	}
+
+	#[test]
+	fn keep_cached_copy_until_verified() {
+		let (cache, repo) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		let git = |args: &[&str]| {
+			assert!(Command::new("git")
+				.args(args)
+				.current_dir(repo.path())
+				.status()
+				.unwrap()
+				.success())
+		};
+		let commit = |content: &str| {
+			std::fs::write(repo.path().join("config.toml"), content).unwrap();
+			git(&["add", "config.toml"]);
+			git(&[
+				"-c",
+				"user.name=organize",
+				"-c",
+				"user.email=organize@localhost",
+				"commit",
+				"-q",
+				"-m",
+				content,
+			]);
+		};
+		git(&["init", "-q"]);
+		commit("[[rules]]");
+		let pinned = Remote::parse(&format!("git+{}#sha256={}", repo.path().display(), digest(b"[[rules]]"))).unwrap();
+		let unpinned = Remote::parse(&format!("git+{}", repo.path().display())).unwrap();
+		let (pinned_path, unpinned_path) = (pinned.fetch_git(cache.path()).unwrap(), unpinned.fetch_git(cache.path()).unwrap());
+		commit("[[rules]]\n");
+		assert!(pinned.fetch_git(cache.path()).is_err());
+		assert_eq!(std::fs::read_to_string(&pinned_path).unwrap(), "[[rules]]");
+		assert_eq!(unpinned.fetch_git(cache.path()).unwrap(), unpinned_path);
+		assert_eq!(std::fs::read_to_string(&unpinned_path).unwrap(), "[[rules]]\n");
+		// nothing is left behind next to the copy, which both share
+		assert_eq!(std::fs::read_dir(cache.path()).unwrap().count(), 1);
+	}
+
+	#[test]
+	fn cache_each_checkout() {
+		let cache = Path::new("/cache");
+		let cached = |source: &str| Remote::parse(source).unwrap().cached_in(cache);
+		let main = cached("git+https://example.com/policies.git#ref=main");
+		assert_ne!(main, cached("git+https://example.com/policies.git#ref=release"));
+		assert_ne!(main, cached("git+https://example.com/policies.git#ref=main&path=team.toml"));
+		assert_eq!(main, cached("git+https://example.com/policies.git#ref=main&sha256=abc"));
+	}
+
+	#[test]
+	fn check_digest() {
+		let content = b"[[rules]]";
+		let remote = Remote::parse(&format!("https://example.com/config.toml#sha256={}", digest(content))).unwrap();
+		assert!(remote.check(content).is_ok());
+		assert!(remote.check(b"[[rules]]\n").is_err());
+	}
+}
@@ -0,0 +1,124 @@
+use std::{fmt, process::Command, str::FromStr};
+
+use anyhow::{anyhow, bail, Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{de::Error, Deserialize, Deserializer};
+
+use crate::{settings, PROJECT_NAME};
+
+lazy_static! {
+	static ref SECRET_REGEX: Regex = Regex::new(r#"^\s*(env|secret|credential)\(\s*"([^"]+)"\s*\)\s*$"#).unwrap(); // a panic here indicates a compile-time bug
+}
+
+/// A reference to a credential that is looked up when it's needed, so that it never has to be written in the config.
+///
+/// - `env("NAME")` reads the `NAME` environment variable
+/// - `secret("name")` reads the `name` entry of the `organize` service from the OS keychain
+/// - `credential("name")` is whatever `name` refers to under `[credentials]` in the settings
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Secret {
+	Env(String),
+	Keychain(String),
+	Credential(String),
+}
+
+impl FromStr for Secret {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let captures = SECRET_REGEX
+			.captures(s)
+			.ok_or_else(|| anyhow!("expected env(\"NAME\"), secret(\"name\") or credential(\"name\"), found {:?}", s))?;
+		let name = captures[2].to_string();
+		match &captures[1] {
+			"env" => Ok(Self::Env(name)),
+			"secret" => Ok(Self::Keychain(name)),
+			_ => Ok(Self::Credential(name)),
+		}
+	}
+}
+
+impl<'de> Deserialize<'de> for Secret {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		let str = String::deserialize(deserializer)?;
+		Self::from_str(&str).map_err(D::Error::custom)
+	}
+}
+
+// only the reference is ever printed, never the value
+impl fmt::Debug for Secret {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Env(name) => write!(f, "env({:?})", name),
+			Self::Keychain(name) => write!(f, "secret({:?})", name),
+			Self::Credential(name) => write!(f, "credential({:?})", name),
+		}
+	}
+}
+
+impl Secret {
+	/// Looks up the value of this secret
+	pub fn resolve(&self) -> Result<String> {
+		match self {
+			Self::Env(name) => std::env::var(name).with_context(|| format!("could not find ${} environment variable", name)),
+			Self::Keychain(name) => Self::keychain(name),
+			Self::Credential(name) => match settings::get().credentials.get(name) {
+				Some(Self::Credential(_)) => bail!("credential {:?} refers to another credential", name),
+				Some(secret) => secret
+					.resolve()
+					.with_context(|| format!("could not resolve credential {:?}", name)),
+				None => bail!("could not find credential {:?} under [credentials] in the settings", name),
+			},
+		}
+	}
+
+	fn keychain(name: &str) -> Result<String> {
+		let mut command = if cfg!(target_os = "macos") {
+			let mut command = Command::new("security");
+			command.args(["find-generic-password", "-s", PROJECT_NAME, "-a", name, "-w"]);
+			command
+		} else if cfg!(target_os = "linux") {
+			let mut command = Command::new("secret-tool");
+			command.args(["lookup", "service", PROJECT_NAME, "account", name]);
+			command
+		} else {
+			bail!("keychain lookups are not supported on this platform, use env(\"{}\") instead", name)
+		};
+		let output = command.output().context("could not query the keychain")?;
+		if !output.status.success() || output.stdout.is_empty() {
+			bail!("could not find secret {:?} in the keychain (service: {})", name, PROJECT_NAME)
+		}
+		let value = String::from_utf8(output.stdout).context("secret is not valid UTF-8")?;
+		Ok(value.trim_end_matches('\n').to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_references() {
+		assert_eq!(Secret::from_str("env(\"SMTP_PASSWORD\")").unwrap(), Secret::Env("SMTP_PASSWORD".into()));
+		assert_eq!(Secret::from_str(" secret( \"s3\" ) ").unwrap(), Secret::Keychain("s3".into()));
+		assert!(Secret::from_str("hunter2").is_err());
+		assert_eq!(Secret::from_str("credential(\"nas\")").unwrap(), Secret::Credential("nas".into()));
+		assert!(Secret::from_str("file(\"token\")").is_err());
+	}
+
+	#[test]
+	fn resolve_env() {
+		std::env::set_var("ORGANIZE_TEST_SECRET", "hunter2");
+		assert_eq!(Secret::Env("ORGANIZE_TEST_SECRET".into()).resolve().unwrap(), "hunter2");
+		std::env::remove_var("ORGANIZE_TEST_SECRET");
+		assert!(Secret::Env("ORGANIZE_TEST_SECRET".into()).resolve().is_err());
+		assert_eq!(
+			format!("{:?}", Secret::Env("ORGANIZE_TEST_SECRET".into())),
+			"env(\"ORGANIZE_TEST_SECRET\")"
+		);
+	}
+}
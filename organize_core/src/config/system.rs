@@ -0,0 +1,184 @@
+use std::{
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+	config::{options::Options, ConfigBuilder},
+	utils::DefaultOpt,
+	PROJECT_NAME,
+};
+
+/// Where administrators put the rules every user of the machine gets
+pub fn path() -> PathBuf {
+	std::env::var_os("ORGANIZE_SYSTEM_CONFIG").map_or_else(|| Path::new("/etc").join(PROJECT_NAME).join("config.toml"), PathBuf::from)
+}
+
+/// Merges the system-wide config, if there is one, into the user's config at `path`
+pub fn apply<T: AsRef<Path>>(user: ConfigBuilder, path: T) -> Result<ConfigBuilder> {
+	let system = self::path();
+	if !system.exists() || path.as_ref() == system {
+		return Ok(user);
+	}
+	let system = ConfigBuilder::parse(&system).with_context(|| format!("could not parse the system config ({})", system.display()))?;
+	Ok(merge(system, user))
+}
+
+/// Combines the system config with a user's.
+///
+/// The user's defaults take precedence over the system's, and a user rule replaces the system rule with the same id.
+/// Rules marked as `locked` can't be replaced, and they only see the system defaults, so users can't change their behavior.
+/// For the same reason, users can add locations and constants but not redefine the system's.
+pub fn merge(system: ConfigBuilder, user: ConfigBuilder) -> ConfigBuilder {
+	let baseline = system.local_defaults.or(&Options::default_some());
+	let mut rules = Vec::with_capacity(system.rules.len() + user.rules.len());
+	for mut rule in system.rules {
+		if rule.locked {
+			rule.options = rule.options.or(&baseline);
+		} else if let Some(id) = &rule.id {
+			if user.rules.iter().any(|user_rule| user_rule.id.as_ref() == Some(id)) {
+				continue;
+			}
+		}
+		rules.push(rule);
+	}
+	for rule in user.rules {
+		let locked = rules
+			.iter()
+			.any(|system_rule| system_rule.locked && rule.id.is_some() && system_rule.id == rule.id);
+		match locked {
+			true => log::warn!(
+				"rule `{}` is locked by the system config, ignoring the user's version",
+				rule.id.as_deref().unwrap_or_default()
+			),
+			false => rules.push(rule),
+		}
+	}
+	ConfigBuilder {
+		version: user.version,
+		locations: definitions(system.locations, user.locations, "location"),
+		constants: definitions(system.constants, user.constants, "constant"),
+		rules,
+		local_defaults: user.local_defaults.or(&system.local_defaults),
+		global_defaults: user.global_defaults,
+		journal: user.journal.or(&system.journal),
+		trash: user.trash.or(system.trash),
+		elevation: user.elevation.or(system.elevation),
+		messages: user.messages.or(&system.messages),
+	}
+}
+
+/// The system's definitions, and the user's that don't replace one of them.
+/// Locked rules resolve `@location` and `{const.name}` like any other rule, so letting users redefine them would let them
+/// point those rules somewhere else.
+fn definitions<T>(mut system: BTreeMap<String, T>, user: BTreeMap<String, T>, kind: &str) -> BTreeMap<String, T> {
+	for (name, definition) in user {
+		match system.contains_key(&name) {
+			true => log::warn!("{} `{}` is defined by the system config, ignoring the user's definition", kind, name),
+			false => {
+				system.insert(name, definition);
+			}
+		}
+	}
+	system
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn builder(content: &str) -> ConfigBuilder {
+		toml::from_str(content).unwrap()
+	}
+
+	#[test]
+	fn user_overrides_unlocked_rules() {
+		let system = builder(
+			r#"
+[defaults]
+hidden_files = true
+watch = false
+
+[[rules]]
+id = "tmp"
+locked = true
+actions = []
+filters = []
+
+[[rules]]
+id = "downloads"
+actions = []
+filters = []
+"#,
+		);
+		let user = builder(
+			r#"
+[defaults]
+watch = true
+
+[[rules]]
+id = "tmp"
+actions = [{ type = "echo", message = "{filename}" }]
+filters = []
+
+[[rules]]
+id = "downloads"
+actions = [{ type = "echo", message = "{filename}" }]
+filters = []
+"#,
+		);
+		let merged = merge(system, user);
+		assert_eq!(merged.rules.len(), 2);
+		assert!(merged.rules[0].locked && merged.rules[0].actions.is_empty());
+		assert_eq!(merged.rules[1].id.as_deref(), Some("downloads"));
+		assert!(!merged.rules[1].actions.is_empty());
+		assert_eq!(merged.local_defaults.watch, Some(true));
+		assert_eq!(merged.local_defaults.hidden_files, Some(true));
+		// locked rules ignore the user's defaults
+		assert_eq!(merged.rules[0].options.watch, Some(false));
+		assert_eq!(merged.rules[0].options.hidden_files, Some(true));
+	}
+
+	#[test]
+	fn locked_rules_keep_system_definitions() {
+		let system = builder(
+			r#"
+[locations.spool]
+path = "/var/spool/scans"
+
+[constants]
+archive = "/srv/archive"
+
+[[rules]]
+id = "scans"
+locked = true
+folders = []
+actions = [{ type = "move", to = "@spool/{const.archive}/" }]
+filters = []
+"#,
+		);
+		let user = builder(
+			r#"
+rules = []
+
+[locations.spool]
+path = "/home/user"
+
+[locations.inbox]
+path = "/home/user/inbox"
+
+[constants]
+archive = "/tmp"
+"#,
+		);
+		let merged = merge(system, user);
+		assert_eq!(
+			merged.locations["spool"],
+			builder("rules = []\n[locations.spool]\npath = \"/var/spool/scans\"").locations["spool"]
+		);
+		assert!(merged.locations.contains_key("inbox"));
+		assert_eq!(merged.constants["archive"], "/srv/archive");
+	}
+}
@@ -0,0 +1,218 @@
+use std::{
+	ffi::OsStr,
+	fs,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use walkdir::WalkDir;
+
+use crate::{
+	config::{
+		actions::delete::Trash,
+		filters::{deserialize_duration, deserialize_size},
+	},
+	path::Expand,
+};
+
+/// Keeps the trash from growing forever. Set under `[trash]` in the config, it's applied after every `run`
+/// and by `organize purge-trash`, which can be scheduled on its own.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct TrashRetention {
+	/// remove the items that were trashed longer ago than this, e.g. `30d`
+	#[serde(default, deserialize_with = "deserialize_duration")]
+	pub older_than: Option<Duration>,
+	/// then remove the oldest items until each directory is smaller than this, e.g. `5GB`
+	#[serde(default, deserialize_with = "deserialize_size")]
+	pub max_size: Option<u64>,
+	/// directories to prune besides organize's own trash, such as the backups made by scripts.
+	/// Their items are aged from when organize first sees them, which is recorded in a `.trashed` directory inside them.
+	#[serde(default)]
+	pub dirs: Vec<PathBuf>,
+	/// also remove the items older than `older_than` from the system trash (Linux and Windows only)
+	#[serde(default)]
+	pub system: bool,
+}
+
+/// The directory, inside each pruned one, that records when every item was put there
+const RECORDS: &str = ".trashed";
+
+/// Records that `name` was put in the trash directory `dir` just now.
+/// The retention goes by this time rather than by the item's own modification time, which copies and backups keep.
+pub(crate) fn record(dir: &Path, name: &OsStr) -> Result<()> {
+	let records = dir.join(RECORDS);
+	fs::create_dir_all(&records).with_context(|| format!("could not create {}", records.display()))?;
+	let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+	fs::write(records.join(name), now.to_string()).with_context(|| format!("could not record when {} was trashed", name.to_string_lossy()))
+}
+
+/// When `name` was put in `dir`. Items that were never recorded, like the backups written by scripts in `dirs`,
+/// are recorded when they're first seen, so they're never removed before they've been kept for `older_than`.
+fn trashed(dir: &Path, name: &OsStr) -> Option<SystemTime> {
+	let recorded = fs::read_to_string(dir.join(RECORDS).join(name))
+		.ok()
+		.and_then(|secs| secs.trim().parse().ok())
+		.map(|secs| UNIX_EPOCH + Duration::from_secs(secs));
+	match recorded {
+		Some(trashed) => Some(trashed),
+		None => match record(dir, name) {
+			Ok(()) => Some(SystemTime::now()),
+			Err(e) => {
+				log::warn!("{:?}", e);
+				None
+			}
+		},
+	}
+}
+
+struct Item {
+	path: PathBuf,
+	trashed: SystemTime,
+	size: u64,
+}
+
+impl TrashRetention {
+	/// Removes everything outside the limits and returns what was removed
+	pub fn purge(&self) -> Result<Vec<PathBuf>> {
+		let mut purged = Vec::new();
+		purged.extend(self.purge_dir(&Trash::dir()?)?);
+		for dir in self.dirs.iter() {
+			let dir = dir.clone().expand_user()?.expand_vars()?;
+			purged.extend(
+				self.purge_dir(&dir)
+					.with_context(|| format!("could not prune {}", dir.display()))?,
+			);
+		}
+		if self.system {
+			purged.extend(self.purge_system()?);
+		}
+		Ok(purged)
+	}
+
+	fn purge_dir(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+		if !dir.is_dir() {
+			return Ok(Vec::new());
+		}
+		let mut items = fs::read_dir(dir)?
+			.filter_map(|entry| entry.ok())
+			.filter(|entry| entry.file_name() != RECORDS)
+			.filter_map(|entry| {
+				let metadata = entry.metadata().ok()?;
+				let size = match metadata.is_dir() {
+					true => WalkDir::new(entry.path())
+						.into_iter()
+						.filter_map(|entry| entry.ok()?.metadata().ok())
+						.filter(|metadata| metadata.is_file())
+						.map(|metadata| metadata.len())
+						.sum(),
+					false => metadata.len(),
+				};
+				Some(Item {
+					path: entry.path(),
+					trashed: trashed(dir, &entry.file_name())?,
+					size,
+				})
+			})
+			.collect::<Vec<_>>();
+		// newest first, so the oldest items are the ones left over when the size limit is reached
+		items.sort_by_key(|item| std::cmp::Reverse(item.trashed));
+
+		let cutoff = self.older_than.and_then(|age| SystemTime::now().checked_sub(age));
+		let (mut total, mut full) = (0, false);
+		let mut purged = Vec::new();
+		for item in items {
+			let expired = cutoff.is_some_and(|cutoff| item.trashed < cutoff);
+			full = full || self.max_size.is_some_and(|max_size| total + item.size > max_size);
+			if !expired && !full {
+				total += item.size;
+			} else {
+				let removed = match item.path.is_dir() {
+					true => fs::remove_dir_all(&item.path),
+					false => fs::remove_file(&item.path),
+				};
+				removed.with_context(|| format!("could not remove {}", item.path.display()))?;
+				if let Some(name) = item.path.file_name() {
+					let _ = fs::remove_file(dir.join(RECORDS).join(name));
+				}
+				purged.push(item.path);
+			}
+		}
+		Ok(purged)
+	}
+
+	#[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+	fn purge_system(&self) -> Result<Vec<PathBuf>> {
+		use trash::os_limited;
+
+		let cutoff = match self.older_than.and_then(|age| SystemTime::now().checked_sub(age)) {
+			Some(cutoff) => cutoff.duration_since(SystemTime::UNIX_EPOCH)?.as_secs() as i64,
+			None => return Ok(Vec::new()),
+		};
+		let expired = os_limited::list()
+			.context("could not list the system trash")?
+			.into_iter()
+			.filter(|item| item.time_deleted < cutoff)
+			.collect::<Vec<_>>();
+		let paths = expired.iter().map(|item| item.original_path()).collect();
+		os_limited::purge_all(expired).context("could not empty the system trash")?;
+		Ok(paths)
+	}
+
+	#[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos")))))]
+	fn purge_system(&self) -> Result<Vec<PathBuf>> {
+		log::warn!("the system trash can't be pruned on this platform");
+		Ok(Vec::new())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn trash(dir: &Path, name: &str, size: usize, age: u64) {
+		fs::write(dir.join(name), vec![0u8; size]).unwrap();
+		let time = SystemTime::now() - Duration::from_secs(age);
+		fs::create_dir_all(dir.join(RECORDS)).unwrap();
+		fs::write(
+			dir.join(RECORDS).join(name),
+			time.duration_since(UNIX_EPOCH).unwrap().as_secs().to_string(),
+		)
+		.unwrap();
+	}
+
+	#[test]
+	fn purge_old_then_oversized() {
+		let dir = tempfile::tempdir().unwrap();
+		trash(dir.path(), "ancient", 1, 100_000);
+		trash(dir.path(), "old", 600, 3000);
+		trash(dir.path(), "recent", 600, 2000);
+		trash(dir.path(), "new", 300, 1000);
+		let retention: TrashRetention = toml::from_str("older_than = \"1d\"\nmax_size = \"1KB\"").unwrap();
+		let mut purged = retention.purge_dir(dir.path()).unwrap();
+		purged.sort();
+		assert_eq!(purged, vec![dir.path().join("ancient"), dir.path().join("old")]);
+		assert!(dir.path().join("recent").exists() && dir.path().join("new").exists());
+	}
+
+	#[test]
+	fn go_by_when_items_were_trashed() {
+		let dir = tempfile::tempdir().unwrap();
+		// a copy or a backup that kept the modification time of an old file
+		let path = dir.path().join("old-backup.tar");
+		fs::write(&path, "").unwrap();
+		let modified = SystemTime::now() - Duration::from_secs(100_000);
+		fs::File::options()
+			.write(true)
+			.open(&path)
+			.unwrap()
+			.set_modified(modified)
+			.unwrap();
+		let retention: TrashRetention = toml::from_str("older_than = \"1d\"").unwrap();
+		assert_eq!(retention.purge_dir(dir.path()).unwrap(), Vec::<PathBuf>::new());
+		assert!(path.exists());
+		assert!(dir.path().join(RECORDS).join("old-backup.tar").exists());
+	}
+}
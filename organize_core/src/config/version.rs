@@ -0,0 +1,100 @@
+use std::{
+	path::Path,
+	sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{bail, Result};
+
+/// The config schema understood by this build
+pub const CONFIG_VERSION: u32 = 2;
+
+static STRICT: AtomicBool = AtomicBool::new(false);
+
+/// Refuse configs written for another schema version instead of just warning about them (`--strict`)
+pub fn set_strict(strict: bool) {
+	STRICT.store(strict, Ordering::Relaxed);
+}
+
+/// Configs without a version are taken to be written for the current one
+pub(crate) fn check(version: Option<u32>, path: &Path) -> Result<()> {
+	let problem = match problem(version, path) {
+		Some(problem) => problem,
+		None => return Ok(()),
+	};
+	if STRICT.load(Ordering::Relaxed) {
+		bail!(problem);
+	}
+	log::warn!("{}", problem);
+	Ok(())
+}
+
+/// Why a config written for `version` might not be read as intended, if it might not
+fn problem(version: Option<u32>, path: &Path) -> Option<String> {
+	Some(match version? {
+		CONFIG_VERSION => return None,
+		version if version > CONFIG_VERSION => format!(
+			"{} was written for config version {}, but this version of organize only understands up to version {}, some settings might be misread",
+			path.display(),
+			version,
+			CONFIG_VERSION
+		),
+		version => format!(
+			"{} was written for config version {}, run `organize migrate` to update it to version {}",
+			path.display(),
+			version,
+			CONFIG_VERSION
+		),
+	})
+}
+
+/// Sets the version of the config in `content` to the current one, leaving everything else untouched
+pub fn migrate(content: &str) -> String {
+	let line = format!("version = {}", CONFIG_VERSION);
+	let mut lines: Vec<String> = content.lines().map(String::from).collect();
+	// only top-level keys can set the version, and those come before the first table
+	let top_level = lines
+		.iter()
+		.position(|l| l.trim_start().starts_with('['))
+		.unwrap_or(lines.len());
+	let existing = lines[..top_level].iter().position(|l| {
+		let l = l.trim_start();
+		l.strip_prefix("version").is_some_and(|rest| rest.trim_start().starts_with('='))
+	});
+	match existing {
+		Some(i) => lines[i] = line,
+		None => lines.insert(0, line),
+	}
+	let mut migrated = lines.join("\n");
+	if content.ends_with('\n') || content.is_empty() {
+		migrated.push('\n');
+	}
+	migrated
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn migrate_adds_version() {
+		let migrated = migrate("[[rules]]\nversion = 1\n");
+		assert_eq!(migrated, format!("version = {}\n[[rules]]\nversion = 1\n", CONFIG_VERSION));
+	}
+
+	#[test]
+	fn migrate_bumps_version() {
+		let migrated = migrate("# my config\nversion = 1\n\n[[rules]]\n");
+		assert_eq!(migrated, format!("# my config\nversion = {}\n\n[[rules]]\n", CONFIG_VERSION));
+	}
+
+	#[test]
+	fn check_versions() {
+		let path = Path::new("config.toml");
+		assert!(check(Some(CONFIG_VERSION), path).is_ok());
+		assert!(check(None, path).is_ok());
+		assert_eq!(problem(None, path), None);
+		assert_eq!(problem(Some(CONFIG_VERSION), path), None);
+		assert!(problem(Some(1), path).is_some());
+		assert!(problem(Some(CONFIG_VERSION + 1), path).is_some());
+	}
+}
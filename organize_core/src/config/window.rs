@@ -0,0 +1,179 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use serde::{de::Error, Deserialize, Deserializer};
+
+/// When a rule is allowed to run, e.g. `active = "22:00-06:00"`, or `active = { hours = "22:00-06:00", days = ["mon-fri"] }`.
+/// Outside of it the rule waits like a deferred one, and the daemon catches up on its locations once it opens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Window {
+	pub hours: Option<Hours>,
+	/// the days the window opens on, every day if it's empty
+	pub days: Vec<Weekday>,
+}
+
+/// A range of hours, which goes past midnight if it ends before it starts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hours {
+	pub start: NaiveTime,
+	pub end: NaiveTime,
+}
+
+impl Window {
+	/// Whether the rule can run at `now`. A window that goes past midnight belongs to the day it opened on.
+	pub fn contains(&self, now: NaiveDateTime) -> bool {
+		let (time, today) = (now.time(), now.weekday());
+		let opened_on = match self.hours {
+			Some(Hours { start, end }) if start <= end && (start..end).contains(&time) => today,
+			Some(Hours { start, end }) if start > end && time >= start => today,
+			Some(Hours { start, end }) if start > end && time < end => today.pred(),
+			Some(_) => return false,
+			None => today,
+		};
+		self.days.is_empty() || self.days.contains(&opened_on)
+	}
+}
+
+impl FromStr for Hours {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (start, end) = s
+			.split_once('-')
+			.ok_or_else(|| anyhow!("expected hours like \"22:00-06:00\", found {:?}", s))?;
+		let time = |time: &str| NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|e| anyhow!("invalid time {:?}: {}", time.trim(), e));
+		let hours = Self {
+			start: time(start)?,
+			end: time(end)?,
+		};
+		if hours.start == hours.end {
+			bail!("{:?} is an empty window", s);
+		}
+		Ok(hours)
+	}
+}
+
+/// `mon`, or a range of days like `mon-fri`
+fn days(s: &str) -> Result<Vec<Weekday>> {
+	let day = |day: &str| Weekday::from_str(day.trim()).map_err(|_| anyhow!("unknown day {:?}", day.trim()));
+	match s.split_once('-') {
+		Some((first, last)) => {
+			let (mut day, last) = (day(first)?, day(last)?);
+			let mut days = vec![day];
+			while day != last {
+				day = day.succ();
+				days.push(day);
+			}
+			Ok(days)
+		}
+		None => Ok(vec![day(s)?]),
+	}
+}
+
+impl<'de> Deserialize<'de> for Window {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+	where
+		D: Deserializer<'de>,
+	{
+		#[derive(Deserialize)]
+		#[serde(deny_unknown_fields)]
+		struct Table {
+			#[serde(default)]
+			hours: Option<String>,
+			#[serde(default)]
+			days: Vec<String>,
+		}
+
+		#[derive(Deserialize)]
+		#[serde(untagged)]
+		enum Raw {
+			Hours(String),
+			Table(Table),
+		}
+
+		let table = match Raw::deserialize(deserializer)? {
+			Raw::Hours(hours) => Table {
+				hours: Some(hours),
+				days: Vec::new(),
+			},
+			Raw::Table(table) => table,
+		};
+		let mut window = Self {
+			hours: table
+				.hours
+				.as_deref()
+				.map(Hours::from_str)
+				.transpose()
+				.map_err(D::Error::custom)?,
+			days: Vec::new(),
+		};
+		for range in table.days.iter() {
+			for day in days(range).map_err(D::Error::custom)? {
+				if !window.days.contains(&day) {
+					window.days.push(day);
+				}
+			}
+		}
+		Ok(window)
+	}
+}
+
+impl fmt::Display for Window {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let days = self
+			.days
+			.iter()
+			.map(|day| day.to_string().to_lowercase())
+			.collect::<Vec<_>>()
+			.join(", ");
+		match (&self.hours, days.is_empty()) {
+			(Some(hours), true) => write!(f, "{}-{}", hours.start.format("%H:%M"), hours.end.format("%H:%M")),
+			(Some(hours), false) => write!(f, "{}-{} on {}", hours.start.format("%H:%M"), hours.end.format("%H:%M"), days),
+			(None, _) => write!(f, "on {}", days),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use chrono::NaiveDate;
+
+	use super::*;
+
+	#[derive(Deserialize)]
+	struct Rule {
+		active: Window,
+	}
+
+	fn at(day: u32, time: &str) -> NaiveDateTime {
+		// 2024-01-01 was a monday
+		NaiveDate::from_ymd_opt(2024, 1, day)
+			.unwrap()
+			.and_time(NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+	}
+
+	#[test]
+	fn overnight_window() {
+		let rule: Rule = toml::from_str("active = \"22:00-06:00\"").unwrap();
+		assert!(rule.active.contains(at(1, "23:30")));
+		assert!(rule.active.contains(at(2, "05:59")));
+		assert!(!rule.active.contains(at(2, "06:00")));
+		assert!(!rule.active.contains(at(2, "12:00")));
+		assert_eq!(rule.active.to_string(), "22:00-06:00");
+	}
+
+	#[test]
+	fn window_on_some_days() {
+		let rule: Rule = toml::from_str("active = { hours = \"22:00-06:00\", days = [\"fri-sat\"] }").unwrap();
+		assert!(rule.active.contains(at(5, "23:00")));
+		// the window that opened on saturday night
+		assert!(rule.active.contains(at(7, "01:00")));
+		assert!(!rule.active.contains(at(7, "23:00")));
+		let weekends: Rule = toml::from_str("active = { days = [\"sat\", \"sun\"] }").unwrap();
+		assert!(weekends.active.contains(at(6, "12:00")));
+		assert!(!weekends.active.contains(at(8, "12:00")));
+		assert!(toml::from_str::<Rule>("active = \"22:00\"").is_err());
+		assert!(toml::from_str::<Rule>("active = { days = [\"someday\"] }").is_err());
+	}
+}
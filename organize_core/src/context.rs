@@ -0,0 +1,130 @@
+use std::{
+	cell::RefCell,
+	collections::HashMap,
+	path::{Path, PathBuf},
+};
+
+use crate::variables::Variable;
+
+thread_local! {
+	// the file currently being processed by this thread, if any
+	static CURRENT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+/// What we know about a file before any rule touched it.
+/// It's carried along while the file moves between locations, so that later rules can still refer to where it came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Context {
+	pub original: PathBuf,
+	/// the location the file was found in
+	pub root: Option<PathBuf>,
+	/// what the filters computed while matching the file
+	pub variables: HashMap<Variable, String>,
+	/// the rule whose actions are being carried out on the file
+	pub rule: Option<String>,
+	/// what a classifier said about the file, available to templates as `{label.<name>}`
+	pub labels: HashMap<String, String>,
+}
+
+impl Context {
+	/// Makes `path` the current file of this thread until the returned guard is dropped.
+	/// The original path is resolved right away, because it might not exist anymore by the time a template asks for it.
+	pub fn enter<T: AsRef<Path>>(path: T, root: Option<PathBuf>) -> ContextGuard {
+		let path = path.as_ref();
+		let context = Context {
+			original: path.canonicalize().unwrap_or_else(|_| path.to_path_buf()),
+			root,
+			variables: HashMap::new(),
+			rule: None,
+			labels: HashMap::new(),
+		};
+		let previous = CURRENT.with(|current| current.replace(Some(context)));
+		ContextGuard { previous }
+	}
+
+	/// The original path of the file currently being processed by this thread
+	pub fn original() -> Option<PathBuf> {
+		CURRENT.with(|current| current.borrow().as_ref().map(|context| context.original.clone()))
+	}
+
+	/// The location the file currently being processed by this thread was found in
+	pub fn root() -> Option<PathBuf> {
+		CURRENT.with(|current| current.borrow().as_ref().and_then(|context| context.root.clone()))
+	}
+
+	/// Stores a value computed for the current file, if there is one
+	pub fn set_variable<T: Into<String>>(variable: Variable, value: T) {
+		CURRENT.with(|current| {
+			if let Some(context) = current.borrow_mut().as_mut() {
+				context.variables.insert(variable, value.into());
+			}
+		})
+	}
+
+	/// Marks the actions carried out on the current file from now on as `rule`'s, so that the journal can tell who did what
+	pub fn set_rule<T: Into<String>>(rule: T) {
+		CURRENT.with(|current| {
+			if let Some(context) = current.borrow_mut().as_mut() {
+				context.rule = Some(rule.into());
+			}
+		})
+	}
+
+	/// The rule whose actions are being carried out on the current file
+	pub fn rule() -> Option<String> {
+		CURRENT.with(|current| current.borrow().as_ref().and_then(|context| context.rule.clone()))
+	}
+
+	/// Stores a label a classifier gave to the current file, if there is one
+	pub fn set_label<K: Into<String>, V: Into<String>>(name: K, value: V) {
+		CURRENT.with(|current| {
+			if let Some(context) = current.borrow_mut().as_mut() {
+				context.labels.insert(name.into(), value.into());
+			}
+		})
+	}
+
+	pub fn label(name: &str) -> Option<String> {
+		CURRENT.with(|current| current.borrow().as_ref().and_then(|context| context.labels.get(name).cloned()))
+	}
+
+	pub fn variable(variable: Variable) -> Option<String> {
+		CURRENT.with(|current| {
+			current
+				.borrow()
+				.as_ref()
+				.and_then(|context| context.variables.get(&variable).cloned())
+		})
+	}
+}
+
+/// Restores the previous context when dropped
+pub struct ContextGuard {
+	previous: Option<Context>,
+}
+
+impl Drop for ContextGuard {
+	fn drop(&mut self) {
+		CURRENT.with(|current| *current.borrow_mut() = self.previous.take());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn restore_on_drop() {
+		assert_eq!(Context::original(), None);
+		{
+			let _outer = Context::enter("/outer/file.txt", Some(PathBuf::from("/outer")));
+			{
+				let _inner = Context::enter("/inner/file.txt", None);
+				assert_eq!(Context::original(), Some(PathBuf::from("/inner/file.txt")));
+			}
+			assert_eq!(Context::original(), Some(PathBuf::from("/outer/file.txt")));
+			assert_eq!(Context::root(), Some(PathBuf::from("/outer")));
+		}
+		assert_eq!(Context::original(), None);
+	}
+}
@@ -0,0 +1,84 @@
+use std::{
+	path::{Path, PathBuf},
+	sync::atomic::{AtomicBool, Ordering},
+	time::Duration,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{journal, layout};
+
+/// How often a paused run checks whether it was resumed
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// whether this process is waiting in `checkpoint`, so that it's only logged once
+static WAITING: AtomicBool = AtomicBool::new(false);
+
+/// Runs are paused for as long as this file exists, so that any process can pause them (`organize pause`, a GUI...),
+/// and a paused run stays paused if whatever paused it goes away
+fn marker() -> Result<PathBuf> {
+	Ok(layout::state_dir()?.join("paused"))
+}
+
+/// Pauses every run and watcher before the next file they handle
+pub fn pause() -> Result<()> {
+	pause_at(&marker()?)
+}
+
+fn pause_at(marker: &Path) -> Result<()> {
+	if let Some(parent) = marker.parent() {
+		std::fs::create_dir_all(parent).with_context(|| format!("could not create {}", parent.display()))?;
+	}
+	std::fs::write(marker, std::process::id().to_string()).with_context(|| format!("could not create {}", marker.display()))
+}
+
+/// Lets paused runs and watchers carry on
+pub fn resume() -> Result<()> {
+	resume_at(&marker()?)
+}
+
+fn resume_at(marker: &Path) -> Result<()> {
+	match std::fs::remove_file(marker) {
+		Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).with_context(|| format!("could not remove {}", marker.display())),
+		_ => Ok(()),
+	}
+}
+
+pub fn is_paused() -> bool {
+	marker().is_ok_and(|marker| marker.exists())
+}
+
+/// Called between operations: waits there for as long as runs are paused.
+/// The journal is written first, so that what was done until then is safe even if the process is stopped while paused.
+pub fn checkpoint() {
+	if !is_paused() {
+		return;
+	}
+	if !WAITING.swap(true, Ordering::Relaxed) {
+		journal::flush();
+		log::info!("paused, run `organize resume` to carry on");
+	}
+	while is_paused() {
+		std::thread::sleep(POLL_INTERVAL);
+	}
+	if WAITING.swap(false, Ordering::Relaxed) {
+		log::info!("resumed");
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn pause_and_resume() {
+		let dir = tempfile::tempdir().unwrap();
+		let marker = dir.path().join("organize").join("paused");
+		pause_at(&marker).unwrap();
+		assert!(marker.exists());
+		resume_at(&marker).unwrap();
+		assert!(!marker.exists());
+		// resuming what isn't paused is fine
+		resume_at(&marker).unwrap();
+	}
+}
@@ -0,0 +1,422 @@
+use std::{
+	collections::{BTreeMap, HashMap, HashSet},
+	fmt::{Display, Formatter},
+	path::{Path, PathBuf},
+	str::FromStr,
+	time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Local, TimeZone};
+use rusqlite::{params, Connection};
+use strum_macros::{Display, EnumString};
+
+use crate::{
+	config::actions::ActionType,
+	journal::{self, Entry},
+	with_db,
+};
+
+/// Only the actions of this long ago are checked for corrections, since a file moved after that was probably moved for other reasons
+const WINDOW: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// How the user went back on an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Kind {
+	/// with `organize history undo`
+	Undone,
+	/// the file was put back where it was found
+	Reverted,
+	/// the file was moved somewhere else
+	Moved,
+	/// the file was removed, or moved somewhere it couldn't be found
+	Removed,
+}
+
+/// An action that the user went back on after it was carried out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Correction {
+	pub time: DateTime<Local>,
+	pub kind: Kind,
+	/// the rule that carried out the action
+	pub rule: Option<String>,
+	pub action: ActionType,
+	pub from: PathBuf,
+	pub to: Option<PathBuf>,
+	/// where the user put the file instead, if it was found
+	pub moved_to: Option<PathBuf>,
+}
+
+impl Correction {
+	pub fn new(kind: Kind, entry: &Entry, moved_to: Option<PathBuf>) -> Self {
+		Self {
+			time: Local::now(),
+			kind,
+			rule: entry.rule.clone(),
+			action: entry.action,
+			from: entry.from.clone(),
+			to: entry.to.clone(),
+			moved_to,
+		}
+	}
+}
+
+pub(crate) fn init(conn: &Connection) -> Result<()> {
+	journal::init(conn)?;
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS corrections (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			time INTEGER NOT NULL,
+			kind TEXT NOT NULL,
+			rule TEXT,
+			action TEXT NOT NULL,
+			source TEXT NOT NULL,
+			destination TEXT,
+			moved_to TEXT,
+			entry INTEGER UNIQUE
+		)",
+		[],
+	)
+	.context("could not create the corrections table")?;
+	Ok(())
+}
+
+/// Records `correction`. `entry` is the id of the journal entry it corrects, if it's still in the journal.
+pub(crate) fn insert(conn: &Connection, correction: &Correction, entry: Option<i64>) -> Result<()> {
+	let path = |path: &Option<PathBuf>| path.as_ref().map(|path| path.to_string_lossy().to_string());
+	conn.execute(
+		"INSERT OR IGNORE INTO corrections (time, kind, rule, action, source, destination, moved_to, entry)
+		VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+		params![
+			correction.time.timestamp(),
+			correction.kind.to_string(),
+			correction.rule,
+			correction.action.to_string(),
+			correction.from.to_string_lossy(),
+			path(&correction.to),
+			path(&correction.moved_to),
+			entry
+		],
+	)
+	.context("could not record a correction")?;
+	Ok(())
+}
+
+/// Every correction recorded so far, oldest first
+pub fn all() -> Result<Vec<Correction>> {
+	with_db(init, read)
+}
+
+fn read(conn: &Connection) -> Result<Vec<Correction>> {
+	let mut statement = conn.prepare("SELECT time, kind, rule, action, source, destination, moved_to FROM corrections ORDER BY id")?;
+	let rows = statement
+		.query_map([], |row| {
+			Ok((
+				row.get::<_, i64>(0)?,
+				row.get::<_, String>(1)?,
+				row.get::<_, Option<String>>(2)?,
+				row.get::<_, String>(3)?,
+				row.get::<_, String>(4)?,
+				row.get::<_, Option<String>>(5)?,
+				row.get::<_, Option<String>>(6)?,
+			))
+		})?
+		.collect::<rusqlite::Result<Vec<_>>>()?;
+	rows.into_iter()
+		.map(|(time, kind, rule, action, from, to, moved_to)| {
+			Ok(Correction {
+				time: Local.timestamp_opt(time, 0).single().unwrap_or_else(Local::now),
+				kind: Kind::from_str(&kind).map_err(|_| anyhow!("unknown correction `{}`", kind))?,
+				rule,
+				action: ActionType::from_str(&action).map_err(|_| anyhow!("unknown action `{}` in the corrections", action))?,
+				from: from.into(),
+				to: to.map(PathBuf::from),
+				moved_to: moved_to.map(PathBuf::from),
+			})
+		})
+		.collect()
+}
+
+/// Compares the recent actions in the journal with what's on disk, recording those whose result the user moved or removed since.
+/// Returns how many new corrections were found.
+pub fn detect() -> Result<usize> {
+	journal::flush();
+	with_db(init, detect_with)
+}
+
+/// Runs [`detect`] before a run or a watch starts, mentioning the report if anything was found
+pub fn check() {
+	match detect() {
+		Ok(0) => {}
+		Ok(found) => log::info!(
+			"{} actions were corrected since they were carried out, see `organize history corrections`",
+			found
+		),
+		Err(e) => log::warn!("could not look for corrected actions: {:?}", e),
+	}
+}
+
+fn detect_with(conn: &Connection) -> Result<usize> {
+	let checked = conn
+		.prepare("SELECT entry FROM corrections WHERE entry IS NOT NULL")?
+		.query_map([], |row| row.get::<_, i64>(0))?
+		.collect::<rusqlite::Result<HashSet<_>>>()?;
+	let entries = journal::read(conn)?;
+	// a destination that a later action picked up again was moved by organize itself
+	let picked_up = entries
+		.iter()
+		.enumerate()
+		.map(|(i, (_, entry))| (entry.from.as_path(), i))
+		.collect::<HashMap<_, _>>();
+	let cutoff = Local::now().timestamp() - WINDOW.as_secs() as i64;
+	let mut found = 0;
+	for (i, (id, entry)) in entries.iter().enumerate() {
+		let to = match &entry.to {
+			// actions journaled before they were attributed to rules can't tell anything about them,
+			// and thumbnails are moved and removed by organize along with their videos
+			Some(to)
+				if entry.rule.is_some() && entry.action != ActionType::Thumbnail && !checked.contains(id) && entry.time.timestamp() >= cutoff =>
+			{
+				to
+			}
+			_ => continue,
+		};
+		if to.symlink_metadata().is_ok() || picked_up.get(to.as_path()).is_some_and(|later| *later > i) {
+			continue;
+		}
+		let (kind, moved_to) = match (entry.action, find(entry, to)) {
+			(ActionType::Move, Some(found)) if found == entry.from => (Kind::Reverted, Some(found)),
+			(_, Some(found)) => (Kind::Moved, Some(found)),
+			(_, None) => (Kind::Removed, None),
+		};
+		log::debug!(
+			"({}) {} after {} by {}",
+			kind,
+			to.display(),
+			entry.action,
+			entry.rule.as_deref().unwrap_or("an unknown rule")
+		);
+		insert(conn, &Correction::new(kind, entry, moved_to), Some(*id))?;
+		found += 1;
+	}
+	Ok(found)
+}
+
+/// Where the user put the result of `entry` instead of `to`: back where it was, or next to `to` (in a sibling folder or one level up)
+fn find(entry: &Entry, to: &Path) -> Option<PathBuf> {
+	if entry.action == ActionType::Move && entry.from.exists() {
+		return Some(entry.from.clone());
+	}
+	let name = to.file_name()?;
+	let dir = to.parent()?;
+	let up = dir.parent()?;
+	std::iter::once(up.join(name))
+		.chain(
+			std::fs::read_dir(up)
+				.ok()?
+				.filter_map(|entry| entry.ok())
+				.map(|entry| entry.path())
+				.filter(|sibling| sibling != dir && sibling.is_dir())
+				.map(|sibling| sibling.join(name)),
+		)
+		.find(|candidate| candidate.is_file())
+}
+
+/// How often the actions of a rule were corrected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleReport {
+	pub rule: String,
+	/// how many of its actions are known, in the journal or undone
+	pub actions: usize,
+	pub corrections: BTreeMap<Kind, usize>,
+	/// changes to the rule that would have avoided the corrections
+	pub tweaks: Vec<String>,
+}
+
+impl RuleReport {
+	pub fn corrected(&self) -> usize {
+		self.corrections.values().sum()
+	}
+}
+
+impl Display for RuleReport {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		let kinds = self
+			.corrections
+			.iter()
+			.map(|(kind, count)| format!("{} {}", count, kind))
+			.collect::<Vec<_>>()
+			.join(", ");
+		write!(f, "{}: {} of {} actions corrected ({})", self.rule, self.corrected(), self.actions, kinds)?;
+		for tweak in &self.tweaks {
+			write!(f, "\n  - {}", tweak)?;
+		}
+		Ok(())
+	}
+}
+
+fn extension(path: &Path) -> Option<String> {
+	Some(path.extension()?.to_string_lossy().to_lowercase())
+}
+
+/// Groups `corrections` by the rule that made the corrected actions, the most corrected first
+pub fn report(entries: &[Entry], corrections: &[Correction]) -> Vec<RuleReport> {
+	let mut by_rule: BTreeMap<&str, Vec<&Correction>> = BTreeMap::new();
+	for correction in corrections {
+		if let Some(rule) = &correction.rule {
+			by_rule.entry(rule).or_default().push(correction);
+		}
+	}
+	let mut reports = by_rule
+		.into_iter()
+		.map(|(name, corrections)| {
+			let acted = entries
+				.iter()
+				.filter(|entry| entry.rule.as_deref() == Some(name))
+				.collect::<Vec<_>>();
+			// undone actions are no longer in the journal
+			let undone = corrections.iter().filter(|correction| correction.kind == Kind::Undone).count();
+			let mut counts = BTreeMap::new();
+			for correction in corrections.iter() {
+				*counts.entry(correction.kind).or_default() += 1;
+			}
+			RuleReport {
+				actions: acted.len() + undone,
+				rule: name.to_string(),
+				corrections: counts,
+				tweaks: tweaks(&acted, &corrections),
+			}
+		})
+		.collect::<Vec<_>>();
+	reports.sort_by(|a, b| b.corrected().cmp(&a.corrected()).then_with(|| a.rule.cmp(&b.rule)));
+	reports
+}
+
+/// Changes to a rule that the way its actions were corrected point to
+fn tweaks(acted: &[&Entry], corrections: &[&Correction]) -> Vec<String> {
+	let mut tweaks = Vec::new();
+	let mut corrected: BTreeMap<String, usize> = BTreeMap::new();
+	for extension in corrections.iter().filter_map(|correction| extension(&correction.from)) {
+		*corrected.entry(extension).or_default() += 1;
+	}
+	for (extension, count) in corrected {
+		let has_extension = |path: &Path| self::extension(path).as_deref() == Some(extension.as_str());
+		// undone actions are no longer in the journal, the other corrected ones are
+		let total = acted.iter().filter(|entry| has_extension(&entry.from)).count()
+			+ corrections
+				.iter()
+				.filter(|correction| correction.kind == Kind::Undone && has_extension(&correction.from))
+				.count();
+		if count >= 2 && count * 2 >= total {
+			tweaks.push(format!(
+				"{} of its {} .{} files were corrected, consider leaving them out with a filter",
+				count, total, extension
+			));
+		}
+	}
+	let mut moved_to: BTreeMap<&Path, usize> = BTreeMap::new();
+	for correction in corrections.iter().filter(|correction| correction.kind == Kind::Moved) {
+		if let Some(dir) = correction.moved_to.as_deref().and_then(Path::parent) {
+			*moved_to.entry(dir).or_default() += 1;
+		}
+	}
+	for (dir, count) in moved_to.into_iter().filter(|(_, count)| *count >= 2) {
+		tweaks.push(format!(
+			"{} of its files were moved to {} afterwards, consider sending them there",
+			count,
+			dir.display()
+		));
+	}
+	let reverted = corrections
+		.iter()
+		.filter(|correction| correction.kind == Kind::Reverted)
+		.count();
+	if reverted >= 2 && reverted * 2 >= corrections.len() {
+		tweaks.push(format!(
+			"{} of its files were put back where they were, its filters might be too broad",
+			reverted
+		));
+	}
+	tweaks
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	fn entry(rule: &str, from: &Path, to: &Path) -> Entry {
+		Entry {
+			rule: Some(rule.into()),
+			..Entry::new(ActionType::Move, from.into(), Some(to.into()))
+		}
+	}
+
+	#[test]
+	fn detect_corrections() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = |path: &str| dir.path().join(path);
+		for folder in ["in", "docs/pdf", "docs/taxes", "photos"] {
+			fs::create_dir_all(path(folder)).unwrap();
+		}
+		fs::write(path("docs/pdf/kept.pdf"), "").unwrap();
+		fs::write(path("docs/taxes/moved.pdf"), "").unwrap();
+		fs::write(path("in/reverted.jpg"), "").unwrap();
+		fs::write(path("docs/pdf/again.pdf"), "").unwrap();
+		let conn = Connection::open_in_memory().unwrap();
+		init(&conn).unwrap();
+		for entry in [
+			entry("pdfs", &path("in/kept.pdf"), &path("docs/pdf/kept.pdf")),
+			entry("pdfs", &path("in/moved.pdf"), &path("docs/pdf/moved.pdf")),
+			entry("photos", &path("in/reverted.jpg"), &path("photos/reverted.jpg")),
+			entry("photos", &path("in/removed.jpg"), &path("photos/removed.jpg")),
+			// picked up again by another rule
+			entry("pdfs", &path("in/again.pdf"), &path("photos/again.pdf")),
+			entry("misfiled", &path("photos/again.pdf"), &path("docs/pdf/again.pdf")),
+		] {
+			journal::insert(&conn, &entry).unwrap();
+		}
+		assert_eq!(detect_with(&conn).unwrap(), 3);
+		// nothing is recorded twice
+		assert_eq!(detect_with(&conn).unwrap(), 0);
+		let corrections = read(&conn).unwrap();
+		let kinds = corrections.iter().map(|correction| correction.kind).collect::<Vec<_>>();
+		assert_eq!(kinds, vec![Kind::Moved, Kind::Reverted, Kind::Removed]);
+		assert_eq!(corrections[0].moved_to, Some(path("docs/taxes/moved.pdf")));
+
+		let entries = journal::read(&conn)
+			.unwrap()
+			.into_iter()
+			.map(|(_, entry)| entry)
+			.collect::<Vec<_>>();
+		let reports = report(&entries, &corrections);
+		assert_eq!(reports[0].rule, "photos");
+		assert_eq!(reports[0].corrected(), 2);
+		assert_eq!(reports[1].rule, "pdfs");
+		assert_eq!(reports[1].actions, 3);
+	}
+
+	#[test]
+	fn suggest_tweaks() {
+		let from = |name: &str| PathBuf::from("/in").join(name);
+		let entries = ["a.zip", "b.zip", "c.zip", "d.pdf"]
+			.iter()
+			.map(|name| entry("docs", &from(name), &PathBuf::from("/docs").join(name)))
+			.collect::<Vec<_>>();
+		let corrections = entries[..2]
+			.iter()
+			.map(|entry| Correction::new(Kind::Moved, entry, Some(PathBuf::from("/archives").join(entry.from.file_name().unwrap()))))
+			.collect::<Vec<_>>();
+		let reports = report(&entries, &corrections);
+		assert_eq!(reports[0].actions, 4);
+		assert_eq!(
+			reports[0].tweaks,
+			vec![
+				"2 of its 3 .zip files were corrected, consider leaving them out with a filter".to_string(),
+				"2 of its files were moved to /archives afterwards, consider sending them there".to_string(),
+			]
+		);
+	}
+}
@@ -0,0 +1,147 @@
+use std::{fs, io::BufReader, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use strum_macros::{Display, EnumString};
+
+use crate::variables::Variable;
+
+/// How much a picture's aspect ratio can be off from the one it's compared to, since few are cropped to the exact pixel
+const RATIO_TOLERANCE: f64 = 0.01;
+
+/// The size of a picture as it's displayed, which is its stored size turned a quarter if its EXIF orientation says it was taken sideways
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dimensions {
+	pub width: u32,
+	pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Display, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Orientation {
+	Portrait,
+	Landscape,
+	Square,
+}
+
+impl Dimensions {
+	/// The variables that hold the dimensions
+	pub(crate) const VARIABLES: [Variable; 5] = [
+		Variable::ImageWidth,
+		Variable::ImageHeight,
+		Variable::ImageMegapixels,
+		Variable::ImageAspectRatio,
+		Variable::ImageOrientation,
+	];
+
+	/// Reads the dimensions of the picture at `path` from its header, without decoding it
+	pub fn read<T: AsRef<Path>>(path: T) -> Result<Self> {
+		let path = path.as_ref();
+		let (width, height) = image::image_dimensions(path).with_context(|| format!("could not read the dimensions of {}", path.display()))?;
+		Ok(match rotated(path) {
+			true => Self {
+				width: height,
+				height: width,
+			},
+			false => Self { width, height },
+		})
+	}
+
+	pub fn megapixels(&self) -> f64 {
+		f64::from(self.width) * f64::from(self.height) / 1_000_000.0
+	}
+
+	pub fn aspect_ratio(&self) -> f64 {
+		f64::from(self.width) / f64::from(self.height.max(1))
+	}
+
+	/// Whether the aspect ratio is `ratio`, give or take 1%
+	pub fn has_aspect_ratio(&self, ratio: f64) -> bool {
+		(self.aspect_ratio() - ratio).abs() <= ratio * RATIO_TOLERANCE
+	}
+
+	pub fn orientation(&self) -> Orientation {
+		match self.width.cmp(&self.height) {
+			std::cmp::Ordering::Less => Orientation::Portrait,
+			std::cmp::Ordering::Greater => Orientation::Landscape,
+			std::cmp::Ordering::Equal => Orientation::Square,
+		}
+	}
+
+	/// The value of one of the [`VARIABLES`](Self::VARIABLES)
+	pub fn get(&self, variable: Variable) -> Option<String> {
+		Some(match variable {
+			Variable::ImageWidth => self.width.to_string(),
+			Variable::ImageHeight => self.height.to_string(),
+			Variable::ImageMegapixels => format!("{:.1}", self.megapixels()),
+			Variable::ImageAspectRatio => self.ratio_name(),
+			Variable::ImageOrientation => self.orientation().to_string(),
+			_ => return None,
+		})
+	}
+
+	/// `16:9` or `4:3` when the dimensions reduce to small enough numbers, or the ratio with two decimals otherwise, e.g. `2.16`
+	fn ratio_name(&self) -> String {
+		let divisor = gcd(self.width, self.height).max(1);
+		let (width, height) = (self.width / divisor, self.height / divisor);
+		match width <= 32 && height <= 32 {
+			true => format!("{}:{}", width, height),
+			false => format!("{:.2}", self.aspect_ratio()),
+		}
+	}
+}
+
+fn gcd(a: u32, b: u32) -> u32 {
+	match b {
+		0 => a,
+		b => gcd(b, a % b),
+	}
+}
+
+/// Whether the EXIF orientation of the picture at `path` turns it a quarter, which pictures without one never do
+fn rotated(path: &Path) -> bool {
+	let file = match fs::File::open(path) {
+		Ok(file) => file,
+		Err(_) => return false,
+	};
+	let exif = match exif::Reader::new().read_from_container(&mut BufReader::new(file)) {
+		Ok(exif) => exif,
+		Err(_) => return false,
+	};
+	// 5 to 8 are the transposed orientations
+	exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+		.and_then(|field| field.value.get_uint(0))
+		.is_some_and(|orientation| (5..=8).contains(&orientation))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn read_dimensions() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("phone.png");
+		image::RgbImage::new(30, 40).save(&path).unwrap();
+		let dimensions = Dimensions::read(&path).unwrap();
+		assert_eq!(dimensions, Dimensions { width: 30, height: 40 });
+		assert_eq!(dimensions.orientation(), Orientation::Portrait);
+		assert_eq!(dimensions.get(Variable::ImageAspectRatio).unwrap(), "3:4");
+		assert_eq!(dimensions.get(Variable::ImageMegapixels).unwrap(), "0.0");
+		assert!(Dimensions::read(dir.path().join("missing.png")).is_err());
+	}
+
+	#[test]
+	fn describe_ratios() {
+		let wallpaper = Dimensions { width: 1920, height: 1080 };
+		assert_eq!(wallpaper.ratio_name(), "16:9");
+		assert!(wallpaper.has_aspect_ratio(16.0 / 9.0));
+		assert!(!wallpaper.has_aspect_ratio(16.0 / 10.0));
+		assert_eq!(wallpaper.orientation(), Orientation::Landscape);
+		assert_eq!(wallpaper.get(Variable::ImageMegapixels).unwrap(), "2.1");
+		let screenshot = Dimensions { width: 1170, height: 2532 };
+		assert_eq!(screenshot.ratio_name(), "0.46");
+		assert_eq!(Dimensions { width: 512, height: 512 }.orientation(), Orientation::Square);
+	}
+}
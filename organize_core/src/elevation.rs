@@ -0,0 +1,199 @@
+use std::{
+	cell::RefCell,
+	ffi::OsString,
+	io,
+	path::{Path, PathBuf},
+	process::{Command, Stdio},
+	sync::Mutex,
+};
+
+use anyhow::{anyhow, Error, Result};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use crate::config::actions::ActionType;
+
+lazy_static! {
+	static ref ELEVATION: Mutex<Option<Elevation>> = Mutex::new(None);
+}
+
+thread_local! {
+	// the helper the operations of the current step can be retried with, see [`allowed`]
+	static HELPER: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Runs the operations that were refused for lack of permissions again through a helper, set under `[elevation]`.
+/// Only the operation that failed gets the higher privileges, organize itself keeps running as the user.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Elevation {
+	/// the helper and its arguments, e.g. `["sudo", "-n"]`, `["pkexec"]` or `["gsudo"]`, followed by the operation
+	pub helper: Vec<String>,
+}
+
+/// Makes `elevation` the one used from now on, if any
+pub fn set(elevation: Option<&Elevation>) {
+	*ELEVATION.lock().unwrap_or_else(|e| e.into_inner()) = elevation.cloned();
+}
+
+/// Whether `error` comes from the system refusing an operation to the current user
+pub fn is_permission_denied(error: &Error) -> bool {
+	error
+		.chain()
+		.filter_map(|cause| cause.downcast_ref::<io::Error>())
+		.any(|e| e.kind() == io::ErrorKind::PermissionDenied)
+}
+
+/// Runs `f`, a step, letting the operations it's refused be retried through the helper
+pub(crate) fn allowed<T>(f: impl FnOnce() -> T) -> T {
+	let helper = match ELEVATION.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+		Some(elevation) if !elevation.helper.is_empty() => Some(elevation.helper.clone()),
+		_ => None,
+	};
+	let previous = HELPER.with(|current| current.replace(helper));
+	let result = f();
+	HELPER.with(|current| current.replace(previous));
+	result
+}
+
+/// Carries out `action` from `from` to `to` through the helper if it failed with `error` for lack of permissions,
+/// returning where the file ended up. Otherwise, if there is no helper or the step isn't [`allowed`] to use it,
+/// `error` is returned as it was.
+pub(crate) fn retry(error: Error, action: ActionType, from: &Path, to: Option<&Path>) -> Result<Option<PathBuf>> {
+	match HELPER.with(|helper| helper.borrow().clone()) {
+		Some(helper) => elevate(&helper, error, action, from, to),
+		None => Err(error),
+	}
+}
+
+/// Like [`retry`], with `helper`
+fn elevate(helper: &[String], error: Error, action: ActionType, from: &Path, to: Option<&Path>) -> Result<Option<PathBuf>> {
+	let operation = match operation(action, from, to) {
+		Some(operation) if is_permission_denied(&error) => operation,
+		_ => return Err(error),
+	};
+	// the helpers don't overwrite, and some of them (`mv -n`) succeed without doing anything when they'd have to
+	if let Some(to) = to.filter(|to| exists(to) == Some(true)) {
+		return Err(error.context(format!("{} already exists, not retrying with {}", to.display(), helper[0])));
+	}
+	log::info!("({}) permission denied for {}, retrying with {}", action, from.display(), helper[0]);
+	let output = match Command::new(&helper[0])
+		.args(&helper[1..])
+		.args(&operation)
+		.stdin(Stdio::null())
+		.output()
+	{
+		Ok(output) => output,
+		Err(e) => return Err(error.context(anyhow!("could not run {}: {}", helper[0], e))),
+	};
+	if !output.status.success() {
+		let stderr = String::from_utf8_lossy(&output.stderr);
+		let reason = match stderr.trim() {
+			"" => output.status.to_string(),
+			stderr => stderr.to_string(),
+		};
+		return Err(error.context(format!("{} failed too: {}", helper[0], reason)));
+	}
+	let done = match (action, to) {
+		(ActionType::Move, Some(to)) => exists(to) != Some(false) && exists(from) != Some(true),
+		(ActionType::Delete, _) => exists(from) != Some(true),
+		(_, Some(to)) => exists(to) != Some(false),
+		(_, None) => true,
+	};
+	if !done {
+		return Err(error.context(format!("{} succeeded but {} was not carried out", helper[0], action)));
+	}
+	Ok(match action {
+		ActionType::Move => to.map(Path::to_path_buf),
+		ActionType::Delete => None,
+		_ => Some(from.to_path_buf()),
+	})
+}
+
+/// Whether something is at `path`, if that can be told without the helper's privileges
+fn exists(path: &Path) -> Option<bool> {
+	match path.symlink_metadata() {
+		Ok(_) => Some(true),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => Some(false),
+		Err(_) => None,
+	}
+}
+
+/// The command that carries out `action`, for the actions that can be elevated
+#[cfg(unix)]
+fn operation(action: ActionType, from: &Path, to: Option<&Path>) -> Option<Vec<OsString>> {
+	let (program, flags): (&str, &[&str]) = match (action, to) {
+		(ActionType::Move, Some(_)) => ("mv", &["-n"]),
+		(ActionType::Copy, Some(_)) => ("cp", &["-n", "-p"]),
+		(ActionType::Hardlink, Some(_)) => ("ln", &[]),
+		(ActionType::Symlink, Some(_)) => ("ln", &["-s"]),
+		(ActionType::Delete, _) => ("rm", &["-f"]),
+		_ => return None,
+	};
+	let mut operation = vec![program.into()];
+	operation.extend(flags.iter().map(OsString::from));
+	operation.push("--".into());
+	operation.push(from.into());
+	operation.extend(to.map(OsString::from));
+	Some(operation)
+}
+
+#[cfg(windows)]
+fn operation(action: ActionType, from: &Path, to: Option<&Path>) -> Option<Vec<OsString>> {
+	let (builtin, flags): (&str, &[&str]) = match (action, to) {
+		// `/-Y` asks before overwriting, which nobody answers
+		(ActionType::Move, Some(_)) => ("move", &["/-Y"]),
+		(ActionType::Copy, Some(_)) => ("copy", &["/-Y"]),
+		(ActionType::Delete, _) => ("del", &["/F"]),
+		_ => return None,
+	};
+	let mut operation = vec!["cmd".into(), "/C".into(), builtin.into()];
+	operation.extend(flags.iter().map(OsString::from));
+	operation.push(from.into());
+	operation.extend(to.map(OsString::from));
+	Some(operation)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn permission_errors() {
+		let denied = Error::new(io::Error::from(io::ErrorKind::PermissionDenied)).context("Failed to move file");
+		assert!(is_permission_denied(&denied));
+		assert!(!is_permission_denied(&anyhow!("could not render the destination")));
+		// without a helper the error is left as it was
+		let error = retry(denied, ActionType::Move, Path::new("/etc/a"), Some(Path::new("/tmp/a"))).unwrap_err();
+		assert!(is_permission_denied(&error));
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn check_what_the_helper_did() {
+		let dir = tempfile::tempdir().unwrap();
+		let (from, to) = (dir.path().join("a"), dir.path().join("b"));
+		std::fs::write(&from, "a").unwrap();
+		let denied = || Error::new(io::Error::from(io::ErrorKind::PermissionDenied));
+		// `true` succeeds without doing anything
+		let error = elevate(&["true".into()], denied(), ActionType::Move, &from, Some(&to)).unwrap_err();
+		assert!(error.to_string().contains("was not carried out"), "{:?}", error);
+		std::fs::write(&to, "b").unwrap();
+		let error = elevate(&["true".into()], denied(), ActionType::Copy, &from, Some(&to)).unwrap_err();
+		assert!(error.to_string().contains("already exists"), "{:?}", error);
+		assert_eq!(elevate(&["env".into()], denied(), ActionType::Delete, &to, None).unwrap(), None);
+		assert_eq!(
+			elevate(&["env".into()], denied(), ActionType::Move, &from, Some(&to)).unwrap(),
+			Some(to.clone())
+		);
+		assert_eq!(std::fs::read_to_string(&to).unwrap(), "a");
+	}
+
+	#[test]
+	#[cfg(unix)]
+	fn operations() {
+		let operation = operation(ActionType::Move, Path::new("/etc/a"), Some(Path::new("/tmp/a"))).unwrap();
+		assert_eq!(operation, ["mv", "-n", "--", "/etc/a", "/tmp/a"].map(OsString::from));
+		assert!(super::operation(ActionType::Trash, Path::new("/etc/a"), None).is_none());
+	}
+}
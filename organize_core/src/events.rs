@@ -0,0 +1,152 @@
+use std::{
+	cell::RefCell,
+	path::PathBuf,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Mutex,
+	},
+};
+
+use lazy_static::lazy_static;
+use log::Level;
+use strum_macros::{Display, EnumString};
+
+use crate::{config::actions::ActionType, context::Context, elevation, journal, thumbnails};
+
+static RECORDING: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+	static ref EVENTS: Mutex<Vec<Event>> = Mutex::new(Vec::new());
+	static ref RUN_ID: String = uuid::Uuid::new_v4().to_string();
+}
+
+thread_local! {
+	// the journal entries of the actions this thread carried out since [`acted`] started collecting them
+	static ACTED: RefCell<Option<Vec<journal::Entry>>> = const { RefCell::new(None) };
+}
+
+/// Identifies this invocation of organize in the log files, the journal and the JSON report,
+/// so that what each of them says about the same run can be put together. A whole `watch` is one run.
+pub fn run_id() -> &'static str {
+	&RUN_ID
+}
+
+/// Something that happened during a run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+	/// a rule was applied to a file
+	Matched {
+		rule: usize,
+		path: PathBuf,
+	},
+	/// an action was carried out on a file, `to` is where the file ended up, if it still exists
+	Acted {
+		action: ActionType,
+		from: PathBuf,
+		to: Option<PathBuf>,
+		/// what the action printed, which is kept in the journal
+		output: Option<String>,
+	},
+	/// a file was left alone because its destination was already taken
+	Conflict {
+		from: PathBuf,
+		to: PathBuf,
+	},
+	Error {
+		message: String,
+	},
+	/// an action made a file smaller
+	Saved {
+		path: PathBuf,
+		bytes: u64,
+	},
+	/// a file was not acted on
+	Skipped {
+		path: PathBuf,
+		reason: SkipReason,
+	},
+}
+
+/// Why a file was left alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Display, EnumString)]
+#[strum(serialize_all = "kebab-case")]
+pub enum SkipReason {
+	/// no rule's filters matched it
+	FilteredOut,
+	/// it was inside a location, but the location's options (depth, hidden files, ignored dirs...) left it out
+	Excluded,
+	/// its destination was already taken
+	ConflictSkip,
+	/// a safeguard refused to touch it, or to write where a template pointed
+	ProtectedPath,
+	/// an action failed on it
+	Error,
+	/// the system refused an action on it to the current user, and it couldn't be elevated
+	PermissionDenied,
+}
+
+impl SkipReason {
+	/// How much attention a skip deserves: files that didn't match are expected, files that matched but couldn't be acted on aren't
+	pub fn severity(&self) -> Level {
+		match self {
+			Self::FilteredOut | Self::Excluded => Level::Info,
+			Self::ConflictSkip | Self::ProtectedPath | Self::PermissionDenied => Level::Warn,
+			Self::Error => Level::Error,
+		}
+	}
+
+	/// Why a file is left alone after an action failed on it with `error`
+	pub fn of(error: &anyhow::Error) -> Self {
+		match elevation::is_permission_denied(error) {
+			true => Self::PermissionDenied,
+			false => Self::Error,
+		}
+	}
+}
+
+/// Starts keeping track of events, until `finish` is called.
+/// Nothing is recorded otherwise, so that long-running commands like `watch` don't accumulate them forever.
+pub fn start() {
+	EVENTS.lock().unwrap_or_else(|e| e.into_inner()).clear();
+	RECORDING.store(true, Ordering::Relaxed);
+}
+
+pub fn record(event: Event) {
+	if let Event::Acted { action, from, to, output } = &event {
+		ACTED.with(|acted| {
+			if let Some(acted) = acted.borrow_mut().as_mut() {
+				acted.push(journal::Entry {
+					rule: Context::rule(),
+					output: output.clone(),
+					run: Some(run_id().to_string()),
+					..journal::Entry::new(*action, from.clone(), to.clone())
+				});
+			}
+		});
+		thumbnails::follow(*action, from, to.as_deref());
+	}
+	if RECORDING.load(Ordering::Relaxed) {
+		EVENTS.lock().unwrap_or_else(|e| e.into_inner()).push(event);
+	}
+}
+
+/// Runs `f`, returning the journal entries of the actions it carried out along with its result
+pub(crate) fn acted<T>(f: impl FnOnce() -> T) -> (T, Vec<journal::Entry>) {
+	let previous = ACTED.with(|acted| acted.replace(Some(Vec::new())));
+	let result = f();
+	let entries = ACTED.with(|acted| acted.replace(previous)).unwrap_or_default();
+	(result, entries)
+}
+
+/// Records that `path` was skipped, and says why in the verbose logs
+pub fn skip<T: Into<PathBuf>>(path: T, reason: SkipReason) {
+	let path = path.into();
+	log::debug!("(skip) [{}] {}: {}", reason.severity().as_str().to_lowercase(), reason, path.display());
+	record(Event::Skipped { path, reason });
+}
+
+/// Stops recording and returns everything that was recorded since `start`
+pub fn finish() -> Vec<Event> {
+	RECORDING.store(false, Ordering::Relaxed);
+	std::mem::take(&mut *EVENTS.lock().unwrap_or_else(|e| e.into_inner()))
+}
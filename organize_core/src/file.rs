@@ -1,12 +1,41 @@
 use crate::{
-	config::{options::r#match::Match, Config},
+	capacity::Transfer,
+	config::{
+		filters::AsFilter,
+		options::{apply::Apply, r#match::Match, recursive::Recursive, targets::Targets},
+		Config,
+	},
+	context::Context,
+	control,
+	events::{self, Event, SkipReason},
 	path::IsHidden,
+	profile::{self, Stage},
+	throttle,
 };
 use std::{
-	collections::HashMap,
+	collections::{HashMap, HashSet},
 	path::{Path, PathBuf},
 };
 
+/// One of the checks a file goes through before a rule applies to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gate {
+	pub name: String,
+	/// what was actually computed for the file
+	pub value: String,
+	pub passed: bool,
+}
+
+impl Gate {
+	fn new<T: Into<String>, U: Into<String>>(name: T, value: U, passed: bool) -> Self {
+		Self {
+			name: name.into(),
+			value: value.into(),
+			passed,
+		}
+	}
+}
+
 pub struct File<'a> {
 	pub path: PathBuf,
 	config: &'a Config,
@@ -22,25 +51,121 @@ impl<'a> File<'a> {
 		}
 	}
 
-	pub fn act(mut self, path_to_rules: &'a HashMap<PathBuf, Vec<(usize, usize)>>) {
-		let rules = self.get_matching_rules(path_to_rules);
-		for (i, j) in rules {
-			let rule = &self.config.rules[*i];
-			match rule.actions.act(self.path, self.config.get_apply_actions(*i, *j)) {
-				None => break,
-				Some(new_path) => {
-					self.path = new_path;
+	/// Applies the matching rules and returns where the file ended up, or `None` if it no longer exists.
+	/// If a rule moves the file into another location, that location's rules are applied right away,
+	/// with the file's original path still available to their templates. Each rule runs at most once per file.
+	pub fn act(mut self, path_to_rules: &'a HashMap<PathBuf, Vec<(usize, usize)>>) -> Option<PathBuf> {
+		control::checkpoint();
+		let _definitions = self.config.enter();
+		let original = self.path.clone();
+		let location = |path: &Path| location(path, path_to_rules);
+		let root = location(&self.path);
+		let _context = Context::enter(&self.path, root.clone());
+		let mut applied = HashSet::new();
+		loop {
+			let before = location(&self.path);
+			let rules = self.matching_rules(path_to_rules, &applied);
+			if rules.is_empty() {
+				if applied.is_empty() {
+					events::skip(&self.path, self.skip_reason(path_to_rules));
 				}
+				break;
+			}
+			for (i, j) in rules {
+				applied.insert((*i, *j));
+				events::record(Event::Matched {
+					rule: *i,
+					path: self.path.clone(),
+				});
+				let rule = &self.config.rules[*i];
+				Context::set_rule(rule.name(*i));
+				let _limits = throttle::enter(*i, rule);
+				let path = profile::in_rule(*i, || rule.actions.act(&self.path, self.config.get_apply_actions(*i, *j)));
+				if path.as_ref() != Some(&self.path) {
+					if let Some(root) = &root {
+						self.prune(&original, root, path_to_rules);
+					}
+				}
+				self.path = path?;
+			}
+			// the rules that matched are all that applies to the file where it is
+			if location(&self.path) == before {
+				break;
+			}
+		}
+		Some(self.path)
+	}
+
+	/// Where the rules that match the file where it is would copy or move it, without acting on it
+	pub(crate) fn transfers(&self, path_to_rules: &'a HashMap<PathBuf, Vec<(usize, usize)>>) -> Vec<Transfer> {
+		let _definitions = self.config.enter();
+		let _context = Context::enter(&self.path, location(&self.path, path_to_rules));
+		let mut transfers = Vec::new();
+		for (i, j) in self.matching_rules(path_to_rules, &HashSet::new()) {
+			let rule = &self.config.rules[*i];
+			Context::set_rule(rule.name(*i));
+			let steps = match self.config.get_apply_actions(*i, *j) {
+				Apply::AllOf(indices) => indices.iter().filter_map(|index| rule.actions.get(*index)).collect::<Vec<_>>(),
+				_ => rule.actions.iter().collect(),
+			};
+			for step in steps.into_iter().filter(|step| !step.dry_run) {
+				transfers.extend(step.action.transfers(&self.path));
 			}
 		}
+		transfers
+	}
+
+	/// Why no rule applies to this file: either the options of every location it's in leave it out, or no filters matched it
+	fn skip_reason(&self, path_to_rules: &HashMap<PathBuf, Vec<(usize, usize)>>) -> SkipReason {
+		let included = self
+			.path
+			.ancestors()
+			.skip(1)
+			.filter_map(|ancestor| path_to_rules.get_key_value(ancestor))
+			.any(|(ancestor, rules)| {
+				rules
+					.iter()
+					.any(|(rule, folder)| self.filter_by_options(ancestor, *rule, *folder))
+			});
+		match included {
+			true => SkipReason::FilteredOut,
+			false => SkipReason::Excluded,
+		}
+	}
+
+	/// Removes the directories between `from` and the location `root` that were left empty,
+	/// if any of the location's rules asks for it. Ignored directories are left alone.
+	fn prune(&self, from: &Path, root: &Path, path_to_rules: &HashMap<PathBuf, Vec<(usize, usize)>>) {
+		let rules = &path_to_rules[root];
+		if !rules
+			.iter()
+			.any(|(rule, folder)| *self.config.prunes_empty_dirs(*rule, *folder))
+		{
+			return;
+		}
+		for dir in from.ancestors().skip(1).take_while(|dir| *dir != root && dir.starts_with(root)) {
+			if rules
+				.iter()
+				.any(|(rule, folder)| self.ignored_dirs(*rule, *folder).any(|ignored| ignored == dir))
+			{
+				break;
+			}
+			// only succeeds if the directory is empty
+			if std::fs::remove_dir(dir).is_err() {
+				break;
+			}
+			log::debug!("removed empty directory {}", dir.display());
+		}
 	}
 
 	fn filter_by_recursive<T: AsRef<Path>>(&self, ancestor: T, rule: usize, folder: usize) -> bool {
-		let depth = *self.config.get_recursive_depth(rule, folder) as usize;
+		let depth = *self.config.get_recursive_depth(rule, folder);
+		let location = &self.config.rules[rule].folders[folder].path;
+		let depth = Recursive::clamp_home(location, depth, *self.config.allows_deep_home(rule, folder)) as usize;
 		if depth == 0 {
 			return true;
 		}
-		return self.path.components().count() - ancestor.as_ref().components().count() <= depth;
+		self.path.components().count() - ancestor.as_ref().components().count() <= depth
 	}
 
 	fn filter_by_partial_files(&self, rule: usize, folder: usize) -> bool {
@@ -59,29 +184,31 @@ impl<'a> File<'a> {
 		(self.path.is_hidden() && *self.config.allows_hidden_files(rule, folder)) || !self.path.is_hidden()
 	}
 
+	fn ignored_dirs(&self, rule: usize, folder: usize) -> impl Iterator<Item = &'a PathBuf> {
+		let config = self.config;
+		vec![
+			&config.global_defaults.ignored_dirs,
+			&config.local_defaults.ignored_dirs,
+			&config.rules[rule].options.ignored_dirs,
+			&config.rules[rule].folders[folder].options.ignored_dirs,
+		]
+		.into_iter()
+		.flatten()
+		.flatten()
+	}
+
 	fn filter_by_ignored_dirs(&self, rule: usize, folder: usize) -> bool {
-		let check_ignored = |dir: &PathBuf| -> bool { self.path.parent().map(|parent| dir == parent).unwrap_or_default() };
-		if let Some(ignored_dirs) = &self.config.global_defaults.ignored_dirs {
-			if ignored_dirs.iter().any(check_ignored) {
-				return false;
-			}
-		}
-		if let Some(ignored_dirs) = &self.config.local_defaults.ignored_dirs {
-			if ignored_dirs.iter().any(check_ignored) {
-				return false;
-			}
-		}
-		if let Some(ignored_dirs) = &self.config.rules[rule].options.ignored_dirs {
-			if ignored_dirs.iter().any(check_ignored) {
-				return false;
-			}
-		}
-		if let Some(ignored_dirs) = &self.config.rules[rule].folders[folder].options.ignored_dirs {
-			if ignored_dirs.iter().any(check_ignored) {
-				return false;
-			}
+		let parent = self.path.parent();
+		!self
+			.ignored_dirs(rule, folder)
+			.any(|dir| parent.map(|parent| dir == parent).unwrap_or_default())
+	}
+
+	fn filter_by_targets(&self, rule: usize, folder: usize) -> bool {
+		match self.config.get_targets(rule, folder) {
+			Targets::Files => !self.path.is_dir(),
+			Targets::Dirs => self.path.is_dir(),
 		}
-		true
 	}
 
 	fn filter_by_watch(&self, rule: usize, folder: usize) -> bool {
@@ -90,6 +217,7 @@ impl<'a> File<'a> {
 
 	fn filter_by_options<T: AsRef<Path>>(&self, ancestor: T, rule: usize, folder: usize) -> bool {
 		self.filter_by_recursive(ancestor, rule, folder)
+			&& self.filter_by_targets(rule, folder)
 			&& self.filter_by_hidden_files(rule, folder)
 			&& self.filter_by_ignored_dirs(rule, folder)
 			&& self.filter_by_partial_files(rule, folder)
@@ -97,9 +225,12 @@ impl<'a> File<'a> {
 	}
 
 	fn filter_by_filters(&self, rule: usize, folder: usize) -> bool {
-		let apply = self.config.get_apply_filters(rule, folder);
-		let rule = &self.config.rules[rule];
-		rule.filters.r#match(&self.path, apply)
+		throttle::within(rule, &self.config.rules[rule], || {
+			profile::in_rule(rule, || {
+				self.config.predicates[rule][folder]
+					.matches_by(|index, name, check| profile::time(|| Stage::Filter { index, name }, || check(&self.path)))
+			})
+		})
 	}
 
 	fn filter<T: AsRef<Path>>(&self, ancestor: T, rule: &usize, folder: &usize) -> bool {
@@ -107,22 +238,234 @@ impl<'a> File<'a> {
 		self.filter_by_options(ancestor, rule, folder) && self.filter_by_filters(rule, folder)
 	}
 
+	/// Evaluates every check between this file and `rule`, including the ones that wouldn't be reached because an earlier one failed
+	pub fn gates(&self, rule: usize) -> Vec<Gate> {
+		let _definitions = self.config.enter();
+		let location = self.config.rules[rule]
+			.folders
+			.iter()
+			.enumerate()
+			.filter(|(_, folder)| self.path.starts_with(&folder.path) && self.path != folder.path)
+			.max_by_key(|(_, folder)| folder.path.components().count());
+		let (folder, location) = match location {
+			Some((folder, location)) => (folder, &location.path),
+			None => return vec![Gate::new("location", "not inside any of the rule's locations", false)],
+		};
+
+		let depth = *self.config.get_recursive_depth(rule, folder);
+		let depth = Recursive::clamp_home(location, depth, *self.config.allows_deep_home(rule, folder));
+		let actual = self.path.components().count() - location.components().count();
+		let ignored = self
+			.ignored_dirs(rule, folder)
+			.map(|dir| dir.display().to_string())
+			.collect::<Vec<_>>();
+		let mut gates = vec![
+			Gate::new("location", location.display().to_string(), true),
+			Gate::new(
+				"depth",
+				match depth {
+					0 => format!("{} (unlimited)", actual),
+					depth => format!("{} (at most {})", actual, depth),
+				},
+				self.filter_by_recursive(location, rule, folder),
+			),
+			Gate::new(
+				"targets",
+				format!("dir = {}, targets = {:?}", self.path.is_dir(), self.config.get_targets(rule, folder)),
+				self.filter_by_targets(rule, folder),
+			),
+			Gate::new(
+				"hidden files",
+				format!(
+					"hidden = {}, allowed = {}",
+					self.path.is_hidden(),
+					self.config.allows_hidden_files(rule, folder)
+				),
+				self.filter_by_hidden_files(rule, folder),
+			),
+			Gate::new(
+				"ignored dirs",
+				format!("[{}]", ignored.join(", ")),
+				self.filter_by_ignored_dirs(rule, folder),
+			),
+			Gate::new(
+				"partial files",
+				format!("allowed = {}", self.config.allows_partial_files(rule, folder)),
+				self.filter_by_partial_files(rule, folder),
+			),
+			Gate::new(
+				"watch",
+				format!("watching = {}, allowed = {}", self.is_watching, self.config.allows_watching(rule, folder)),
+				self.filter_by_watch(rule, folder),
+			),
+		];
+		for (i, filter) in self.config.rules[rule].filters.iter().enumerate() {
+			let name: &'static str = filter.into();
+			let passed = filter.matches(&self.path);
+			gates.push(Gate::new(
+				format!("filter #{} ({})", i, name),
+				if passed { "matches" } else { "no match" },
+				passed,
+			));
+		}
+		let apply = self.config.get_apply_filters(rule, folder);
+		gates.push(Gate::new("filters", format!("apply = {}", apply), self.filter_by_filters(rule, folder)));
+		gates
+	}
+
+	/// Collects the rules that apply to this file, from the locations it's in, nearest first
 	pub fn get_matching_rules(&self, path_to_rules: &'a HashMap<PathBuf, Vec<(usize, usize)>>) -> Vec<&'a (usize, usize)> {
-		let (ancestor, rules) = self
+		let _definitions = self.config.enter();
+		self.matching_rules(path_to_rules, &HashSet::new())
+	}
+
+	fn matching_rules(&self, path_to_rules: &'a HashMap<PathBuf, Vec<(usize, usize)>>, applied: &HashSet<(usize, usize)>) -> Vec<&'a (usize, usize)> {
+		let mut candidates = self
 			.path
 			.ancestors()
-			.find_map(|ancestor| path_to_rules.get_key_value(&ancestor.to_path_buf()))
-			.unwrap();
+			.skip(1)
+			.filter_map(|ancestor| path_to_rules.get_key_value(ancestor))
+			.flat_map(|(ancestor, rules)| rules.iter().map(move |rule| (ancestor, rule)))
+			.filter(|(_, rule)| !applied.contains(*rule));
 
 		match self.config.match_rules() {
-			Match::First => rules
-				.iter()
-				.find(|(rule, folder)| self.filter(ancestor, rule, folder))
-				.map_or_else(Vec::new, |rule| vec![rule]),
-			Match::All => rules
-				.iter()
-				.filter(|(rule, folder)| self.filter(ancestor, rule, folder))
+			Match::First => candidates
+				.find(|(ancestor, (rule, folder))| self.filter(ancestor, rule, folder))
+				.map_or_else(Vec::new, |(_, rule)| vec![rule]),
+			Match::All => candidates
+				.filter(|(ancestor, (rule, folder))| self.filter(ancestor, rule, folder))
+				.map(|(_, rule)| rule)
 				.collect(),
 		}
 	}
 }
+
+/// The location `path` is in, the nearest one if there are several
+fn location(path: &Path, path_to_rules: &HashMap<PathBuf, Vec<(usize, usize)>>) -> Option<PathBuf> {
+	path.ancestors()
+		.skip(1)
+		.find(|ancestor| path_to_rules.contains_key(*ancestor))
+		.map(Path::to_path_buf)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn prune_empty_dirs_up_to_location() {
+		let dir = tempfile::tempdir().unwrap();
+		let (root, nested, dest) = (
+			dir.path().join("root"),
+			dir.path().join("root").join("a").join("b"),
+			dir.path().join("dest"),
+		);
+		std::fs::create_dir_all(&nested).unwrap();
+		std::fs::create_dir_all(&dest).unwrap();
+		let config = format!(
+			r#"
+[[rules]]
+folders = [{{ path = "{}", options = {{ recursive = 0, prune_empty_dirs = true }} }}]
+filters = [{{ type = "extension", extensions = ["txt"] }}]
+actions = [{{ type = "move", to = "{}/" }}]
+"#,
+			root.display(),
+			dest.display()
+		);
+		let path = dir.path().join("organize.toml");
+		std::fs::write(&path, config).unwrap();
+		let config = Config::parse(path).unwrap();
+		let file = nested.canonicalize().unwrap().join("test.txt");
+		std::fs::write(&file, "").unwrap();
+		let new_path = File::new(&file, &config, false).act(&config.path_to_rules);
+		assert_eq!(new_path, Some(dest.join("test.txt")));
+		assert!(!root.join("a").exists());
+		assert!(root.exists());
+	}
+
+	#[test]
+	fn match_first_applies_one_rule() {
+		let dir = tempfile::tempdir().unwrap();
+		let root = dir.path().join("root");
+		std::fs::create_dir_all(&root).unwrap();
+		let config = format!(
+			r#"
+[defaults]
+match = "first"
+
+[[rules]]
+folders = ["{root}"]
+filters = [{{ type = "extension", extensions = ["txt"] }}]
+actions = [{{ type = "copy", to = "{dir}/a/" }}]
+
+[[rules]]
+folders = ["{root}"]
+filters = [{{ type = "extension", extensions = ["txt"] }}]
+actions = [{{ type = "copy", to = "{dir}/b/" }}]
+"#,
+			root = root.display(),
+			dir = dir.path().display()
+		);
+		let path = dir.path().join("organize.toml");
+		std::fs::write(&path, config).unwrap();
+		let config = Config::parse(path).unwrap();
+		let file = root.canonicalize().unwrap().join("x.txt");
+		std::fs::write(&file, "").unwrap();
+		assert_eq!(File::new(&file, &config, false).act(&config.path_to_rules), Some(file));
+		assert!(dir.path().join("a").join("x.txt").exists());
+		assert!(!dir.path().join("b").exists());
+	}
+
+	#[test]
+	fn gates_report_failing_filter() {
+		let dir = tempfile::tempdir().unwrap();
+		let root = dir.path().join("root");
+		std::fs::create_dir_all(&root).unwrap();
+		let config = format!(
+			r#"
+[[rules]]
+folders = ["{}"]
+filters = [{{ type = "extension", extensions = ["txt"] }}]
+actions = []
+"#,
+			root.display()
+		);
+		let path = dir.path().join("organize.toml");
+		std::fs::write(&path, config).unwrap();
+		let config = Config::parse(path).unwrap();
+		let file = root.canonicalize().unwrap().join("test.pdf");
+		let gates = File::new(&file, &config, false).gates(0);
+		let failed = gates
+			.iter()
+			.filter(|gate| !gate.passed)
+			.map(|gate| gate.name.as_str())
+			.collect::<Vec<_>>();
+		assert_eq!(failed, vec!["filter #0 (extension)", "filters"]);
+		let outside = File::new(dir.path().join("test.txt"), &config, false).gates(0);
+		assert_eq!(outside.len(), 1);
+		assert!(!outside[0].passed);
+	}
+
+	#[test]
+	fn skip_reason_tells_filters_from_options() {
+		let dir = tempfile::tempdir().unwrap();
+		let root = dir.path().join("root");
+		std::fs::create_dir_all(&root).unwrap();
+		let config = format!(
+			r#"
+[[rules]]
+folders = ["{}"]
+filters = [{{ type = "extension", extensions = ["txt"] }}]
+actions = []
+"#,
+			root.display()
+		);
+		let path = dir.path().join("organize.toml");
+		std::fs::write(&path, config).unwrap();
+		let config = Config::parse(path).unwrap();
+		let root = root.canonicalize().unwrap();
+		let reason = |name: &str| File::new(root.join(name), &config, false).skip_reason(&config.path_to_rules);
+		assert_eq!(reason("test.pdf"), SkipReason::FilteredOut);
+		assert_eq!(reason(".test.txt"), SkipReason::Excluded);
+	}
+}
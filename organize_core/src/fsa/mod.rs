@@ -4,7 +4,7 @@ use std::fmt::Debug;
 use std::hash::Hash;
 
 #[derive(Debug)]
-pub(in crate) struct Fsa<'a, Q> {
+pub(crate) struct Fsa<'a, Q> {
 	pub accept_states: &'a [Q],
 	pub transition_matrix: Vec<Vec<Option<Q>>>,
 	pub start_state: Q,
@@ -12,7 +12,7 @@ pub(in crate) struct Fsa<'a, Q> {
 	pub symbol_to_index: HashMap<String, usize>,
 }
 
-pub(in crate) struct Transition<Q, S> {
+pub(crate) struct Transition<Q, S> {
 	pub from: Q,
 	pub to: Q,
 	pub on: Vec<S>,
@@ -0,0 +1,230 @@
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+	sync::Mutex,
+	time::UNIX_EPOCH,
+};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use strum_macros::{Display, EnumString};
+
+use crate::with_cache;
+
+/// The size of a file and when it was last modified, in nanoseconds since the epoch.
+/// A digest is reused for as long as both are unchanged.
+type Stamp = (i64, i64);
+
+lazy_static! {
+	/// the digest of every file hashed so far
+	static ref DIGESTS: Mutex<HashMap<(PathBuf, HashAlgorithm), (Stamp, String)>> = Mutex::new(HashMap::new());
+	/// the first file seen with each digest
+	static ref SEEN: Mutex<HashMap<(HashAlgorithm, String), PathBuf>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq, Hash, Display, EnumString)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum HashAlgorithm {
+	#[default]
+	Blake3,
+	Sha256,
+}
+
+impl HashAlgorithm {
+	fn hash(&self, path: &Path) -> Result<String> {
+		let mut file = fs::File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+		let read = |e| anyhow::Error::new(e).context(format!("could not read {}", path.display()));
+		Ok(match self {
+			Self::Blake3 => {
+				let mut hasher = blake3::Hasher::new();
+				std::io::copy(&mut file, &mut hasher).map_err(read)?;
+				hasher.finalize().to_hex().to_string()
+			}
+			Self::Sha256 => {
+				let mut hasher = Sha256::new();
+				std::io::copy(&mut file, &mut hasher).map_err(read)?;
+				hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect()
+			}
+		})
+	}
+}
+
+fn stamp(path: &Path) -> Result<Stamp> {
+	let metadata = path
+		.metadata()
+		.with_context(|| format!("could not read the metadata of {}", path.display()))?;
+	let modified = metadata
+		.modified()?
+		.duration_since(UNIX_EPOCH)
+		.map_or(0, |duration| duration.as_nanos() as i64);
+	Ok((metadata.len() as i64, modified))
+}
+
+fn init(conn: &Connection) -> Result<()> {
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS hash_index (
+			path TEXT NOT NULL,
+			algorithm TEXT NOT NULL,
+			size INTEGER NOT NULL,
+			modified INTEGER NOT NULL,
+			digest TEXT NOT NULL,
+			PRIMARY KEY (path, algorithm)
+		)",
+		[],
+	)
+	.context("could not create the hash index")?;
+	conn.execute("CREATE INDEX IF NOT EXISTS hash_index_digest ON hash_index (algorithm, digest)", [])
+		.context("could not create the hash index")?;
+	Ok(())
+}
+
+/// The digest of the file at `path`, computed once for as long as its size and modification time don't change.
+/// With `persistent`, digests are also kept in the database, so that they survive restarts.
+pub fn digest<T: AsRef<Path>>(path: T, algorithm: HashAlgorithm, persistent: bool) -> Result<String> {
+	let path = path.as_ref();
+	let stamp = stamp(path)?;
+	let key = (path.to_path_buf(), algorithm);
+	let cached = match DIGESTS.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+		Some((when, digest)) if *when == stamp => Some(digest.clone()),
+		_ => None,
+	};
+	let stored = match (&cached, persistent) {
+		(None, true) => with_cache(init, |conn| stored_with(conn, path, algorithm, stamp))?,
+		_ => None,
+	};
+	let digest = match cached.clone().or_else(|| stored.clone()) {
+		Some(digest) => digest,
+		None => algorithm.hash(path)?,
+	};
+	// digests only kept in memory so far, e.g. by a filter that isn't persistent, are stored too
+	if persistent && stored.is_none() {
+		with_cache(init, |conn| store_with(conn, path, algorithm, stamp, &digest))?;
+	}
+	if cached.is_none() {
+		DIGESTS
+			.lock()
+			.unwrap_or_else(|e| e.into_inner())
+			.insert(key, (stamp, digest.clone()));
+	}
+	Ok(digest)
+}
+
+/// Another file with the same contents as the one at `path`, that was seen before it in this run or,
+/// with `persistent`, recorded in the database by an earlier one. The file is recorded as seen otherwise.
+pub fn original<T: AsRef<Path>>(path: T, algorithm: HashAlgorithm, persistent: bool) -> Result<Option<PathBuf>> {
+	let path = path.as_ref();
+	let digest = digest(path, algorithm, persistent)?;
+	// the other file may have been removed or changed since
+	let same = |other: &Path| other != path && digest_of(other, algorithm, persistent).is_some_and(|other| other == digest);
+	if persistent {
+		let candidates = with_cache(init, |conn| candidates_with(conn, path, algorithm, &digest))?;
+		if let Some(other) = candidates.into_iter().find(|other| same(other)) {
+			return Ok(Some(other));
+		}
+	}
+	let mut seen = SEEN.lock().unwrap_or_else(|e| e.into_inner());
+	let key = (algorithm, digest.clone());
+	match seen.get(&key) {
+		Some(other) if same(other) => Ok(Some(other.clone())),
+		_ => {
+			seen.insert(key, path.to_path_buf());
+			Ok(None)
+		}
+	}
+}
+
+fn digest_of(path: &Path, algorithm: HashAlgorithm, persistent: bool) -> Option<String> {
+	digest(path, algorithm, persistent)
+		.map_err(|e| log::debug!("could not hash {}: {:?}", path.display(), e))
+		.ok()
+}
+
+fn stored_with(conn: &Connection, path: &Path, algorithm: HashAlgorithm, stamp: Stamp) -> Result<Option<String>> {
+	conn.query_row(
+		"SELECT digest FROM hash_index WHERE path = ?1 AND algorithm = ?2 AND size = ?3 AND modified = ?4",
+		params![path.to_string_lossy(), algorithm.to_string(), stamp.0, stamp.1],
+		|row| row.get(0),
+	)
+	.optional()
+	.context("could not read the hash index")
+}
+
+fn store_with(conn: &Connection, path: &Path, algorithm: HashAlgorithm, stamp: Stamp, digest: &str) -> Result<()> {
+	conn.execute(
+		"INSERT OR REPLACE INTO hash_index (path, algorithm, size, modified, digest) VALUES (?1, ?2, ?3, ?4, ?5)",
+		params![path.to_string_lossy(), algorithm.to_string(), stamp.0, stamp.1, digest],
+	)
+	.context("could not update the hash index")?;
+	Ok(())
+}
+
+fn candidates_with(conn: &Connection, path: &Path, algorithm: HashAlgorithm, digest: &str) -> Result<Vec<PathBuf>> {
+	let mut statement = conn.prepare("SELECT path FROM hash_index WHERE algorithm = ?1 AND digest = ?2 AND path != ?3 ORDER BY path")?;
+	let paths = statement
+		.query_map(params![algorithm.to_string(), digest, path.to_string_lossy()], |row| {
+			Ok(PathBuf::from(row.get::<_, String>(0)?))
+		})?
+		.collect::<rusqlite::Result<Vec<_>>>()?;
+	Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn hash_with_each_algorithm() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("a");
+		fs::write(&path, "abc").unwrap();
+		assert_eq!(
+			HashAlgorithm::Sha256.hash(&path).unwrap(),
+			"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+		);
+		assert_eq!(
+			HashAlgorithm::Blake3.hash(&path).unwrap(),
+			"6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+		);
+	}
+
+	#[test]
+	fn find_first_copy() {
+		let dir = tempfile::tempdir().unwrap();
+		let (first, copy, other) = (dir.path().join("first"), dir.path().join("copy"), dir.path().join("other"));
+		// the files seen are shared by the whole process, so the contents are unique to this test
+		let contents = dir.path().to_string_lossy().to_string();
+		fs::write(&first, &contents).unwrap();
+		fs::write(&copy, &contents).unwrap();
+		fs::write(&other, "different").unwrap();
+		assert_eq!(original(&first, HashAlgorithm::Blake3, false).unwrap(), None);
+		assert_eq!(original(&copy, HashAlgorithm::Blake3, false).unwrap(), Some(first.clone()));
+		assert_eq!(original(&other, HashAlgorithm::Blake3, false).unwrap(), None);
+		// the first copy is still the original when it's seen again
+		assert_eq!(original(&first, HashAlgorithm::Blake3, false).unwrap(), None);
+
+		fs::remove_file(&first).unwrap();
+		assert_eq!(original(&copy, HashAlgorithm::Blake3, false).unwrap(), None);
+	}
+
+	#[test]
+	fn store_digests() {
+		let dir = tempfile::tempdir().unwrap();
+		let (a, b) = (dir.path().join("a"), dir.path().join("b"));
+		fs::write(&a, "same").unwrap();
+		let conn = Connection::open_in_memory().unwrap();
+		init(&conn).unwrap();
+		let stamp = stamp(&a).unwrap();
+		assert_eq!(stored_with(&conn, &a, HashAlgorithm::Sha256, stamp).unwrap(), None);
+		store_with(&conn, &a, HashAlgorithm::Sha256, stamp, "digest").unwrap();
+		assert_eq!(stored_with(&conn, &a, HashAlgorithm::Sha256, stamp).unwrap(), Some("digest".to_string()));
+		assert_eq!(stored_with(&conn, &a, HashAlgorithm::Sha256, (stamp.0 + 1, stamp.1)).unwrap(), None);
+		assert_eq!(stored_with(&conn, &a, HashAlgorithm::Blake3, stamp).unwrap(), None);
+		assert_eq!(candidates_with(&conn, &b, HashAlgorithm::Sha256, "digest").unwrap(), vec![a.clone()]);
+		assert!(candidates_with(&conn, &a, HashAlgorithm::Sha256, "digest").unwrap().is_empty());
+	}
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+/// Whether another process has the file at `path` open, e.g. because it's still downloading or being written.
+/// On Linux, the open files of the processes the current user can see are listed in `/proc`;
+/// on other unixes `lsof` is asked, and nothing is considered open if it isn't installed.
+/// On Windows, the file is in use if it can't be opened without sharing it.
+pub fn is_open<T: AsRef<Path>>(path: T) -> bool {
+	let path = path.as_ref();
+	if !path.exists() {
+		return false;
+	}
+	probe(path)
+}
+
+#[cfg(target_os = "linux")]
+fn probe(path: &Path) -> bool {
+	use std::fs;
+
+	let target = match path.canonicalize() {
+		Ok(target) => target,
+		Err(_) => return false,
+	};
+	let own = std::process::id().to_string();
+	let processes = match fs::read_dir("/proc") {
+		Ok(processes) => processes,
+		Err(e) => {
+			log::debug!("could not list the processes: {}", e);
+			return false;
+		}
+	};
+	processes
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| {
+			let name = entry.file_name();
+			let name = name.to_string_lossy();
+			name.chars().all(|c| c.is_ascii_digit()) && name != own
+		})
+		// the descriptors of other users' processes can't be read, and are skipped
+		.filter_map(|entry| fs::read_dir(entry.path().join("fd")).ok())
+		.any(|descriptors| {
+			descriptors
+				.filter_map(|descriptor| descriptor.ok())
+				.any(|descriptor| fs::read_link(descriptor.path()).is_ok_and(|link| link == target))
+		})
+}
+
+#[cfg(all(unix, not(target_os = "linux")))]
+fn probe(path: &Path) -> bool {
+	let output = match std::process::Command::new("lsof").arg("-t").arg("--").arg(path).output() {
+		Ok(output) => output,
+		Err(e) => {
+			log::debug!("could not run lsof to tell whether {} is open: {}", path.display(), e);
+			return false;
+		}
+	};
+	let own = std::process::id().to_string();
+	String::from_utf8_lossy(&output.stdout)
+		.lines()
+		.any(|pid| !pid.trim().is_empty() && pid.trim() != own)
+}
+
+#[cfg(windows)]
+fn probe(path: &Path) -> bool {
+	use std::os::windows::fs::OpenOptionsExt;
+
+	const ERROR_SHARING_VIOLATION: i32 = 32;
+	match std::fs::OpenOptions::new().read(true).share_mode(0).open(path) {
+		Ok(_) => false,
+		Err(e) => e.raw_os_error() == Some(ERROR_SHARING_VIOLATION),
+	}
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+	use std::{
+		fs,
+		process::Command,
+		thread,
+		time::{Duration, Instant},
+	};
+
+	use super::*;
+
+	#[test]
+	fn detect_files_open_elsewhere() {
+		let dir = tempfile::tempdir().unwrap();
+		let (open, closed) = (dir.path().join("open"), dir.path().join("closed"));
+		fs::write(&open, "").unwrap();
+		fs::write(&closed, "").unwrap();
+		// files open in this process don't count
+		let _file = fs::File::open(&closed).unwrap();
+		let mut child = Command::new("sh")
+			.arg("-c")
+			.arg("exec 3>>\"$0\"; sleep 10")
+			.arg(&open)
+			.spawn()
+			.unwrap();
+		let start = Instant::now();
+		while !is_open(&open) && start.elapsed() < Duration::from_secs(5) {
+			thread::sleep(Duration::from_millis(20));
+		}
+		let detected = is_open(&open);
+		child.kill().unwrap();
+		child.wait().unwrap();
+		assert!(detected);
+		assert!(!is_open(&closed));
+		assert!(!is_open(dir.path().join("missing")));
+	}
+}
@@ -0,0 +1,160 @@
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+
+use crate::{config::options::recursive::Recursive, with_cache};
+
+/// The files in a location and when they were last modified, in seconds since the epoch.
+/// A copy of it is kept in the database, so that what changed while nothing was watching the location can be found later.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot(HashMap<PathBuf, i64>);
+
+impl Snapshot {
+	pub fn scan<T: AsRef<Path>>(root: T, recursive: &Recursive) -> Self {
+		let files = recursive
+			.to_walker(root)
+			.into_iter()
+			.filter_map(|entry| entry.ok())
+			.filter(|entry| entry.file_type().is_file())
+			.filter_map(|entry| Some((entry.path().to_path_buf(), modified(&entry.metadata().ok()?.modified().ok()?))))
+			.collect();
+		Self(files)
+	}
+
+	/// The files in `current` that weren't in this snapshot, modified within `window` if there is one
+	pub fn added(&self, current: &Self, window: Option<Duration>) -> Vec<PathBuf> {
+		let cutoff = window
+			.and_then(|window| SystemTime::now().checked_sub(window))
+			.map(|cutoff| modified(&cutoff));
+		let mut added = current
+			.0
+			.iter()
+			.filter(|(path, time)| !self.0.contains_key(*path) && cutoff.is_none_or(|cutoff| **time >= cutoff))
+			.map(|(path, _)| path.clone())
+			.collect::<Vec<_>>();
+		added.sort();
+		added
+	}
+}
+
+fn modified(time: &SystemTime) -> i64 {
+	time.duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs() as i64)
+}
+
+fn init(conn: &Connection) -> Result<()> {
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS file_index (
+			root TEXT NOT NULL,
+			path TEXT NOT NULL,
+			modified INTEGER NOT NULL,
+			PRIMARY KEY (root, path)
+		)",
+		[],
+	)
+	.context("could not create the file index")?;
+	conn.execute("CREATE TABLE IF NOT EXISTS indexed_roots (root TEXT PRIMARY KEY)", [])
+		.context("could not create the file index")?;
+	Ok(())
+}
+
+/// The last snapshot saved for `root`, if one was ever saved
+pub fn load<T: AsRef<Path>>(root: T) -> Result<Option<Snapshot>> {
+	with_cache(init, |conn| load_with(conn, root.as_ref()))
+}
+
+fn load_with(conn: &Connection, root: &Path) -> Result<Option<Snapshot>> {
+	let indexed = conn
+		.prepare("SELECT 1 FROM indexed_roots WHERE root = ?1")?
+		.exists(params![root.to_string_lossy()])?;
+	if !indexed {
+		return Ok(None);
+	}
+	let mut statement = conn.prepare("SELECT path, modified FROM file_index WHERE root = ?1")?;
+	let files = statement
+		.query_map(params![root.to_string_lossy()], |row| {
+			Ok((PathBuf::from(row.get::<_, String>(0)?), row.get::<_, i64>(1)?))
+		})?
+		.collect::<rusqlite::Result<HashMap<_, _>>>()?;
+	Ok(Some(Snapshot(files)))
+}
+
+/// Replaces the snapshot of `root`
+pub fn save<T: AsRef<Path>>(root: T, snapshot: &Snapshot) -> Result<()> {
+	with_cache(init, |conn| save_with(conn, root.as_ref(), snapshot))
+}
+
+fn save_with(conn: &Connection, root: &Path, snapshot: &Snapshot) -> Result<()> {
+	let root = root.to_string_lossy();
+	let tx = conn.unchecked_transaction()?;
+	tx.execute("DELETE FROM file_index WHERE root = ?1", params![root])?;
+	tx.execute("INSERT OR IGNORE INTO indexed_roots (root) VALUES (?1)", params![root])?;
+	{
+		let mut statement = tx.prepare("INSERT INTO file_index (root, path, modified) VALUES (?1, ?2, ?3)")?;
+		for (path, modified) in snapshot.0.iter() {
+			statement.execute(params![root, path.to_string_lossy(), modified])?;
+		}
+	}
+	tx.commit().context("could not save the file index")
+}
+
+/// Records that `path`, under `root`, now exists (or no longer does), without rescanning the whole location
+pub fn update<T: AsRef<Path>>(root: T, path: &Path) -> Result<()> {
+	with_cache(init, |conn| {
+		let root = root.as_ref().to_string_lossy();
+		match path.metadata().and_then(|metadata| metadata.modified()) {
+			Ok(time) => conn.execute(
+				"INSERT OR REPLACE INTO file_index (root, path, modified) VALUES (?1, ?2, ?3)",
+				params![root, path.to_string_lossy(), modified(&time)],
+			)?,
+			Err(_) => conn.execute(
+				"DELETE FROM file_index WHERE root = ?1 AND path = ?2",
+				params![root, path.to_string_lossy()],
+			)?,
+		};
+		Ok(())
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	#[test]
+	fn find_added_files() {
+		let dir = tempfile::tempdir().unwrap();
+		let recursive = Recursive { depth: Some(0) };
+		fs::write(dir.path().join("a"), "").unwrap();
+		let conn = Connection::open_in_memory().unwrap();
+		init(&conn).unwrap();
+		save_with(&conn, dir.path(), &Snapshot::scan(dir.path(), &recursive)).unwrap();
+
+		fs::create_dir(dir.path().join("sub")).unwrap();
+		fs::write(dir.path().join("sub").join("b"), "").unwrap();
+		let old = dir.path().join("c");
+		fs::write(&old, "").unwrap();
+		fs::File::options()
+			.write(true)
+			.open(&old)
+			.unwrap()
+			.set_modified(SystemTime::now() - Duration::from_secs(3600))
+			.unwrap();
+
+		let previous = load_with(&conn, dir.path()).unwrap().unwrap();
+		let current = Snapshot::scan(dir.path(), &recursive);
+		assert_eq!(previous.added(&current, None).len(), 2);
+		assert_eq!(
+			previous.added(&current, Some(Duration::from_secs(60))),
+			vec![dir.path().join("sub").join("b")]
+		);
+		assert_eq!(load_with(&conn, Path::new("/elsewhere")).unwrap(), None);
+		save_with(&conn, Path::new("/empty"), &Snapshot::default()).unwrap();
+		assert_eq!(load_with(&conn, Path::new("/empty")).unwrap(), Some(Snapshot::default()));
+	}
+}
@@ -0,0 +1,638 @@
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	str::FromStr,
+	sync::{
+		atomic::{AtomicBool, AtomicUsize, Ordering},
+		Mutex,
+	},
+	time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Local, TimeZone};
+use lazy_static::lazy_static;
+use rusqlite::{params, Connection};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use strum_macros::{Display, EnumString};
+
+use crate::{
+	config::{actions::ActionType, filters::deserialize_duration},
+	corrections::{self, Correction, Kind},
+	path::{stored, StoredPath},
+	with_db,
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// how many entries were added since the journal was last pruned
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+/// the journal is pruned every this many entries, so that a long-running `watch` stays within its retention
+const PRUNE_EVERY: usize = 100;
+/// entries are written in batches of this many, see [`checkpoint_every`]
+static CHECKPOINT: AtomicUsize = AtomicUsize::new(1);
+/// how many entries were written since the journal was enabled
+static WRITTEN: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+	static ref RETENTION: Mutex<Retention> = Mutex::new(Retention::default());
+	/// entries that will be written with the next batch
+	static ref PENDING: Mutex<Vec<Entry>> = Mutex::new(Vec::new());
+}
+
+#[cfg(any(test, feature = "test-util"))]
+lazy_static! {
+	/// entries kept in memory instead of being written, while the test harness is capturing them
+	static ref CAPTURED: Mutex<Option<Vec<Entry>>> = Mutex::new(None);
+}
+
+/// How much of the journal is kept, set under `[journal]` in the config. Everything is kept by default.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Retention {
+	/// only the most recent entries are kept
+	#[serde(default)]
+	pub max_entries: Option<usize>,
+	/// entries older than this are removed, e.g. `90d`
+	#[serde(default, deserialize_with = "deserialize_duration")]
+	pub max_age: Option<Duration>,
+}
+
+impl Retention {
+	/// Fills the unset limits with those in `fallback`
+	pub fn or(&self, fallback: &Self) -> Self {
+		Self {
+			max_entries: self.max_entries.or(fallback.max_entries),
+			max_age: self.max_age.or(fallback.max_age),
+		}
+	}
+}
+
+/// An action carried out on a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+	pub time: DateTime<Local>,
+	pub action: ActionType,
+	pub from: PathBuf,
+	/// where the file ended up, if it still exists
+	pub to: Option<PathBuf>,
+	/// the sha256 of what was written to `to` by a copy or a hardlink, so that undoing it never removes a file edited since
+	pub hash: Option<String>,
+	/// the rule that carried out the action
+	pub rule: Option<String>,
+	/// what the action printed, e.g. the id of the ticket a script filed the document under
+	pub output: Option<String>,
+	/// the [run](crate::events::run_id) that carried out the action
+	pub run: Option<String>,
+}
+
+impl Entry {
+	pub fn new(action: ActionType, from: PathBuf, to: Option<PathBuf>) -> Self {
+		Self {
+			time: Local::now(),
+			action,
+			from,
+			to,
+			hash: None,
+			rule: None,
+			output: None,
+			run: None,
+		}
+	}
+
+	/// Reverts this action, refusing to if anything changed since it was carried out
+	pub fn undo(&self) -> Result<()> {
+		let to = self
+			.to
+			.as_deref()
+			.ok_or_else(|| anyhow!("{} of {} can't be undone", self.action, self.from.display()))?;
+		match self.action {
+			ActionType::Move => {
+				if !to.exists() {
+					bail!("{} no longer exists", to.display());
+				}
+				if self.from.exists() {
+					bail!("{} already exists", self.from.display());
+				}
+				if let Some(parent) = self.from.parent() {
+					fs::create_dir_all(parent).with_context(|| format!("could not create {}", parent.display()))?;
+				}
+				fs::rename(to, &self.from).with_context(|| format!("could not move {} back to {}", to.display(), self.from.display()))
+			}
+			ActionType::Copy | ActionType::Hardlink => {
+				let expected = self
+					.hash
+					.as_deref()
+					.ok_or_else(|| anyhow!("no hash was recorded for {}, so it's left in place", to.display()))?;
+				if !to.is_file() || hash(to)? != expected {
+					bail!("{} was changed after the {}, so it's left in place", to.display(), self.action);
+				}
+				fs::remove_file(to).with_context(|| format!("could not remove {}", to.display()))
+			}
+			ActionType::Symlink => {
+				if fs::read_link(to).ok().as_deref() != Some(self.from.as_path()) {
+					bail!("{} is no longer a link to {}, so it's left in place", to.display(), self.from.display());
+				}
+				fs::remove_file(to).with_context(|| format!("could not remove {}", to.display()))
+			}
+			// the video was left alone, only what was made or fetched for it goes
+			ActionType::Thumbnail | ActionType::Fetch => match fs::remove_file(to) {
+				Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e).with_context(|| format!("could not remove {}", to.display())),
+				_ => Ok(()),
+			},
+			_ => bail!("{} of {} can't be undone", self.action, self.from.display()),
+		}
+	}
+
+	/// Whether this action left the file at `path` where it is, or put it there, by the rule named `rule` if there's one
+	fn processed(&self, path: &Path, rule: Option<&str>) -> bool {
+		let touched = self.to.as_deref() == Some(path) || (self.from == path && IN_PLACE.contains(&self.action));
+		touched && rule.is_none_or(|rule| self.rule.as_deref() == Some(rule))
+	}
+}
+
+/// Actions that leave their source where it is, so that it still counts as processed afterwards
+const IN_PLACE: [ActionType; 7] = [
+	ActionType::Copy,
+	ActionType::Hardlink,
+	ActionType::Symlink,
+	ActionType::Echo,
+	ActionType::Script,
+	ActionType::Fetch,
+	ActionType::Thumbnail,
+];
+
+/// The sha256 of the contents of the file at `path`
+fn hash(path: &Path) -> Result<String> {
+	let mut file = fs::File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+	let mut hasher = Sha256::new();
+	std::io::copy(&mut file, &mut hasher).with_context(|| format!("could not read {}", path.display()))?;
+	Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Format {
+	Csv,
+	Json,
+}
+
+/// Starts writing every action carried out to the journal, keeping it within `retention`.
+/// Nothing is written otherwise, so that tests and commands that don't act on files leave it alone.
+pub fn enable(retention: Retention) {
+	*RETENTION.lock().unwrap_or_else(|e| e.into_inner()) = retention;
+	ENABLED.store(true, Ordering::Relaxed);
+	if let Err(e) = with_db(init, prune) {
+		log::warn!("could not prune the journal: {:?}", e);
+	}
+}
+
+/// Writes entries in batches of `count` instead of one at a time, logging the progress after each of them.
+/// Large runs spend much less time on the journal that way, while an interruption loses at most the last batch.
+/// Whatever is left is written by [`flush`].
+pub fn checkpoint_every(count: usize) {
+	CHECKPOINT.store(count.max(1), Ordering::Relaxed);
+}
+
+/// Adds an entry to the journal, if it's enabled
+pub fn append(mut entry: Entry) {
+	if !ENABLED.load(Ordering::Relaxed) && !capturing() {
+		return;
+	}
+	if let (ActionType::Copy | ActionType::Hardlink, Some(to)) = (entry.action, &entry.to) {
+		if to.is_file() {
+			entry.hash = hash(to).map_err(|e| log::warn!("{:?}", e)).ok();
+		}
+	}
+	#[cfg(any(test, feature = "test-util"))]
+	if let Some(captured) = CAPTURED.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+		captured.push(entry);
+		return;
+	}
+	let mut pending = PENDING.lock().unwrap_or_else(|e| e.into_inner());
+	pending.push(entry);
+	if pending.len() >= CHECKPOINT.load(Ordering::Relaxed) {
+		write(&mut pending);
+	}
+}
+
+/// Keeps the entries appended from now on in memory instead of writing them, until [`captured`] is called
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) fn capture() {
+	*CAPTURED.lock().unwrap_or_else(|e| e.into_inner()) = Some(Vec::new());
+}
+
+/// Stops capturing, and returns the entries that were appended since [`capture`]
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) fn captured() -> Vec<Entry> {
+	CAPTURED.lock().unwrap_or_else(|e| e.into_inner()).take().unwrap_or_default()
+}
+
+#[cfg(any(test, feature = "test-util"))]
+fn capturing() -> bool {
+	CAPTURED.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+#[cfg(not(any(test, feature = "test-util")))]
+fn capturing() -> bool {
+	false
+}
+
+/// Writes the entries that are still waiting for their batch to fill up
+pub fn flush() {
+	write(&mut PENDING.lock().unwrap_or_else(|e| e.into_inner()));
+}
+
+/// Writes `pending` in a single transaction. The lock on it is held meanwhile so that batches are written in order.
+fn write(pending: &mut Vec<Entry>) {
+	if pending.is_empty() {
+		return;
+	}
+	let batch = std::mem::take(pending);
+	let result = with_db(init, |conn| {
+		let tx = conn.unchecked_transaction()?;
+		for entry in &batch {
+			insert(&tx, entry)?;
+		}
+		tx.commit().context("could not write to the journal")?;
+		if WRITES.fetch_add(batch.len(), Ordering::Relaxed) + batch.len() >= PRUNE_EVERY {
+			WRITES.store(0, Ordering::Relaxed);
+			prune(conn)?;
+		}
+		Ok(())
+	});
+	match result {
+		Ok(_) => {
+			let written = WRITTEN.fetch_add(batch.len(), Ordering::Relaxed) + batch.len();
+			if CHECKPOINT.load(Ordering::Relaxed) > 1 {
+				log::info!("{} actions carried out so far", written);
+			}
+		}
+		Err(e) => log::warn!("could not write {} entries to the journal: {:?}", batch.len(), e),
+	}
+}
+
+pub(crate) fn init(conn: &Connection) -> Result<()> {
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS journal (
+			id INTEGER PRIMARY KEY AUTOINCREMENT,
+			time INTEGER NOT NULL,
+			action TEXT NOT NULL,
+			source TEXT NOT NULL,
+			destination TEXT,
+			hash TEXT,
+			rule TEXT,
+			output TEXT,
+			run TEXT
+		)",
+		[],
+	)
+	.context("could not create the journal")?;
+	// journals written before actions were attributed to rules and runs, or before their output was kept
+	for column in ["rule", "output", "run"] {
+		let exists = conn
+			.prepare("SELECT 1 FROM pragma_table_info('journal') WHERE name = ?1")?
+			.exists([column])?;
+		if !exists {
+			conn.execute(&format!("ALTER TABLE journal ADD COLUMN {} TEXT", column), [])
+				.context("could not update the journal")?;
+		}
+	}
+	// so that telling whether a file was processed doesn't go through the whole journal
+	conn.execute("CREATE INDEX IF NOT EXISTS journal_source ON journal (source)", [])
+		.context("could not create the journal")?;
+	conn.execute("CREATE INDEX IF NOT EXISTS journal_destination ON journal (destination)", [])
+		.context("could not create the journal")?;
+	Ok(())
+}
+
+pub(crate) fn insert(conn: &Connection, entry: &Entry) -> Result<()> {
+	conn.execute(
+		"INSERT INTO journal (time, action, source, destination, hash, rule, output, run) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+		params![
+			entry.time.timestamp(),
+			entry.action.to_string(),
+			stored(&entry.from),
+			entry.to.as_deref().map(stored),
+			entry.hash,
+			entry.rule,
+			entry.output,
+			entry.run
+		],
+	)
+	.context("could not write to the journal")?;
+	Ok(())
+}
+
+/// Removes the entries that fall outside the current retention
+fn prune(conn: &Connection) -> Result<()> {
+	let retention = RETENTION.lock().unwrap_or_else(|e| e.into_inner()).clone();
+	prune_with(conn, &retention)
+}
+
+fn prune_with(conn: &Connection, retention: &Retention) -> Result<()> {
+	if let Some(max_age) = retention.max_age {
+		let cutoff = Local::now().timestamp() - max_age.as_secs() as i64;
+		conn.execute("DELETE FROM journal WHERE time < ?1", params![cutoff])?;
+	}
+	if let Some(max_entries) = retention.max_entries {
+		conn.execute(
+			"DELETE FROM journal WHERE id NOT IN (SELECT id FROM journal ORDER BY id DESC LIMIT ?1)",
+			params![max_entries as i64],
+		)?;
+	}
+	Ok(())
+}
+
+/// Whether the journal has any action, by the rule named `rule` if there's one, that left the file at `path` where it is
+/// or put it there. While the test harness is capturing entries, only those count.
+pub fn processed<T: AsRef<Path>>(path: T, rule: Option<&str>) -> Result<bool> {
+	let path = path.as_ref();
+	#[cfg(any(test, feature = "test-util"))]
+	if let Some(captured) = CAPTURED.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+		return Ok(captured.iter().any(|entry| entry.processed(path, rule)));
+	}
+	if PENDING
+		.lock()
+		.unwrap_or_else(|e| e.into_inner())
+		.iter()
+		.any(|entry| entry.processed(path, rule))
+	{
+		return Ok(true);
+	}
+	with_db(init, |conn| processed_with(conn, path, rule))
+}
+
+fn processed_with(conn: &Connection, path: &Path, rule: Option<&str>) -> Result<bool> {
+	let in_place = IN_PLACE
+		.iter()
+		.map(|action| format!("'{}'", action))
+		.collect::<Vec<_>>()
+		.join(", ");
+	let processed = conn
+		.prepare(&format!(
+			"SELECT 1 FROM journal WHERE (destination = ?1 OR (source = ?1 AND action IN ({}))) AND (?2 IS NULL OR rule = ?2)",
+			in_place
+		))?
+		.exists(params![stored(path), rule])?;
+	Ok(processed)
+}
+
+/// Every entry in the journal, oldest first
+pub fn entries() -> Result<Vec<Entry>> {
+	flush();
+	with_db(init, |conn| Ok(read(conn)?.into_iter().map(|(_, entry)| entry).collect()))
+}
+
+pub(crate) fn read(conn: &Connection) -> Result<Vec<(i64, Entry)>> {
+	let mut statement = conn.prepare("SELECT id, time, action, source, destination, hash, rule, output, run FROM journal ORDER BY id")?;
+	let rows = statement
+		.query_map([], |row| {
+			Ok((
+				row.get::<_, i64>(0)?,
+				row.get::<_, i64>(1)?,
+				row.get::<_, String>(2)?,
+				row.get::<_, StoredPath>(3)?,
+				row.get::<_, Option<StoredPath>>(4)?,
+				row.get::<_, Option<String>>(5)?,
+				row.get::<_, Option<String>>(6)?,
+				row.get::<_, Option<String>>(7)?,
+				row.get::<_, Option<String>>(8)?,
+			))
+		})?
+		.collect::<rusqlite::Result<Vec<_>>>()?;
+	rows.into_iter()
+		.map(|(id, time, action, from, to, hash, rule, output, run)| {
+			let entry = Entry {
+				time: Local.timestamp_opt(time, 0).single().unwrap_or_else(Local::now),
+				action: ActionType::from_str(&action).map_err(|_| anyhow!("unknown action `{}` in the journal", action))?,
+				from: from.0,
+				to: to.map(|to| to.0),
+				hash,
+				rule,
+				output,
+				run,
+			};
+			Ok((id, entry))
+		})
+		.collect()
+}
+
+/// Undoes the last `count` actions in the journal, most recent first, and removes them from it.
+/// Stops at the first one that can't be undone, since the ones before it may depend on it.
+/// Each of them is recorded as a correction of the rule that carried it out, if it's known.
+pub fn undo(count: usize) -> Result<Vec<Entry>> {
+	flush();
+	with_db(init, |conn| undo_with(conn, count))
+}
+
+fn undo_with(conn: &Connection, count: usize) -> Result<Vec<Entry>> {
+	corrections::init(conn)?;
+	let mut undone = Vec::with_capacity(count);
+	// deferred rules didn't do anything that could be undone
+	let entries = read(conn)?
+		.into_iter()
+		.rev()
+		.filter(|(_, entry)| entry.action != ActionType::Defer);
+	for (id, entry) in entries.take(count) {
+		entry.undo()?;
+		conn.execute("DELETE FROM journal WHERE id = ?1", params![id])?;
+		if entry.rule.is_some() {
+			corrections::insert(conn, &Correction::new(Kind::Undone, &entry, None), None)?;
+		}
+		undone.push(entry);
+	}
+	Ok(undone)
+}
+
+/// Applies `retention` to the journal right away
+pub fn prune_now(retention: &Retention) -> Result<()> {
+	with_db(init, |conn| prune_with(conn, retention))
+}
+
+pub fn export(entries: &[Entry], format: Format) -> String {
+	match format {
+		Format::Csv => {
+			let quote = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+			let mut out = String::from("time,action,from,to,hash,output,run\n");
+			for entry in entries {
+				out.push_str(&format!(
+					"{},{},{},{},{},{},{}\n",
+					entry.time.to_rfc3339(),
+					entry.action,
+					quote(&entry.from.to_string_lossy()),
+					entry.to.as_ref().map(|to| quote(&to.to_string_lossy())).unwrap_or_default(),
+					entry.hash.as_deref().unwrap_or_default(),
+					entry.output.as_deref().map(quote).unwrap_or_default(),
+					entry.run.as_deref().unwrap_or_default()
+				));
+			}
+			out
+		}
+		Format::Json => {
+			let entries = entries
+				.iter()
+				.map(|entry| {
+					json!({
+						"time": entry.time.to_rfc3339(),
+						"action": entry.action.to_string(),
+						"from": entry.from,
+						"to": entry.to,
+						"hash": entry.hash,
+						"output": entry.output,
+						"run": entry.run,
+					})
+				})
+				.collect::<Vec<_>>();
+			format!("{:#}\n", serde_json::Value::Array(entries))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn entry(age: i64, from: &str) -> Entry {
+		Entry {
+			time: Local.timestamp_opt(Local::now().timestamp() - age, 0).unwrap(),
+			..Entry::new(ActionType::Move, from.into(), Some("/docs/a, \"b\".pdf".into()))
+		}
+	}
+
+	#[test]
+	fn prune_by_age_and_count() {
+		let conn = Connection::open_in_memory().unwrap();
+		init(&conn).unwrap();
+		for (age, from) in [(1000, "/old"), (30, "/a"), (20, "/b"), (10, "/c")] {
+			insert(&conn, &entry(age, from)).unwrap();
+		}
+		let retention = Retention {
+			max_entries: None,
+			max_age: Some(Duration::from_secs(100)),
+		};
+		prune_with(&conn, &retention).unwrap();
+		assert_eq!(read(&conn).unwrap().len(), 3);
+		let retention = Retention {
+			max_entries: Some(2),
+			max_age: None,
+		};
+		prune_with(&conn, &retention).unwrap();
+		let left = read(&conn)
+			.unwrap()
+			.into_iter()
+			.map(|(_, entry)| entry.from)
+			.collect::<Vec<_>>();
+		assert_eq!(left, vec![PathBuf::from("/b"), PathBuf::from("/c")]);
+	}
+
+	#[test]
+	fn find_processed_files() {
+		let conn = Connection::open_in_memory().unwrap();
+		init(&conn).unwrap();
+		let moved = Entry {
+			rule: Some("import".into()),
+			..Entry::new(ActionType::Move, "/in/a.pdf".into(), Some("/docs/a.pdf".into()))
+		};
+		insert(&conn, &moved).unwrap();
+		insert(&conn, &Entry::new(ActionType::Copy, "/in/b.pdf".into(), Some("/backup/b.pdf".into()))).unwrap();
+		assert!(processed_with(&conn, Path::new("/docs/a.pdf"), None).unwrap());
+		assert!(processed_with(&conn, Path::new("/docs/a.pdf"), Some("import")).unwrap());
+		assert!(!processed_with(&conn, Path::new("/docs/a.pdf"), Some("backup")).unwrap());
+		// whatever arrives where a file was moved from is new
+		assert!(!processed_with(&conn, Path::new("/in/a.pdf"), None).unwrap());
+		assert!(processed_with(&conn, Path::new("/in/b.pdf"), None).unwrap());
+		assert!(!processed_with(&conn, Path::new("/in/c.pdf"), None).unwrap());
+		assert!(moved.processed(Path::new("/docs/a.pdf"), Some("import")));
+		assert!(!moved.processed(Path::new("/in/a.pdf"), None));
+	}
+
+	#[test]
+	fn export_csv_and_json() {
+		let entries = vec![
+			entry(0, "/in/a.pdf"),
+			Entry {
+				output: Some("ticket 42".into()),
+				run: Some("67e55044-10b1-426f-9247-bb680e5fe0c8".into()),
+				..entry(0, "/in/b.pdf")
+			},
+		];
+		let csv = export(&entries, Format::Csv);
+		assert!(csv.contains(",move,\"/in/a.pdf\",\"/docs/a, \"\"b\"\".pdf\",,,\n"));
+		assert!(csv.ends_with(",\"ticket 42\",67e55044-10b1-426f-9247-bb680e5fe0c8\n"));
+		let json: serde_json::Value = serde_json::from_str(&export(&entries, Format::Json)).unwrap();
+		assert_eq!(json[0]["from"], "/in/a.pdf");
+		assert_eq!(json[1]["output"], "ticket 42");
+		assert_eq!(json[1]["run"], "67e55044-10b1-426f-9247-bb680e5fe0c8");
+	}
+
+	#[test]
+	fn undo_checks_hashes() {
+		let dir = tempfile::tempdir().unwrap();
+		let (original, copy, edited, moved) = (
+			dir.path().join("a.txt"),
+			dir.path().join("b.txt"),
+			dir.path().join("c.txt"),
+			dir.path().join("d.txt"),
+		);
+		fs::write(&original, "a").unwrap();
+		fs::write(&copy, "a").unwrap();
+		fs::write(&edited, "a").unwrap();
+		fs::write(&moved, "d").unwrap();
+		let conn = Connection::open_in_memory().unwrap();
+		init(&conn).unwrap();
+		for (action, from, to) in [
+			(ActionType::Move, dir.path().join("e.txt"), &moved),
+			(ActionType::Copy, original.clone(), &edited),
+			(ActionType::Copy, original.clone(), &copy),
+		] {
+			let entry = Entry {
+				hash: Some(hash(&original).unwrap()),
+				..Entry::new(action, from, Some(to.clone()))
+			};
+			insert(&conn, &entry).unwrap();
+		}
+		fs::write(&edited, "edited").unwrap();
+		assert_eq!(read(&conn).unwrap()[0].1.run, None);
+
+		assert_eq!(undo_with(&conn, 1).unwrap().len(), 1);
+		assert!(!copy.exists() && original.exists());
+		assert!(undo_with(&conn, 2).is_err());
+		assert!(edited.exists());
+		assert_eq!(read(&conn).unwrap().len(), 2);
+	}
+
+	#[test]
+	fn undo_moves_of_paths_that_arent_unicode() {
+		use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+		let dir = tempfile::tempdir().unwrap();
+		let (from, to) = (
+			dir.path().join(OsStr::from_bytes(b"caf\xe9.txt")),
+			dir.path().join(OsStr::from_bytes(b"th\xe9.txt")),
+		);
+		fs::write(&to, "a").unwrap();
+		let conn = Connection::open_in_memory().unwrap();
+		init(&conn).unwrap();
+		insert(&conn, &Entry::new(ActionType::Move, from.clone(), Some(to.clone()))).unwrap();
+		assert!(processed_with(&conn, &to, None).unwrap());
+		assert!(!processed_with(&conn, &dir.path().join("th\u{fffd}.txt"), None).unwrap());
+		assert_eq!(read(&conn).unwrap()[0].1.from, from);
+		undo_with(&conn, 1).unwrap();
+		assert!(from.exists() && !to.exists());
+	}
+
+	#[test]
+	fn undo_symlink() {
+		let dir = tempfile::tempdir().unwrap();
+		let (original, link, other) = (dir.path().join("a"), dir.path().join("b"), dir.path().join("c"));
+		fs::write(&original, "a").unwrap();
+		std::os::unix::fs::symlink(&other, &link).unwrap();
+		let entry = Entry::new(ActionType::Symlink, original.clone(), Some(link.clone()));
+		assert!(entry.undo().is_err());
+		fs::remove_file(&link).unwrap();
+		std::os::unix::fs::symlink(&original, &link).unwrap();
+		entry.undo().unwrap();
+		assert!(fs::symlink_metadata(&link).is_err() && original.exists());
+	}
+}
@@ -0,0 +1,178 @@
+use std::{
+	fs,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{config::Config, settings, DB, PROJECT_NAME};
+
+/// Written to the state directory once the files of an older layout were moved, so that they're only looked for once
+const MIGRATED: &str = "layout-v2";
+
+/// The tables that used to be kept with the journal, and are rebuilt in the cache now
+const CACHE_TABLES: [&str; 3] = ["file_index", "indexed_roots", "hash_index"];
+
+/// Where organize keeps what it can't rebuild: the journal, the thumbnail register and the trash
+pub fn data_dir() -> Result<PathBuf> {
+	dir(settings::get().data_dir, dirs_next::data_local_dir(), "data_dir")
+}
+
+/// Where organize keeps what it can rebuild if it's removed: the scan index and the hash index
+pub fn cache_dir() -> Result<PathBuf> {
+	dir(settings::get().cache_dir, dirs_next::cache_dir(), "cache_dir")
+}
+
+/// Where organize keeps what only matters to the runs on this machine: the logs and whether runs are paused
+pub fn state_dir() -> Result<PathBuf> {
+	dir(settings::get().state_dir, platform_state_dir(), "state_dir")
+}
+
+/// `$XDG_STATE_HOME` or `~/.local/state` on Linux, and the data directory elsewhere since there's no such thing there
+fn platform_state_dir() -> Option<PathBuf> {
+	if cfg!(target_os = "linux") {
+		return std::env::var_os("XDG_STATE_HOME")
+			.map(PathBuf::from)
+			.filter(|dir| dir.is_absolute())
+			.or_else(|| dirs_next::home_dir().map(|home| home.join(".local").join("state")));
+	}
+	dirs_next::data_local_dir()
+}
+
+fn dir(configured: Option<PathBuf>, platform: Option<PathBuf>, setting: &str) -> Result<PathBuf> {
+	match configured {
+		Some(dir) => Ok(dir),
+		None => platform
+			.map(|dir| dir.join(PROJECT_NAME))
+			.ok_or_else(|| anyhow!("could not determine the directory, please set `{}` in the settings", setting)),
+	}
+}
+
+pub fn logs_dir() -> Result<PathBuf> {
+	Ok(state_dir()?.join("logs"))
+}
+
+/// Every location organize uses, by name, in the order `organize paths` prints them
+pub fn all() -> Result<Vec<(&'static str, PathBuf)>> {
+	Ok(vec![
+		("config", Config::default_path()),
+		("settings", settings::path()),
+		("data", data_dir()?),
+		("database", data_dir()?.join("organize.db")),
+		("trash", data_dir()?.join(".trash")),
+		("plugins", data_dir()?.join("plugins")),
+		("cache", cache_dir()?),
+		("state", state_dir()?),
+		("logs", logs_dir()?),
+	])
+}
+
+/// Moves the files an older version of organize kept elsewhere to where they belong now, once,
+/// and returns what was moved where. It runs before the logger is set up, so that the old logs are moved before new ones are written.
+pub fn migrate() -> Result<Vec<(PathBuf, PathBuf)>> {
+	let (data, state) = (data_dir()?, state_dir()?);
+	if state.join(MIGRATED).exists() {
+		return Ok(Vec::new());
+	}
+	let mut moved = Vec::new();
+	move_path(&Config::default_dir().join("logs"), &state.join("logs"), &mut moved)?;
+	move_path(&data.join("logs").join("runs"), &state.join("logs").join("runs"), &mut moved)?;
+	move_path(&data.join("paused"), &state.join("paused"), &mut moved)?;
+	fs::remove_dir(data.join("logs")).ok();
+	if data.join("organize.db").exists() {
+		drop_cache_tables(&DB.lock().unwrap_or_else(|e| e.into_inner()))?;
+	}
+	fs::create_dir_all(&state).with_context(|| format!("could not create {}", state.display()))?;
+	fs::write(state.join(MIGRATED), "").with_context(|| format!("could not write to {}", state.display()))?;
+	Ok(moved)
+}
+
+/// Moves `from` to `to`, merging it into `to` if they're both directories. Files already at `to` are kept.
+fn move_path(from: &Path, to: &Path, moved: &mut Vec<(PathBuf, PathBuf)>) -> Result<()> {
+	if !from.exists() || from == to {
+		return Ok(());
+	}
+	if from.is_dir() && to.is_dir() {
+		for entry in fs::read_dir(from).with_context(|| format!("could not list {}", from.display()))? {
+			let entry = entry?;
+			move_path(&entry.path(), &to.join(entry.file_name()), moved)?;
+		}
+		// only succeeds if everything was moved
+		fs::remove_dir(from).ok();
+		return Ok(());
+	}
+	if to.exists() {
+		return Ok(());
+	}
+	if let Some(parent) = to.parent() {
+		fs::create_dir_all(parent).with_context(|| format!("could not create {}", parent.display()))?;
+	}
+	if fs::rename(from, to).is_err() {
+		// across filesystems
+		if from.is_dir() {
+			fs::create_dir_all(to).with_context(|| format!("could not create {}", to.display()))?;
+			return move_path(from, to, moved);
+		}
+		fs::copy(from, to).with_context(|| format!("could not copy {} to {}", from.display(), to.display()))?;
+		fs::remove_file(from).with_context(|| format!("could not remove {}", from.display()))?;
+	}
+	moved.push((from.to_path_buf(), to.to_path_buf()));
+	Ok(())
+}
+
+/// The scan and hash indices are rebuilt in the cache, so they're dropped instead of being copied over
+fn drop_cache_tables(conn: &rusqlite::Connection) -> Result<()> {
+	for table in CACHE_TABLES {
+		conn.execute(&format!("DROP TABLE IF EXISTS {}", table), [])
+			.with_context(|| format!("could not remove the old {} table", table))?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn move_old_files() {
+		let dir = tempfile::tempdir().unwrap();
+		let (old, new) = (dir.path().join("config/logs"), dir.path().join("state/logs"));
+		fs::create_dir_all(old.join("runs")).unwrap();
+		fs::write(old.join("errors.log"), "old errors").unwrap();
+		fs::write(old.join("runs/first.log"), "first").unwrap();
+		fs::create_dir_all(&new).unwrap();
+		fs::write(new.join("errors.log"), "new errors").unwrap();
+		let mut moved = Vec::new();
+		move_path(&old, &new, &mut moved).unwrap();
+		assert_eq!(moved, vec![(old.join("runs"), new.join("runs"))]);
+		assert_eq!(fs::read_to_string(new.join("runs/first.log")).unwrap(), "first");
+		// what's already there wins
+		assert_eq!(fs::read_to_string(new.join("errors.log")).unwrap(), "new errors");
+		assert!(old.join("errors.log").exists());
+		assert!(!old.join("runs").exists());
+
+		let marker = dir.path().join("paused");
+		fs::write(&marker, "").unwrap();
+		move_path(&marker, &dir.path().join("state/paused"), &mut moved).unwrap();
+		assert!(!marker.exists());
+		assert!(dir.path().join("state/paused").exists());
+		move_path(&dir.path().join("missing"), &new, &mut moved).unwrap();
+		assert_eq!(moved.len(), 2);
+	}
+
+	#[test]
+	fn drop_old_cache_tables() {
+		let conn = rusqlite::Connection::open_in_memory().unwrap();
+		conn.execute("CREATE TABLE hash_index (path TEXT)", []).unwrap();
+		conn.execute("CREATE TABLE journal (path TEXT)", []).unwrap();
+		drop_cache_tables(&conn).unwrap();
+		let tables = conn
+			.prepare("SELECT name FROM sqlite_master WHERE type = 'table'")
+			.unwrap()
+			.query_map([], |row| row.get::<_, String>(0))
+			.unwrap()
+			.collect::<rusqlite::Result<Vec<_>>>()
+			.unwrap();
+		assert_eq!(tables, vec!["journal".to_string()]);
+	}
+}
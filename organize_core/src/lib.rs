@@ -1,37 +1,108 @@
 use std::sync::{Arc, Mutex};
 
+use anyhow::Result;
 use lazy_static::lazy_static;
 use rusqlite::Connection;
 
 extern crate strum_macros;
 
-pub(crate) mod path {
+pub mod path {
 	pub(crate) use expand::*;
+	pub use identity::*;
 	pub(crate) use is_hidden::*;
+	pub(crate) use reserve::*;
+	pub(crate) use stored::*;
+	pub use update::renamed;
 	pub(crate) use update::*;
+	pub(crate) use validate::*;
+	pub(crate) use zone::*;
 
 	mod expand;
+	mod identity;
 	mod is_hidden;
+	mod reserve;
+	pub(crate) mod sandbox;
+	mod stored;
 	mod update;
+	mod validate;
+	mod zone;
 }
 
-pub(crate) mod string {
+pub mod string {
 	pub(crate) use capitalize::*;
+	pub(crate) use os::*;
 	pub(crate) use placeholder::*;
+	pub use placeholder::{render, substitute};
 
 	mod capitalize;
+	mod os;
 	mod placeholder;
 }
+pub mod audio;
+pub mod capacity;
 pub mod config;
+pub mod context;
+pub mod control;
+pub mod corrections;
+pub mod dimensions;
+pub mod elevation;
+pub mod events;
 pub mod file;
 mod fsa;
+pub mod hash_index;
+pub mod in_use;
+pub mod index;
+pub mod journal;
+pub mod layout;
 pub mod logger;
+pub mod media;
+pub mod memory;
+pub mod messages;
+pub mod mount;
+pub mod preflight;
+pub mod priority;
+pub mod profile;
+pub mod queue;
+pub mod register;
+pub mod report;
+pub mod resources;
+pub mod settings;
+pub mod suggest;
+pub mod summary;
+pub mod synthetic;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
+mod throttle;
+pub mod thumbnails;
 pub mod utils;
+pub mod variables;
 
 pub const PROJECT_NAME: &str = "organize";
 
 lazy_static! {
-	pub static ref DB: Arc<Mutex<Connection>> = Arc::new(Mutex::new(
-		Connection::open(dirs_next::data_local_dir().unwrap().join("organize").join("organize.db")).unwrap()
-	));
+	pub static ref DB: Arc<Mutex<Connection>> = Arc::new(Mutex::new({
+		let dir = layout::data_dir().unwrap();
+		std::fs::create_dir_all(&dir).ok();
+		Connection::open(dir.join("organize.db")).unwrap()
+	}));
+	/// what can be rebuilt if it's lost, kept apart from the journal
+	pub static ref CACHE: Arc<Mutex<Connection>> = Arc::new(Mutex::new({
+		let dir = layout::cache_dir().unwrap();
+		std::fs::create_dir_all(&dir).ok();
+		Connection::open(dir.join("cache.db")).unwrap()
+	}));
+}
+
+/// Runs `f` on the database, once `init` made sure the tables it needs exist
+pub(crate) fn with_db<T, F: FnOnce(&Connection) -> Result<T>>(init: fn(&Connection) -> Result<()>, f: F) -> Result<T> {
+	let conn = DB.lock().unwrap_or_else(|e| e.into_inner());
+	init(&conn)?;
+	f(&conn)
+}
+
+/// Like [`with_db`], on the cache database
+pub(crate) fn with_cache<T, F: FnOnce(&Connection) -> Result<T>>(init: fn(&Connection) -> Result<()>, f: F) -> Result<T> {
+	let conn = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+	init(&conn)?;
+	f(&conn)
 }
@@ -1,7 +1,6 @@
 use std::{
 	fmt::{Arguments, Display},
-	io::Write,
-	path::PathBuf,
+	path::{Path, PathBuf},
 	str::FromStr,
 };
 
@@ -15,22 +14,29 @@ use fern::{
 	Dispatch, FormatCallback, Output,
 };
 use lazy_static::lazy_static;
-use log::{Level, Record};
+use log::{Level, LevelFilter, Record};
 use regex::Regex;
 
-use crate::config::Config;
+use crate::{
+	events::{self, Event},
+	layout,
+};
 
 lazy_static! {
 	static ref COLORS: ColoredLevelConfig = Logger::colors();
 	static ref TIME_FORMAT: &'static str = "[%F][%T]";
-	pub static ref LOG_PATTERN: Regex =
-		Regex::new(r"(?P<timestamp>\[\d{4}?-\d{2}-\d{2}]\[\d{2}:\d{2}:\d{2}]) (?P<level>INFO|DEBUG|WARN|ERROR|TRACE): (?P<message>.+$)").unwrap();
+	pub static ref LOG_PATTERN: Regex = Regex::new(
+		r"(?P<timestamp>\[\d{4}?-\d{2}-\d{2}]\[\d{2}:\d{2}:\d{2}]) (?:\[(?P<run>[0-9a-f-]+)] )?(?P<level>INFO|DEBUG|WARN|ERROR|TRACE): (?P<message>.+$)"
+	)
+	.unwrap();
 }
 
 pub struct Log {
 	timestamp: NaiveDateTime,
 	level: Level,
 	message: String,
+	/// the run that wrote the message, which log files written before runs had ids don't say
+	pub run: Option<String>,
 }
 
 impl<T: AsRef<str>> From<T> for Log {
@@ -45,6 +51,7 @@ impl<T: AsRef<str>> From<T> for Log {
 			timestamp: NaiveDateTime::parse_from_str(timestamp, *TIME_FORMAT).unwrap(),
 			level: Level::from_str(level).unwrap(),
 			message: message.to_string(),
+			run: groups.name("run").map(|run| run.as_str().to_string()),
 		}
 	}
 }
@@ -88,6 +95,12 @@ impl Logger {
 		out.finish(format_args!("{}", Log::format(Self::time(), record.level(), message)))
 	}
 
+	/// Like [`plain_format`](Self::plain_format), with the id of the run, so that the log files can be matched with the journal
+	fn file_format(out: FormatCallback, message: &Arguments, record: &Record) {
+		let time = format!("{} [{}]", Self::time(), events::run_id());
+		out.finish(format_args!("{}", Log::format(time, record.level(), message)))
+	}
+
 	fn colored_format(out: FormatCallback, message: &Arguments, record: &Record) {
 		out.finish(format_args!(
 			"{}",
@@ -96,7 +109,7 @@ impl Logger {
 	}
 
 	fn path(level: Level) -> anyhow::Result<PathBuf> {
-		let dir = Config::default_dir().join("logs");
+		let dir = layout::logs_dir()?;
 		match level {
 			Level::Error | Level::Warn => Ok(dir.join("errors.log")),
 			Level::Info => Ok(dir.join("output.log")),
@@ -104,9 +117,44 @@ impl Logger {
 		}
 	}
 
-	fn build_dispatchers<T: Into<Output> + Write>(level: Level, no_color: bool, writer: T) -> anyhow::Result<(Dispatch, Dispatch)> {
-		let console_output = fern::Dispatch::new()
-			.filter(move |metadata| metadata.level() == level)
+	/// A new file under the data dir for the logs of this invocation only, named after when it started
+	pub fn run_log_path() -> anyhow::Result<PathBuf> {
+		let dir = layout::logs_dir()?.join("runs");
+		let name = format!("{}-{}.log", Local::now().format("%Y-%m-%dT%H-%M-%S"), std::process::id());
+		Ok(dir.join(name))
+	}
+
+	/// Opens one of the log files, creating its directory if needed
+	fn log_file(path: &Path) -> anyhow::Result<std::fs::File> {
+		match path.parent() {
+			None => return Err(anyhow::Error::msg("could not determine parent directory")),
+			Some(parent) => {
+				if !parent.as_os_str().is_empty() && !parent.exists() {
+					std::fs::create_dir_all(parent)?;
+				}
+			}
+		}
+		Ok(fern::log_file(path)?)
+	}
+
+	/// Writes `levels` to the shared log file they belong to
+	fn shared_file(levels: &'static [Level]) -> anyhow::Result<Dispatch> {
+		let path = Self::path(levels[0])?;
+		Ok(fern::Dispatch::new()
+			.filter(move |metadata| levels.contains(&metadata.level()))
+			.chain(Self::log_file(&path)?))
+	}
+
+	/// `verbosity` is the number of times `-v` was passed: debug messages are shown from 1, trace messages from 2.
+	/// `filter` gives some subsystems their own level on top of that.
+	/// If `log_file` is given, every message of this invocation is written there instead of the shared log files.
+	pub fn setup(no_color: bool, verbosity: u8, filter: &LogFilter, log_file: Option<&Path>) -> Result<(), anyhow::Error> {
+		let level = filter.level.unwrap_or(match verbosity {
+			0 => LevelFilter::Info,
+			1 => LevelFilter::Debug,
+			_ => LevelFilter::Trace,
+		});
+		let console = fern::Dispatch::new()
 			.format(move |out, args, record| {
 				if no_color {
 					Self::plain_format(out, args, record)
@@ -114,43 +162,113 @@ impl Logger {
 					Self::colored_format(out, args, record);
 				}
 			})
-			.chain(writer);
-
-		let file = Self::path(level).map(|path| -> anyhow::Result<Dispatch> {
-			match path.parent() {
-				None => return Err(anyhow::Error::msg("could not determine parent directory")),
-				Some(parent) => {
-					if !parent.exists() {
-						std::fs::create_dir_all(parent)?;
-					}
+			.chain(
+				fern::Dispatch::new()
+					.filter(|metadata| metadata.level() <= Level::Warn)
+					.chain(std::io::stderr()),
+			)
+			.chain(
+				fern::Dispatch::new()
+					.filter(|metadata| metadata.level() > Level::Warn)
+					.chain(std::io::stdout()),
+			);
+		// we don't want ANSI escape codes to be written to the log files
+		let files = fern::Dispatch::new().format(Self::file_format);
+		let files = match log_file {
+			Some(path) => files.chain(Self::log_file(path)?),
+			None => files
+				.chain(Self::shared_file(&[Level::Error, Level::Warn])?)
+				.chain(Self::shared_file(&[Level::Info])?)
+				.chain(Self::shared_file(&[Level::Debug, Level::Trace])?),
+		};
+
+		let mut dispatch = fern::Dispatch::new().level(level);
+		for (target, level) in filter.targets.iter() {
+			dispatch = dispatch.level_for(target.clone(), *level);
+		}
+		dispatch
+			.chain(console)
+			.chain(files)
+			.chain(Output::call(|record| {
+				if record.level() == Level::Error {
+					events::record(Event::Error {
+						message: record.args().to_string(),
+					})
 				}
-			}
-			Ok(fern::Dispatch::new()
-				.filter(move |metadata| metadata.level() == level)
-				.format(Self::plain_format) // we don't want ANSI escape codes to be written to the log file
-				.chain(fern::log_file(path)?))
-		})??;
-
-		Ok((console_output, file))
-	}
-
-	pub fn setup(no_color: bool) -> Result<(), anyhow::Error> {
-		let (info_stdout, info_file) = Self::build_dispatchers(Level::Info, no_color, std::io::stdout())?;
-		let (debug_stdout, debug_file) = Self::build_dispatchers(Level::Debug, no_color, std::io::stdout())?;
-		let (error_stderr, error_file) = Self::build_dispatchers(Level::Error, no_color, std::io::stderr())?;
-		let (warn_stderr, warn_file) = Self::build_dispatchers(Level::Warn, no_color, std::io::stderr())?;
-
-		fern::Dispatch::new()
-			.chain(info_stdout)
-			.chain(info_file)
-			.chain(debug_stdout)
-			.chain(debug_file)
-			.chain(error_stderr)
-			.chain(error_file)
-			.chain(warn_stderr)
-			.chain(warn_file)
+			}))
 			.apply()?;
 
 		Ok(())
 	}
 }
+
+/// Parts of organize that can be given their own level with `--log-filter`, and the modules they log from
+const SUBSYSTEMS: &[(&str, &[&str])] = &[
+	("engine", &["organize_core::file", "organize_core::events", "organize::cmd::run"]),
+	(
+		"templates",
+		&["organize_core::string", "organize_core::variables", "organize_core::context"],
+	),
+	("filters", &["organize_core::config::filters"]),
+	("actions", &["organize_core::config::actions", "organize_core::path"]),
+	("config", &["organize_core::config"]),
+	("journal", &["organize_core::journal"]),
+	(
+		"watch",
+		&["organize::cmd::watch", "organize_core::index", "organize_core::queue", "notify"],
+	),
+];
+
+/// Levels for single subsystems, e.g. `engine=debug,templates=trace`.
+/// Anything that isn't a subsystem is taken as a module path, and a level on its own replaces the one set by `-v`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LogFilter {
+	pub level: Option<LevelFilter>,
+	pub targets: Vec<(String, LevelFilter)>,
+}
+
+impl FromStr for LogFilter {
+	type Err = anyhow::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let mut filter = Self::default();
+		for directive in s.split(',').map(str::trim).filter(|directive| !directive.is_empty()) {
+			let parse = |level: &str| LevelFilter::from_str(level.trim()).map_err(|_| anyhow::anyhow!("unknown log level `{}`", level));
+			match directive.split_once('=') {
+				None => filter.level = Some(parse(directive)?),
+				Some((name, level)) => {
+					let (name, level) = (name.trim(), parse(level)?);
+					match SUBSYSTEMS.iter().find(|(subsystem, _)| *subsystem == name) {
+						Some((_, modules)) => filter.targets.extend(modules.iter().map(|module| (module.to_string(), level))),
+						None => filter.targets.push((name.to_string(), level)),
+					}
+				}
+			}
+		}
+		Ok(filter)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_log_filter() {
+		let filter = LogFilter::from_str("warn, templates=trace,organize_core::mount=debug").unwrap();
+		assert_eq!(filter.level, Some(LevelFilter::Warn));
+		assert!(filter
+			.targets
+			.contains(&("organize_core::string".to_string(), LevelFilter::Trace)));
+		assert_eq!(filter.targets.last().unwrap(), &("organize_core::mount".to_string(), LevelFilter::Debug));
+		assert!(LogFilter::from_str("engine=loud").is_err());
+	}
+
+	#[test]
+	fn parse_run_ids() {
+		let log = Log::from("[2024-01-31][18:30:00] [67e55044-10b1-426f-9247-bb680e5fe0c8] INFO: (move) a -> b");
+		assert_eq!(log.run.as_deref(), Some("67e55044-10b1-426f-9247-bb680e5fe0c8"));
+		assert_eq!(log.message, "(move) a -> b");
+		assert_eq!(Log::from("[2024-01-31][18:30:00] WARN: older line").run, None);
+	}
+}
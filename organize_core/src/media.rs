@@ -0,0 +1,123 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+	/// the release tags that usually follow the title in the name of a video, e.g. `Movie.2019.1080p.BluRay.x264`
+	pub(crate) static ref RELEASE_TAGS: Regex = Regex::new(
+		r"(?i)\b(2160p|1080p|720p|480p|4k|uhd|bluray|blu-ray|bdrip|brrip|web-?dl|webrip|hdtv|dvdrip|x26[45]|h\.?26[45]|hevc|proper|repack)\b"
+	)
+	.unwrap(); // a panic here indicates a compile-time bug
+	/// release groups and other annotations, e.g. `[group]`
+	pub(crate) static ref BRACKETS: Regex = Regex::new(r"\[[^\]]*\]").unwrap(); // a panic here indicates a compile-time bug
+	/// `Show S01E05`, `Show s1e5` or `Show S01E05E06`
+	static ref EPISODE: Regex = Regex::new(r"(?i)^(?P<show>.*?)\bS(?P<season>\d{1,2}) ?E(?P<episode>\d{1,3})(?: ?E\d{1,3})*\b(?P<rest>.*)$").unwrap(); // a panic here indicates a compile-time bug
+	/// `Show 1x05`
+	static ref CROSS: Regex = Regex::new(r"(?i)^(?P<show>.*?)\b(?P<season>\d{1,2})x(?P<episode>\d{2,3})\b(?P<rest>.*)$").unwrap(); // a panic here indicates a compile-time bug
+	static ref YEAR: Regex = Regex::new(r"\b(?:19|20)\d{2}\b").unwrap(); // a panic here indicates a compile-time bug
+}
+
+/// What the name of a movie or an episode says about it, e.g. `Show.S01E05.Pilot.1080p` or `Movie (2021)`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MediaName {
+	/// the title of the movie, or the name of the show
+	pub title: String,
+	pub year: Option<u16>,
+	pub season: Option<u16>,
+	pub episode: Option<u16>,
+	pub episode_title: Option<String>,
+}
+
+impl MediaName {
+	/// Parses the name of a file, without its extension
+	pub fn parse(name: &str) -> Self {
+		let name = BRACKETS.replace_all(name, " ").replace(['.', '_'], " ");
+		let episode = EPISODE.captures(&name).or_else(|| CROSS.captures(&name));
+		match episode {
+			Some(captures) => {
+				let (title, year) = title_and_year(&captures["show"]);
+				let episode_title = clean(without_tags(&captures["rest"]));
+				Self {
+					title,
+					year,
+					season: captures["season"].parse().ok(),
+					episode: captures["episode"].parse().ok(),
+					episode_title: (!episode_title.is_empty()).then_some(episode_title),
+				}
+			}
+			None => {
+				let (title, year) = title_and_year(without_tags(&name));
+				Self {
+					title,
+					year,
+					..Self::default()
+				}
+			}
+		}
+	}
+}
+
+/// `name` up to the first release tag, if anything comes before it
+fn without_tags(name: &str) -> &str {
+	match RELEASE_TAGS.find(name) {
+		Some(tag) if !clean(&name[..tag.start()]).is_empty() => &name[..tag.start()],
+		_ => name,
+	}
+}
+
+/// Splits the title from the year that follows it. The last year is taken, so that it can be part of the title
+/// as in `Blade Runner 2049 (2017)`.
+fn title_and_year(name: &str) -> (String, Option<u16>) {
+	let year = YEAR
+		.find_iter(name)
+		.filter(|year| !clean(&name[..year.start()]).is_empty())
+		.last();
+	match year {
+		Some(year) => (clean(&name[..year.start()]), year.as_str().parse().ok()),
+		None => (clean(name), None),
+	}
+}
+
+fn clean(s: &str) -> String {
+	s.split_whitespace()
+		.collect::<Vec<_>>()
+		.join(" ")
+		.trim_matches(|c: char| c.is_whitespace() || matches!(c, '-' | '(' | ')' | '[' | ']'))
+		.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_episodes() {
+		assert_eq!(
+			MediaName::parse("The.Show.S01E05.The.Pilot.1080p.WEB-DL.x264"),
+			MediaName {
+				title: "The Show".into(),
+				year: None,
+				season: Some(1),
+				episode: Some(5),
+				episode_title: Some("The Pilot".into()),
+			}
+		);
+		let name = MediaName::parse("[group] Some Show (2019) - 2x10 [720p]");
+		assert_eq!(
+			(name.title.as_str(), name.year, name.season, name.episode),
+			("Some Show", Some(2019), Some(2), Some(10))
+		);
+		assert_eq!(name.episode_title, None);
+		assert_eq!(MediaName::parse("show_s3e12").season, Some(3));
+	}
+
+	#[test]
+	fn parse_movies() {
+		let name = MediaName::parse("Movie (2021)");
+		assert_eq!((name.title.as_str(), name.year, name.season), ("Movie", Some(2021), None));
+		let name = MediaName::parse("Blade.Runner.2049.2017.2160p.UHD.BluRay");
+		assert_eq!((name.title.as_str(), name.year), ("Blade Runner 2049", Some(2017)));
+		let name = MediaName::parse("2001.A.Space.Odyssey.1968.1080p");
+		assert_eq!((name.title.as_str(), name.year), ("2001 A Space Odyssey", Some(1968)));
+		assert_eq!(MediaName::parse("holiday_video").title, "holiday video");
+	}
+}
@@ -0,0 +1,139 @@
+use std::path::PathBuf;
+
+use sysinfo::{ProcessExt, System, SystemExt};
+
+/// How many files are handled at a time once a location went over the memory budget, unless a chunk size was given
+const STREAMING_CHUNK: usize = 1000;
+/// Reading the resident memory of the process isn't free, so it's only checked every so many files
+const RSS_CHECK_EVERY: usize = 4096;
+
+/// Resident memory of this process in bytes, if the platform reports it
+pub fn rss() -> Option<u64> {
+	let pid = sysinfo::get_current_pid().ok()?;
+	let mut system = System::new();
+	system.refresh_process(pid);
+	system.process(pid).map(|process| process.memory())
+}
+
+/// How much of a location's listing is kept in memory before its files are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Budget {
+	/// once the buffered files or the whole process use more than this many bytes, files are handled in chunks
+	pub bytes: Option<u64>,
+	/// always handle files in chunks of this many, regardless of memory
+	pub chunk_size: Option<usize>,
+}
+
+/// The files of a location waiting to be handled.
+/// They're all listed before any of them is handled, so that the actions don't change a directory while it's being read,
+/// unless that goes over the [`Budget`], in which case they're handed out in chunks as they're listed.
+#[derive(Debug)]
+pub struct Chunks {
+	budget: Budget,
+	paths: Vec<PathBuf>,
+	/// approximate size of `paths`, in bytes
+	used: u64,
+	pushed: usize,
+	streaming: bool,
+}
+
+impl Chunks {
+	pub fn new(budget: Budget) -> Self {
+		Self {
+			budget,
+			paths: Vec::new(),
+			used: 0,
+			pushed: 0,
+			streaming: false,
+		}
+	}
+
+	/// Whether the budget was exceeded, and files are now handled as they're listed
+	pub fn is_streaming(&self) -> bool {
+		self.streaming
+	}
+
+	/// Buffers `path`, returning the files to handle right away if that fills a chunk
+	pub fn push(&mut self, path: PathBuf) -> Option<Vec<PathBuf>> {
+		self.used += (std::mem::size_of::<PathBuf>() + path.as_os_str().len()) as u64;
+		self.pushed += 1;
+		self.paths.push(path);
+		if !self.streaming && self.over_budget() {
+			self.streaming = true;
+			return Some(self.take());
+		}
+		let chunk_size = match (self.budget.chunk_size, self.streaming) {
+			(Some(chunk_size), _) => chunk_size.max(1),
+			(None, true) => STREAMING_CHUNK,
+			(None, false) => return None,
+		};
+		(self.paths.len() >= chunk_size).then(|| self.take())
+	}
+
+	/// The files that are left
+	pub fn finish(mut self) -> Vec<PathBuf> {
+		self.take()
+	}
+
+	fn take(&mut self) -> Vec<PathBuf> {
+		self.used = 0;
+		std::mem::take(&mut self.paths)
+	}
+
+	fn over_budget(&self) -> bool {
+		match self.budget.bytes {
+			Some(bytes) => self.used > bytes || (self.pushed.is_multiple_of(RSS_CHECK_EVERY) && rss().is_some_and(|rss| rss > bytes)),
+			None => false,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn paths(n: usize) -> impl Iterator<Item = PathBuf> {
+		(0..n).map(|i| PathBuf::from(format!("/downloads/{}", i)))
+	}
+
+	#[test]
+	fn buffer_everything_without_budget() {
+		let mut chunks = Chunks::new(Budget::default());
+		assert!(paths(5000).all(|path| chunks.push(path).is_none()));
+		assert_eq!(chunks.finish().len(), 5000);
+	}
+
+	#[test]
+	fn fixed_chunk_size() {
+		let mut chunks = Chunks::new(Budget {
+			bytes: None,
+			chunk_size: Some(2),
+		});
+		let sizes = paths(5)
+			.filter_map(|path| chunks.push(path))
+			.map(|chunk| chunk.len())
+			.collect::<Vec<_>>();
+		assert_eq!(sizes, vec![2, 2]);
+		assert_eq!(chunks.finish().len(), 1);
+	}
+
+	#[test]
+	fn stream_once_over_budget() {
+		let per_path = (std::mem::size_of::<PathBuf>() + "/downloads/0".len()) as u64;
+		let mut chunks = Chunks::new(Budget {
+			bytes: Some(per_path * 3),
+			chunk_size: None,
+		});
+		let mut paths = paths(STREAMING_CHUNK + 10);
+		assert!(paths.by_ref().take(3).all(|path| chunks.push(path).is_none()));
+		assert!(!chunks.is_streaming());
+		assert_eq!(chunks.push(paths.next().unwrap()).unwrap().len(), 4);
+		assert!(chunks.is_streaming());
+		let sizes = paths
+			.filter_map(|path| chunks.push(path))
+			.map(|chunk| chunk.len())
+			.collect::<Vec<_>>();
+		assert_eq!(sizes, vec![STREAMING_CHUNK]);
+		assert_eq!(chunks.finish().len(), 6);
+	}
+}
@@ -0,0 +1,203 @@
+use std::{path::PathBuf, sync::Mutex};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+
+use crate::config::Config;
+
+lazy_static! {
+	static ref CATALOG: Mutex<Messages> = Mutex::new(Messages::default());
+}
+
+/// What organize says about the files it handles, set under `[messages]` in the config.
+/// Each message is a template: `{action}`, `{from}` and `{to}` are replaced by the action and the paths it involved.
+///
+/// Messages can also be translated in `messages/<locale>.toml` in the config directory, which has the same keys.
+/// The locale is `locale`, or the language of `LC_ALL`, `LC_MESSAGES` or `LANG` if it isn't set.
+/// Messages set here take precedence over the translated ones, which take precedence over the built-in ones.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Messages {
+	#[serde(default)]
+	pub locale: Option<String>,
+	/// a file was moved, copied or linked
+	#[serde(default)]
+	pub acted: Option<String>,
+	/// a file was deleted or trashed
+	#[serde(default)]
+	pub acted_in_place: Option<String>,
+	/// an action marked with `dry_run` didn't run
+	#[serde(default)]
+	pub dry_run: Option<String>,
+	/// an action marked with `confirm` was declined
+	#[serde(default)]
+	pub declined: Option<String>,
+	/// a file was left alone because its destination was taken, in reports
+	#[serde(default)]
+	pub conflict: Option<String>,
+	/// the title of desktop notifications
+	#[serde(default)]
+	pub notification_title: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+	Acted,
+	ActedInPlace,
+	DryRun,
+	Declined,
+	Conflict,
+	NotificationTitle,
+}
+
+impl Message {
+	fn default_template(&self) -> &'static str {
+		match self {
+			Self::Acted => "({action}) {from} -> {to}",
+			Self::ActedInPlace => "({action}) {from}",
+			Self::DryRun => "(dry run) ({action}) {from}",
+			Self::Declined => "(skipped) ({action}) {from}",
+			Self::Conflict => "{from} was not moved, {to} already exists",
+			Self::NotificationTitle => "organize",
+		}
+	}
+}
+
+impl Messages {
+	/// Fills the unset messages with those in `fallback`
+	pub fn or(&self, fallback: &Self) -> Self {
+		Self {
+			locale: self.locale.clone().or_else(|| fallback.locale.clone()),
+			acted: self.acted.clone().or_else(|| fallback.acted.clone()),
+			acted_in_place: self.acted_in_place.clone().or_else(|| fallback.acted_in_place.clone()),
+			dry_run: self.dry_run.clone().or_else(|| fallback.dry_run.clone()),
+			declined: self.declined.clone().or_else(|| fallback.declined.clone()),
+			conflict: self.conflict.clone().or_else(|| fallback.conflict.clone()),
+			notification_title: self.notification_title.clone().or_else(|| fallback.notification_title.clone()),
+		}
+	}
+
+	fn template(&self, message: Message) -> &str {
+		let template = match message {
+			Message::Acted => &self.acted,
+			Message::ActedInPlace => &self.acted_in_place,
+			Message::DryRun => &self.dry_run,
+			Message::Declined => &self.declined,
+			Message::Conflict => &self.conflict,
+			Message::NotificationTitle => &self.notification_title,
+		};
+		template.as_deref().unwrap_or_else(|| message.default_template())
+	}
+
+	/// The locale to translate the messages to, e.g. `fr` for `fr_FR.UTF-8`
+	fn locale(&self) -> Option<String> {
+		let locale = self.locale.clone().or_else(|| {
+			["LC_ALL", "LC_MESSAGES", "LANG"]
+				.iter()
+				.filter_map(std::env::var_os)
+				.map(|var| var.to_string_lossy().to_string())
+				.find(|var| !var.is_empty())
+		})?;
+		let language = locale.split(['_', '.', '@']).next().unwrap_or_default();
+		(!language.is_empty() && language != "C" && language != "POSIX").then(|| language.to_string())
+	}
+
+	fn catalog_path(locale: &str) -> PathBuf {
+		Config::default_dir().join("messages").join(format!("{}.toml", locale))
+	}
+
+	/// Fills the unset messages with the translated ones, if there's a catalog for the locale
+	fn translated(&self) -> Result<Self> {
+		let path = match self.locale().map(|locale| Self::catalog_path(&locale)) {
+			Some(path) if path.exists() => path,
+			_ => return Ok(self.clone()),
+		};
+		let content = std::fs::read_to_string(&path).with_context(|| format!("could not read {}", path.display()))?;
+		let catalog: Self = toml::from_str(&content).with_context(|| format!("could not parse {}", path.display()))?;
+		Ok(self.or(&catalog))
+	}
+}
+
+/// Makes `messages` the ones used from now on, along with their translation
+pub fn set(messages: &Messages) {
+	let messages = messages.translated().unwrap_or_else(|e| {
+		log::warn!("{:?}", e);
+		messages.clone()
+	});
+	*CATALOG.lock().unwrap_or_else(|e| e.into_inner()) = messages;
+}
+
+/// Renders `message`, replacing each `{name}` in its template by its value in `values`
+pub fn format(message: Message, values: &[(&str, &dyn std::fmt::Display)]) -> String {
+	let catalog = CATALOG.lock().unwrap_or_else(|e| e.into_inner());
+	render(catalog.template(message), values)
+}
+
+fn render(template: &str, values: &[(&str, &dyn std::fmt::Display)]) -> String {
+	let mut rendered = String::with_capacity(template.len());
+	let mut rest = template;
+	// a single pass, so that a value containing something like `{to}` is left as it is
+	while let Some(start) = rest.find('{') {
+		rendered.push_str(&rest[..start]);
+		rest = &rest[start..];
+		let value = rest.find('}').and_then(|end| {
+			values
+				.iter()
+				.find(|(name, _)| *name == &rest[1..end])
+				.map(|(_, value)| (end, value))
+		});
+		match value {
+			Some((end, value)) => {
+				rendered.push_str(&value.to_string());
+				rest = &rest[end + 1..];
+			}
+			None => {
+				rendered.push('{');
+				rest = &rest[1..];
+			}
+		}
+	}
+	rendered.push_str(rest);
+	rendered
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn user_messages_override_translation() {
+		let user = Messages {
+			acted: Some("{from} => {to}".into()),
+			..Messages::default()
+		};
+		let translation = Messages {
+			acted: Some("{from} déplacé vers {to}".into()),
+			dry_run: Some("(essai) ({action}) {from}".into()),
+			..Messages::default()
+		};
+		let messages = user.or(&translation);
+		assert_eq!(render(messages.template(Message::Acted), &[("from", &"a"), ("to", &"b")]), "a => b");
+		assert_eq!(
+			render(messages.template(Message::DryRun), &[("action", &"move"), ("from", &"a")]),
+			"(essai) (move) a"
+		);
+		assert_eq!(messages.template(Message::Declined), "(skipped) ({action}) {from}");
+		assert_eq!(render("{from} -> {to} {other}", &[("from", &"{to}"), ("to", &"b")]), "{to} -> b {other}");
+	}
+
+	#[test]
+	fn language_of_locale() {
+		let locale = |locale: &str| {
+			Messages {
+				locale: Some(locale.into()),
+				..Messages::default()
+			}
+			.locale()
+		};
+		assert_eq!(locale("fr_FR.UTF-8").as_deref(), Some("fr"));
+		assert_eq!(locale("de").as_deref(), Some("de"));
+		assert_eq!(locale("C"), None);
+	}
+}
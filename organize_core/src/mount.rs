@@ -0,0 +1,197 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use sysinfo::{DiskExt, System, SystemExt};
+
+/// Triggers a rule when a volume matching these properties is mounted.
+/// The mount point is used as the location root for the rule.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct OnMount {
+	pub label: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Volume {
+	pub label: String,
+	pub mount_point: PathBuf,
+}
+
+impl Volume {
+	/// Lists the volumes that are currently mounted
+	pub fn mounted() -> Vec<Self> {
+		let mut system = System::new();
+		system.refresh_disks_list();
+		system
+			.disks()
+			.iter()
+			.map(|disk| Self::new(disk.mount_point(), disk.name().to_string_lossy()))
+			.collect()
+	}
+
+	/// Removable media are mounted under a directory named after their label (udisks, DiskArbitration),
+	/// so that's preferred over the device name when it's available
+	fn new<T: AsRef<Path>, S: Into<String>>(mount_point: T, name: S) -> Self {
+		let mount_point = mount_point.as_ref().to_path_buf();
+		let label = mount_point
+			.file_name()
+			.map(|name| name.to_string_lossy().to_string())
+			.unwrap_or_else(|| name.into());
+		Self { label, mount_point }
+	}
+
+	pub fn matches(&self, on_mount: &OnMount) -> bool {
+		self.label == on_mount.label
+	}
+}
+
+/// Information about the filesystem a path lives in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filesystem {
+	pub mount_point: PathBuf,
+	pub free_space: u64,
+}
+
+impl Filesystem {
+	/// Finds the filesystem `path` lives in, see [`Disks::of`]
+	pub fn of<T: AsRef<Path>>(path: T) -> Option<Self> {
+		Disks::query().of(path).cloned()
+	}
+}
+
+/// The filesystems that are mounted, listed once so that many paths can be looked up without asking the system each time
+pub struct Disks(Vec<Filesystem>);
+
+impl Disks {
+	pub fn query() -> Self {
+		let mut system = System::new();
+		system.refresh_disks_list();
+		Self(
+			system
+				.disks()
+				.iter()
+				.map(|disk| Filesystem {
+					mount_point: disk.mount_point().to_path_buf(),
+					free_space: disk.available_space(),
+				})
+				.collect(),
+		)
+	}
+
+	/// Finds the filesystem `path` lives in, i.e. the disk with the longest mount point that's an ancestor of it.
+	/// `path` doesn't need to exist, its closest existing ancestor is used instead.
+	pub fn of<T: AsRef<Path>>(&self, path: T) -> Option<&Filesystem> {
+		let path = path.as_ref().ancestors().find_map(|ancestor| ancestor.canonicalize().ok())?;
+		self.0
+			.iter()
+			.filter(|disk| path.starts_with(&disk.mount_point))
+			.max_by_key(|disk| disk.mount_point.components().count())
+	}
+}
+
+/// The directory at or above `path` where the filesystem it lives on is mounted, if it can be told apart from `/`
+pub fn mount_point<T: AsRef<Path>>(path: T) -> Option<PathBuf> {
+	let path = path.as_ref().ancestors().find_map(|ancestor| ancestor.canonicalize().ok())?;
+	path.ancestors()
+		.find(|ancestor| is_mount_point(ancestor))
+		.map(Path::to_path_buf)
+}
+
+/// Whether a filesystem is mounted at `dir`, i.e. it's on another device than its parent
+#[cfg(unix)]
+pub fn is_mount_point<T: AsRef<Path>>(dir: T) -> bool {
+	let dir = dir.as_ref();
+	match dir.parent() {
+		Some(parent) => device(dir).is_some_and(|device| Some(device) != self::device(parent)),
+		None => true,
+	}
+}
+
+#[cfg(unix)]
+fn device(path: &Path) -> Option<u64> {
+	use std::os::unix::fs::MetadataExt;
+	path.metadata().map(|metadata| metadata.dev()).ok()
+}
+
+#[cfg(not(unix))]
+pub fn is_mount_point<T: AsRef<Path>>(dir: T) -> bool {
+	dir.as_ref().parent().is_none()
+}
+
+/// Whether `path` sits on a filesystem that isn't mounted right now, so that what's there is the empty directory it's
+/// mounted over rather than its contents. That's the case if `expected`, where its filesystem was mounted the last time,
+/// or a mount point listed for it in `/etc/fstab`, is no longer a mount point.
+pub fn is_unmounted<T: AsRef<Path>>(path: T, expected: Option<&Path>) -> bool {
+	let path = path.as_ref();
+	expected
+		.into_iter()
+		.map(Path::to_path_buf)
+		.chain(fstab().into_iter().filter(|mount_point| path.starts_with(mount_point)))
+		.filter(|mount_point| mount_point.parent().is_some())
+		.any(|mount_point| !is_mount_point(mount_point))
+}
+
+/// The mount points listed in `/etc/fstab`
+fn fstab() -> Vec<PathBuf> {
+	if !cfg!(target_os = "linux") {
+		return Vec::new();
+	}
+	std::fs::read_to_string("/etc/fstab")
+		.map(|content| parse_fstab(&content))
+		.unwrap_or_default()
+}
+
+fn parse_fstab(content: &str) -> Vec<PathBuf> {
+	content
+		.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty() && !line.starts_with('#'))
+		.filter_map(|line| line.split_whitespace().nth(1))
+		.filter(|mount_point| mount_point.starts_with('/'))
+		// spaces in mount points are written as `\040`
+		.map(|mount_point| PathBuf::from(mount_point.replace("\\040", " ")))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn label_from_mount_point() {
+		let volume = Volume::new("/media/user/CAMERA_SD", "/dev/sdb1");
+		assert!(volume.matches(&OnMount { label: "CAMERA_SD".into() }));
+	}
+
+	#[test]
+	fn filesystem_of_missing_path() {
+		let dir = tempfile::tempdir().unwrap();
+		let existing = Filesystem::of(dir.path());
+		let missing = Filesystem::of(dir.path().join("missing").join("file.txt"));
+		assert_eq!(existing.map(|fs| fs.mount_point), missing.map(|fs| fs.mount_point));
+	}
+
+	#[test]
+	fn label_from_device_name() {
+		let volume = Volume::new("/", "/dev/sda1");
+		assert_eq!(volume.label, "/dev/sda1");
+	}
+
+	#[test]
+	fn read_fstab() {
+		let fstab = "# <file system> <mount point> <type>\nUUID=1234 / ext4 defaults 0 1\n\n//nas/share /mnt/my\\040nas cifs noauto 0 0\n/swapfile none swap sw 0 0\n";
+		assert_eq!(parse_fstab(fstab), [PathBuf::from("/"), PathBuf::from("/mnt/my nas")]);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn unmounted_share() {
+		let dir = tempfile::tempdir().unwrap();
+		let share = dir.path().join("nas");
+		std::fs::create_dir(&share).unwrap();
+		assert!(is_mount_point("/"));
+		assert!(!is_mount_point(&share));
+		// the share was mounted there before, but what's left is the directory it was mounted over
+		assert!(is_unmounted(share.join("inbox"), Some(&share)));
+		assert!(!is_unmounted(&share, None));
+	}
+}
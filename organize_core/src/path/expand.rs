@@ -1,3 +1,4 @@
+use crate::config::{constants, locations};
 use anyhow::{anyhow, Context, Result};
 use std::{
 	env,
@@ -17,7 +18,12 @@ pub trait Expand {
 
 impl<T: Into<PathBuf>> Expand for T {
 	fn expand_user(self) -> Result<PathBuf> {
-		let path = self.into();
+		let path: PathBuf = self.into();
+		let path = match path.to_str() {
+			Some(str) => PathBuf::from(constants::expand(str)?),
+			None => path,
+		};
+		let path = locations::expand(path)?;
 		let mut components = path.components();
 		if let Some(component) = components.next() {
 			if component.as_os_str() == OsStr::new("~") {
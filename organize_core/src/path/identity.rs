@@ -0,0 +1,39 @@
+use std::path::Path;
+
+/// Identifies a file independently of its path, so that it can be recognized after being renamed or moved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Identity {
+	dev: u64,
+	ino: u64,
+}
+
+impl Identity {
+	#[cfg(unix)]
+	pub fn of<T: AsRef<Path>>(path: T) -> Option<Self> {
+		use std::os::unix::fs::MetadataExt;
+		path.as_ref().metadata().ok().map(|metadata| Self {
+			dev: metadata.dev(),
+			ino: metadata.ino(),
+		})
+	}
+
+	#[cfg(not(unix))]
+	pub fn of<T: AsRef<Path>>(_path: T) -> Option<Self> {
+		None
+	}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn identity_survives_rename() {
+		let dir = tempfile::tempdir().unwrap();
+		let (from, to) = (dir.path().join("from.txt"), dir.path().join("to.txt"));
+		std::fs::write(&from, "").unwrap();
+		let identity = Identity::of(&from).unwrap();
+		std::fs::rename(&from, &to).unwrap();
+		assert_eq!(Identity::of(&to), Some(identity));
+	}
+}
@@ -8,11 +8,35 @@ impl IsHidden for Path {
 	fn is_hidden(&self) -> bool {
 		match self.file_name() {
 			None => false,
-			Some(filename) => filename.to_string_lossy().starts_with('.'),
+			Some(filename) => filename.to_string_lossy().starts_with('.') || has_hidden_attribute(self),
 		}
 	}
 }
 
+#[cfg(target_os = "windows")]
+fn has_hidden_attribute(path: &Path) -> bool {
+	use std::os::windows::fs::MetadataExt;
+	const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+	const FILE_ATTRIBUTE_SYSTEM: u32 = 0x4;
+	path.symlink_metadata()
+		.map(|metadata| metadata.file_attributes() & (FILE_ATTRIBUTE_HIDDEN | FILE_ATTRIBUTE_SYSTEM) != 0)
+		.unwrap_or_default()
+}
+
+#[cfg(target_os = "macos")]
+fn has_hidden_attribute(path: &Path) -> bool {
+	use std::os::macos::fs::MetadataExt;
+	const UF_HIDDEN: u32 = 0x8000;
+	path.symlink_metadata()
+		.map(|metadata| metadata.st_flags() & UF_HIDDEN != 0)
+		.unwrap_or_default()
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn has_hidden_attribute(_path: &Path) -> bool {
+	false
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -21,4 +45,10 @@ mod tests {
 		let path = Path::new(".testfile");
 		assert!(path.is_hidden())
 	}
+
+	#[test]
+	fn check_visible() {
+		let path = Path::new("testfile");
+		assert!(!path.is_hidden())
+	}
 }
@@ -0,0 +1,80 @@
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	sync::{Mutex, MutexGuard},
+};
+
+use derive_more::Deref;
+use lazy_static::lazy_static;
+
+lazy_static! {
+	// destinations currently handed out to in-flight actions, with the number of claims on each
+	static ref RESERVED: Mutex<HashMap<PathBuf, usize>> = Mutex::new(HashMap::new());
+}
+
+/// A destination path claimed by an in-flight action.
+/// While it's alive, no other worker will be handed the same name. The claim is released on drop.
+#[derive(Debug, Deref, PartialEq, Eq)]
+pub struct Reservation(PathBuf);
+
+impl Reservation {
+	pub(crate) fn registry() -> MutexGuard<'static, HashMap<PathBuf, usize>> {
+		RESERVED.lock().unwrap_or_else(|e| e.into_inner())
+	}
+
+	/// Whether `path` exists on disk or has been claimed by another worker
+	pub(crate) fn is_taken(registry: &HashMap<PathBuf, usize>, path: &Path) -> bool {
+		path.exists() || registry.contains_key(path)
+	}
+
+	/// Claims `path` using an already locked registry, so that lookup and claim happen atomically
+	pub(crate) fn claim(registry: &mut HashMap<PathBuf, usize>, path: PathBuf) -> Self {
+		*registry.entry(path.clone()).or_insert(0) += 1;
+		Self(path)
+	}
+}
+
+impl AsRef<Path> for Reservation {
+	fn as_ref(&self) -> &Path {
+		&self.0
+	}
+}
+
+impl Drop for Reservation {
+	fn drop(&mut self) {
+		let mut registry = Self::registry();
+		if let Some(count) = registry.get_mut(&self.0) {
+			*count -= 1;
+			if *count == 0 {
+				registry.remove(&self.0);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn release_on_drop() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("reserved.txt");
+		let reservation = Reservation::claim(&mut Reservation::registry(), path.clone());
+		assert!(Reservation::is_taken(&Reservation::registry(), &path));
+		drop(reservation);
+		assert!(!Reservation::is_taken(&Reservation::registry(), &path));
+	}
+
+	#[test]
+	fn shared_claims() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("shared.txt");
+		let first = Reservation::claim(&mut Reservation::registry(), path.clone());
+		let second = Reservation::claim(&mut Reservation::registry(), path.clone());
+		drop(first);
+		assert!(Reservation::is_taken(&Reservation::registry(), &path));
+		drop(second);
+		assert!(!Reservation::is_taken(&Reservation::registry(), &path));
+	}
+}
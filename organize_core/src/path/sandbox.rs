@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+#[cfg(any(test, feature = "test-util"))]
+use std::{cell::RefCell, path::Path};
+
+#[cfg(any(test, feature = "test-util"))]
+use anyhow::bail;
+use anyhow::Result;
+
+#[cfg(any(test, feature = "test-util"))]
+use crate::path::{normalize, Expand};
+
+#[cfg(any(test, feature = "test-util"))]
+thread_local! {
+	// test runs act on the thread that started them, so one doesn't confine the tests running next to it
+	static SANDBOX: RefCell<Option<Sandbox>> = const { RefCell::new(None) };
+}
+
+/// Keeps the actions of a test run inside its temporary tree: destinations inside the mapped directories are moved to the
+/// matching directory of the tree, and any other destination outside of it is refused.
+/// Scripts can still write anywhere, they're not looked into.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Clone)]
+pub(crate) struct Sandbox {
+	root: PathBuf,
+	/// real directories and the directory of the tree that stands for them, both absolute
+	roots: Vec<(PathBuf, PathBuf)>,
+	/// where trashed files go instead of organize's trash
+	trash: PathBuf,
+}
+
+/// Lifts the sandbox when it's dropped
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) struct Guard;
+
+#[cfg(any(test, feature = "test-util"))]
+impl Drop for Guard {
+	fn drop(&mut self) {
+		SANDBOX.with(|sandbox| sandbox.replace(None));
+	}
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl Sandbox {
+	pub(crate) fn new(root: &Path, roots: &[(PathBuf, PathBuf)], trash: PathBuf) -> Result<Self> {
+		let mut resolved = Vec::new();
+		for (from, to) in roots {
+			let from = from.clone().expand_user()?.expand_vars()?;
+			// destinations are rendered with `~` expanded but aren't canonicalized, so both spellings are mapped
+			if let Ok(canonical) = from.canonicalize() {
+				resolved.push((canonical, to.clone()));
+			}
+			resolved.push((normalize(&from), to.clone()));
+		}
+		Ok(Self {
+			root: root.canonicalize()?,
+			roots: resolved,
+			trash,
+		})
+	}
+
+	fn confine(&self, path: PathBuf) -> Result<PathBuf> {
+		let path = normalize(&path);
+		if resolve(&path).starts_with(&self.root) {
+			return Ok(path);
+		}
+		let rerooted = self
+			.roots
+			.iter()
+			.filter(|(from, _)| path.starts_with(from))
+			.max_by_key(|(from, _)| from.components().count())
+			.map(|(from, to)| to.join(path.strip_prefix(from).unwrap_or(&path)));
+		match rerooted {
+			Some(rerooted) if resolve(&rerooted).starts_with(&self.root) => Ok(rerooted),
+			_ => bail!("{} is outside of the test tree ({})", path.display(), self.root.display()),
+		}
+	}
+}
+
+/// Confines the actions of this thread to `sandbox` until the guard is dropped
+#[cfg(any(test, feature = "test-util"))]
+pub(crate) fn enter(sandbox: Sandbox) -> Guard {
+	SANDBOX.with(|current| current.replace(Some(sandbox)));
+	Guard
+}
+
+/// Where an action should write instead of `path`, which is `path` itself outside of a sandbox
+pub(crate) fn confine(path: PathBuf) -> Result<PathBuf> {
+	#[cfg(any(test, feature = "test-util"))]
+	if let Some(confined) = SANDBOX.with(|sandbox| sandbox.borrow().as_ref().map(|sandbox| sandbox.confine(path.clone()))) {
+		return confined;
+	}
+	Ok(path)
+}
+
+/// Where trashed files go instead of organize's trash, if a sandbox is in place
+pub(crate) fn trash() -> Option<PathBuf> {
+	#[cfg(any(test, feature = "test-util"))]
+	if let Some(trash) = SANDBOX.with(|sandbox| sandbox.borrow().as_ref().map(|sandbox| sandbox.trash.clone())) {
+		return Some(trash);
+	}
+	None
+}
+
+/// `path` with its deepest existing ancestor canonicalized, so that a symlink in the tree can't lead out of it
+#[cfg(any(test, feature = "test-util"))]
+fn resolve(path: &Path) -> PathBuf {
+	let mut existing = path;
+	let mut rest = Vec::new();
+	loop {
+		if let Ok(canonical) = existing.canonicalize() {
+			return rest.iter().rev().fold(canonical, |path, name| path.join(name));
+		}
+		match (existing.parent(), existing.file_name()) {
+			(Some(parent), Some(name)) => {
+				rest.push(name.to_os_string());
+				existing = parent;
+			}
+			_ => return path.to_path_buf(),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn confine_to_tree() {
+		let (tree, real) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		let documents = tree.path().join("documents");
+		let sandbox = Sandbox::new(tree.path(), &[(real.path().to_path_buf(), documents.clone())], tree.path().join(".trash")).unwrap();
+		let root = tree.path().canonicalize().unwrap();
+		let real = real.path().canonicalize().unwrap();
+		assert_eq!(confine(real.join("a.pdf")).unwrap(), real.join("a.pdf"));
+		let _guard = enter(sandbox);
+		assert_eq!(confine(root.join("pdfs/a.pdf")).unwrap(), root.join("pdfs/a.pdf"));
+		assert_eq!(confine(real.join("pdfs/a.pdf")).unwrap(), documents.join("pdfs/a.pdf"));
+		assert!(confine(root.join("../elsewhere/a.pdf")).is_err());
+		assert!(confine(PathBuf::from("/etc/a.pdf")).is_err());
+		#[cfg(unix)]
+		{
+			std::os::unix::fs::symlink("/etc", root.join("link")).unwrap();
+			assert!(confine(root.join("link/a.pdf")).is_err());
+		}
+	}
+}
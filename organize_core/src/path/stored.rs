@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, Value, ValueRef};
+
+/// How `path` is kept in the database. Paths that are valid Unicode are kept as text, like they always were,
+/// so that they still compare equal to the rows written before; the others are kept as the raw bytes the OS uses for them,
+/// which text can't hold without replacing them.
+pub(crate) fn stored(path: &Path) -> Value {
+	match path.to_str() {
+		Some(path) => Value::Text(path.to_string()),
+		None => Value::Blob(to_bytes(path)),
+	}
+}
+
+/// A path read back from the database, see [`stored`]
+pub(crate) struct StoredPath(pub(crate) PathBuf);
+
+impl FromSql for StoredPath {
+	fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+		match value {
+			ValueRef::Text(text) => std::str::from_utf8(text)
+				.map(|path| Self(path.into()))
+				.map_err(|e| FromSqlError::Other(Box::new(e))),
+			ValueRef::Blob(bytes) => from_bytes(bytes).map(Self),
+			_ => Err(FromSqlError::InvalidType),
+		}
+	}
+}
+
+#[cfg(unix)]
+fn to_bytes(path: &Path) -> Vec<u8> {
+	use std::os::unix::ffi::OsStrExt;
+	path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+fn from_bytes(bytes: &[u8]) -> FromSqlResult<PathBuf> {
+	use std::os::unix::ffi::OsStrExt;
+	Ok(std::ffi::OsStr::from_bytes(bytes).into())
+}
+
+#[cfg(windows)]
+fn to_bytes(path: &Path) -> Vec<u8> {
+	use std::os::windows::ffi::OsStrExt;
+	path.as_os_str().encode_wide().flat_map(u16::to_le_bytes).collect()
+}
+
+#[cfg(windows)]
+fn from_bytes(bytes: &[u8]) -> FromSqlResult<PathBuf> {
+	use std::os::windows::ffi::OsStringExt;
+	if bytes.len() % 2 != 0 {
+		return Err(FromSqlError::InvalidType);
+	}
+	let wide = bytes
+		.chunks_exact(2)
+		.map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+		.collect::<Vec<_>>();
+	Ok(std::ffi::OsString::from_wide(&wide).into())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn to_bytes(path: &Path) -> Vec<u8> {
+	path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(not(any(unix, windows)))]
+fn from_bytes(bytes: &[u8]) -> FromSqlResult<PathBuf> {
+	std::str::from_utf8(bytes)
+		.map(PathBuf::from)
+		.map_err(|e| FromSqlError::Other(Box::new(e)))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+	use rusqlite::Connection;
+
+	use super::*;
+
+	#[test]
+	fn keep_paths_that_arent_unicode() {
+		let conn = Connection::open_in_memory().unwrap();
+		conn.execute("CREATE TABLE paths (path TEXT NOT NULL)", []).unwrap();
+		let (unicode, raw) = (PathBuf::from("/tmp/café.txt"), PathBuf::from(OsStr::from_bytes(b"/tmp/caf\xe9.txt")));
+		for path in [&unicode, &raw] {
+			conn.execute("INSERT INTO paths (path) VALUES (?1)", [stored(path)]).unwrap();
+		}
+		let read = conn
+			.prepare("SELECT path FROM paths")
+			.unwrap()
+			.query_map([], |row| row.get::<_, StoredPath>(0))
+			.unwrap()
+			.map(|path| path.unwrap().0)
+			.collect::<Vec<_>>();
+		assert_eq!(read, vec![unicode.clone(), raw]);
+		// rows written as text before still match
+		let matches = conn
+			.prepare("SELECT 1 FROM paths WHERE path = ?1")
+			.unwrap()
+			.exists([unicode.to_string_lossy()])
+			.unwrap();
+		assert!(matches);
+	}
+}
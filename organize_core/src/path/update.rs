@@ -1,28 +1,100 @@
-use crate::config::actions::io_action::ConflictOption;
+use crate::{config::actions::io_action::ConflictOption, path::Reservation};
 
-use std::path::PathBuf;
+use std::{
+	ffi::OsString,
+	path::{Path, PathBuf},
+};
 
 pub trait ResolveConflict {
-	fn resolve_naming_conflict(self, if_exists: &ConflictOption) -> Option<PathBuf>;
+	fn resolve_naming_conflict(self, if_exists: &ConflictOption) -> Option<Reservation>;
 }
 
 impl<T: Into<PathBuf>> ResolveConflict for T {
-	fn resolve_naming_conflict(self, if_exists: &ConflictOption) -> Option<PathBuf> {
+	fn resolve_naming_conflict(self, if_exists: &ConflictOption) -> Option<Reservation> {
 		use ConflictOption::*;
+		// hold the registry for the whole lookup so that concurrent workers can't be handed the same name
+		let mut registry = Reservation::registry();
+		let path = self.into();
+		if !Reservation::is_taken(&registry, &path) {
+			return Some(Reservation::claim(&mut registry, path));
+		}
 		match if_exists {
 			Skip | Delete => None,
-			Overwrite => Some(self.into()),
+			Overwrite | Merge => Some(Reservation::claim(&mut registry, path)),
 			Rename => {
-				let counter_separator = " ";
-				let mut path = self.into();
-				let extension = path.extension().unwrap_or_default().to_string_lossy().to_string();
-				let stem = path.file_stem()?.to_string_lossy().to_string();
-				let mut n = 1;
-				while path.exists() {
-					path.set_file_name(format!("{}{}({:?}).{}", stem, counter_separator, n, extension));
-					n += 1;
-				}
-				Some(path)
+				let path = renamed(&path, |path| Reservation::is_taken(&registry, path))?;
+				Some(Reservation::claim(&mut registry, path))
+			}
+		}
+	}
+}
+
+/// The first of `file (1).txt`, `file (2).txt`... next to `path` that `is_taken` says is free, or `None` if `path` has no filename.
+/// Names that aren't valid UTF-8 are kept as they are, and names without an extension, like `.bashrc`, don't get a trailing dot.
+pub fn renamed(path: &Path, is_taken: impl Fn(&Path) -> bool) -> Option<PathBuf> {
+	let stem = path.file_stem()?;
+	let extension = path.extension();
+	let mut renamed = path.to_path_buf();
+	for n in 1.. {
+		let mut name = OsString::from(stem);
+		name.push(format!(" ({})", n));
+		if let Some(extension) = extension {
+			name.push(".");
+			name.push(extension);
+		}
+		renamed.set_file_name(name);
+		if !is_taken(&renamed) {
+			break;
+		}
+	}
+	Some(renamed)
+}
+
+#[cfg(test)]
+mod tests {
+	use proptest::prelude::*;
+
+	use super::*;
+	use crate::testing::names;
+
+	#[test]
+	fn rename_concurrent_claims() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("file.txt");
+		std::fs::write(&path, "").unwrap();
+		let first = path.clone().resolve_naming_conflict(&ConflictOption::Rename).unwrap();
+		let second = path.clone().resolve_naming_conflict(&ConflictOption::Rename).unwrap();
+		assert_eq!(*first, dir.path().join("file (1).txt"));
+		assert_eq!(*second, dir.path().join("file (2).txt"));
+	}
+
+	#[test]
+	fn skip_claimed_path() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("file.txt");
+		let _first = path.clone().resolve_naming_conflict(&ConflictOption::Skip).unwrap();
+		assert!(path.resolve_naming_conflict(&ConflictOption::Skip).is_none());
+	}
+
+	#[test]
+	fn rename_edge_cases() {
+		let free = |_: &Path| false;
+		assert_eq!(renamed(Path::new("/a/.bashrc"), free), Some(PathBuf::from("/a/.bashrc (1)")));
+		assert_eq!(renamed(Path::new("/a/Makefile"), free), Some(PathBuf::from("/a/Makefile (1)")));
+		assert_eq!(renamed(Path::new("/a/b.tar.gz"), free), Some(PathBuf::from("/a/b.tar (1).gz")));
+		assert_eq!(renamed(Path::new("/"), free), None);
+		assert_eq!(renamed(Path::new("/a/.."), free), None);
+	}
+
+	proptest! {
+		#[test]
+		fn renamed_is_free_and_stays_in_place(name in names(), taken in 0..5usize) {
+			let path = Path::new("/dir").join(&name);
+			let is_taken = |candidate: &Path| candidate == path || (1..=taken).any(|n| candidate.to_string_lossy().contains(&format!(" ({})", n)));
+			if let Some(renamed) = renamed(&path, is_taken) {
+				prop_assert!(!is_taken(&renamed));
+				prop_assert_eq!(renamed.parent(), path.parent());
+				prop_assert_eq!(renamed.extension(), path.extension());
 			}
 		}
 	}
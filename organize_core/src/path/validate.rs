@@ -0,0 +1,132 @@
+use anyhow::{bail, Result};
+use std::path::{Component, Path, PathBuf};
+
+use crate::path::Expand;
+
+const MAX_COMPONENT_LENGTH: usize = 255;
+const RESERVED_NAMES: &[&str] = &[
+	"CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5",
+	"LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Whether Windows reserves `component` for a device, whatever its extension
+pub(crate) fn is_reserved(component: &str) -> bool {
+	let name = component.split('.').next().unwrap_or_default().trim_end();
+	RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(name))
+}
+
+pub trait ValidateDestination {
+	/// Checks that a destination rendered from `template` is safe to write to
+	fn validate_destination<T: AsRef<Path>>(&self, template: T) -> Result<()>;
+}
+
+impl ValidateDestination for Path {
+	fn validate_destination<T: AsRef<Path>>(&self, template: T) -> Result<()> {
+		let template = template.as_ref();
+		for component in self.components() {
+			if let Component::Normal(component) = component {
+				let component = component.to_string_lossy();
+				if component.chars().any(char::is_control) {
+					bail!("{} (from {}) contains control characters", self.display(), template.display())
+				}
+				if component.len() > MAX_COMPONENT_LENGTH {
+					bail!(
+						"{} (from {}) has a component longer than {} bytes",
+						self.display(),
+						template.display(),
+						MAX_COMPONENT_LENGTH
+					)
+				}
+				// device names are only reserved on Windows, `sanitize` takes care of them on NTFS and exFAT volumes elsewhere
+				if cfg!(windows) && is_reserved(&component) {
+					bail!(
+						"{} (from {}) contains the reserved name {}",
+						self.display(),
+						template.display(),
+						component
+					)
+				}
+			}
+		}
+		let root = static_prefix(template);
+		// the destination was rendered with `~` and `@location` expanded
+		let root = root.clone().expand_user().unwrap_or(root);
+		if !normalize(self).starts_with(&root) {
+			bail!("{} (from {}) escapes {}", self.display(), template.display(), root.display())
+		}
+		Ok(())
+	}
+}
+
+/// The components of a template that come before the first placeholder
+pub(crate) fn static_prefix(template: &Path) -> PathBuf {
+	template
+		.components()
+		.take_while(|comp| !comp.as_os_str().to_string_lossy().contains('{'))
+		.collect()
+}
+
+/// Lexically resolves `.` and `..` components
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+	let mut normalized = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir => {
+				normalized.pop();
+			}
+			other => normalized.push(other),
+		}
+	}
+	normalized
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn valid_destination() {
+		let path = Path::new("/home/user/Documents/pdf/test.pdf");
+		assert!(path.validate_destination("/home/user/Documents/{extension}").is_ok())
+	}
+
+	#[test]
+	fn control_characters() {
+		let path = Path::new("/home/user/Documents/te\u{7}st");
+		assert!(path.validate_destination("/home/user/Documents/{stem}").is_err())
+	}
+
+	#[test]
+	fn reserved_names() {
+		assert_eq!(
+			Path::new("/home/user/con.txt")
+				.validate_destination("/home/user/{filename}")
+				.is_err(),
+			cfg!(windows)
+		);
+		assert_eq!(
+			Path::new("/home/user/LPT1").validate_destination("/home/user/{stem}").is_err(),
+			cfg!(windows)
+		);
+		assert_eq!(
+			Path::new("/home/aux/a.c").validate_destination("/home/{parent}/a.c").is_err(),
+			cfg!(windows)
+		);
+		assert!(Path::new("/home/user/console.txt")
+			.validate_destination("/home/user/{filename}")
+			.is_ok());
+	}
+
+	#[test]
+	fn long_component() {
+		let path = PathBuf::from("/home").join("a".repeat(MAX_COMPONENT_LENGTH + 1));
+		assert!(path.validate_destination("/home/{stem}").is_err())
+	}
+
+	#[test]
+	fn escaping_root() {
+		let path = Path::new("/home/user/Documents/../../etc");
+		assert!(path.validate_destination("/home/user/Documents/{stem}").is_err())
+	}
+}
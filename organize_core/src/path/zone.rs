@@ -0,0 +1,43 @@
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::config::filters::Zone;
+
+/// Access to the `Zone.Identifier` alternate data stream Windows attaches to downloaded files
+pub trait ZoneIdentifier {
+	fn zone(&self) -> Option<Zone>;
+	fn strip_zone_identifier(&self) -> Result<()>;
+}
+
+#[cfg(target_os = "windows")]
+impl ZoneIdentifier for Path {
+	fn zone(&self) -> Option<Zone> {
+		let mut stream = self.as_os_str().to_os_string();
+		stream.push(":Zone.Identifier");
+		std::fs::read_to_string(stream).ok().and_then(|content| Zone::parse(&content))
+	}
+
+	fn strip_zone_identifier(&self) -> Result<()> {
+		use anyhow::Context;
+		let mut stream = self.as_os_str().to_os_string();
+		stream.push(":Zone.Identifier");
+		match std::fs::remove_file(&stream) {
+			Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+				Err(e).with_context(|| format!("could not strip the zone identifier of {}", self.display()))
+			}
+			_ => Ok(()),
+		}
+	}
+}
+
+#[cfg(not(target_os = "windows"))]
+impl ZoneIdentifier for Path {
+	fn zone(&self) -> Option<Zone> {
+		None
+	}
+
+	fn strip_zone_identifier(&self) -> Result<()> {
+		Ok(())
+	}
+}
@@ -0,0 +1,241 @@
+//! Checks, before anything is done, that the locations of every rule can be read and that the directories their actions
+//! write to can be written to, so that a run doesn't fail one file at a time halfway through.
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	fmt::{self, Display, Formatter},
+	fs,
+	path::{Component, Path, PathBuf},
+};
+
+use strum_macros::Display;
+
+use crate::{
+	config::{constants, Config},
+	path::Expand,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum Access {
+	Read,
+	Write,
+}
+
+/// A directory the run needs but can't use
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+	pub path: PathBuf,
+	pub access: Access,
+	pub reason: String,
+	/// the rules that need it
+	pub rules: Vec<String>,
+}
+
+impl Display for Problem {
+	fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+		let verb = match self.access {
+			Access::Read => "read",
+			Access::Write => "written to",
+		};
+		write!(
+			f,
+			"{} can't be {}: {} (needed by {})",
+			self.path.display(),
+			verb,
+			self.reason,
+			self.rules.join(", ")
+		)
+	}
+}
+
+/// Everything that would make the rules of `config` fail, one problem per directory.
+/// Directories that are only denied to the current user aren't problems when operations can be elevated.
+pub fn check(config: &Config) -> Vec<Problem> {
+	let _definitions = config.enter();
+	let elevates = config.elevation.is_some();
+	let mut needed: BTreeMap<(PathBuf, Access), BTreeSet<String>> = BTreeMap::new();
+	for (i, rule) in config.rules.iter().enumerate() {
+		let steps = rule.actions.iter().filter(|step| !step.dry_run);
+		let removes = steps.clone().any(|step| step.action.removes_source());
+		for folder in rule.folders.iter() {
+			needed
+				.entry((folder.path.clone(), Access::Read))
+				.or_default()
+				.insert(rule.name(i));
+			if removes {
+				needed
+					.entry((folder.path.clone(), Access::Write))
+					.or_default()
+					.insert(rule.name(i));
+			}
+		}
+		for template in steps.flat_map(|step| step.action.destinations()) {
+			// `{root}` is the location the file was found in, so it stands for each of them in turn
+			let templates = match template.strip_prefix("{root}") {
+				Ok(rest) => rule.folders.iter().map(|folder| folder.path.join(rest)).collect(),
+				Err(_) => vec![template],
+			};
+			for dir in templates.iter().filter_map(|template| destination_root(template)) {
+				needed.entry((dir, Access::Write)).or_default().insert(rule.name(i));
+			}
+		}
+	}
+	needed
+		.into_iter()
+		.filter_map(|((path, access), rules)| {
+			let result = match access {
+				Access::Read => readable(&path),
+				Access::Write => writable(&path, elevates),
+			};
+			result.err().map(|reason| Problem {
+				path,
+				access,
+				reason,
+				rules: rules.into_iter().collect(),
+			})
+		})
+		.collect()
+}
+
+/// The closest existing directory to a destination template before its first placeholder, which is where the
+/// directories it needs are created. Templates relative to the file, like `{parent}/sorted`, have none.
+fn destination_root(template: &Path) -> Option<PathBuf> {
+	let template = PathBuf::from(constants::expand(&template.to_string_lossy()).ok()?);
+	let fixed = template
+		.components()
+		.take_while(|component| !component.as_os_str().to_string_lossy().contains('{'))
+		.collect::<PathBuf>();
+	let fixed = fixed.expand_user().ok()?.expand_vars().ok()?;
+	if !fixed.components().any(|component| matches!(component, Component::Normal(_))) {
+		return None;
+	}
+	fixed.ancestors().find(|ancestor| ancestor.is_dir()).map(Path::to_path_buf)
+}
+
+fn readable(dir: &Path) -> Result<(), String> {
+	fs::read_dir(dir).map(|_| ()).map_err(|e| e.to_string())
+}
+
+fn writable(dir: &Path, elevates: bool) -> Result<(), String> {
+	if is_read_only_mount(dir) {
+		return Err("it's on a read-only filesystem".into());
+	}
+	if let Some(flag) = immutable_flag(dir) {
+		return Err(format!("it's marked {}", flag));
+	}
+	if !elevates && !can_write(dir) {
+		return Err("permission denied".into());
+	}
+	Ok(())
+}
+
+#[cfg(unix)]
+fn c_path(path: &Path) -> Option<std::ffi::CString> {
+	use std::os::unix::ffi::OsStrExt;
+	std::ffi::CString::new(path.as_os_str().as_bytes()).ok()
+}
+
+/// Whether the process, with its effective user and groups, can create and remove entries in `dir`
+#[cfg(unix)]
+fn can_write(dir: &Path) -> bool {
+	let path = match c_path(dir) {
+		Some(path) => path,
+		None => return true,
+	};
+	// SAFETY: `path` is a valid C string that outlives the call
+	unsafe { libc::faccessat(libc::AT_FDCWD, path.as_ptr(), libc::W_OK | libc::X_OK, libc::AT_EACCESS) == 0 }
+}
+
+#[cfg(not(unix))]
+fn can_write(dir: &Path) -> bool {
+	dir.metadata()
+		.map(|metadata| !metadata.permissions().readonly())
+		.unwrap_or(true)
+}
+
+#[cfg(unix)]
+fn is_read_only_mount(dir: &Path) -> bool {
+	let path = match c_path(dir) {
+		Some(path) => path,
+		None => return false,
+	};
+	let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+	// SAFETY: `path` is a valid C string and `stat` is only read if the call filled it
+	unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) == 0 && stat.assume_init().f_flag & libc::ST_RDONLY != 0 }
+}
+
+#[cfg(not(unix))]
+fn is_read_only_mount(_dir: &Path) -> bool {
+	false
+}
+
+/// `immutable` or `append-only` if `dir` has a flag that keeps even root from removing or adding entries
+#[cfg(target_os = "linux")]
+fn immutable_flag(dir: &Path) -> Option<&'static str> {
+	use std::os::unix::io::AsRawFd;
+	const FS_IMMUTABLE_FL: libc::c_int = 0x10;
+	const FS_APPEND_FL: libc::c_int = 0x20;
+	let file = fs::File::open(dir).ok()?;
+	let mut flags: libc::c_int = 0;
+	// SAFETY: the descriptor is open for the duration of the call, which writes an int to `flags`
+	if unsafe { libc::ioctl(file.as_raw_fd(), libc::FS_IOC_GETFLAGS, &mut flags) } != 0 {
+		// the filesystem doesn't support flags
+		return None;
+	}
+	match flags {
+		flags if flags & FS_IMMUTABLE_FL != 0 => Some("immutable"),
+		flags if flags & FS_APPEND_FL != 0 => Some("append-only"),
+		_ => None,
+	}
+}
+
+#[cfg(target_os = "macos")]
+fn immutable_flag(dir: &Path) -> Option<&'static str> {
+	use std::os::macos::fs::MetadataExt;
+	let flags = dir.metadata().ok()?.st_flags();
+	match flags {
+		flags if flags & (libc::UF_IMMUTABLE | libc::SF_IMMUTABLE) != 0 => Some("immutable"),
+		flags if flags & (libc::UF_APPEND | libc::SF_APPEND) != 0 => Some("append-only"),
+		_ => None,
+	}
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn immutable_flag(_dir: &Path) -> Option<&'static str> {
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn destination_roots() {
+		let dir = tempfile::tempdir().unwrap();
+		let existing = dir.path().join("docs");
+		fs::create_dir(&existing).unwrap();
+		assert_eq!(destination_root(&existing.join("{extension}").join("{filename}")), Some(existing.clone()));
+		assert_eq!(destination_root(&existing.join("new").join("deeper")), Some(existing.clone()));
+		assert_eq!(destination_root(Path::new("{parent}/sorted")), None);
+		assert_eq!(destination_root(Path::new("/")), None);
+	}
+
+	#[test]
+	fn no_problems_in_a_usable_config() {
+		let dir = tempfile::tempdir().unwrap();
+		let (location, destination) = (dir.path().join("in"), dir.path().join("out"));
+		fs::create_dir(&location).unwrap();
+		let config = format!(
+			"[[rules]]\nfolders = [{:?}]\nfilters = []\nactions = [{{ type = \"move\", to = \"{}/{{extension}}/\" }}]",
+			location,
+			destination.display()
+		);
+		let path = dir.path().join("config.toml");
+		fs::write(&path, config).unwrap();
+		let config = Config::parse(&path).unwrap();
+		assert_eq!(check(&config), vec![]);
+		assert_eq!(writable(dir.path(), false), Ok(()));
+		assert!(readable(&dir.path().join("missing")).is_err());
+	}
+}
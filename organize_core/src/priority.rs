@@ -0,0 +1,142 @@
+//! Lowers the scheduling priority of the current process, so that background runs don't make the machine sluggish.
+//! It must be called before any worker thread is spawned, since they inherit the priority of their parent.
+//! The IO priority of a single thread can also be lowered for a while, for the rules that ask for it with `io_priority`.
+
+use anyhow::Result;
+use serde::Deserialize;
+
+/// How much of the disk a rule gets while it runs, compared to the rest of the machine
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IoPriority {
+	/// only when nothing else needs the disk
+	Idle,
+	/// the lowest priority among the processes that share the disk
+	Low,
+}
+
+#[cfg(target_os = "linux")]
+pub fn lower_priority() -> Result<()> {
+	const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+	const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+	const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+	// SAFETY: both calls only affect the scheduling of the calling process and take no pointers
+	unsafe {
+		if libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT) != 0 {
+			return Err(std::io::Error::last_os_error().into());
+		}
+		if libc::setpriority(libc::PRIO_PROCESS, 0, 19) != 0 {
+			return Err(std::io::Error::last_os_error().into());
+		}
+	}
+	Ok(())
+}
+
+#[cfg(target_os = "macos")]
+pub fn lower_priority() -> Result<()> {
+	const IOPOL_TYPE_DISK: libc::c_int = 0;
+	const IOPOL_SCOPE_PROCESS: libc::c_int = 0;
+	const IOPOL_THROTTLE: libc::c_int = 3;
+	extern "C" {
+		fn setiopolicy_np(iotype: libc::c_int, scope: libc::c_int, policy: libc::c_int) -> libc::c_int;
+	}
+	// SAFETY: both calls only affect the scheduling of the calling process and take no pointers
+	unsafe {
+		if setiopolicy_np(IOPOL_TYPE_DISK, IOPOL_SCOPE_PROCESS, IOPOL_THROTTLE) != 0 {
+			return Err(std::io::Error::last_os_error().into());
+		}
+		if libc::setpriority(libc::PRIO_PROCESS, 0, 19) != 0 {
+			return Err(std::io::Error::last_os_error().into());
+		}
+	}
+	Ok(())
+}
+
+#[cfg(target_os = "windows")]
+pub fn lower_priority() -> Result<()> {
+	use std::ffi::c_void;
+	const PROCESS_MODE_BACKGROUND_BEGIN: u32 = 0x0010_0000;
+	#[link(name = "kernel32")]
+	extern "system" {
+		fn GetCurrentProcess() -> *mut c_void;
+		fn SetPriorityClass(process: *mut c_void, priority_class: u32) -> i32;
+	}
+	// SAFETY: GetCurrentProcess returns a pseudo-handle that is always valid and doesn't need to be closed
+	unsafe {
+		if SetPriorityClass(GetCurrentProcess(), PROCESS_MODE_BACKGROUND_BEGIN) == 0 {
+			return Err(std::io::Error::last_os_error().into());
+		}
+	}
+	Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+pub fn lower_priority() -> Result<()> {
+	log::warn!("lowering the IO priority is not supported on this platform");
+	Ok(())
+}
+
+/// Runs `f` with the IO priority of the current thread lowered to `priority`, and then restores it
+#[cfg(target_os = "linux")]
+pub(crate) fn with_io_priority<T>(priority: IoPriority, f: impl FnOnce() -> T) -> T {
+	const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+	const IOPRIO_CLASS_BE: libc::c_int = 2;
+	const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+	const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+	let value = match priority {
+		IoPriority::Idle => IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+		IoPriority::Low => IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT | 7,
+	};
+	// SAFETY: the calls only affect the scheduling of the calling thread and take no pointers
+	let previous = unsafe {
+		let tid = libc::syscall(libc::SYS_gettid);
+		let previous = libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, tid);
+		if previous < 0 || libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, tid, value) != 0 {
+			log::debug!("could not lower the IO priority: {}", std::io::Error::last_os_error());
+			return f();
+		}
+		(tid, previous)
+	};
+	let result = f();
+	// SAFETY: as above
+	unsafe {
+		libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, previous.0, previous.1);
+	}
+	result
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn with_io_priority<T>(priority: IoPriority, f: impl FnOnce() -> T) -> T {
+	const IOPOL_TYPE_DISK: libc::c_int = 0;
+	const IOPOL_SCOPE_THREAD: libc::c_int = 1;
+	const IOPOL_UTILITY: libc::c_int = 4;
+	const IOPOL_THROTTLE: libc::c_int = 3;
+	extern "C" {
+		fn getiopolicy_np(iotype: libc::c_int, scope: libc::c_int) -> libc::c_int;
+		fn setiopolicy_np(iotype: libc::c_int, scope: libc::c_int, policy: libc::c_int) -> libc::c_int;
+	}
+	let policy = match priority {
+		IoPriority::Idle => IOPOL_THROTTLE,
+		IoPriority::Low => IOPOL_UTILITY,
+	};
+	// SAFETY: the calls only affect the scheduling of the calling thread and take no pointers
+	let previous = unsafe {
+		let previous = getiopolicy_np(IOPOL_TYPE_DISK, IOPOL_SCOPE_THREAD);
+		if previous < 0 || setiopolicy_np(IOPOL_TYPE_DISK, IOPOL_SCOPE_THREAD, policy) != 0 {
+			log::debug!("could not lower the IO priority: {}", std::io::Error::last_os_error());
+			return f();
+		}
+		previous
+	};
+	let result = f();
+	// SAFETY: as above
+	unsafe {
+		setiopolicy_np(IOPOL_TYPE_DISK, IOPOL_SCOPE_THREAD, previous);
+	}
+	result
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub(crate) fn with_io_priority<T>(_priority: IoPriority, f: impl FnOnce() -> T) -> T {
+	f()
+}
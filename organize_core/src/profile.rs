@@ -0,0 +1,203 @@
+use std::{
+	cell::Cell,
+	collections::HashMap,
+	fmt,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+use colored::Colorize;
+use lazy_static::lazy_static;
+
+use crate::config::{actions::ActionType, Config};
+
+static PROFILING: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+	static ref TIMES: Mutex<HashMap<(Option<usize>, Stage), Timing>> = Mutex::new(HashMap::new());
+}
+
+thread_local! {
+	/// the rule the current thread is working on
+	static RULE: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Something that takes time during a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stage {
+	/// listing the files of the locations
+	Walk,
+	/// expanding the placeholders of a template
+	Template,
+	Filter {
+		index: usize,
+		name: &'static str,
+	},
+	/// includes rendering the action's templates
+	Action {
+		index: usize,
+		action: ActionType,
+	},
+}
+
+impl fmt::Display for Stage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Stage::Walk => write!(f, "walking"),
+			Stage::Template => write!(f, "templates"),
+			Stage::Filter { index, name } => write!(f, "filter #{} ({})", index, name),
+			Stage::Action { index, action } => write!(f, "action #{} ({})", index, action),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timing {
+	pub count: usize,
+	pub total: Duration,
+}
+
+/// Starts timing the stages of the run, until `finish` is called.
+/// Nothing is timed otherwise, so that runs that don't ask for it only pay for checking a flag.
+pub fn start() {
+	TIMES.lock().unwrap_or_else(|e| e.into_inner()).clear();
+	PROFILING.store(true, Ordering::Relaxed);
+}
+
+/// Attributes whatever is timed inside `f` on this thread to `rule`
+pub fn in_rule<T, F: FnOnce() -> T>(rule: usize, f: F) -> T {
+	if !PROFILING.load(Ordering::Relaxed) {
+		return f();
+	}
+	let previous = RULE.with(|current| current.replace(Some(rule)));
+	let result = f();
+	RULE.with(|current| current.set(previous));
+	result
+}
+
+/// Runs `f`, adding the time it took to `stage`
+pub fn time<T, S: FnOnce() -> Stage, F: FnOnce() -> T>(stage: S, f: F) -> T {
+	if !PROFILING.load(Ordering::Relaxed) {
+		return f();
+	}
+	let start = Instant::now();
+	let result = f();
+	record(stage(), start.elapsed());
+	result
+}
+
+pub fn record(stage: Stage, elapsed: Duration) {
+	if !PROFILING.load(Ordering::Relaxed) {
+		return;
+	}
+	let rule = match stage {
+		Stage::Walk => None,
+		_ => RULE.with(Cell::get),
+	};
+	let mut times = TIMES.lock().unwrap_or_else(|e| e.into_inner());
+	let timing = times.entry((rule, stage)).or_default();
+	timing.count += 1;
+	timing.total += elapsed;
+}
+
+/// Stops timing and returns where the time went, slowest first
+pub fn finish(config: &Config) -> Profile {
+	PROFILING.store(false, Ordering::Relaxed);
+	let times = std::mem::take(&mut *TIMES.lock().unwrap_or_else(|e| e.into_inner()));
+	Profile::new(times, config)
+}
+
+/// Where the time of a run went
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile(pub Vec<(String, Timing)>);
+
+impl Profile {
+	fn new(times: HashMap<(Option<usize>, Stage), Timing>, config: &Config) -> Self {
+		let mut rows = times
+			.into_iter()
+			.map(|((rule, stage), timing)| {
+				let name = match rule {
+					Some(i) => format!("{} {}", config.rules.get(i).map_or_else(|| format!("#{}", i), |rule| rule.name(i)), stage),
+					None => stage.to_string(),
+				};
+				(name, timing)
+			})
+			.collect::<Vec<_>>();
+		rows.sort_by(|(a, a_timing), (b, b_timing)| b_timing.total.cmp(&a_timing.total).then_with(|| a.cmp(b)));
+		Self(rows)
+	}
+}
+
+impl fmt::Display for Profile {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", "Profile".bold())?;
+		if self.0.is_empty() {
+			write!(f, "\n  nothing was timed")?;
+		}
+		for (name, timing) in self.0.iter() {
+			let average = timing.total / timing.count.max(1) as u32;
+			write!(f, "\n  {:>10.2?} {:>8}x {:>10.2?}  {}", timing.total, timing.count, average, name.cyan())?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+
+	use super::*;
+	use crate::{
+		config::{options::Options, Rule},
+		utils::DefaultOpt,
+	};
+
+	#[test]
+	fn slowest_first() {
+		let config = Config {
+			rules: vec![Rule {
+				id: Some("docs".into()),
+				..Rule::default()
+			}],
+			path: PathBuf::new(),
+			local_defaults: Options::default_none(),
+			global_defaults: Options::default_some(),
+			path_to_rules: HashMap::new(),
+			path_to_recursive: HashMap::new(),
+			predicates: vec![],
+			journal: Default::default(),
+			trash: None,
+			elevation: None,
+			locations: Default::default(),
+			constants: Default::default(),
+			messages: Default::default(),
+		};
+		let timing = |millis| Timing {
+			count: 2,
+			total: Duration::from_millis(millis),
+		};
+		let times = HashMap::from([
+			((None, Stage::Walk), timing(10)),
+			((Some(0), Stage::Filter { index: 1, name: "script" }), timing(300)),
+			(
+				(
+					Some(0),
+					Stage::Action {
+						index: 0,
+						action: ActionType::Move,
+					},
+				),
+				timing(20),
+			),
+		]);
+		let names = Profile::new(times, &config)
+			.0
+			.into_iter()
+			.map(|(name, _)| name)
+			.collect::<Vec<_>>();
+		assert_eq!(names, vec!["docs filter #1 (script)", "docs action #0 (move)", "walking"]);
+	}
+}
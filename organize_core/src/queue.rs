@@ -0,0 +1,216 @@
+use std::{
+	collections::{HashMap, VecDeque},
+	hash::Hash,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+
+use crate::{
+	path::{stored, StoredPath},
+	with_db,
+};
+
+/// Items waiting to be handled, grouped by where they come from.
+/// Groups take turns, each handing out as many items per turn as its priority,
+/// so that a burst in one of them (a big unzip in Downloads) doesn't hold up the others (a screenshot on the Desktop).
+#[derive(Debug)]
+pub struct FairQueue<K, T> {
+	groups: HashMap<K, (u16, VecDeque<T>)>,
+	/// the groups that have items waiting, in the order they'll get their next turn
+	turns: VecDeque<K>,
+}
+
+impl<K: Clone + Eq + Hash, T> Default for FairQueue<K, T> {
+	fn default() -> Self {
+		Self {
+			groups: HashMap::new(),
+			turns: VecDeque::new(),
+		}
+	}
+}
+
+impl<K: Clone + Eq + Hash, T> FairQueue<K, T> {
+	pub fn push(&mut self, group: K, priority: u16, item: T) {
+		let (current, items) = self.groups.entry(group.clone()).or_insert_with(|| (priority, VecDeque::new()));
+		*current = priority.max(1);
+		if items.is_empty() {
+			self.turns.push_back(group);
+		}
+		items.push_back(item);
+	}
+
+	/// The items of the group whose turn it is, as many as its priority
+	pub fn pop(&mut self) -> Option<Vec<T>> {
+		let group = self.turns.pop_front()?;
+		let (priority, items) = self.groups.get_mut(&group)?;
+		let batch = items.drain(..items.len().min(*priority as usize)).collect();
+		match items.is_empty() {
+			true => {
+				self.groups.remove(&group);
+			}
+			false => self.turns.push_back(group),
+		}
+		Some(batch)
+	}
+
+	pub fn len(&self) -> usize {
+		self.groups.values().map(|(_, items)| items.len()).sum()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.turns.is_empty()
+	}
+}
+
+/// Files that didn't fit in memory while the queue was full are kept in the database until there's room for them again,
+/// which also keeps them across restarts.
+/// They're kept by the config that was handling them, since a single `watch` can handle several;
+/// the ones spilled before that was the case can be taken back by any of them.
+pub mod spill {
+	use super::*;
+
+	pub(super) fn init(conn: &Connection) -> Result<()> {
+		conn.execute(
+			"CREATE TABLE IF NOT EXISTS spilled_queue (id INTEGER PRIMARY KEY AUTOINCREMENT, path TEXT NOT NULL, config TEXT NOT NULL DEFAULT '')",
+			[],
+		)
+		.context("could not create the spilled queue")?;
+		let columns = conn
+			.prepare("SELECT name FROM pragma_table_info('spilled_queue')")?
+			.query_map([], |row| row.get::<_, String>(0))?
+			.collect::<rusqlite::Result<Vec<_>>>()?;
+		if !columns.iter().any(|column| column == "config") {
+			conn.execute("ALTER TABLE spilled_queue ADD COLUMN config TEXT NOT NULL DEFAULT ''", [])
+				.context("could not update the spilled queue")?;
+		}
+		Ok(())
+	}
+
+	pub fn push(config: &Path, paths: &[PathBuf]) -> Result<()> {
+		with_db(init, |conn| push_with(conn, config, paths))
+	}
+
+	pub(super) fn push_with(conn: &Connection, config: &Path, paths: &[PathBuf]) -> Result<()> {
+		let tx = conn.unchecked_transaction()?;
+		{
+			let mut statement = tx.prepare("INSERT INTO spilled_queue (path, config) VALUES (?1, ?2)")?;
+			for path in paths {
+				statement.execute(params![stored(path), stored(config)])?;
+			}
+		}
+		tx.commit().context("could not spill the queue to disk")
+	}
+
+	/// Takes back up to `limit` of the files spilled while handling `config`, the ones that were spilled first
+	pub fn pop(config: &Path, limit: usize) -> Result<Vec<PathBuf>> {
+		with_db(init, |conn| pop_with(conn, config, limit))
+	}
+
+	pub(super) fn pop_with(conn: &Connection, config: &Path, limit: usize) -> Result<Vec<PathBuf>> {
+		let tx = conn.unchecked_transaction()?;
+		let rows = tx
+			.prepare("SELECT id, path FROM spilled_queue WHERE config IN (?1, '') ORDER BY id LIMIT ?2")?
+			.query_map(params![stored(config), limit as i64], |row| {
+				Ok((row.get::<_, i64>(0)?, row.get::<_, StoredPath>(1)?))
+			})?
+			.collect::<rusqlite::Result<Vec<_>>>()?;
+		{
+			let mut statement = tx.prepare("DELETE FROM spilled_queue WHERE id = ?1")?;
+			for (id, _) in rows.iter() {
+				statement.execute(params![id])?;
+			}
+		}
+		tx.commit()?;
+		Ok(rows.into_iter().map(|(_, path)| path.0).collect())
+	}
+
+	pub fn len(config: &Path) -> Result<usize> {
+		with_db(init, |conn| len_with(conn, config))
+	}
+
+	pub(super) fn len_with(conn: &Connection, config: &Path) -> Result<usize> {
+		let len: i64 = conn.query_row(
+			"SELECT COUNT(*) FROM spilled_queue WHERE config IN (?1, '')",
+			params![stored(config)],
+			|row| row.get(0),
+		)?;
+		Ok(len as usize)
+	}
+
+	#[cfg(test)]
+	pub(super) fn memory() -> Connection {
+		let conn = Connection::open_in_memory().unwrap();
+		init(&conn).unwrap();
+		conn
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn groups_take_turns() {
+		let mut queue = FairQueue::default();
+		for i in 0..5 {
+			queue.push("downloads", 1, format!("archive/{}", i));
+		}
+		queue.push("desktop", 2, "a".to_string());
+		queue.push("desktop", 2, "b".to_string());
+		queue.push("desktop", 2, "c".to_string());
+		assert_eq!(queue.len(), 8);
+		assert_eq!(queue.pop().unwrap(), vec!["archive/0"]);
+		assert_eq!(queue.pop().unwrap(), vec!["a", "b"]);
+		assert_eq!(queue.pop().unwrap(), vec!["archive/1"]);
+		assert_eq!(queue.pop().unwrap(), vec!["c"]);
+		assert_eq!(queue.pop().unwrap(), vec!["archive/2"]);
+		assert_eq!(queue.pop().unwrap(), vec!["archive/3"]);
+		assert_eq!(queue.pop().unwrap(), vec!["archive/4"]);
+		assert!(queue.is_empty() && queue.pop().is_none());
+	}
+
+	#[test]
+	fn spill_in_order() {
+		let conn = spill::memory();
+		let (personal, work) = (Path::new("personal.toml"), Path::new("work.toml"));
+		let paths = (0..5).map(|i| PathBuf::from(format!("/downloads/{}", i))).collect::<Vec<_>>();
+		spill::push_with(&conn, personal, &paths).unwrap();
+		spill::push_with(&conn, work, &[PathBuf::from("/reports/a")]).unwrap();
+		assert_eq!(spill::len_with(&conn, personal).unwrap(), 5);
+		assert_eq!(spill::pop_with(&conn, personal, 3).unwrap(), paths[..3]);
+		assert_eq!(spill::pop_with(&conn, personal, 3).unwrap(), paths[3..]);
+		assert!(spill::pop_with(&conn, personal, 3).unwrap().is_empty());
+		assert_eq!(spill::pop_with(&conn, work, 3).unwrap(), vec![PathBuf::from("/reports/a")]);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn spill_paths_that_arent_unicode() {
+		use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+		let conn = spill::memory();
+		let config = Path::new("personal.toml");
+		let path = PathBuf::from(OsStr::from_bytes(b"/downloads/caf\xe9.txt"));
+		spill::push_with(&conn, config, std::slice::from_ref(&path)).unwrap();
+		assert_eq!(spill::pop_with(&conn, config, 3).unwrap(), vec![path]);
+	}
+
+	#[test]
+	fn adopt_files_spilled_before_configs_were_kept() {
+		let conn = Connection::open_in_memory().unwrap();
+		conn.execute(
+			"CREATE TABLE spilled_queue (id INTEGER PRIMARY KEY AUTOINCREMENT, path TEXT NOT NULL)",
+			[],
+		)
+		.unwrap();
+		conn.execute("INSERT INTO spilled_queue (path) VALUES ('/downloads/old')", [])
+			.unwrap();
+		spill::init(&conn).unwrap();
+		assert_eq!(spill::len_with(&conn, Path::new("work.toml")).unwrap(), 1);
+		assert_eq!(
+			spill::pop_with(&conn, Path::new("personal.toml"), 3).unwrap(),
+			vec![PathBuf::from("/downloads/old")]
+		);
+	}
+}
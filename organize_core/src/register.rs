@@ -0,0 +1,29 @@
+use std::{
+	collections::BTreeMap,
+	path::PathBuf,
+	sync::{Arc, Mutex},
+};
+
+use crate::config::Config;
+
+/// The configs a single `watch` has loaded, by path, so that personal and work rules can run side by side in one process
+/// and be reloaded on their own.
+///
+/// Each config resolves its own `@locations` and `{const.*}` (see [`Config::enter`]), so two of them can give the same name
+/// different values.
+#[derive(Debug, Clone, Default)]
+pub struct Register(Arc<Mutex<BTreeMap<PathBuf, Arc<Config>>>>);
+
+impl Register {
+	/// Adds `config`, or replaces the one loaded from the same path
+	pub fn insert(&self, config: Arc<Config>) {
+		self.0
+			.lock()
+			.unwrap_or_else(|e| e.into_inner())
+			.insert(config.path.clone(), config);
+	}
+
+	pub fn paths(&self) -> Vec<PathBuf> {
+		self.0.lock().unwrap_or_else(|e| e.into_inner()).keys().cloned().collect()
+	}
+}
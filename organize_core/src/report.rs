@@ -0,0 +1,347 @@
+use std::{
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+
+use serde_json::json;
+
+use crate::{
+	events::{self, Event, SkipReason},
+	messages::{self, Message},
+	summary::{self, Summary},
+};
+
+/// A standalone document describing a run, meant to be archived or sent around
+pub struct Report<'a> {
+	events: &'a [Event],
+	summary: &'a Summary,
+}
+
+/// The files that left and arrived in a directory during the run
+#[derive(Default)]
+struct Changes {
+	removed: Vec<String>,
+	added: Vec<String>,
+}
+
+impl<'a> Report<'a> {
+	pub fn new(events: &'a [Event], summary: &'a Summary) -> Self {
+		Self { events, summary }
+	}
+
+	/// Writes the report to `path`, as HTML, Markdown or JSON depending on its extension
+	pub fn write<T: AsRef<Path>>(&self, path: T) -> Result<()> {
+		let path = path.as_ref();
+		let content = match path.extension().and_then(|extension| extension.to_str()) {
+			Some("html") | Some("htm") => self.html(),
+			Some("md") | Some("markdown") => self.markdown(),
+			Some("json") => self.json(),
+			_ => bail!("unsupported report format for {} (expected .html, .md or .json)", path.display()),
+		};
+		std::fs::write(path, content).with_context(|| format!("could not write report to {}", path.display()))
+	}
+
+	fn operations(&self) -> impl Iterator<Item = (String, &PathBuf, Option<&PathBuf>)> {
+		self.events.iter().filter_map(|event| match event {
+			Event::Acted { action, from, to, .. } => Some((action.to_string(), from, to.as_ref())),
+			_ => None,
+		})
+	}
+
+	fn conflicts(&self) -> impl Iterator<Item = (&PathBuf, &PathBuf)> {
+		self.events.iter().filter_map(|event| match event {
+			Event::Conflict { from, to } => Some((from, to)),
+			_ => None,
+		})
+	}
+
+	/// The files that were left alone because the system refused an action on them
+	fn denied(&self) -> impl Iterator<Item = &PathBuf> {
+		self.events.iter().filter_map(|event| match event {
+			Event::Skipped {
+				path,
+				reason: SkipReason::PermissionDenied,
+			} => Some(path),
+			_ => None,
+		})
+	}
+
+	fn errors(&self) -> impl Iterator<Item = &String> {
+		self.events.iter().filter_map(|event| match event {
+			Event::Error { message } => Some(message),
+			_ => None,
+		})
+	}
+
+	/// What changed in every directory touched by the run
+	fn changes(&self) -> BTreeMap<PathBuf, Changes> {
+		let mut changes: BTreeMap<PathBuf, Changes> = BTreeMap::new();
+		let name = |path: &Path| {
+			path.file_name()
+				.map(|name| name.to_string_lossy().to_string())
+				.unwrap_or_default()
+		};
+		for (_, from, to) in self.operations() {
+			if Some(from) == to {
+				continue;
+			}
+			if let Some(parent) = from.parent() {
+				changes.entry(parent.to_path_buf()).or_default().removed.push(name(from));
+			}
+			if let Some(parent) = to.and_then(|to| to.parent()) {
+				changes.entry(parent.to_path_buf()).or_default().added.push(name(to.unwrap()));
+			}
+		}
+		changes
+	}
+
+	pub fn markdown(&self) -> String {
+		let summary = self.summary;
+		let mut out = String::from("# organize report\n\n");
+		out.push_str(&format!("Elapsed: {:.2}s\n\n", summary.elapsed.as_secs_f64()));
+		out.push_str("| rule | files |\n|---|---|\n");
+		for (rule, count) in summary.rules.iter() {
+			out.push_str(&format!("| {} | {} |\n", rule, count));
+		}
+		out.push_str(&format!(
+			"\n{} skipped due to conflicts, {} error(s)\n",
+			summary.conflicts, summary.errors
+		));
+		if summary.saved > 0 {
+			out.push_str(&format!("\n{} saved\n", summary::bytes(summary.saved)));
+		}
+
+		out.push_str("\n## Operations\n\n| action | from | to |\n|---|---|---|\n");
+		for (action, from, to) in self.operations() {
+			let to = to.map(|to| format!("`{}`", to.display())).unwrap_or_else(|| "-".into());
+			out.push_str(&format!("| {} | `{}` | {} |\n", action, from.display(), to));
+		}
+
+		out.push_str("\n## Changes\n");
+		for (dir, changes) in self.changes() {
+			out.push_str(&format!("\n```\n{}\n", dir.display()));
+			for name in changes.removed {
+				out.push_str(&format!("- {}\n", name));
+			}
+			for name in changes.added {
+				out.push_str(&format!("+ {}\n", name));
+			}
+			out.push_str("```\n");
+		}
+
+		let conflicts = self.conflicts().collect::<Vec<_>>();
+		if !conflicts.is_empty() {
+			out.push_str("\n## Conflicts\n\n");
+			for (from, to) in conflicts {
+				let (from, to) = (format!("`{}`", from.display()), format!("`{}`", to.display()));
+				out.push_str(&format!("- {}\n", messages::format(Message::Conflict, &[("from", &from), ("to", &to)])));
+			}
+		}
+		let denied = self.denied().collect::<Vec<_>>();
+		if !denied.is_empty() {
+			out.push_str("\n## Skipped for lack of permissions\n\n");
+			for path in denied {
+				out.push_str(&format!("- `{}`\n", path.display()));
+			}
+		}
+		let errors = self.errors().collect::<Vec<_>>();
+		if !errors.is_empty() {
+			out.push_str("\n## Errors\n\n");
+			for error in errors {
+				out.push_str(&format!("- {}\n", error.replace('\n', " ")));
+			}
+		}
+		out
+	}
+
+	/// Every event of the run, for tools auditing it
+	pub fn json(&self) -> String {
+		let summary = self.summary;
+		let events = self
+			.events
+			.iter()
+			.map(|event| match event {
+				Event::Matched { rule, path } => json!({ "event": "matched", "rule": rule, "path": path }),
+				Event::Acted { action, from, to, output } => {
+					json!({ "event": "acted", "action": action.to_string(), "from": from, "to": to, "output": output })
+				}
+				Event::Conflict { from, to } => json!({ "event": "conflict", "from": from, "to": to }),
+				Event::Error { message } => json!({ "event": "error", "message": message }),
+				Event::Saved { path, bytes } => json!({ "event": "saved", "path": path, "bytes": bytes }),
+				Event::Skipped { path, reason } => json!({
+					"event": "skipped",
+					"path": path,
+					"reason": reason.to_string(),
+					"severity": reason.severity().as_str().to_lowercase(),
+				}),
+			})
+			.collect::<Vec<_>>();
+		let report = json!({
+			"run": events::run_id(),
+			"elapsed": summary.elapsed.as_secs_f64(),
+			"rules": summary.rules.iter().map(|(rule, count)| json!({ "rule": rule, "files": count })).collect::<Vec<_>>(),
+			"conflicts": summary.conflicts,
+			"errors": summary.errors,
+			"denied": summary.denied,
+			"saved": summary.saved,
+			"events": events,
+		});
+		format!("{:#}\n", report)
+	}
+
+	pub fn html(&self) -> String {
+		let summary = self.summary;
+		let mut out = String::from(
+			"<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>organize report</title>\n<style>\
+			body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}td,th{border:1px solid #ccc;padding:.3em .6em;text-align:left}\
+			pre{background:#f6f6f6;padding:.6em}.removed{color:#b00}.added{color:#070}.error{color:#b00}</style>\n</head>\n<body>\n",
+		);
+		out.push_str("<h1>organize report</h1>\n");
+		out.push_str(&format!("<p>Elapsed: {:.2}s</p>\n", summary.elapsed.as_secs_f64()));
+		out.push_str("<table>\n<tr><th>rule</th><th>files</th></tr>\n");
+		for (rule, count) in summary.rules.iter() {
+			out.push_str(&format!("<tr><td>{}</td><td>{}</td></tr>\n", escape(rule), count));
+		}
+		out.push_str("</table>\n");
+		out.push_str(&format!(
+			"<p>{} skipped due to conflicts, {} error(s)</p>\n",
+			summary.conflicts, summary.errors
+		));
+		if summary.saved > 0 {
+			out.push_str(&format!("<p>{} saved</p>\n", summary::bytes(summary.saved)));
+		}
+
+		out.push_str("<h2>Operations</h2>\n<table>\n<tr><th>action</th><th>from</th><th>to</th></tr>\n");
+		for (action, from, to) in self.operations() {
+			let to = to.map(|to| escape(&to.display().to_string())).unwrap_or_else(|| "-".into());
+			out.push_str(&format!(
+				"<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+				action,
+				escape(&from.display().to_string()),
+				to
+			));
+		}
+		out.push_str("</table>\n");
+
+		out.push_str("<h2>Changes</h2>\n");
+		for (dir, changes) in self.changes() {
+			out.push_str(&format!("<pre>{}\n", escape(&dir.display().to_string())));
+			for name in changes.removed {
+				out.push_str(&format!("<span class=\"removed\">- {}</span>\n", escape(&name)));
+			}
+			for name in changes.added {
+				out.push_str(&format!("<span class=\"added\">+ {}</span>\n", escape(&name)));
+			}
+			out.push_str("</pre>\n");
+		}
+
+		let conflicts = self.conflicts().collect::<Vec<_>>();
+		if !conflicts.is_empty() {
+			out.push_str("<h2>Conflicts</h2>\n<ul>\n");
+			for (from, to) in conflicts {
+				let conflict = messages::format(Message::Conflict, &[("from", &from.display()), ("to", &to.display())]);
+				out.push_str(&format!("<li>{}</li>\n", escape(&conflict)));
+			}
+			out.push_str("</ul>\n");
+		}
+		let denied = self.denied().collect::<Vec<_>>();
+		if !denied.is_empty() {
+			out.push_str("<h2>Skipped for lack of permissions</h2>\n<ul>\n");
+			for path in denied {
+				out.push_str(&format!("<li>{}</li>\n", escape(&path.display().to_string())));
+			}
+			out.push_str("</ul>\n");
+		}
+		let errors = self.errors().collect::<Vec<_>>();
+		if !errors.is_empty() {
+			out.push_str("<h2>Errors</h2>\n<ul>\n");
+			for error in errors {
+				out.push_str(&format!("<li class=\"error\">{}</li>\n", escape(error)));
+			}
+			out.push_str("</ul>\n");
+		}
+		out.push_str("</body>\n</html>\n");
+		out
+	}
+}
+
+fn escape(s: &str) -> String {
+	s.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+	use std::time::Duration;
+
+	use super::*;
+	use crate::config::actions::ActionType;
+
+	fn sample() -> (Vec<Event>, Summary) {
+		let events = vec![
+			Event::Acted {
+				action: ActionType::Move,
+				from: "/in/a.pdf".into(),
+				to: Some("/docs/a.pdf".into()),
+				output: None,
+			},
+			Event::Error { message: "<bad>".into() },
+			Event::Skipped {
+				path: "/in/b.pdf".into(),
+				reason: SkipReason::ConflictSkip,
+			},
+			Event::Skipped {
+				path: "/etc/c.pdf".into(),
+				reason: SkipReason::PermissionDenied,
+			},
+		];
+		let summary = Summary {
+			rules: vec![("docs".into(), 1)],
+			destinations: vec![("/docs".into(), 1)],
+			conflicts: 0,
+			errors: 1,
+			denied: 0,
+			saved: 0,
+			elapsed: Duration::from_secs(1),
+		};
+		(events, summary)
+	}
+
+	#[test]
+	fn markdown_report() {
+		let (events, summary) = sample();
+		let markdown = Report::new(&events, &summary).markdown();
+		assert!(markdown.contains("| move | `/in/a.pdf` | `/docs/a.pdf` |"));
+		assert!(markdown.contains("/in\n- a.pdf\n"));
+		assert!(markdown.contains("/docs\n+ a.pdf\n"));
+		assert!(markdown.contains("## Skipped for lack of permissions\n\n- `/etc/c.pdf`\n"));
+	}
+
+	#[test]
+	fn html_report_is_escaped() {
+		let (events, summary) = sample();
+		let html = Report::new(&events, &summary).html();
+		assert!(html.contains("&lt;bad&gt;"));
+		assert!(!html.contains("<bad>"));
+	}
+
+	#[test]
+	fn json_report() {
+		let (events, summary) = sample();
+		let json: serde_json::Value = serde_json::from_str(&Report::new(&events, &summary).json()).unwrap();
+		let skipped = &json["events"][2];
+		assert_eq!(skipped["reason"], "conflict-skip");
+		assert_eq!(skipped["severity"], "warn");
+		assert_eq!(json["events"][0]["to"], "/docs/a.pdf");
+	}
+
+	#[test]
+	fn reject_unknown_format() {
+		let (events, summary) = sample();
+		assert!(Report::new(&events, &summary).write("/tmp/report.txt").is_err());
+	}
+}
@@ -0,0 +1,93 @@
+//! What the machine can spare right now, for the rules that wait for a better time with `defer_if`.
+//! Each reading is only taken when a rule asks for it, and at most once per check.
+
+use std::cell::OnceCell;
+
+use sysinfo::{System, SystemExt};
+
+#[derive(Debug, Default)]
+pub struct Resources {
+	on_battery: OnceCell<bool>,
+	metered: OnceCell<bool>,
+	load: OnceCell<f64>,
+}
+
+impl Resources {
+	/// Whether the machine is running off its battery. Machines without one never are.
+	pub fn on_battery(&self) -> bool {
+		*self.on_battery.get_or_init(on_battery)
+	}
+
+	/// Whether the network connection is metered, as far as the system knows
+	pub fn metered(&self) -> bool {
+		*self.metered.get_or_init(metered)
+	}
+
+	/// The average number of processes waiting to run over the last minute
+	pub fn load(&self) -> f64 {
+		*self.load.get_or_init(|| System::new().load_average().one)
+	}
+
+	#[cfg(test)]
+	pub(crate) fn fixed(on_battery: bool, metered: bool, load: f64) -> Self {
+		Self {
+			on_battery: OnceCell::from(on_battery),
+			metered: OnceCell::from(metered),
+			load: OnceCell::from(load),
+		}
+	}
+}
+
+#[cfg(target_os = "linux")]
+fn on_battery() -> bool {
+	let supplies = match std::fs::read_dir("/sys/class/power_supply") {
+		Ok(supplies) => supplies,
+		Err(_) => return false,
+	};
+	let read = |supply: &std::path::Path, attribute: &str| std::fs::read_to_string(supply.join(attribute)).unwrap_or_default();
+	let mut discharging = false;
+	for supply in supplies.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+		match read(&supply, "type").trim() {
+			"Mains" | "USB" if read(&supply, "online").trim() == "1" => return false,
+			"Battery" => discharging |= read(&supply, "status").trim() == "Discharging",
+			_ => {}
+		}
+	}
+	discharging
+}
+
+#[cfg(target_os = "macos")]
+fn on_battery() -> bool {
+	std::process::Command::new("pmset")
+		.args(["-g", "batt"])
+		.output()
+		.map(|output| String::from_utf8_lossy(&output.stdout).contains("'Battery Power'"))
+		.unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn on_battery() -> bool {
+	false
+}
+
+/// Asks NetworkManager, whose `Metered` property is 1 when the connection is known to be metered and 3 when it guesses so
+#[cfg(target_os = "linux")]
+fn metered() -> bool {
+	std::process::Command::new("busctl")
+		.args([
+			"--system",
+			"get-property",
+			"org.freedesktop.NetworkManager",
+			"/org/freedesktop/NetworkManager",
+			"org.freedesktop.NetworkManager",
+			"Metered",
+		])
+		.output()
+		.map(|output| matches!(String::from_utf8_lossy(&output.stdout).trim(), "u 1" | "u 3"))
+		.unwrap_or(false)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn metered() -> bool {
+	false
+}
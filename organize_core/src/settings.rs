@@ -0,0 +1,315 @@
+use std::{
+	collections::BTreeMap,
+	fs,
+	path::{Path, PathBuf},
+	sync::RwLock,
+	time::Duration,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use toml::{Table, Value};
+
+use crate::config::{filters::deserialize_duration, secret::Secret, Config};
+
+/// The built-in settings, which every other source only overrides
+const DEFAULTS: &str = r#"
+[log]
+filter = ""
+color = true
+per_run = false
+
+[notifications]
+backends = ["desktop"]
+
+[credentials]
+
+[limits]
+skip_open = false
+"#;
+
+/// The settings that can be overridden with an `ORGANIZE_SETTING_<KEY>` environment variable,
+/// the key being uppercased with its dots replaced by underscores, e.g. `ORGANIZE_SETTING_LOG_PER_RUN`
+const KEYS: [&str; 10] = [
+	"data_dir",
+	"cache_dir",
+	"state_dir",
+	"log.filter",
+	"log.color",
+	"log.per_run",
+	"notifications.backends",
+	"limits.newer_than",
+	"limits.larger_than",
+	"limits.skip_open",
+];
+
+lazy_static! {
+	static ref SETTINGS: RwLock<Option<Settings>> = RwLock::new(None);
+}
+
+/// How organize itself behaves, as opposed to what it does to files, which is up to the rules.
+/// They're read from `settings.toml` next to the config (or `ORGANIZE_SETTINGS`), then from the environment, then from `--set`.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+	/// where the journal and the trash are kept, instead of the platform's data directory
+	#[serde(default)]
+	pub data_dir: Option<PathBuf>,
+	/// where the scan and hash indices are kept, instead of the platform's cache directory
+	#[serde(default)]
+	pub cache_dir: Option<PathBuf>,
+	/// where the logs are kept, instead of the platform's state directory
+	#[serde(default)]
+	pub state_dir: Option<PathBuf>,
+	pub log: LogSettings,
+	pub notifications: Notifications,
+	/// credentials that configs can refer to as `credential("name")`, so that they can be shared without them
+	pub credentials: BTreeMap<String, Secret>,
+	/// the limits of destructive actions that don't set their own
+	pub limits: Limits,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct LogSettings {
+	/// used when `--log-filter` isn't passed, e.g. `engine=debug`
+	pub filter: String,
+	pub color: bool,
+	/// write the logs of every invocation to their own file, as with `--log-per-run`
+	pub per_run: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Notifications {
+	/// where `echo` actions writing to `notification` end up; none of them silences notifications
+	pub backends: Vec<NotificationBackend>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationBackend {
+	/// a desktop notification
+	Desktop,
+	/// an info message in the logs
+	Log,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+	#[serde(default, deserialize_with = "deserialize_duration")]
+	pub newer_than: Option<Duration>,
+	#[serde(default)]
+	pub larger_than: Option<u64>,
+	pub skip_open: bool,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Self::from_table(defaults()).unwrap() // a panic here indicates a compile-time bug
+	}
+}
+
+impl Settings {
+	fn from_table(table: Table) -> Result<Self> {
+		Value::Table(table).try_into().context("invalid settings")
+	}
+}
+
+/// Where the settings are read from and written to
+pub fn path() -> PathBuf {
+	std::env::var_os("ORGANIZE_SETTINGS").map_or_else(|| Config::default_dir().join("settings.toml"), PathBuf::from)
+}
+
+/// The settings in effect, loaded without overrides if [`load`] wasn't called
+pub fn get() -> Settings {
+	if let Some(settings) = SETTINGS.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+		return settings.clone();
+	}
+	let settings = merged(&path(), &[]).and_then(Settings::from_table).unwrap_or_else(|e| {
+		log::warn!("{:?}, using the default settings", e);
+		Settings::default()
+	});
+	*SETTINGS.write().unwrap_or_else(|e| e.into_inner()) = Some(settings.clone());
+	settings
+}
+
+/// Reads the settings at `path` (or the default [`path`]), overridden by the environment and then by `overrides`,
+/// which are `key=value` pairs, and makes them the ones in effect
+pub fn load(path: Option<&Path>, overrides: &[String]) -> Result<Settings> {
+	let path = path.map_or_else(self::path, Path::to_path_buf);
+	let settings = Settings::from_table(merged(&path, overrides)?)?;
+	*SETTINGS.write().unwrap_or_else(|e| e.into_inner()) = Some(settings.clone());
+	Ok(settings)
+}
+
+/// The defaults, the file at `path`, the environment and `overrides`, merged in this order
+pub fn merged(path: &Path, overrides: &[String]) -> Result<Table> {
+	let mut table = defaults();
+	merge(&mut table, read(path)?);
+	for key in KEYS {
+		let var = format!("ORGANIZE_SETTING_{}", key.replace('.', "_").to_uppercase());
+		if let Ok(value) = std::env::var(&var) {
+			insert(&mut table, key, parse_value(&value)).with_context(|| format!("invalid {}", var))?;
+		}
+	}
+	for pair in overrides {
+		let (key, value) = pair
+			.split_once('=')
+			.ok_or_else(|| anyhow!("expected a setting as key=value, found {:?}", pair))?;
+		insert(&mut table, key.trim(), parse_value(value.trim()))?;
+	}
+	Ok(table)
+}
+
+/// The value of the dotted `key` in `table`, e.g. `log.color`
+pub fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+	let mut parts = key.split('.');
+	let mut value = table.get(parts.next()?)?;
+	for part in parts {
+		value = value.as_table()?.get(part)?;
+	}
+	Some(value)
+}
+
+/// Sets `key` to `value` in the file at `path`, or removes it if `value` is `None`.
+/// The file is only written if the settings in it are still valid afterwards.
+pub fn write(path: &Path, key: &str, value: Option<&str>) -> Result<()> {
+	let mut file = read(path)?;
+	match value {
+		Some(value) => insert(&mut file, key, parse_value(value))?,
+		None => remove(&mut file, key),
+	}
+	let mut table = defaults();
+	merge(&mut table, file.clone());
+	Settings::from_table(table)?;
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent).with_context(|| format!("could not create {}", parent.display()))?;
+	}
+	let contents = toml::to_string_pretty(&file).context("could not serialize the settings")?;
+	fs::write(path, contents).with_context(|| format!("could not write {}", path.display()))
+}
+
+fn defaults() -> Table {
+	toml::from_str(DEFAULTS).unwrap() // a panic here indicates a compile-time bug
+}
+
+fn read(path: &Path) -> Result<Table> {
+	if !path.exists() {
+		return Ok(Table::new());
+	}
+	let contents = fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+	toml::from_str(&contents).with_context(|| format!("could not parse the settings ({})", path.display()))
+}
+
+/// Values are read as TOML, and taken as strings if they aren't valid, so that `debug` needn't be quoted
+fn parse_value(value: &str) -> Value {
+	toml::from_str::<Table>(&format!("value = {}", value))
+		.ok()
+		.and_then(|mut table| table.remove("value"))
+		.unwrap_or_else(|| Value::String(value.to_string()))
+}
+
+/// Merges `other` into `table`, tables being merged key by key and any other value replaced
+fn merge(table: &mut Table, other: Table) {
+	for (key, value) in other {
+		match (table.get_mut(&key), value) {
+			(Some(Value::Table(existing)), Value::Table(value)) => merge(existing, value),
+			(_, value) => {
+				table.insert(key, value);
+			}
+		}
+	}
+}
+
+fn insert(table: &mut Table, key: &str, value: Value) -> Result<()> {
+	let (parents, last) = match key.rsplit_once('.') {
+		Some((parents, last)) => (Some(parents), last),
+		None => (None, key),
+	};
+	let mut table = table;
+	for part in parents.into_iter().flat_map(|parents| parents.split('.')) {
+		table = match table.entry(part).or_insert_with(|| Value::Table(Table::new())) {
+			Value::Table(table) => table,
+			_ => bail!("`{}` is not a table, so `{}` can't be set", part, key),
+		};
+	}
+	if last.is_empty() {
+		bail!("invalid setting `{}`", key);
+	}
+	table.insert(last.to_string(), value);
+	Ok(())
+}
+
+fn remove(table: &mut Table, key: &str) {
+	match key.split_once('.') {
+		Some((first, rest)) => {
+			if let Some(Value::Table(table)) = table.get_mut(first) {
+				remove(table, rest);
+			}
+		}
+		None => {
+			table.remove(key);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn merge_sources() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("settings.toml");
+		fs::write(
+			&path,
+			"data_dir = \"/srv/organize\"\n[log]\ncolor = false\n[credentials]\nnas = 'env(\"NAS_PASSWORD\")'",
+		)
+		.unwrap();
+		std::env::set_var("ORGANIZE_SETTING_LOG_PER_RUN", "true");
+		let table = merged(&path, &["limits.newer_than=1h".into(), "log.filter=engine=debug".into()]).unwrap();
+		std::env::remove_var("ORGANIZE_SETTING_LOG_PER_RUN");
+		let settings = Settings::from_table(table).unwrap();
+		assert_eq!(settings.data_dir, Some(PathBuf::from("/srv/organize")));
+		assert_eq!(
+			settings.log,
+			LogSettings {
+				filter: "engine=debug".into(),
+				color: false,
+				per_run: true
+			}
+		);
+		assert_eq!(settings.credentials["nas"], Secret::Env("NAS_PASSWORD".into()));
+		assert_eq!(settings.limits.newer_than, Some(Duration::from_secs(3600)));
+		assert_eq!(settings.notifications.backends, vec![NotificationBackend::Desktop]);
+
+		assert!(merged(&path, &["log.color".into()]).is_err());
+		assert!(Settings::from_table(merged(&path, &["log.colour=false".into()]).unwrap()).is_err());
+		assert_eq!(
+			Settings::from_table(merged(&dir.path().join("missing"), &[]).unwrap()).unwrap(),
+			Settings::default()
+		);
+	}
+
+	#[test]
+	fn write_settings() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("nested").join("settings.toml");
+		write(&path, "notifications.backends", Some("[\"log\"]")).unwrap();
+		write(&path, "limits.larger_than", Some("1000")).unwrap();
+		assert!(write(&path, "limits.larger_than", Some("a lot")).is_err());
+		assert!(write(&path, "unknown", Some("1")).is_err());
+		let table = read(&path).unwrap();
+		assert_eq!(lookup(&table, "limits.larger_than"), Some(&Value::Integer(1000)));
+		assert_eq!(
+			lookup(&table, "notifications.backends"),
+			Some(&Value::Array(vec![Value::String("log".into())]))
+		);
+		write(&path, "limits.larger_than", None).unwrap();
+		assert_eq!(lookup(&read(&path).unwrap(), "limits.larger_than"), None);
+		assert_eq!(lookup(&read(&path).unwrap(), "limits"), Some(&Value::Table(Table::new())));
+	}
+}
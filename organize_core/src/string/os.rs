@@ -0,0 +1,52 @@
+use std::ffi::{OsStr, OsString};
+
+/// Applies `f` to the parts of `s` that are valid UTF-8, keeping the bytes in between as they are,
+/// so that changing the case of a name that isn't valid UTF-8 doesn't replace what can't be decoded.
+/// On platforms where names aren't bytes, the invalid parts are replaced instead.
+pub(crate) fn map_utf8<F: FnMut(&str) -> String>(s: &OsStr, mut f: F) -> OsString {
+	#[cfg(unix)]
+	{
+		use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+		if let Some(s) = s.to_str() {
+			return f(s).into();
+		}
+		let mut mapped = Vec::with_capacity(s.len());
+		for chunk in s.as_bytes().utf8_chunks() {
+			mapped.extend_from_slice(f(chunk.valid()).as_bytes());
+			mapped.extend_from_slice(chunk.invalid());
+		}
+		OsString::from_vec(mapped)
+	}
+	#[cfg(not(unix))]
+	{
+		f(&s.to_string_lossy()).into()
+	}
+}
+
+/// The bytes of `s`, which are written as they are on unix, and replaced where they aren't valid UTF-8 elsewhere
+pub(crate) fn into_bytes(s: OsString) -> Vec<u8> {
+	#[cfg(unix)]
+	{
+		std::os::unix::ffi::OsStringExt::into_vec(s)
+	}
+	#[cfg(not(unix))]
+	{
+		s.to_string_lossy().into_owned().into_bytes()
+	}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	use std::os::unix::ffi::OsStrExt;
+
+	use super::*;
+
+	#[test]
+	fn keep_invalid_bytes() {
+		let name = OsStr::from_bytes(b"caf\xe9 photo.jpg");
+		assert_eq!(map_utf8(name, str::to_uppercase), OsStr::from_bytes(b"CAF\xe9 PHOTO.JPG"));
+		assert_eq!(map_utf8(OsStr::new("photo"), str::to_uppercase), "PHOTO");
+		assert_eq!(into_bytes(name.to_os_string()), b"caf\xe9 photo.jpg");
+	}
+}
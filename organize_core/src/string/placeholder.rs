@@ -1,10 +1,20 @@
 use anyhow::{anyhow, bail, Context, Result};
-use std::{collections::HashMap, ffi::OsString, path::Path, str::FromStr};
+use std::{
+	collections::HashMap,
+	ffi::OsString,
+	path::{Path, PathBuf},
+	str::FromStr,
+};
 
 use crate::{
+	config::constants,
+	context,
 	fsa::{Fsa, Transition},
-	string::Capitalize,
+	mount::Filesystem,
+	path::Expand,
+	string::{map_utf8, Capitalize},
 	transition, transitions,
+	variables::Variable,
 };
 use lazy_static::lazy_static;
 use regex::Regex;
@@ -21,6 +31,10 @@ lazy_static! {
 			(Placeholder::ToUpperCase, "to_uppercase"),
 			(Placeholder::ToLowerCase, "to_lowercase"),
 			(Placeholder::Capitalize, "capitalize"),
+			(Placeholder::FreeSpace, "free_space"),
+			(Placeholder::Original, "original"),
+			(Placeholder::Name, "name"),
+			(Placeholder::Root, "root"),
 		]);
 
 	static ref PLACEHOLDER_ALIASES: Vec<&'static str> = vec![
@@ -31,31 +45,41 @@ lazy_static! {
 		PLACEHOLDER_TO_ALIASES[&Placeholder::Extension],
 		PLACEHOLDER_TO_ALIASES[&Placeholder::ToLowerCase],
 		PLACEHOLDER_TO_ALIASES[&Placeholder::ToUpperCase],
-		PLACEHOLDER_TO_ALIASES[&Placeholder::Capitalize]
+		PLACEHOLDER_TO_ALIASES[&Placeholder::Capitalize],
+		PLACEHOLDER_TO_ALIASES[&Placeholder::FreeSpace],
+		PLACEHOLDER_TO_ALIASES[&Placeholder::Original],
+		PLACEHOLDER_TO_ALIASES[&Placeholder::Name],
+		PLACEHOLDER_TO_ALIASES[&Placeholder::Root]
 	];
 
 	static ref PARSER: Fsa<'static, u8> = Fsa::new(
-		&[0, 1, 2, 3, 4, 5],
+		&[0, 1, 2, 3, 4, 5, 6, 7],
 		&PLACEHOLDER_ALIASES,
 		0,
-		&[0, 1, 2, 3, 4, 5],
+		&[0, 1, 2, 3, 4, 5, 6, 7],
 		transitions![
 			// On <string>, on <int>, go to  <int>
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Path], 0) => 0,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Parent], 0) => 1,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Filename], 0) => 2,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Name], 0) => 2,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Stem], 0) => 4,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Extension], 0) => 4,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::ToLowerCase], 0) => 3,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::ToUpperCase], 0) => 3,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Capitalize], 0) => 3,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::FreeSpace], 0) => 6,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Original], 0) => 7,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Root], 0) => 1,
 			// --------------------
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Filename], 1) => 5,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Name], 1) => 5,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Path], 1) => 1,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Parent], 1) => 1,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::ToLowerCase], 1) => 3,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::ToUpperCase], 1) => 3,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Capitalize], 1) => 3,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::FreeSpace], 1) => 6,
 			// --------------------
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Stem], 2) => 4,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::Extension], 2) => 4,
@@ -69,7 +93,18 @@ lazy_static! {
 			// --------------------
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::ToUpperCase], 5) => 3,
 			(PLACEHOLDER_TO_ALIASES[&Placeholder::ToLowerCase], 5) => 3,
-			(PLACEHOLDER_TO_ALIASES[&Placeholder::Capitalize], 5) => 3
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Capitalize], 5) => 3,
+			// --------------------
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Path], 7) => 7,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Parent], 7) => 1,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Root], 7) => 1,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Filename], 7) => 2,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Name], 7) => 2,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Stem], 7) => 4,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Extension], 7) => 4,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::ToLowerCase], 7) => 3,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::ToUpperCase], 7) => 3,
+			(PLACEHOLDER_TO_ALIASES[&Placeholder::Capitalize], 7) => 3
 		]
 	);
 
@@ -87,7 +122,11 @@ where
 // used inside Visitor impls
 pub fn visit_placeholder_string(val: &str) -> Result<String> {
 	POTENTIAL_PH_REGEX.find_iter(val).try_for_each(|capture| {
-		let pieces = capture.as_str().trim_matches(|pat| pat == '{' || pat == '}').split('.');
+		let name = capture.as_str().trim_matches(|pat| pat == '{' || pat == '}');
+		if Variable::from_str(name).is_ok() || label(name).is_some() || constants::name(name).is_some() {
+			return Ok(());
+		}
+		let pieces = name.split('.');
 		match PARSER.accepts(pieces) {
 			true => Ok(()),
 			false => bail!("Invalid placeholder"),
@@ -97,6 +136,20 @@ pub fn visit_placeholder_string(val: &str) -> Result<String> {
 	Ok(val.to_string())
 }
 
+/// The name of the label `{label.<name>}` refers to
+fn label(placeholder: &str) -> Option<&str> {
+	placeholder.strip_prefix("label.").filter(|name| !name.contains('.'))
+}
+
+/// Expands every placeholder in `template` for the file at `path`, as an action would do during a run
+pub fn render<P: AsRef<Path>>(template: &str, path: P) -> Result<OsString> {
+	let path = path.as_ref();
+	visit_placeholder_string(template).with_context(|| format!("{} contains an invalid placeholder", template))?;
+	let _context = context::Context::enter(path, path.parent().map(Path::to_path_buf));
+	let rendered = template.expand_placeholders(path)?;
+	Ok(PathBuf::from(rendered).expand_user()?.expand_vars()?.into_os_string())
+}
+
 pub trait ExpandPlaceholder {
 	fn expand_placeholders<P: AsRef<Path>>(self, path: P) -> Result<OsString>;
 }
@@ -111,6 +164,10 @@ enum Placeholder {
 	ToLowerCase,
 	ToUpperCase,
 	Capitalize,
+	FreeSpace,
+	Original,
+	Name,
+	Root,
 }
 
 impl FromStr for Placeholder {
@@ -138,7 +195,7 @@ impl Placeholder {
 				.parent()
 				.ok_or_else(|| anyhow!("{} does not have a parent directory", path.display()))
 				.map(OsString::from),
-			Self::Filename => path
+			Self::Filename | Self::Name => path
 				.file_name()
 				.ok_or_else(|| anyhow!("{} does not have a filename", path.display()))
 				.map(OsString::from),
@@ -150,35 +207,77 @@ impl Placeholder {
 				.extension()
 				.ok_or_else(|| anyhow!("{} does not have an extension", path.display()))
 				.map(OsString::from),
-			Self::ToLowerCase => Ok(path.to_string_lossy().to_lowercase().into()),
-			Self::ToUpperCase => Ok(path.to_string_lossy().to_uppercase().into()),
-			Self::Capitalize => Ok(path.to_string_lossy().capitalize().into()),
+			Self::ToLowerCase => Ok(map_utf8(path.as_os_str(), str::to_lowercase)),
+			Self::ToUpperCase => Ok(map_utf8(path.as_os_str(), str::to_uppercase)),
+			Self::Capitalize => {
+				let mut first = true;
+				Ok(map_utf8(path.as_os_str(), |part| match std::mem::replace(&mut first, false) {
+					true => part.capitalize(),
+					false => part.to_string(),
+				}))
+			}
+			Self::FreeSpace => Filesystem::of(path)
+				.ok_or_else(|| anyhow!("could not determine the filesystem of {}", path.display()))
+				.map(|fs| fs.free_space.to_string().into()),
+			// outside of a run nothing has moved yet, so the file is still where it started
+			Self::Original => Ok(context::Context::original().unwrap_or_else(|| path.to_path_buf()).into()),
+			// the location the file was found in
+			Self::Root => context::Context::root()
+				.or_else(|| path.parent().map(Path::to_path_buf))
+				.ok_or_else(|| anyhow!("could not determine the location of {}", path.display()))
+				.map(OsString::from),
 		}
 	}
 }
 
+/// Replaces every `{...}` in `template` with what `resolve` returns for the name between the braces.
+/// The values are inserted as they are, so a filename that looks like a placeholder isn't expanded again,
+/// and names that aren't valid UTF-8 are kept intact.
+pub fn substitute<F>(template: &str, mut resolve: F) -> Result<OsString>
+where
+	F: FnMut(&str) -> Result<OsString>,
+{
+	let mut new = OsString::with_capacity(template.len());
+	let mut last = 0;
+	for span in POTENTIAL_PH_REGEX.find_iter(template) {
+		new.push(&template[last..span.start()]);
+		new.push(resolve(span.as_str().trim_matches(|x| x == '{' || x == '}'))?);
+		last = span.end();
+	}
+	new.push(&template[last..]);
+	Ok(new)
+}
+
 impl<T: AsRef<str>> ExpandPlaceholder for T {
 	fn expand_placeholders<P: AsRef<Path>>(self, path: P) -> Result<OsString> {
-		let mut new = self.as_ref().to_string();
-		let original = new.clone();
-
-		for span in POTENTIAL_PH_REGEX.find_iter(&original) {
-			let span = span.as_str();
-			let mut current = path.as_ref().to_path_buf().into_os_string();
-			let placeholders: Vec<Placeholder> = span
-				.trim_matches(|x| x == '{' || x == '}')
+		let path = path.as_ref();
+		substitute(self.as_ref(), |name| {
+			if let Ok(variable) = Variable::from_str(name) {
+				return Ok(variable.value(path)?.into());
+			}
+			if let Some(name) = label(name) {
+				let value = context::Context::label(name).ok_or_else(|| anyhow!("{} has no {} label", path.display(), name))?;
+				return Ok(value.into());
+			}
+			if let Some(name) = constants::name(name) {
+				return Ok(constants::value(name)?.into());
+			}
+			let mut current = path.to_path_buf().into_os_string();
+			let placeholders: Vec<Placeholder> = name
 				.split('.')
 				.map(Placeholder::from_str)
 				.collect::<Result<Vec<Placeholder>, _>>()?;
 
+			let mut previous = None;
 			for placeholder in placeholders.into_iter() {
-				current = placeholder.expand(&current)?;
+				// the original path is already absolute, and it might not exist anymore
+				if !(previous == Some(Placeholder::Original) && placeholder == Placeholder::Path) {
+					current = placeholder.expand(&current)?;
+				}
+				previous = Some(placeholder);
 			}
-
-			new = new.replace(span, &current.to_string_lossy());
-		}
-
-		Ok(new.into())
+			Ok(current)
+		})
 	}
 }
 
@@ -186,7 +285,10 @@ impl<T: AsRef<str>> ExpandPlaceholder for T {
 pub mod tests {
 	use std::path::PathBuf;
 
+	use proptest::prelude::*;
+
 	use super::*;
+	use crate::testing::names;
 	#[test]
 	fn deserialize_invalid_ph_non_symbol() {
 		let str = "$HOME/{extension.name}";
@@ -329,6 +431,37 @@ pub mod tests {
 		assert!(visit_placeholder_string(str).is_ok())
 	}
 	#[test]
+	fn deserialize_valid_ph_parent_free_space() {
+		let str = "$HOME/{parent.free_space}";
+		assert!(visit_placeholder_string(str).is_ok())
+	}
+	#[test]
+	fn deserialize_invalid_ph_free_space_uppercase() {
+		let str = "$HOME/{free_space.to_uppercase}";
+		assert!(visit_placeholder_string(str).is_err())
+	}
+	#[test]
+	fn deserialize_valid_ph_original_parent() {
+		let str = "$HOME/{original.parent.filename}";
+		assert!(visit_placeholder_string(str).is_ok())
+	}
+	#[test]
+	fn deserialize_invalid_ph_parent_original() {
+		let str = "$HOME/{parent.original}";
+		assert!(visit_placeholder_string(str).is_err())
+	}
+	#[test]
+	fn deserialize_valid_ph_original_root() {
+		let str = "$HOME/{original.root.filename}/{original.name}";
+		assert!(visit_placeholder_string(str).is_ok())
+	}
+	#[test]
+	fn deserialize_valid_ph_root() {
+		assert!(visit_placeholder_string("{root}/sorted/{extension}").is_ok());
+		assert!(visit_placeholder_string("{root.filename}/{name}").is_ok());
+		assert!(visit_placeholder_string("{root.extension}").is_err());
+	}
+	#[test]
 	fn deserialize_valid_ph_path_extension() {
 		let str = "$HOME/{path.extension}";
 		assert!(visit_placeholder_string(str).is_ok())
@@ -359,10 +492,104 @@ pub mod tests {
 		assert_eq!(new_str, expected)
 	}
 	#[test]
+	fn original_placeholder() {
+		let _context = context::Context::enter("$HOME/Downloads/test.pdf", None);
+		let with_ph = "$HOME/{original.parent.filename}/{filename}";
+		let path = Path::new("$HOME/Documents/test.pdf");
+		let new_str = with_ph.expand_placeholders(path).unwrap();
+		assert_eq!(new_str, OsString::from("$HOME/Downloads/test.pdf"))
+	}
+	#[test]
+	fn original_path_root_and_name() {
+		let _context = context::Context::enter("/nonexistent/Downloads/pdfs/test.pdf", Some(PathBuf::from("/nonexistent/Downloads")));
+		let with_ph = "{original.path} {original.root} {original.name}";
+		let path = Path::new("/nonexistent/Documents/test.pdf");
+		let new_str = with_ph.expand_placeholders(path).unwrap();
+		assert_eq!(
+			new_str,
+			OsString::from("/nonexistent/Downloads/pdfs/test.pdf /nonexistent/Downloads test.pdf")
+		)
+	}
+	#[test]
+	fn root_placeholder() {
+		let _context = context::Context::enter("/nonexistent/Downloads/pdfs/test.pdf", Some(PathBuf::from("/nonexistent/Downloads")));
+		let with_ph = "{root}/sorted/{extension}/{root.filename}";
+		let path = Path::new("/nonexistent/Downloads/pdfs/test.pdf");
+		let new_str = with_ph.expand_placeholders(path).unwrap();
+		assert_eq!(new_str, OsString::from("/nonexistent/Downloads/sorted/pdf/Downloads"))
+	}
+	#[test]
+	fn render_template() {
+		let path = Path::new("/nonexistent/Documents/test.pdf");
+		let rendered = render("/archive/{extension.to_uppercase}/{original.parent.filename}/{name}", path).unwrap();
+		assert_eq!(rendered, OsString::from("/archive/PDF/Documents/test.pdf"));
+		assert!(render("/archive/{extension.stem}", path).is_err());
+	}
+	#[test]
+	fn variable_placeholders() {
+		assert!(visit_placeholder_string("$HOME/{mime.type}/{size.bucket}").is_ok());
+		assert!(visit_placeholder_string("$HOME/{mime.parent}").is_err());
+		let _context = context::Context::enter("/nonexistent/test.pdf", None);
+		context::Context::set_variable(Variable::SizeBucket, "huge");
+		context::Context::set_label("correspondent", "ACME");
+		assert!(visit_placeholder_string("{label.correspondent}").is_ok());
+		assert_eq!(
+			"/{label.correspondent}/{filename}"
+				.expand_placeholders("/nonexistent/test.pdf")
+				.unwrap(),
+			OsString::from("/ACME/test.pdf")
+		);
+		assert!("{label.tags}".expand_placeholders("/nonexistent/test.pdf").is_err());
+		let new_str = "/{mime.subtype}/{size.bucket}/{filename}"
+			.expand_placeholders("/nonexistent/test.pdf")
+			.unwrap();
+		assert_eq!(new_str, OsString::from("/pdf/huge/test.pdf"))
+	}
+	#[test]
 	fn no_placeholder() {
 		let tested = "/home/cabero/Documents/test.pdf";
 		let dummy_path = PathBuf::from(tested);
 		let new = tested.expand_placeholders(&dummy_path).unwrap();
 		assert_eq!(new, tested)
 	}
+	#[test]
+	fn filename_looking_like_placeholder() {
+		let new = "/{filename}/{extension}"
+			.expand_placeholders("/nonexistent/{extension}.txt")
+			.unwrap();
+		assert_eq!(new, OsString::from("/{extension}.txt/txt"))
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn invalid_utf8_filename() {
+		use std::os::unix::ffi::OsStrExt;
+
+		let path = Path::new(std::ffi::OsStr::from_bytes(b"/nonexistent/caf\xe9.jpg"));
+		let new = "/{extension.to_uppercase}/{stem.capitalize}.{extension}"
+			.expand_placeholders(path)
+			.unwrap();
+		assert_eq!(new, std::ffi::OsStr::from_bytes(b"/JPG/Caf\xe9.jpg"));
+		let new = "{filename.to_uppercase}".expand_placeholders(path).unwrap();
+		assert_eq!(new, std::ffi::OsStr::from_bytes(b"CAF\xe9.JPG"));
+	}
+
+	proptest! {
+		#[test]
+		fn substitute_inserts_values_verbatim(template in ".{0,40}") {
+			let same = substitute(&template, |name| Ok(format!("{{{}}}", name).into())).unwrap();
+			prop_assert_eq!(same, OsString::from(&template));
+		}
+
+		#[test]
+		fn expand_any_filename(name in names()) {
+			let path = Path::new("/nonexistent").join(&name);
+			let expanded = "{filename}".expand_placeholders(&path);
+			match path.file_name() {
+				Some(filename) => prop_assert_eq!(expanded.unwrap(), filename.to_os_string()),
+				None => prop_assert!(expanded.is_err()),
+			}
+			let _ = "{stem}.{extension.to_uppercase}/{parent.filename.capitalize}".expand_placeholders(&path);
+		}
+	}
 }
@@ -0,0 +1,256 @@
+use std::{
+	collections::{BTreeMap, HashMap},
+	fmt::Write,
+	path::{Path, PathBuf},
+};
+
+use walkdir::WalkDir;
+
+use crate::{config::version::CONFIG_VERSION, path::IsHidden};
+
+/// What the files a rule would catch have in common
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pattern {
+	Extensions(Vec<String>),
+	/// the first word of the filename
+	Prefix(String),
+}
+
+/// A rule that would have sent `files` of the analyzed files to `folder`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+	pub pattern: Pattern,
+	/// relative to the analyzed directory
+	pub folder: PathBuf,
+	pub files: usize,
+}
+
+/// How sure a pattern must be before it's suggested
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Thresholds {
+	/// how many files must follow the pattern
+	pub min_files: usize,
+	/// the share of the files with the pattern that must be in the same folder
+	pub min_share: f64,
+}
+
+impl Default for Thresholds {
+	fn default() -> Self {
+		Self {
+			min_files: 3,
+			min_share: 0.8,
+		}
+	}
+}
+
+/// Lists the files under `root`, relative to it, skipping hidden files and directories
+pub fn files(root: &Path) -> Vec<PathBuf> {
+	WalkDir::new(root)
+		.min_depth(1)
+		.into_iter()
+		.filter_entry(|entry| !entry.path().is_hidden())
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.file_type().is_file())
+		.filter_map(|entry| entry.path().strip_prefix(root).ok().map(Path::to_path_buf))
+		.collect()
+}
+
+/// The first word of the filename, if it's long enough to mean something
+fn prefix(file: &Path) -> Option<String> {
+	let stem = file.file_stem()?.to_string_lossy().to_lowercase();
+	let word = stem.split(|c: char| !c.is_alphanumeric()).next()?;
+	(word.chars().count() >= 3 && !word.chars().all(|c| c.is_ascii_digit())).then(|| word.to_string())
+}
+
+fn extension(file: &Path) -> Option<String> {
+	Some(file.extension()?.to_string_lossy().to_lowercase())
+}
+
+/// The folder most of the files with some key are in, if there are enough of them and they agree enough
+fn dominant<K: Ord + Clone>(counts: HashMap<K, HashMap<PathBuf, usize>>, thresholds: &Thresholds) -> BTreeMap<K, (PathBuf, usize)> {
+	counts
+		.into_iter()
+		.filter_map(|(key, folders)| {
+			let total = folders.values().sum::<usize>();
+			let (folder, count) = folders
+				.into_iter()
+				.max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then_with(|| b.cmp(a)))?;
+			(count >= thresholds.min_files && count as f64 / total as f64 >= thresholds.min_share).then_some((key, (folder, count)))
+		})
+		.collect()
+}
+
+/// Proposes rules that would have sorted `files` (relative to the analyzed directory) the way they are sorted now.
+/// Filename prefixes come first, since they're more specific than extensions, and are only suggested when the extensions
+/// of the files don't already send them to the same folder.
+pub fn suggest(files: &[PathBuf], thresholds: &Thresholds) -> Vec<Suggestion> {
+	// files right in the analyzed directory haven't been sorted
+	let sorted = files
+		.iter()
+		.filter_map(|file| Some((file, file.parent().filter(|parent| !parent.as_os_str().is_empty())?)))
+		.collect::<Vec<_>>();
+
+	let mut by_extension: HashMap<String, HashMap<PathBuf, usize>> = HashMap::new();
+	let mut by_prefix: HashMap<String, HashMap<PathBuf, usize>> = HashMap::new();
+	for (file, folder) in sorted.iter() {
+		if let Some(extension) = extension(file) {
+			*by_extension
+				.entry(extension)
+				.or_default()
+				.entry(folder.to_path_buf())
+				.or_default() += 1;
+		}
+		if let Some(prefix) = prefix(file) {
+			*by_prefix.entry(prefix).or_default().entry(folder.to_path_buf()).or_default() += 1;
+		}
+	}
+	let extensions = dominant(by_extension, thresholds);
+	let prefixes = dominant(by_prefix, thresholds);
+
+	let explained = |file: &Path, folder: &Path| {
+		extension(file)
+			.and_then(|extension| extensions.get(&extension))
+			.is_some_and(|(to, _)| to == folder)
+	};
+	let mut suggestions = prefixes
+		.into_iter()
+		.filter(|(prefix, (folder, _))| {
+			sorted
+				.iter()
+				.any(|(file, parent)| parent == folder && prefix.as_str() == self::prefix(file).unwrap_or_default() && !explained(file, folder))
+		})
+		.map(|(prefix, (folder, files))| Suggestion {
+			pattern: Pattern::Prefix(prefix),
+			folder,
+			files,
+		})
+		.collect::<Vec<_>>();
+	suggestions.sort_by_key(|suggestion| std::cmp::Reverse(suggestion.files));
+
+	let mut grouped: BTreeMap<PathBuf, (Vec<String>, usize)> = BTreeMap::new();
+	for (extension, (folder, files)) in extensions {
+		let group = grouped.entry(folder).or_default();
+		group.0.push(extension);
+		group.1 += files;
+	}
+	let mut by_folder = grouped
+		.into_iter()
+		.map(|(folder, (extensions, files))| Suggestion {
+			pattern: Pattern::Extensions(extensions),
+			folder,
+			files,
+		})
+		.collect::<Vec<_>>();
+	by_folder.sort_by_key(|suggestion| std::cmp::Reverse(suggestion.files));
+	suggestions.extend(by_folder);
+	suggestions
+}
+
+fn quote<T: AsRef<str>>(s: T) -> String {
+	toml::Value::String(s.as_ref().to_string()).to_string()
+}
+
+/// Writes `suggestions` as a config whose rules move the files found in `from` to the folders of `root` they were found in
+pub fn to_config(suggestions: &[Suggestion], from: &Path, root: &Path) -> String {
+	let mut config = format!("version = {}\n", CONFIG_VERSION);
+	for suggestion in suggestions {
+		let folder = root.join(&suggestion.folder);
+		let (comment, filter) = match &suggestion.pattern {
+			Pattern::Extensions(extensions) => (
+				format!("{} files with these extensions are in {}", suggestion.files, suggestion.folder.display()),
+				format!(
+					"{{ type = \"extension\", extensions = [{}] }}",
+					extensions.iter().map(quote).collect::<Vec<_>>().join(", ")
+				),
+			),
+			Pattern::Prefix(prefix) => (
+				format!(
+					"{} files starting with \"{}\" are in {}",
+					suggestion.files,
+					prefix,
+					suggestion.folder.display()
+				),
+				format!("{{ type = \"filename\", startswith = {} }}", quote(prefix)),
+			),
+		};
+		let _ = write!(
+			config,
+			"\n# {}\n[[rules]]\nfolders = [{}]\nfilters = [{}]\nactions = [{{ type = \"move\", to = {} }}]\n",
+			comment,
+			quote(from.to_string_lossy()),
+			filter,
+			quote(format!("{}{}", folder.to_string_lossy(), std::path::MAIN_SEPARATOR)),
+		);
+	}
+	config
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn paths(paths: &[&str]) -> Vec<PathBuf> {
+		paths.iter().map(PathBuf::from).collect()
+	}
+
+	#[test]
+	fn suggests_extensions_and_prefixes() {
+		let files = paths(&[
+			"Photos/a.jpg",
+			"Photos/b.JPG",
+			"Photos/c.png",
+			"Photos/d.png",
+			"Photos/e.png",
+			"Photos/f.jpg",
+			"Finance/invoice-01.pdf",
+			"Finance/invoice-02.pdf",
+			"Finance/Invoice_03.pdf",
+			"Papers/attention.pdf",
+			"Papers/bert.pdf",
+			"Papers/gpt.pdf",
+			"Papers/resnet.pdf",
+			"loose.txt",
+			"loose2.txt",
+			"loose3.txt",
+		]);
+		let suggestions = suggest(&files, &Thresholds::default());
+		assert_eq!(
+			suggestions,
+			vec![
+				Suggestion {
+					pattern: Pattern::Prefix("invoice".into()),
+					folder: "Finance".into(),
+					files: 3
+				},
+				Suggestion {
+					pattern: Pattern::Extensions(vec!["jpg".into(), "png".into()]),
+					folder: "Photos".into(),
+					files: 6
+				},
+			]
+		);
+		// 4 of 7 pdfs are papers, which isn't enough to send every pdf there
+		let loose = Thresholds {
+			min_share: 0.5,
+			..Thresholds::default()
+		};
+		assert!(suggest(&files, &loose).contains(&Suggestion {
+			pattern: Pattern::Extensions(vec!["pdf".into()]),
+			folder: "Papers".into(),
+			files: 4
+		}));
+	}
+
+	#[test]
+	fn suggestions_are_a_valid_config() {
+		let suggestions = suggest(&paths(&["Docs/a.txt", "Docs/b.txt", "Docs/c.txt"]), &Thresholds::default());
+		let config = to_config(&suggestions, Path::new("/home/user/Downloads"), Path::new("/home/user/Documents"));
+		let value: toml::Value = toml::from_str(&config).unwrap();
+		let rule = &value["rules"][0];
+		assert_eq!(rule["filters"][0]["extensions"][0].as_str(), Some("txt"));
+		assert!(rule["actions"][0]["to"]
+			.as_str()
+			.unwrap()
+			.starts_with("/home/user/Documents/Docs"));
+	}
+}
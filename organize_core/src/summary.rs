@@ -0,0 +1,194 @@
+use std::{
+	collections::HashMap,
+	fmt,
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+use colored::Colorize;
+
+use crate::{
+	config::Config,
+	events::{Event, SkipReason},
+};
+
+/// How many destinations are listed in the summary
+const TOP_DESTINATIONS: usize = 5;
+
+/// The numbers behind a run, grouped for humans
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+	/// how many files each rule was applied to, by rule name
+	pub rules: Vec<(String, usize)>,
+	/// the directories that received the most files, busiest first
+	pub destinations: Vec<(PathBuf, usize)>,
+	pub conflicts: usize,
+	pub errors: usize,
+	/// how many files were left alone because the system refused an action on them
+	pub denied: usize,
+	/// how many bytes actions like `recompress` saved
+	pub saved: u64,
+	pub elapsed: Duration,
+}
+
+impl Summary {
+	pub fn new(events: &[Event], config: &Config, elapsed: Duration) -> Self {
+		let mut rules = vec![0; config.rules.len()];
+		let mut destinations: HashMap<&Path, usize> = HashMap::new();
+		let (mut conflicts, mut errors, mut denied, mut saved) = (0, 0, 0, 0);
+		for event in events {
+			match event {
+				Event::Matched { rule, .. } => {
+					if let Some(count) = rules.get_mut(*rule) {
+						*count += 1;
+					}
+				}
+				Event::Acted { to: Some(to), from, .. } if to != from => {
+					if let Some(parent) = to.parent() {
+						*destinations.entry(parent).or_insert(0) += 1;
+					}
+				}
+				Event::Acted { .. } => {}
+				Event::Conflict { .. } => conflicts += 1,
+				Event::Error { .. } => errors += 1,
+				Event::Saved { bytes, .. } => saved += bytes,
+				Event::Skipped {
+					reason: SkipReason::PermissionDenied,
+					..
+				} => denied += 1,
+				Event::Skipped { .. } => {}
+			}
+		}
+		let mut destinations: Vec<(PathBuf, usize)> = destinations
+			.into_iter()
+			.map(|(path, count)| (path.to_path_buf(), count))
+			.collect();
+		destinations.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+		destinations.truncate(TOP_DESTINATIONS);
+		Self {
+			rules: rules
+				.into_iter()
+				.enumerate()
+				.filter(|(_, count)| *count > 0)
+				.map(|(i, count)| (config.rules[i].name(i), count))
+				.collect(),
+			destinations,
+			conflicts,
+			errors,
+			denied,
+			saved,
+			elapsed,
+		}
+	}
+}
+
+impl fmt::Display for Summary {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "{} ({:.2}s)", "Summary".bold(), self.elapsed.as_secs_f64())?;
+		if self.rules.is_empty() {
+			writeln!(f, "  no rule was applied")?;
+		}
+		for (rule, count) in self.rules.iter() {
+			writeln!(f, "  {}: {} file(s)", rule.cyan(), count)?;
+		}
+		if !self.destinations.is_empty() {
+			writeln!(f, "  {}", "top destinations".bold())?;
+			for (dir, count) in self.destinations.iter() {
+				writeln!(f, "    {} ({})", dir.display(), count)?;
+			}
+		}
+		if self.saved > 0 {
+			writeln!(f, "  {} saved", bytes(self.saved))?;
+		}
+		let conflicts = format!("{} skipped due to conflicts", self.conflicts);
+		writeln!(f, "  {}", if self.conflicts > 0 { conflicts.yellow() } else { conflicts.normal() })?;
+		if self.denied > 0 {
+			writeln!(f, "  {}", format!("{} skipped for lack of permissions", self.denied).yellow())?;
+		}
+		let errors = format!("{} error(s)", self.errors);
+		write!(f, "  {}", if self.errors > 0 { errors.red() } else { errors.normal() })
+	}
+}
+
+/// `bytes` in the largest unit that keeps it above 1, e.g. `12.3 MB`
+pub fn bytes(bytes: u64) -> String {
+	const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+	let mut value = bytes as f64;
+	let mut unit = 0;
+	while value >= 1000.0 && unit < UNITS.len() - 1 {
+		value /= 1000.0;
+		unit += 1;
+	}
+	match unit {
+		0 => format!("{} B", bytes),
+		_ => format!("{:.1} {}", value, UNITS[unit]),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		config::{actions::ActionType, options::Options, Rule},
+		utils::DefaultOpt,
+	};
+
+	#[test]
+	fn count_events() {
+		let config = Config {
+			rules: vec![
+				Rule {
+					id: Some("docs".into()),
+					..Rule::default()
+				},
+				Rule::default(),
+			],
+			path: PathBuf::new(),
+			local_defaults: Options::default_none(),
+			global_defaults: Options::default_some(),
+			path_to_rules: HashMap::new(),
+			path_to_recursive: HashMap::new(),
+			predicates: vec![],
+			journal: Default::default(),
+			trash: None,
+			elevation: None,
+			locations: Default::default(),
+			constants: Default::default(),
+			messages: Default::default(),
+		};
+		let moved = |from: &str, to: &str| Event::Acted {
+			action: ActionType::Move,
+			from: PathBuf::from(from),
+			to: Some(PathBuf::from(to)),
+			output: None,
+		};
+		let events = vec![
+			Event::Matched {
+				rule: 0,
+				path: "/a/1.pdf".into(),
+			},
+			moved("/a/1.pdf", "/docs/1.pdf"),
+			Event::Matched {
+				rule: 0,
+				path: "/a/2.pdf".into(),
+			},
+			moved("/a/2.pdf", "/docs/2.pdf"),
+			Event::Matched {
+				rule: 1,
+				path: "/a/3.png".into(),
+			},
+			moved("/a/3.png", "/pics/3.png"),
+			Event::Conflict {
+				from: "/a/4.png".into(),
+				to: "/pics/4.png".into(),
+			},
+			Event::Error { message: "oops".into() },
+		];
+		let summary = Summary::new(&events, &config, Duration::from_secs(1));
+		assert_eq!(summary.rules, vec![("docs".to_string(), 2), ("#1".to_string(), 1)]);
+		assert_eq!(summary.destinations, vec![(PathBuf::from("/docs"), 2), (PathBuf::from("/pics"), 1)]);
+		assert_eq!((summary.conflicts, summary.errors), (1, 1));
+		assert_eq!(bytes(999), "999 B");
+		assert_eq!(bytes(12_345_678), "12.3 MB");
+	}
+}
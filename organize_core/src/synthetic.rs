@@ -0,0 +1,97 @@
+//! Synthetic directory trees, used to measure how the scan, filter and action stages perform on different layouts.
+
+use std::{
+	fs,
+	io::Write,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use strum_macros::{Display, EnumIter, EnumString};
+
+const EXTENSIONS: &[&str] = &["txt", "pdf", "jpg", "mp3"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum Shape {
+	/// a single directory containing lots of files
+	Wide,
+	/// a long chain of nested directories with a few files each
+	Deep,
+	/// many directories full of tiny files
+	ManySmall,
+	/// a handful of very large files
+	FewHuge,
+}
+
+impl Shape {
+	/// (directories, files per directory, file size in bytes, whether directories are nested)
+	fn layout(&self) -> (usize, usize, usize, bool) {
+		match self {
+			Self::Wide => (1, 10_000, 128, false),
+			Self::Deep => (50, 20, 128, true),
+			Self::ManySmall => (100, 200, 1024, false),
+			Self::FewHuge => (1, 4, 64 * 1024 * 1024, false),
+		}
+	}
+
+	/// Generates the tree inside `root` and returns the paths of the files it created
+	pub fn generate<T: AsRef<Path>>(&self, root: T) -> Result<Vec<PathBuf>> {
+		let (dirs, files_per_dir, size, nested) = self.layout();
+		let content = vec![b'a'; size];
+		let mut files = Vec::with_capacity(dirs * files_per_dir);
+		let mut dir = root.as_ref().to_path_buf();
+		for i in 0..dirs {
+			dir = match nested {
+				true => dir.join(format!("dir_{}", i)),
+				false => root.as_ref().join(format!("dir_{}", i)),
+			};
+			fs::create_dir_all(&dir).with_context(|| format!("could not create {}", dir.display()))?;
+			for j in 0..files_per_dir {
+				let path = dir.join(format!("file_{}.{}", j, EXTENSIONS[j % EXTENSIONS.len()]));
+				fs::File::create(&path)
+					.and_then(|mut file| file.write_all(&content))
+					.with_context(|| format!("could not create {}", path.display()))?;
+				files.push(path);
+			}
+		}
+		Ok(files)
+	}
+}
+
+/// A config with a single rule that copies the PDFs and JPGs inside `tree` into `out`
+pub fn config<T: AsRef<Path>, P: AsRef<Path>>(tree: T, out: P) -> String {
+	format!(
+		r#"
+[[rules]]
+folders = [{{ path = "{}", options = {{ recursive = 0 }} }}]
+filters = [{{ type = "extension", extensions = ["pdf", "jpg"] }}]
+actions = [{{ type = "copy", to = "{}/", allow_cycles = true }}]
+"#,
+		tree.as_ref().display(),
+		out.as_ref().display()
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config::Config;
+
+	#[test]
+	fn generate_deep() {
+		let dir = tempfile::tempdir().unwrap();
+		let files = Shape::Deep.generate(dir.path()).unwrap();
+		assert_eq!(files.len(), 50 * 20);
+		assert!(files.iter().all(|file| file.exists()));
+		assert_eq!(files.last().unwrap().ancestors().count() - dir.path().ancestors().count(), 51);
+	}
+
+	#[test]
+	fn parse_config() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("organize.toml");
+		fs::write(&path, config(dir.path(), dir.path().join("out"))).unwrap();
+		assert!(Config::parse(path).is_ok());
+	}
+}
@@ -0,0 +1,222 @@
+use std::{
+	collections::BTreeMap,
+	fmt, fs,
+	path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Deserialize;
+
+use crate::testing::Tree;
+
+/// A golden-file test for a config: the tree it starts from, and the tree it should leave behind, e.g.
+///
+/// ```toml
+/// config = '''
+/// [[rules]]
+/// folders = ["{tree}/inbox"]
+/// filters = [{ type = "extension", extensions = ["pdf"] }]
+/// actions = [{ type = "move", to = "{tree}/pdfs/" }]
+/// '''
+/// before = ["inbox/invoice.pdf", "inbox/notes.txt"]
+/// after = ["inbox/notes.txt", "pdfs/invoice.pdf"]
+/// ```
+///
+/// `config_file` can be used instead of `config` to test a config that lives elsewhere, relative to the case.
+/// Its locations are usually real directories, which `roots` maps to directories of the tree, e.g.
+/// `roots = { "~/Downloads" = "downloads" }`: the rules then run against `downloads`, and whatever they'd write inside
+/// `~/Downloads` is written there instead. A case that would read or write anything else outside of the tree fails.
+/// Both trees can also be tables of paths to contents, in which case the contents of the files after the run are compared too.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Case {
+	#[serde(skip)]
+	pub name: String,
+	#[serde(default)]
+	config: Option<String>,
+	#[serde(default)]
+	config_file: Option<PathBuf>,
+	/// real directories and the directory of the tree that stands for each of them
+	#[serde(default)]
+	roots: BTreeMap<PathBuf, PathBuf>,
+	#[serde(default)]
+	before: Files,
+	after: Files,
+}
+
+/// Paths relative to the root of the tree, with their contents if they matter
+#[derive(Deserialize, Debug, Default)]
+#[serde(untagged)]
+enum Files {
+	#[default]
+	None,
+	Paths(Vec<String>),
+	Contents(BTreeMap<String, String>),
+}
+
+impl Files {
+	fn iter(&self) -> Box<dyn Iterator<Item = (&str, Option<&str>)> + '_> {
+		match self {
+			Self::None => Box::new(std::iter::empty()),
+			Self::Paths(paths) => Box::new(paths.iter().map(|path| (path.as_str(), None))),
+			Self::Contents(files) => Box::new(files.iter().map(|(path, contents)| (path.as_str(), Some(contents.as_str())))),
+		}
+	}
+}
+
+/// How the tree a case left behind differs from the expected one
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Report {
+	/// files that should be there but aren't
+	pub missing: Vec<String>,
+	/// files that are there but shouldn't be
+	pub unexpected: Vec<String>,
+	/// files whose contents differ, as (path, expected, found)
+	pub changed: Vec<(String, String, String)>,
+	pub errors: Vec<String>,
+}
+
+impl Case {
+	/// Reads the case at `path`, named after its file
+	pub fn parse<T: AsRef<Path>>(path: T) -> Result<Self> {
+		let path = path.as_ref();
+		let content = fs::read_to_string(path).with_context(|| format!("could not read {}", path.display()))?;
+		let mut case: Self = toml::from_str(&content).with_context(|| format!("could not deserialize {}", path.display()))?;
+		case.name = path.file_stem().unwrap_or_default().to_string_lossy().to_string();
+		case.config = match (case.config.take(), &case.config_file) {
+			(Some(config), None) => Some(config),
+			(None, Some(file)) => {
+				let file = path.parent().unwrap_or_else(|| Path::new("")).join(file);
+				Some(fs::read_to_string(&file).with_context(|| format!("could not read {}", file.display()))?)
+			}
+			_ => bail!("{} needs either `config` or `config_file`", path.display()),
+		};
+		Ok(case)
+	}
+
+	/// Runs the case in a temporary tree and compares what's left with what's expected
+	pub fn run(&self) -> Result<Report> {
+		let tree = self
+			.before
+			.iter()
+			.fold(Tree::new(), |tree, (path, contents)| tree.file(path, contents.unwrap_or_default()));
+		let roots = self
+			.roots
+			.iter()
+			.map(|(from, to)| (from.clone(), to.clone()))
+			.collect::<Vec<_>>();
+		let outcome = tree.run_rooted(self.config.as_deref().unwrap_or_default(), &roots)?;
+		let layout = tree.layout();
+		let mut report = Report {
+			errors: outcome.errors().into_iter().map(str::to_string).collect(),
+			..Report::default()
+		};
+		for (path, contents) in self.after.iter() {
+			if !layout.iter().any(|file| file == path) {
+				report.missing.push(path.to_string());
+				continue;
+			}
+			if let Some(expected) = contents {
+				let found = tree.read(path);
+				if found != expected {
+					report.changed.push((path.to_string(), expected.to_string(), found));
+				}
+			}
+		}
+		report.unexpected = layout
+			.into_iter()
+			.filter(|file| !self.after.iter().any(|(path, _)| path == file))
+			.collect();
+		Ok(report)
+	}
+}
+
+impl Report {
+	pub fn passed(&self) -> bool {
+		self.missing.is_empty() && self.unexpected.is_empty() && self.changed.is_empty() && self.errors.is_empty()
+	}
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for path in self.missing.iter() {
+			writeln!(f, "{} {}", "-".red(), path)?;
+		}
+		for path in self.unexpected.iter() {
+			writeln!(f, "{} {}", "+".green(), path)?;
+		}
+		for (path, expected, found) in self.changed.iter() {
+			writeln!(f, "{} {}: expected {:?}, found {:?}", "~".yellow(), path, expected, found)?;
+		}
+		for error in self.errors.iter() {
+			writeln!(f, "{} {}", "error:".red(), error)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn case(dir: &Path, content: &str) -> Case {
+		let path = dir.join("pdfs.toml");
+		fs::write(&path, content).unwrap();
+		Case::parse(path).unwrap()
+	}
+
+	#[test]
+	fn golden_case() {
+		let dir = tempfile::tempdir().unwrap();
+		let config = r#"
+		config = '''
+		version = 2
+
+		[[rules]]
+		folders = ["{tree}/inbox"]
+		filters = [{ type = "extension", extensions = ["pdf"] }]
+		actions = [{ type = "move", to = "{tree}/pdfs/" }]
+		'''
+		"#;
+		let passing = case(
+			dir.path(),
+			&format!(
+				"{}\nbefore = {{ \"inbox/invoice.pdf\" = \"paid\", \"inbox/notes.txt\" = \"\" }}\nafter = {{ \"inbox/notes.txt\" = \"\", \"pdfs/invoice.pdf\" = \"paid\" }}",
+				config
+			),
+		);
+		assert_eq!(passing.name, "pdfs");
+		assert!(passing.run().unwrap().passed());
+		let failing = case(
+			dir.path(),
+			&format!("{}\nbefore = [\"inbox/invoice.pdf\"]\nafter = [\"inbox/invoice.pdf\"]", config),
+		);
+		let report = failing.run().unwrap();
+		assert_eq!(report.missing, vec!["inbox/invoice.pdf".to_string()]);
+		assert_eq!(report.unexpected, vec!["pdfs/invoice.pdf".to_string()]);
+	}
+
+	#[test]
+	fn stay_inside_tree() {
+		let (dir, real) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		fs::write(real.path().join("a.txt"), "real").unwrap();
+		let config = "config_file = \"config.toml\"\nbefore = [\"downloads/a.txt\"]\nafter = [\"downloads/texts/a.txt\"]\n";
+		fs::write(
+			dir.path().join("config.toml"),
+			format!(
+				"[[rules]]\nfolders = [{:?}]\nfilters = [{{ type = \"extension\", extensions = [\"txt\"] }}]\nactions = [{{ type = \"move\", to = {:?} }}]\n",
+				real.path(),
+				real.path().join("texts/")
+			),
+		)
+		.unwrap();
+		assert!(case(dir.path(), config).run().is_err());
+		assert!(real.path().join("a.txt").exists());
+
+		let rooted = case(dir.path(), &format!("{}roots = {{ {:?} = \"downloads\" }}", config, real.path()));
+		assert!(rooted.run().unwrap().passed());
+		assert!(real.path().join("a.txt").exists());
+		assert!(!real.path().join("texts").exists());
+	}
+}
@@ -0,0 +1,307 @@
+//! Helpers to write integration tests for configs and extensions, behind the `test-util` feature.
+//!
+//! A [`Tree`] is a temporary directory built file by file. [`Tree::run`] runs a config against it the way `organize run` would,
+//! with `{tree}` in the config standing for the tree's path, and returns what happened:
+//!
+//! ```ignore
+//! let tree = Tree::new().file("invoice.pdf", "").file("notes.txt", "");
+//! let outcome = tree.run(r#"
+//!     [[rules]]
+//!     folders = ["{tree}"]
+//!     filters = [{ type = "extension", extensions = ["pdf"] }]
+//!     actions = [{ type = "move", to = "{tree}/pdfs/" }]
+//! "#).unwrap();
+//! tree.assert_layout(&["notes.txt", "pdfs/invoice.pdf"]);
+//! outcome.assert_acted(ActionType::Move, "invoice.pdf", Some("pdfs/invoice.pdf"));
+//! ```
+//!
+//! Nothing is written to the journal on disk: the entries a run would have written are returned in its [`Outcome`] instead,
+//! and nothing is written outside of the tree: real directories can be mapped into it with [`Tree::run_rooted`].
+//! [`Case`] builds on it to describe a whole test in a file, for `organize test`.
+
+use std::{
+	collections::HashSet,
+	fs,
+	path::{Path, PathBuf},
+	sync::Mutex,
+};
+
+use anyhow::{bail, Context, Result};
+use lazy_static::lazy_static;
+use tempfile::TempDir;
+
+use crate::{
+	config::{actions::ActionType, Config, ConfigBuilder},
+	events::{self, Event},
+	file::File,
+	journal::{self, Entry},
+	path::sandbox::{self, Sandbox},
+};
+
+pub use case::{Case, Report};
+
+mod case;
+
+lazy_static! {
+	/// events and the journal are global, so runs can't overlap
+	static ref RUNNING: Mutex<()> = Mutex::new(());
+}
+
+/// A temporary directory tree to run configs against, removed when it's dropped
+pub struct Tree {
+	root: TempDir,
+	/// where the config is written, kept apart so that it doesn't show up in the layout
+	configs: TempDir,
+}
+
+/// What happened during a run
+#[derive(Debug)]
+pub struct Outcome {
+	/// the root of the tree the config ran against, which the paths given to the assertions are relative to
+	pub root: PathBuf,
+	pub events: Vec<Event>,
+	/// the entries the run added to the journal
+	pub journal: Vec<Entry>,
+}
+
+impl Default for Tree {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Tree {
+	pub fn new() -> Self {
+		Self {
+			root: tempfile::tempdir().expect("could not create a temporary directory"),
+			configs: tempfile::tempdir().expect("could not create a temporary directory"),
+		}
+	}
+
+	pub fn path(&self) -> &Path {
+		self.root.path()
+	}
+
+	/// Adds a file at `path`, relative to the root of the tree, creating its parent directories
+	pub fn file<T: AsRef<Path>, U: AsRef<[u8]>>(self, path: T, contents: U) -> Self {
+		let path = self.path().join(path);
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent).expect("could not create a directory in the tree");
+		}
+		fs::write(&path, contents).expect("could not write a file in the tree");
+		self
+	}
+
+	/// Adds an empty directory at `path`, relative to the root of the tree
+	pub fn dir<T: AsRef<Path>>(self, path: T) -> Self {
+		fs::create_dir_all(self.path().join(path)).expect("could not create a directory in the tree");
+		self
+	}
+
+	/// Runs `config` against the tree, replacing `{tree}` with its path. Every stage runs in order, one file at a time.
+	pub fn run(&self, config: &str) -> Result<Outcome> {
+		self.run_rooted(config, &[])
+	}
+
+	/// Like [`run`](Self::run), with the locations and destinations inside each real directory of `roots` moved to the matching
+	/// directory of the tree, relative to its root, e.g. `("~/Downloads", "downloads")`.
+	/// Locations outside of the tree are refused, and so are the actions that would write outside of it.
+	pub fn run_rooted(&self, config: &str, roots: &[(PathBuf, PathBuf)]) -> Result<Outcome> {
+		let _running = RUNNING.lock().unwrap_or_else(|e| e.into_inner());
+		let root = self.path().canonicalize()?;
+		let roots = roots.iter().map(|(from, to)| (from.clone(), root.join(to))).collect::<Vec<_>>();
+		for (_, to) in roots.iter() {
+			fs::create_dir_all(to).with_context(|| format!("could not create {} in the tree", to.display()))?;
+		}
+		let path = self.configs.path().join("config.toml");
+		fs::write(&path, config.replace("{tree}", &self.path().to_string_lossy()))?;
+		let config = Config::build(ConfigBuilder::parse(&path)?, &path).context("invalid config")?;
+		let _definitions = config.enter();
+		let config = match roots.is_empty() {
+			true => config,
+			// the locations already inside the tree stay where they are
+			false => config.with_roots(
+				&roots
+					.iter()
+					.cloned()
+					.chain(std::iter::once((root.clone(), root.clone())))
+					.collect::<Vec<_>>(),
+			)?,
+		};
+		if let Some(location) = config.path_to_rules.keys().find(|location| !location.starts_with(&root)) {
+			bail!(
+				"{} is outside of the test tree, map it to a directory of the tree with `roots`",
+				location.display()
+			);
+		}
+		let _sandbox = sandbox::enter(Sandbox::new(&root, &roots, self.configs.path().join("trash"))?);
+		events::start();
+		journal::capture();
+		let mut done = HashSet::new();
+		for stage in config.stages()? {
+			let config = config.restrict(&stage);
+			let dirs = config.targets_dirs();
+			for (location, recursive) in config.scan_roots() {
+				for entry in recursive
+					.to_walker(&location)
+					.contents_first(dirs)
+					.into_iter()
+					.filter_map(|entry| entry.ok())
+					.filter(|entry| entry.file_type().is_file() || (dirs && entry.file_type().is_dir()))
+				{
+					let path = entry.into_path();
+					if (path.is_file() || path.is_dir()) && !done.contains(&path) {
+						if let Some(path) = File::new(&path, &config, false).act(&config.path_to_rules) {
+							done.insert(path);
+						}
+					}
+				}
+			}
+		}
+		Ok(Outcome {
+			root: self.path().to_path_buf(),
+			events: events::finish(),
+			journal: journal::captured(),
+		})
+	}
+
+	/// Every file in the tree, relative to its root and sorted, with `/` as separator
+	pub fn layout(&self) -> Vec<String> {
+		let mut files = walkdir::WalkDir::new(self.path())
+			.into_iter()
+			.filter_map(|entry| entry.ok())
+			.filter(|entry| entry.file_type().is_file())
+			.map(|entry| relative(self.path(), entry.path()))
+			.collect::<Vec<_>>();
+		files.sort();
+		files
+	}
+
+	/// Panics unless the tree holds exactly `expected`, in any order
+	pub fn assert_layout(&self, expected: &[&str]) {
+		let mut expected = expected.iter().map(|path| path.to_string()).collect::<Vec<_>>();
+		expected.sort();
+		assert_eq!(self.layout(), expected, "unexpected layout in {}", self.path().display());
+	}
+
+	/// The contents of the file at `path`, relative to the root of the tree
+	pub fn read<T: AsRef<Path>>(&self, path: T) -> String {
+		let path = self.path().join(path);
+		fs::read_to_string(&path).unwrap_or_else(|e| panic!("could not read {}: {}", path.display(), e))
+	}
+}
+
+impl Outcome {
+	/// The actions that were carried out, as `(action, from, to)` with the paths relative to the root of the tree
+	pub fn actions(&self) -> Vec<(ActionType, String, Option<String>)> {
+		self.journal
+			.iter()
+			.map(|entry| {
+				(
+					entry.action,
+					relative(&self.root, &entry.from),
+					entry.to.as_deref().map(|to| relative(&self.root, to)),
+				)
+			})
+			.collect()
+	}
+
+	/// Panics unless `action` moved (or copied, renamed...) the file at `from` to `to`, both relative to the root of the tree
+	pub fn assert_acted(&self, action: ActionType, from: &str, to: Option<&str>) {
+		let expected = (action, from.to_string(), to.map(str::to_string));
+		let actions = self.actions();
+		assert!(
+			actions.contains(&expected),
+			"{:?} was not carried out, the actions were {:?}",
+			expected,
+			actions
+		);
+	}
+
+	/// The errors that were raised during the run
+	pub fn errors(&self) -> Vec<&str> {
+		self.events
+			.iter()
+			.filter_map(|event| match event {
+				Event::Error { message } => Some(message.as_str()),
+				_ => None,
+			})
+			.collect()
+	}
+}
+
+/// Arbitrary file names for property tests: with or without dots and extensions, with braces that look like placeholders,
+/// and on unix, bytes that aren't valid UTF-8
+#[cfg(test)]
+pub(crate) fn names() -> impl proptest::strategy::Strategy<Value = std::ffi::OsString> {
+	use proptest::prelude::*;
+
+	let text = prop::string::string_regex(r"[.a-zA-Z0-9 {}_\-é]{1,12}").unwrap();
+	#[cfg(unix)]
+	let bytes = prop::collection::vec(any::<u8>().prop_filter("not a separator", |b| *b != b'/' && *b != 0), 1..12).prop_map(|bytes| {
+		use std::os::unix::ffi::OsStringExt;
+		std::ffi::OsString::from_vec(bytes)
+	});
+	#[cfg(not(unix))]
+	let bytes = text.clone().prop_map(std::ffi::OsString::from);
+	prop_oneof![text.prop_map(std::ffi::OsString::from), bytes]
+}
+
+/// `path` relative to `root` with `/` as separator, or as it is if it's outside of it
+fn relative(root: &Path, path: &Path) -> String {
+	let path = path.strip_prefix(root).unwrap_or(path);
+	path.components()
+		.map(|component| component.as_os_str().to_string_lossy())
+		.collect::<Vec<_>>()
+		.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn run_config_against_tree() {
+		let tree = Tree::new().file("invoice.pdf", "paid").file("notes.txt", "").dir("empty");
+		let outcome = tree
+			.run(
+				r#"
+				version = 2
+
+				[[rules]]
+				folders = ["{tree}"]
+				filters = [{ type = "extension", extensions = ["pdf"] }]
+				actions = [{ type = "move", to = "{tree}/pdfs/" }]
+				"#,
+			)
+			.unwrap();
+		tree.assert_layout(&["notes.txt", "pdfs/invoice.pdf"]);
+		assert_eq!(tree.read("pdfs/invoice.pdf"), "paid");
+		outcome.assert_acted(ActionType::Move, "invoice.pdf", Some("pdfs/invoice.pdf"));
+		assert_eq!(outcome.actions().len(), 1);
+		assert!(outcome.errors().is_empty());
+	}
+
+	#[test]
+	fn walk_as_deep_as_recursive() {
+		let config = |recursive: u16| {
+			format!(
+				r#"
+				version = 2
+
+				[[rules]]
+				folders = [{{ path = "{{tree}}/inbox", options = {{ recursive = {} }} }}]
+				filters = [{{ type = "extension", extensions = ["pdf"] }}]
+				actions = [{{ type = "move", to = "{{tree}}/pdfs/" }}]
+				"#,
+				recursive
+			)
+		};
+		let tree = || Tree::new().file("inbox/top.pdf", "").file("inbox/a/b/deep.pdf", "");
+		let (shallow, full) = (tree(), tree());
+		shallow.run(&config(1)).unwrap();
+		shallow.assert_layout(&["inbox/a/b/deep.pdf", "pdfs/top.pdf"]);
+		full.run(&config(0)).unwrap();
+		full.assert_layout(&["pdfs/deep.pdf", "pdfs/top.pdf"]);
+	}
+}
@@ -0,0 +1,136 @@
+//! Keeps each rule within the limits it sets for itself, so that a heavy rule doesn't starve the others running in the same process:
+//! `max_concurrency` caps how many files it handles at a time, and `io_priority` lowers the IO priority of the threads handling them.
+
+use std::{
+	cell::Cell,
+	collections::HashMap,
+	num::NonZeroUsize,
+	sync::{Condvar, Mutex},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{
+	config::Rule,
+	priority::{self, IoPriority},
+};
+
+lazy_static! {
+	/// how many files each rule with a `max_concurrency` is handling right now
+	static ref RUNNING: (Mutex<HashMap<usize, usize>>, Condvar) = (Mutex::new(HashMap::new()), Condvar::new());
+}
+
+thread_local! {
+	// the limits of the rule whose actions this thread is carrying out, see [`enter`]
+	static CURRENT: Cell<Option<Limits>> = const { Cell::new(None) };
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+	rule: usize,
+	max_concurrency: Option<NonZeroUsize>,
+	io_priority: Option<IoPriority>,
+}
+
+impl Limits {
+	fn of(i: usize, rule: &Rule) -> Self {
+		Self {
+			rule: i,
+			max_concurrency: rule.max_concurrency,
+			io_priority: rule.io_priority,
+		}
+	}
+
+	fn apply<T>(self, f: impl FnOnce() -> T) -> T {
+		let _slot = self.max_concurrency.map(|max| Slot::acquire(self.rule, max.get()));
+		match self.io_priority {
+			Some(priority) => priority::with_io_priority(priority, f),
+			None => f(),
+		}
+	}
+}
+
+/// Runs `f` for the rule at position `i`, waiting for its turn if it's already handling as many files as it allows
+pub(crate) fn within<T>(i: usize, rule: &Rule, f: impl FnOnce() -> T) -> T {
+	Limits::of(i, rule).apply(f)
+}
+
+/// Makes the rule at position `i` the one whose actions this thread carries out, until the guard is dropped
+pub(crate) fn enter(i: usize, rule: &Rule) -> Guard {
+	Guard(CURRENT.with(|current| current.replace(Some(Limits::of(i, rule)))))
+}
+
+/// Runs `f`, an action, within the limits of the rule it belongs to, if any
+pub(crate) fn within_current<T>(f: impl FnOnce() -> T) -> T {
+	match CURRENT.with(Cell::get) {
+		Some(limits) => limits.apply(f),
+		None => f(),
+	}
+}
+
+/// Puts back the rule that was current before [`enter`] when it's dropped
+pub(crate) struct Guard(Option<Limits>);
+
+impl Drop for Guard {
+	fn drop(&mut self) {
+		CURRENT.with(|current| current.set(self.0));
+	}
+}
+
+/// One of the files a rule is allowed to handle at the same time, given back when it's dropped
+struct Slot(usize);
+
+impl Slot {
+	fn acquire(rule: usize, max: usize) -> Self {
+		let (running, turn) = &*RUNNING;
+		let mut running = running.lock().unwrap_or_else(|e| e.into_inner());
+		while running.get(&rule).copied().unwrap_or_default() >= max {
+			running = turn.wait(running).unwrap_or_else(|e| e.into_inner());
+		}
+		*running.entry(rule).or_default() += 1;
+		Self(rule)
+	}
+}
+
+impl Drop for Slot {
+	fn drop(&mut self) {
+		let (running, turn) = &*RUNNING;
+		if let Some(count) = running.lock().unwrap_or_else(|e| e.into_inner()).get_mut(&self.0) {
+			*count -= 1;
+		}
+		turn.notify_all();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{
+		num::NonZeroUsize,
+		sync::atomic::{AtomicUsize, Ordering},
+		thread,
+		time::Duration,
+	};
+
+	use super::*;
+
+	#[test]
+	fn caps_concurrency() {
+		let rule = Rule {
+			max_concurrency: NonZeroUsize::new(2),
+			..Rule::default()
+		};
+		let (current, highest) = (AtomicUsize::new(0), AtomicUsize::new(0));
+		thread::scope(|scope| {
+			for _ in 0..6 {
+				scope.spawn(|| {
+					within(usize::MAX, &rule, || {
+						highest.fetch_max(current.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+						thread::sleep(Duration::from_millis(20));
+						current.fetch_sub(1, Ordering::SeqCst);
+					})
+				});
+			}
+		});
+		assert_eq!(highest.load(Ordering::SeqCst), 2);
+	}
+}
@@ -0,0 +1,250 @@
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::{config::actions::ActionType, with_db};
+
+lazy_static! {
+	/// whether any thumbnail was ever made, so that the moves of users who don't make any don't cost a query each
+	static ref KNOWN: AtomicBool = AtomicBool::new(with_db(init, |conn| Ok(conn.prepare("SELECT 1 FROM thumbnails")?.exists([])?)).unwrap_or(false));
+}
+
+/// A thumbnail made for a file, kept in `tree` at the same place the file is in `base`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Thumbnail {
+	pub source: PathBuf,
+	pub path: PathBuf,
+	pub base: PathBuf,
+	pub tree: PathBuf,
+}
+
+impl Thumbnail {
+	/// Where the thumbnail of `source` goes in `tree`, if `source` is inside `base`
+	pub fn path_in(tree: &Path, base: &Path, source: &Path) -> Option<PathBuf> {
+		let relative = source.strip_prefix(base).ok()?;
+		let mut name = relative.as_os_str().to_os_string();
+		name.push(".jpg");
+		Some(tree.join(name))
+	}
+}
+
+fn init(conn: &Connection) -> Result<()> {
+	conn.execute(
+		"CREATE TABLE IF NOT EXISTS thumbnails (
+			source TEXT PRIMARY KEY,
+			thumbnail TEXT NOT NULL,
+			base TEXT NOT NULL,
+			tree TEXT NOT NULL
+		)",
+		[],
+	)
+	.context("could not create the thumbnails table")?;
+	Ok(())
+}
+
+/// Remembers that `thumbnail` was made, so that it follows its source around
+pub fn record(thumbnail: &Thumbnail) -> Result<()> {
+	KNOWN.store(true, Ordering::Relaxed);
+	with_db(init, |conn| insert(conn, thumbnail))
+}
+
+fn insert(conn: &Connection, thumbnail: &Thumbnail) -> Result<()> {
+	conn.execute(
+		"INSERT OR REPLACE INTO thumbnails (source, thumbnail, base, tree) VALUES (?1, ?2, ?3, ?4)",
+		params![
+			thumbnail.source.to_string_lossy(),
+			thumbnail.path.to_string_lossy(),
+			thumbnail.base.to_string_lossy(),
+			thumbnail.tree.to_string_lossy()
+		],
+	)
+	.context("could not record a thumbnail")?;
+	Ok(())
+}
+
+fn get(conn: &Connection, source: &Path) -> Result<Option<Thumbnail>> {
+	Ok(conn
+		.query_row(
+			"SELECT thumbnail, base, tree FROM thumbnails WHERE source = ?1",
+			params![source.to_string_lossy()],
+			|row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)),
+		)
+		.optional()?
+		.map(|(path, base, tree)| Thumbnail {
+			source: source.to_path_buf(),
+			path: path.into(),
+			base: base.into(),
+			tree: tree.into(),
+		}))
+}
+
+fn remove(conn: &Connection, thumbnail: &Thumbnail) -> Result<()> {
+	match fs::remove_file(&thumbnail.path) {
+		Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+			return Err(e).with_context(|| format!("could not remove {}", thumbnail.path.display()))
+		}
+		_ => {}
+	}
+	// leave no empty directories behind in the tree
+	for dir in thumbnail
+		.path
+		.ancestors()
+		.skip(1)
+		.take_while(|dir| dir.starts_with(&thumbnail.tree))
+	{
+		if fs::remove_dir(dir).is_err() {
+			break;
+		}
+	}
+	conn.execute("DELETE FROM thumbnails WHERE source = ?1", params![thumbnail.source.to_string_lossy()])?;
+	Ok(())
+}
+
+/// Keeps the thumbnail of a file in sync with what an action did to it: it's moved along with the file while it stays
+/// inside the same tree, and removed when the file is deleted or leaves the tree
+pub(crate) fn follow(action: ActionType, from: &Path, to: Option<&Path>) {
+	if !KNOWN.load(Ordering::Relaxed) || !matches!(action, ActionType::Move | ActionType::Delete | ActionType::Trash) {
+		return;
+	}
+	if let Err(e) = with_db(init, |conn| follow_with(conn, action, from, to)) {
+		log::warn!("could not update the thumbnail of {}: {:?}", from.display(), e);
+	}
+}
+
+fn follow_with(conn: &Connection, action: ActionType, from: &Path, to: Option<&Path>) -> Result<()> {
+	let thumbnail = match get(conn, from)? {
+		Some(thumbnail) => thumbnail,
+		None => return Ok(()),
+	};
+	let moved = match (action, to) {
+		(ActionType::Move, Some(to)) => Thumbnail::path_in(&thumbnail.tree, &thumbnail.base, to).map(|path| (to, path)),
+		_ => None,
+	};
+	match moved {
+		Some((to, path)) if thumbnail.path.exists() => {
+			if let Some(parent) = path.parent() {
+				fs::create_dir_all(parent).with_context(|| format!("could not create {}", parent.display()))?;
+			}
+			fs::rename(&thumbnail.path, &path).with_context(|| format!("could not move {}", thumbnail.path.display()))?;
+			insert(
+				conn,
+				&Thumbnail {
+					source: to.to_path_buf(),
+					path,
+					..thumbnail.clone()
+				},
+			)?;
+			// forgets the old source and prunes the directories the thumbnail was moved out of
+			remove(
+				conn,
+				&Thumbnail {
+					source: from.to_path_buf(),
+					..thumbnail
+				},
+			)
+		}
+		_ => remove(conn, &thumbnail),
+	}
+}
+
+/// Removes the thumbnails of the files that were removed or moved by something other than organize.
+/// Returns how many were removed.
+pub fn sweep() -> Result<usize> {
+	if !KNOWN.load(Ordering::Relaxed) {
+		return Ok(0);
+	}
+	with_db(init, sweep_with)
+}
+
+/// Runs [`sweep`] before a run or a watch starts
+pub fn check() {
+	match sweep() {
+		Ok(0) => {}
+		Ok(removed) => log::info!("removed {} thumbnails of files that are gone", removed),
+		Err(e) => log::warn!("could not clean up the thumbnails: {:?}", e),
+	}
+}
+
+fn sweep_with(conn: &Connection) -> Result<usize> {
+	let sources = conn
+		.prepare("SELECT source FROM thumbnails")?
+		.query_map([], |row| row.get::<_, String>(0))?
+		.collect::<rusqlite::Result<Vec<_>>>()?;
+	let mut removed = 0;
+	for source in sources.iter().map(PathBuf::from).filter(|source| !source.exists()) {
+		if let Some(thumbnail) = get(conn, &source)? {
+			remove(conn, &thumbnail)?;
+			removed += 1;
+		}
+	}
+	Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn thumbnails_follow_their_source() {
+		let dir = tempfile::tempdir().unwrap();
+		let (base, tree) = (dir.path().join("videos"), dir.path().join("videos/.thumbs"));
+		let source = base.join("2023/trip.mp4");
+		let thumbnail = Thumbnail {
+			path: Thumbnail::path_in(&tree, &base, &source).unwrap(),
+			source: source.clone(),
+			base: base.clone(),
+			tree: tree.clone(),
+		};
+		assert_eq!(thumbnail.path, tree.join("2023/trip.mp4.jpg"));
+		fs::create_dir_all(thumbnail.path.parent().unwrap()).unwrap();
+		fs::write(&thumbnail.path, "jpg").unwrap();
+		let conn = Connection::open_in_memory().unwrap();
+		init(&conn).unwrap();
+		insert(&conn, &thumbnail).unwrap();
+
+		let moved = base.join("sorted/trip.mp4");
+		follow_with(&conn, ActionType::Move, &source, Some(&moved)).unwrap();
+		let thumbnail = get(&conn, &moved).unwrap().unwrap();
+		assert_eq!(thumbnail.path, tree.join("sorted/trip.mp4.jpg"));
+		assert!(thumbnail.path.exists());
+		assert!(!tree.join("2023").exists());
+
+		// moving it out of the tree's base drops the thumbnail
+		follow_with(&conn, ActionType::Move, &moved, Some(&dir.path().join("elsewhere.mp4"))).unwrap();
+		assert!(!thumbnail.path.exists());
+		assert_eq!(get(&conn, &moved).unwrap(), None);
+	}
+
+	#[test]
+	fn sweep_thumbnails_of_missing_files() {
+		let dir = tempfile::tempdir().unwrap();
+		let (kept, gone) = (dir.path().join("kept.mp4"), dir.path().join("gone.mp4"));
+		fs::write(&kept, "").unwrap();
+		let conn = Connection::open_in_memory().unwrap();
+		init(&conn).unwrap();
+		for source in [&kept, &gone] {
+			let path = Thumbnail::path_in(&dir.path().join(".thumbs"), dir.path(), source).unwrap();
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(&path, "jpg").unwrap();
+			insert(
+				&conn,
+				&Thumbnail {
+					source: source.clone(),
+					path,
+					base: dir.path().to_path_buf(),
+					tree: dir.path().join(".thumbs"),
+				},
+			)
+			.unwrap();
+		}
+		assert_eq!(sweep_with(&conn).unwrap(), 1);
+		assert!(dir.path().join(".thumbs/kept.mp4.jpg").exists());
+		assert!(!dir.path().join(".thumbs/gone.mp4.jpg").exists());
+	}
+}
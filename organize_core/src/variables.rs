@@ -0,0 +1,225 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local};
+use strum_macros::{Display, EnumIter, EnumString};
+
+use crate::{audio::Tags, config::filters::mime, context::Context, dimensions::Dimensions, media::MediaName};
+
+/// Values that filters compute while matching a file, which templates can then use without computing them again,
+/// e.g. `~/Pictures/{mime.subtype}/{filename}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString, EnumIter)]
+pub enum Variable {
+	/// the top-level MIME type, e.g. `image`
+	#[strum(serialize = "mime.type")]
+	MimeType,
+	/// e.g. `jpeg`
+	#[strum(serialize = "mime.subtype")]
+	MimeSubtype,
+	/// the top-level MIME type according to the first bytes of the file, or its extension if they aren't recognized
+	#[strum(serialize = "mime_deep.type")]
+	MimeDeepType,
+	/// e.g. `png` for a screenshot saved without an extension
+	#[strum(serialize = "mime_deep.subtype")]
+	MimeDeepSubtype,
+	/// the size in bytes
+	#[strum(serialize = "size.bytes")]
+	SizeBytes,
+	/// `tiny` (under 100KB), `small` (under 10MB), `medium` (under 100MB), `large` (under 1GB) or `huge`
+	#[strum(serialize = "size.bucket")]
+	SizeBucket,
+	/// the image that a `similar` filter found the file to look like
+	#[strum(serialize = "similar")]
+	Similar,
+	/// the file that a `duplicate` filter found to have the same contents
+	#[strum(serialize = "duplicate")]
+	Duplicate,
+	/// the year the file was last modified
+	#[strum(serialize = "modified.year")]
+	ModifiedYear,
+	/// two digits, e.g. `03`
+	#[strum(serialize = "modified.month")]
+	ModifiedMonth,
+	/// two digits, e.g. `09`
+	#[strum(serialize = "modified.day")]
+	ModifiedDay,
+	/// the title of the movie or the name of the show, parsed from the file name like the fields below
+	#[strum(serialize = "media_name.title")]
+	MediaTitle,
+	/// the year of release, e.g. `2021` in `Movie (2021)`
+	#[strum(serialize = "media_name.year")]
+	MediaYear,
+	/// two digits, e.g. `01` in `Show.S01E05`
+	#[strum(serialize = "media_name.season")]
+	MediaSeason,
+	/// two digits, e.g. `05` in `Show.S01E05`
+	#[strum(serialize = "media_name.episode")]
+	MediaEpisode,
+	/// e.g. `Pilot` in `Show.S01E05.Pilot.1080p`
+	#[strum(serialize = "media_name.episode_title")]
+	MediaEpisodeTitle,
+	/// the tags of a song, read from its ID3v2 tag or its Vorbis comments
+	#[strum(serialize = "tags.artist")]
+	TagsArtist,
+	#[strum(serialize = "tags.album_artist")]
+	TagsAlbumArtist,
+	#[strum(serialize = "tags.album")]
+	TagsAlbum,
+	#[strum(serialize = "tags.title")]
+	TagsTitle,
+	#[strum(serialize = "tags.genre")]
+	TagsGenre,
+	/// e.g. `1959`
+	#[strum(serialize = "tags.year")]
+	TagsYear,
+	/// two digits, e.g. `03`
+	#[strum(serialize = "tags.track")]
+	TagsTrack,
+	/// the width of a picture in pixels, as it's displayed
+	#[strum(serialize = "image.width")]
+	ImageWidth,
+	#[strum(serialize = "image.height")]
+	ImageHeight,
+	/// with one decimal, e.g. `12.2`
+	#[strum(serialize = "image.megapixels")]
+	ImageMegapixels,
+	/// e.g. `16:9`, or `2.16` if the dimensions don't reduce to a common ratio
+	#[strum(serialize = "image.aspect_ratio")]
+	ImageAspectRatio,
+	/// `portrait`, `landscape` or `square`
+	#[strum(serialize = "image.orientation")]
+	ImageOrientation,
+}
+
+impl Variable {
+	/// The value of this variable for the file at `path`, reusing the one a filter already computed if there is one
+	pub fn value<T: AsRef<Path>>(&self, path: T) -> Result<String> {
+		if let Some(value) = Context::variable(*self) {
+			return Ok(value);
+		}
+		let path = path.as_ref();
+		match self {
+			Self::MimeType | Self::MimeSubtype => {
+				let guess = mime_guess::from_path(path).first_or_octet_stream();
+				Ok(match self {
+					Self::MimeType => guess.type_().to_string(),
+					_ => guess.subtype().to_string(),
+				})
+			}
+			Self::MimeDeepType | Self::MimeDeepSubtype => {
+				let guess = mime::sniff(path).unwrap_or_else(|| mime_guess::from_path(path).first_or_octet_stream());
+				Ok(match self {
+					Self::MimeDeepType => guess.type_().to_string(),
+					_ => guess.subtype().to_string(),
+				})
+			}
+			Self::SizeBytes | Self::SizeBucket => {
+				let size = path
+					.metadata()
+					.map_err(|e| anyhow!("could not read the size of {}: {}", path.display(), e))?
+					.len();
+				Ok(match self {
+					Self::SizeBytes => size.to_string(),
+					_ => Self::bucket(size).to_string(),
+				})
+			}
+			Self::ModifiedYear | Self::ModifiedMonth | Self::ModifiedDay => {
+				let modified: DateTime<Local> = path
+					.metadata()
+					.and_then(|metadata| metadata.modified())
+					.map_err(|e| anyhow!("could not read when {} was modified: {}", path.display(), e))?
+					.into();
+				Ok(match self {
+					Self::ModifiedYear => modified.format("%Y"),
+					Self::ModifiedMonth => modified.format("%m"),
+					_ => modified.format("%d"),
+				}
+				.to_string())
+			}
+			Self::Similar => Err(anyhow!("{{similar}} is only known once a `similar` filter matched {}", path.display())),
+			Self::Duplicate => Err(anyhow!(
+				"{{duplicate}} is only known once a `duplicate` filter matched {}",
+				path.display()
+			)),
+			Self::MediaTitle | Self::MediaYear | Self::MediaSeason | Self::MediaEpisode | Self::MediaEpisodeTitle => {
+				let name = MediaName::parse(&path.file_stem().unwrap_or_default().to_string_lossy());
+				let value = match self {
+					Self::MediaTitle => (!name.title.is_empty()).then_some(name.title),
+					Self::MediaYear => name.year.map(|year| year.to_string()),
+					Self::MediaSeason => name.season.map(|season| format!("{:02}", season)),
+					Self::MediaEpisode => name.episode.map(|episode| format!("{:02}", episode)),
+					_ => name.episode_title,
+				};
+				// a missing field fails the template, so that the destination falls back to the next one
+				value.ok_or_else(|| anyhow!("the name of {} has no {{{}}}", path.display(), self))
+			}
+			Self::TagsArtist | Self::TagsAlbumArtist | Self::TagsAlbum | Self::TagsTitle | Self::TagsGenre | Self::TagsYear | Self::TagsTrack => {
+				// like above, unless an `audio_tags` filter set a fallback
+				let tags = Tags::read(path)?;
+				tags.as_ref()
+					.and_then(|tags| tags.get(*self))
+					.map(str::to_string)
+					.ok_or_else(|| anyhow!("{} has no {{{}}} tag", path.display(), self))
+			}
+			Self::ImageWidth | Self::ImageHeight | Self::ImageMegapixels | Self::ImageAspectRatio | Self::ImageOrientation => {
+				let dimensions = Dimensions::read(path)?;
+				Ok(dimensions.get(*self).unwrap_or_default())
+			}
+		}
+	}
+
+	pub fn bucket(size: u64) -> &'static str {
+		match size {
+			size if size < 100_000 => "tiny",
+			size if size < 10_000_000 => "small",
+			size if size < 100_000_000 => "medium",
+			size if size < 1_000_000_000 => "large",
+			_ => "huge",
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::str::FromStr;
+
+	use super::*;
+
+	#[test]
+	fn prefer_context() {
+		assert_eq!(Variable::from_str("mime.subtype").unwrap(), Variable::MimeSubtype);
+		assert_eq!(Variable::MimeSubtype.value("photo.png").unwrap(), "png");
+		let _context = Context::enter("photo.png", None);
+		Context::set_variable(Variable::MimeSubtype, "webp");
+		assert_eq!(Variable::MimeSubtype.value("photo.png").unwrap(), "webp");
+		assert_eq!(Variable::bucket(5_000_000), "small");
+	}
+
+	#[test]
+	fn media_name() {
+		let episode = "/tv/The.Show.S01E05.1080p.mkv";
+		assert_eq!(Variable::from_str("media_name.season").unwrap(), Variable::MediaSeason);
+		assert_eq!(Variable::MediaTitle.value(episode).unwrap(), "The Show");
+		assert_eq!(Variable::MediaEpisode.value(episode).unwrap(), "05");
+		assert!(Variable::MediaYear.value(episode).is_err());
+		assert_eq!(Variable::MediaYear.value("/movies/Movie (2021).mkv").unwrap(), "2021");
+	}
+
+	#[test]
+	fn modified_date() {
+		let file = tempfile::NamedTempFile::new().unwrap();
+		let year = Local::now().format("%Y").to_string();
+		assert_eq!(Variable::from_str("modified.month").unwrap(), Variable::ModifiedMonth);
+		assert_eq!(Variable::ModifiedYear.value(file.path()).unwrap(), year);
+		assert_eq!(Variable::ModifiedDay.value(file.path()).unwrap().len(), 2);
+	}
+
+	#[test]
+	fn mime_deep() {
+		let file = tempfile::NamedTempFile::new().unwrap();
+		std::fs::write(file.path(), b"%PDF-1.7\n").unwrap();
+		assert_eq!(Variable::from_str("mime_deep.subtype").unwrap(), Variable::MimeDeepSubtype);
+		assert_eq!(Variable::MimeDeepSubtype.value(file.path()).unwrap(), "pdf");
+		assert_eq!(Variable::MimeDeepType.value("/nonexistent/photo.png").unwrap(), "image");
+	}
+}
@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use clap::Parser;
+use strum::IntoEnumIterator;
+
+use organize_core::{config::Config, file::File, synthetic::Shape};
+
+use crate::cmd::Cmd;
+
+/// Measures scan, filter and action throughput on synthetic directory trees
+#[derive(Parser, Debug)]
+pub struct Bench {
+	/// Only benchmark this shape (wide, deep, many_small, few_huge)
+	#[arg(long)]
+	shape: Option<Shape>,
+}
+
+impl Cmd for Bench {
+	fn run(self) -> Result<()> {
+		let shapes: Vec<Shape> = match self.shape {
+			Some(shape) => vec![shape],
+			None => Shape::iter().collect(),
+		};
+		for shape in shapes {
+			self.bench(shape)?;
+		}
+		Ok(())
+	}
+}
+
+impl Bench {
+	fn bench(&self, shape: Shape) -> Result<()> {
+		let dir = tempfile::tempdir()?;
+		let tree = dir.path().join("tree");
+		let files = shape.generate(&tree)?;
+		let path = dir.path().join("organize.toml");
+		std::fs::write(&path, organize_core::synthetic::config(&tree, dir.path().join("out")))?;
+		let config = Config::parse(path)?;
+
+		let (scan, scanned) = Self::time(|| {
+			config
+				.path_to_recursive
+				.iter()
+				.map(|(path, recursive)| recursive.to_walker(path).into_iter().filter_map(|e| e.ok()).count())
+				.sum::<usize>()
+		});
+		let (filter, matched) = Self::time(|| {
+			files
+				.iter()
+				.filter(|path| {
+					!File::new(*path, &config, false)
+						.get_matching_rules(&config.path_to_rules)
+						.is_empty()
+				})
+				.count()
+		});
+		let (action, _) = Self::time(|| {
+			files.iter().for_each(|path| {
+				File::new(path, &config, false).act(&config.path_to_rules);
+			})
+		});
+
+		println!("{} ({} files)", shape, files.len());
+		println!("  scan:   {:>10.2?} ({:.0} entries/s)", scan, Self::rate(scanned, scan));
+		println!("  filter: {:>10.2?} ({:.0} files/s)", filter, Self::rate(files.len(), filter));
+		println!("  action: {:>10.2?} ({:.0} files/s)", action, Self::rate(matched, action));
+		Ok(())
+	}
+
+	fn time<T, F: FnOnce() -> T>(f: F) -> (Duration, T) {
+		let start = Instant::now();
+		let result = f();
+		(start.elapsed(), result)
+	}
+
+	fn rate(count: usize, elapsed: Duration) -> f64 {
+		count as f64 / elapsed.as_secs_f64()
+	}
+}
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+
+use organize_core::config::{diff::ConfigDiff, Config};
+
+use crate::cmd::Cmd;
+
+/// Shows what changed between two configs, and which locations it affects
+#[derive(Parser, Debug)]
+pub struct DiffConfig {
+	old: PathBuf,
+	/// defaults to the current config
+	new: Option<PathBuf>,
+}
+
+impl Cmd for DiffConfig {
+	fn run(self) -> Result<()> {
+		let old = Config::parse(&self.old)?;
+		let new = Config::parse(match self.new {
+			Some(new) => new,
+			None => Config::path()?,
+		})?;
+		println!("{}", ConfigDiff::new(&old, &new));
+		Ok(())
+	}
+}
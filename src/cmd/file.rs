@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use clap::Parser;
+use walkdir::WalkDir;
+
+use organize_core::{config::Config, file::File, journal, messages};
+
+use crate::cmd::Cmd;
+
+/// Runs the rules on the given files right away, e.g. from a file manager's context menu (see `organize integrate`).
+/// Only the rules of the locations the files are in apply to them. Directories are processed file by file.
+#[derive(Parser, Debug)]
+pub struct Files {
+	#[arg(long, short = 'c')]
+	config: Option<PathBuf>,
+	#[arg(required = true)]
+	paths: Vec<PathBuf>,
+}
+
+impl Cmd for Files {
+	fn run(self) -> Result<()> {
+		let config = Config::parse(match self.config {
+			Some(config) => config,
+			None => Config::path()?,
+		})?;
+		journal::enable(config.journal.clone());
+		messages::set(&config.messages);
+		for path in self.paths {
+			let path = path.canonicalize().unwrap_or(path);
+			if !config.path_to_rules.keys().any(|location| path.starts_with(location)) {
+				log::warn!("{} is not inside any location, no rule applies to it", path.display());
+				continue;
+			}
+			let files = WalkDir::new(&path)
+				.into_iter()
+				.filter_map(|entry| entry.ok())
+				.filter(|entry| entry.file_type().is_file())
+				.map(|entry| entry.into_path())
+				.collect::<Vec<_>>();
+			for file in files {
+				File::new(file, &config, false).act(&config.path_to_rules);
+			}
+		}
+		journal::flush();
+		Ok(())
+	}
+}
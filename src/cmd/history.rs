@@ -0,0 +1,99 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand};
+
+use organize_core::{
+	config::Config,
+	corrections,
+	journal::{self, Format},
+};
+
+use crate::cmd::Cmd;
+
+/// Manages the journal of the actions carried out by `run` and `watch`
+#[derive(Subcommand, Debug)]
+pub enum History {
+	Export(Export),
+	Prune(Prune),
+	Undo(Undo),
+	Corrections(Corrections),
+}
+
+/// Writes every entry in the journal as CSV or JSON, e.g. to archive it before it's pruned
+#[derive(Parser, Debug)]
+pub struct Export {
+	#[arg(long, short = 'f', value_parser = clap::value_parser!(Format), default_value_t = Format::Csv)]
+	format: Format,
+	/// where to write the entries, instead of the standard output
+	#[arg(long, short = 'o')]
+	output: Option<PathBuf>,
+}
+
+/// Removes the entries outside the retention set under `[journal]` in the config
+#[derive(Parser, Debug)]
+pub struct Prune {
+	#[arg(long, short = 'c')]
+	config: Option<PathBuf>,
+}
+
+/// Reverts the most recent actions. Moves are moved back, while copies and hardlinks are only removed
+/// if they're unchanged since they were made, and symlinks if they still point to the same file
+#[derive(Parser, Debug)]
+pub struct Undo {
+	/// how many actions to undo
+	#[arg(long, short = 'n', default_value_t = 1)]
+	last: usize,
+}
+
+/// Lists the rules whose actions were undone, or whose files were moved or removed afterwards, the most corrected first
+#[derive(Parser, Debug)]
+pub struct Corrections {
+	/// Also suggest changes to the rules that would have avoided the corrections
+	#[arg(long, default_value_t = false)]
+	suggest: bool,
+}
+
+impl Cmd for History {
+	fn run(self) -> Result<()> {
+		match self {
+			Self::Export(export) => {
+				let exported = journal::export(&journal::entries()?, export.format);
+				match export.output {
+					Some(path) => fs::write(&path, exported).with_context(|| format!("could not write to {}", path.display())),
+					None => {
+						print!("{}", exported);
+						Ok(())
+					}
+				}
+			}
+			Self::Prune(prune) => {
+				let config = Config::parse(match prune.config {
+					Some(config) => config,
+					None => Config::path()?,
+				})?;
+				journal::prune_now(&config.journal)
+			}
+			Self::Undo(undo) => {
+				for entry in journal::undo(undo.last)? {
+					log::info!("(undo {}) {}", entry.action, entry.from.display());
+				}
+				Ok(())
+			}
+			Self::Corrections(report) => {
+				corrections::detect()?;
+				let reports = corrections::report(&journal::entries()?, &corrections::all()?);
+				if reports.is_empty() {
+					println!("no action was corrected");
+				}
+				for mut rule in reports {
+					if !report.suggest {
+						rule.tweaks.clear();
+					}
+					println!("{}", rule);
+				}
+				Ok(())
+			}
+		}
+	}
+}
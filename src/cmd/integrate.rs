@@ -0,0 +1,270 @@
+use std::{
+	fs,
+	path::{Path, PathBuf},
+	process::Command,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
+use strum::{Display, EnumIter, EnumString, IntoEnumIterator};
+
+use crate::cmd::Cmd;
+
+/// A file manager whose context menu can run `organize file` on the selected files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display, EnumString, EnumIter)]
+#[strum(serialize_all = "lowercase")]
+pub enum FileManager {
+	Nautilus,
+	Dolphin,
+	Finder,
+	/// Windows Explorer
+	Explorer,
+}
+
+/// Adds an "Organize" entry to the context menu of the file managers of this platform, which runs the rules on the selected files
+#[derive(Parser, Debug)]
+pub struct Integrate {
+	/// Only this file manager (nautilus, dolphin, finder or explorer)
+	#[arg(long, value_parser = clap::value_parser!(FileManager))]
+	only: Option<FileManager>,
+	/// Remove the entries instead
+	#[arg(long, default_value_t = false)]
+	uninstall: bool,
+}
+
+impl Cmd for Integrate {
+	fn run(self) -> Result<()> {
+		let managers = match self.only {
+			Some(manager) => vec![manager],
+			None => FileManager::iter().filter(FileManager::is_native).collect(),
+		};
+		let exe = std::env::current_exe().context("could not find the organize executable")?;
+		for manager in managers {
+			match self.uninstall {
+				true => manager.uninstall()?,
+				false => manager.install(&exe)?,
+			}
+		}
+		Ok(())
+	}
+}
+
+const NAUTILUS_SCRIPT: &str = r#"#!/bin/sh
+exec "{exe}" file "$@"
+"#;
+
+const DOLPHIN_SERVICE_MENU: &str = r#"[Desktop Entry]
+Type=Service
+MimeType=all/all;
+Actions=organize;
+X-KDE-ServiceTypes=KonqPopupMenu/Plugin
+
+[Desktop Action organize]
+Name=Organize
+Icon=folder-sync
+Exec="{exe}" file %F
+"#;
+
+const FINDER_INFO: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>NSServices</key>
+	<array>
+		<dict>
+			<key>NSMenuItem</key>
+			<dict>
+				<key>default</key>
+				<string>Organize</string>
+			</dict>
+			<key>NSMessage</key>
+			<string>runWorkflowAsService</string>
+			<key>NSRequiredContext</key>
+			<dict>
+				<key>NSApplicationIdentifier</key>
+				<string>com.apple.finder</string>
+			</dict>
+			<key>NSSendFileTypes</key>
+			<array>
+				<string>public.item</string>
+			</array>
+		</dict>
+	</array>
+</dict>
+</plist>
+"#;
+
+const FINDER_WORKFLOW: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>AMApplicationBuild</key>
+	<string>523</string>
+	<key>AMApplicationVersion</key>
+	<string>2.10</string>
+	<key>AMDocumentVersion</key>
+	<string>2</string>
+	<key>actions</key>
+	<array>
+		<dict>
+			<key>action</key>
+			<dict>
+				<key>AMAccepts</key>
+				<dict>
+					<key>Container</key>
+					<string>List</string>
+					<key>Optional</key>
+					<true/>
+					<key>Types</key>
+					<array>
+						<string>com.apple.cocoa.path</string>
+					</array>
+				</dict>
+				<key>AMActionVersion</key>
+				<string>2.0.3</string>
+				<key>ActionBundlePath</key>
+				<string>/System/Library/Automator/Run Shell Script.action</string>
+				<key>ActionName</key>
+				<string>Run Shell Script</string>
+				<key>ActionParameters</key>
+				<dict>
+					<key>COMMAND_STRING</key>
+					<string>exec "{exe}" file "$@"</string>
+					<key>CheckedForUserDefaultShell</key>
+					<true/>
+					<key>inputMethod</key>
+					<integer>1</integer>
+					<key>shell</key>
+					<string>/bin/sh</string>
+					<key>source</key>
+					<string></string>
+				</dict>
+				<key>BundleIdentifier</key>
+				<string>com.apple.RunShellScript</string>
+				<key>CFBundleVersion</key>
+				<string>2.0.3</string>
+			</dict>
+		</dict>
+	</array>
+	<key>workflowMetaData</key>
+	<dict>
+		<key>serviceInputTypeIdentifier</key>
+		<string>com.apple.Automator.fileSystemObject</string>
+		<key>serviceOutputTypeIdentifier</key>
+		<string>com.apple.Automator.nothing</string>
+		<key>workflowTypeIdentifier</key>
+		<string>com.apple.Automator.servicesMenu</string>
+	</dict>
+</dict>
+</plist>
+"#;
+
+/// The registry keys of the context menus for files and for directories
+const EXPLORER_KEYS: [&str; 2] = [
+	r"HKCU\Software\Classes\*\shell\organize",
+	r"HKCU\Software\Classes\Directory\shell\organize",
+];
+
+impl FileManager {
+	/// Whether the file manager belongs to this platform
+	fn is_native(&self) -> bool {
+		match self {
+			Self::Nautilus | Self::Dolphin => cfg!(all(unix, not(target_os = "macos"))),
+			Self::Finder => cfg!(target_os = "macos"),
+			Self::Explorer => cfg!(windows),
+		}
+	}
+
+	/// The files that make up the entry
+	fn files(&self) -> Result<Vec<PathBuf>> {
+		let home = dirs_next::home_dir().ok_or_else(|| anyhow!("could not find the home directory"))?;
+		let data = dirs_next::data_dir().ok_or_else(|| anyhow!("could not find the data directory"))?;
+		Ok(match self {
+			Self::Nautilus => vec![data.join("nautilus").join("scripts").join("Organize")],
+			// KDE 6 and KDE 5 look for service menus in different places
+			Self::Dolphin => vec![
+				data.join("kio").join("servicemenus").join("organize.desktop"),
+				data.join("kservices5").join("ServiceMenus").join("organize.desktop"),
+			],
+			Self::Finder => vec![home.join("Library").join("Services").join("Organize.workflow")],
+			Self::Explorer => vec![],
+		})
+	}
+
+	fn install(&self, exe: &Path) -> Result<()> {
+		let exe = exe.to_string_lossy();
+		match self {
+			Self::Nautilus | Self::Dolphin => {
+				let content = match self {
+					Self::Nautilus => NAUTILUS_SCRIPT,
+					_ => DOLPHIN_SERVICE_MENU,
+				};
+				for path in self.files()? {
+					write(&path, &content.replace("{exe}", &exe))?;
+					make_executable(&path)?;
+				}
+			}
+			Self::Finder => {
+				for path in self.files()? {
+					let contents = path.join("Contents");
+					write(&contents.join("Info.plist"), FINDER_INFO)?;
+					write(&contents.join("document.wflow"), &FINDER_WORKFLOW.replace("{exe}", &exe))?;
+				}
+			}
+			Self::Explorer => {
+				for key in EXPLORER_KEYS {
+					reg(&["add", key, "/ve", "/d", "Organize", "/f"])?;
+					reg(&["add", key, "/v", "Icon", "/d", &exe, "/f"])?;
+					let command = format!("\"{}\" file \"%1\"", exe);
+					reg(&["add", &format!(r"{}\command", key), "/ve", "/d", &command, "/f"])?;
+				}
+			}
+		}
+		log::info!("added Organize to the context menu of {}", self);
+		Ok(())
+	}
+
+	fn uninstall(&self) -> Result<()> {
+		for path in self.files()?.into_iter().filter(|path| path.exists()) {
+			match path.is_dir() {
+				true => fs::remove_dir_all(&path),
+				false => fs::remove_file(&path),
+			}
+			.with_context(|| format!("could not remove {}", path.display()))?;
+		}
+		if *self == Self::Explorer {
+			for key in EXPLORER_KEYS {
+				// fails if the key isn't there, which is what we want anyway
+				let _ = reg(&["delete", key, "/f"]);
+			}
+		}
+		log::info!("removed Organize from the context menu of {}", self);
+		Ok(())
+	}
+}
+
+fn write(path: &Path, content: &str) -> Result<()> {
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent).with_context(|| format!("could not create {}", parent.display()))?;
+	}
+	fs::write(path, content).with_context(|| format!("could not write {}", path.display()))
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+	use std::os::unix::fs::PermissionsExt;
+	fs::set_permissions(path, fs::Permissions::from_mode(0o755)).with_context(|| format!("could not make {} executable", path.display()))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+	Ok(())
+}
+
+fn reg(args: &[&str]) -> Result<()> {
+	let status = Command::new("reg").args(args).status().context("could not run reg")?;
+	if !status.success() {
+		bail!("reg {} failed", args.join(" "));
+	}
+	Ok(())
+}
@@ -0,0 +1,217 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::Result;
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+
+use organize_core::{
+	config::{actions::ActionType, Config},
+	file::File,
+	journal,
+};
+
+use crate::cmd::Cmd;
+
+/// Prints rules, recent actions or what would happen to a file as script filter JSON,
+/// the format Alfred reads and Raycast and other launchers understand too, so that their workflows can browse and trigger organize.
+/// Every item sets the `command` variable to what the workflow should run with its `arg`.
+#[derive(Subcommand, Debug)]
+pub enum Launcher {
+	Rules(Rules),
+	History(Recent),
+	Suggest(Suggest),
+}
+
+/// Lists the rules, with the folders they watch as their argument
+#[derive(Parser, Debug)]
+pub struct Rules {
+	#[arg(long, short = 'c')]
+	config: Option<PathBuf>,
+	/// only list the rules whose id, folders or actions contain this
+	query: Option<String>,
+}
+
+/// Lists the most recent actions in the journal, with the file they left behind as their argument
+#[derive(Parser, Debug)]
+pub struct Recent {
+	#[arg(long, short = 'n', default_value_t = 50)]
+	last: usize,
+	/// only list the actions whose paths contain this
+	query: Option<String>,
+}
+
+/// Lists the rules that would act on a file, with the file as their argument so that the workflow can run them on it
+#[derive(Parser, Debug)]
+pub struct Suggest {
+	#[arg(long, short = 'c')]
+	config: Option<PathBuf>,
+	path: PathBuf,
+}
+
+/// An item of a script filter, see https://www.alfredapp.com/help/workflows/inputs/script-filter/json/
+#[derive(Serialize, Debug, Default)]
+struct Item {
+	#[serde(skip_serializing_if = "Option::is_none")]
+	uid: Option<String>,
+	title: String,
+	subtitle: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	arg: Option<String>,
+	valid: bool,
+	/// `file` lets the launcher treat the argument as a path (previews, file actions...)
+	#[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+	kind: Option<&'static str>,
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	variables: BTreeMap<&'static str, String>,
+}
+
+impl Item {
+	/// An item that does nothing when selected, to say why there is nothing to show
+	fn notice<T: Into<String>>(title: T) -> Self {
+		Self {
+			title: title.into(),
+			..Self::default()
+		}
+	}
+
+	fn command(mut self, command: &str) -> Self {
+		self.variables.insert("command", command.to_string());
+		self
+	}
+}
+
+#[derive(Serialize, Debug)]
+struct ScriptFilter {
+	items: Vec<Item>,
+}
+
+fn config(path: Option<PathBuf>) -> Result<Config> {
+	Config::parse(match path {
+		Some(path) => path,
+		None => Config::path()?,
+	})
+}
+
+fn actions(config: &Config, rule: usize) -> String {
+	config.rules[rule]
+		.actions
+		.iter()
+		.map(|step| ActionType::from(&step.action).to_string())
+		.collect::<Vec<_>>()
+		.join(", ")
+}
+
+impl Rules {
+	fn items(self) -> Result<Vec<Item>> {
+		let config = config(self.config)?;
+		let query = self.query.unwrap_or_default().to_lowercase();
+		let items = config
+			.rules
+			.iter()
+			.enumerate()
+			.map(|(i, rule)| {
+				let folders = rule
+					.folders
+					.iter()
+					.map(|folder| folder.path.display().to_string())
+					.collect::<Vec<_>>();
+				Item {
+					uid: Some(rule.name(i)),
+					title: rule.name(i),
+					subtitle: format!("{} -> {}", folders.join(", "), actions(&config, i)),
+					arg: folders.first().cloned(),
+					valid: !folders.is_empty(),
+					kind: Some("file"),
+					..Item::default()
+				}
+				.command("file")
+			})
+			.filter(|item| item.title.to_lowercase().contains(&query) || item.subtitle.to_lowercase().contains(&query))
+			.collect::<Vec<_>>();
+		Ok(match items.is_empty() {
+			true => vec![Item::notice("No rules found")],
+			false => items,
+		})
+	}
+}
+
+impl Recent {
+	fn items(self) -> Result<Vec<Item>> {
+		let query = self.query.unwrap_or_default().to_lowercase();
+		let items = journal::entries()?
+			.into_iter()
+			.rev()
+			.filter(|entry| {
+				entry.from.to_string_lossy().to_lowercase().contains(&query)
+					|| entry
+						.to
+						.as_ref()
+						.is_some_and(|to| to.to_string_lossy().to_lowercase().contains(&query))
+			})
+			.take(self.last)
+			.map(|entry| {
+				let title = entry
+					.from
+					.file_name()
+					.unwrap_or(entry.from.as_os_str())
+					.to_string_lossy()
+					.to_string();
+				let subtitle = match &entry.to {
+					Some(to) => format!("({}) {} -> {}", entry.action, entry.from.display(), to.display()),
+					None => format!("({}) {}", entry.action, entry.from.display()),
+				};
+				Item {
+					title,
+					subtitle: format!("{} on {}", subtitle, entry.time.format("%F %T")),
+					valid: entry.to.as_ref().is_some_and(|to| to.exists()),
+					arg: entry.to.map(|to| to.display().to_string()),
+					kind: Some("file"),
+					..Item::default()
+				}
+				.command("open")
+			})
+			.collect::<Vec<_>>();
+		Ok(match items.is_empty() {
+			true => vec![Item::notice("No actions in the history")],
+			false => items,
+		})
+	}
+}
+
+impl Suggest {
+	fn items(self) -> Result<Vec<Item>> {
+		let config = config(self.config)?;
+		let path = self.path.canonicalize().unwrap_or(self.path);
+		let file = File::new(&path, &config, false);
+		let items = file
+			.get_matching_rules(&config.path_to_rules)
+			.into_iter()
+			.map(|(i, _)| Item {
+				uid: Some(config.rules[*i].name(*i)),
+				title: format!("{} with {}", actions(&config, *i), config.rules[*i].name(*i)),
+				subtitle: path.display().to_string(),
+				arg: Some(path.display().to_string()),
+				valid: true,
+				kind: Some("file"),
+				..Item::default()
+			})
+			.map(|item| item.command("file"))
+			.collect::<Vec<_>>();
+		Ok(match items.is_empty() {
+			true => vec![Item::notice(format!("No rule applies to {}", path.display()))],
+			false => items,
+		})
+	}
+}
+
+impl Cmd for Launcher {
+	fn run(self) -> Result<()> {
+		let items = match self {
+			Self::Rules(rules) => rules.items()?,
+			Self::History(recent) => recent.items()?,
+			Self::Suggest(suggest) => suggest.items()?,
+		};
+		println!("{}", serde_json::to_string(&ScriptFilter { items })?);
+		Ok(())
+	}
+}
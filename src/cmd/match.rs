@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+
+use organize_core::config::{
+	filters::{Filter, Filters, Predicate},
+	options::{apply::Apply, recursive::Recursive},
+};
+
+use crate::cmd::Cmd;
+
+/// Lists the files in a directory that match the given filters, to try them out before adding them to a rule
+#[derive(Parser, Debug)]
+pub struct Match {
+	/// e.g. `extension=pdf,docx`, `size>10MB`, `modified<7d`, `regex=^IMG`, `startswith=invoice`,
+	/// or any filter as an inline table: `{ type = "mime", types = ["image/*"] }`
+	#[arg(long = "filter", short = 'f', value_parser = clap::value_parser!(Filter), required = true)]
+	filters: Vec<Filter>,
+	/// List files that match any of the filters, instead of all of them
+	#[arg(long, default_value_t = false)]
+	any: bool,
+	/// How many levels of subdirectories to scan (0 scans all of them)
+	#[arg(long, short = 'r', default_value_t = 1)]
+	recursive: u16,
+	dir: PathBuf,
+}
+
+impl Cmd for Match {
+	fn run(self) -> Result<()> {
+		let dir = self
+			.dir
+			.canonicalize()
+			.with_context(|| format!("could not find {}", self.dir.display()))?;
+		if !dir.is_dir() {
+			bail!("{} is not a directory", dir.display());
+		}
+		let filters: Filters = self.filters.into_iter().collect();
+		let apply = match self.any {
+			true => Apply::Any,
+			false => Apply::All,
+		};
+		let predicate = Predicate::new(&filters, &apply);
+		let recursive = Recursive { depth: Some(self.recursive) };
+		recursive
+			.to_walker(&dir)
+			.into_iter()
+			.filter_map(|entry| entry.ok())
+			.filter(|entry| entry.file_type().is_file() && predicate.matches(entry.path()))
+			.for_each(|entry| println!("{}", entry.path().display()));
+		Ok(())
+	}
+}
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+
+use organize_core::config::{version, Config};
+
+use crate::cmd::Cmd;
+
+/// Updates the config to the schema version understood by this version of organize
+#[derive(Parser, Debug)]
+pub struct Migrate {
+	#[arg(long, short = 'c')]
+	config: Option<PathBuf>,
+}
+
+impl Cmd for Migrate {
+	fn run(self) -> Result<()> {
+		let path = match self.config {
+			Some(config) => config,
+			None => Config::path()?,
+		};
+		let content = std::fs::read_to_string(&path).with_context(|| format!("could not read {}", path.display()))?;
+		std::fs::write(&path, version::migrate(&content)).with_context(|| format!("could not write {}", path.display()))?;
+		log::info!("{} now targets config version {}", path.display(), version::CONFIG_VERSION);
+		Ok(())
+	}
+}
@@ -1,18 +1,78 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand};
-use organize_core::logger::Logger;
+use organize_core::{
+	config::{actions::confirm, version},
+	layout,
+	logger::{LogFilter, Logger},
+	priority,
+};
 
 use self::{run::RunBuilder, watch::WatchBuilder};
-use crate::cmd::edit::Edit;
+use crate::cmd::{
+	bench::Bench,
+	diff_config::DiffConfig,
+	edit::Edit,
+	file::Files,
+	history::History,
+	integrate::Integrate,
+	launcher::Launcher,
+	migrate::Migrate,
+	paths::Paths,
+	pause::{Pause, Resume},
+	purge_trash::PurgeTrash,
+	r#match::Match,
+	render::Render,
+	settings::Settings,
+	suggest::Suggest,
+	why_not::WhyNot,
+};
 
+mod bench;
+mod diff_config;
 mod edit;
+mod file;
+mod history;
+mod integrate;
+mod launcher;
+mod r#match;
+mod migrate;
+mod paths;
+mod pause;
+mod purge_trash;
+mod render;
 mod run;
+mod settings;
+mod suggest;
+mod test;
 mod watch;
+mod why_not;
 
 #[derive(Subcommand)]
 enum Command {
 	Run(RunBuilder),
 	Edit(Edit),
 	Watch(WatchBuilder),
+	WhyNot(WhyNot),
+	DiffConfig(DiffConfig),
+	Migrate(Migrate),
+	Render(Render),
+	Match(Match),
+	#[command(subcommand)]
+	History(History),
+	PurgeTrash(PurgeTrash),
+	Pause(Pause),
+	Resume(Resume),
+	File(Files),
+	Integrate(Integrate),
+	#[command(subcommand)]
+	Launcher(Launcher),
+	Suggest(Suggest),
+	Settings(Settings),
+	Paths(Paths),
+	Test(test::Test),
+	#[command(hide = true)]
+	Bench(Bench),
 }
 
 #[derive(Parser)]
@@ -20,9 +80,37 @@ enum Command {
 pub struct App {
 	#[command(subcommand)]
 	command: Command,
+	/// Print debug messages, or trace messages too when passed twice
+	#[arg(long, short = 'v', action = clap::ArgAction::Count)]
+	pub(crate) verbose: u8,
+	/// Set the level of single subsystems (engine, templates, filters, actions, config, journal, watch) or modules,
+	/// e.g. `engine=debug,templates=trace`
+	#[arg(long, global = true, default_value = "")]
+	pub(crate) log_filter: LogFilter,
 	/// Do not print colored logs
 	#[arg(long, default_value_t = false)]
 	pub(crate) no_color: bool,
+	/// Run with idle IO and CPU priority, for scheduled runs that shouldn't slow down the machine
+	#[arg(long, default_value_t = false)]
+	pub(crate) background: bool,
+	/// Refuse configs written for another schema version instead of warning about them
+	#[arg(long, default_value_t = false)]
+	pub(crate) strict: bool,
+	/// Run actions marked with `confirm = true` without asking
+	#[arg(long, short = 'y', default_value_t = false)]
+	pub(crate) yes: bool,
+	/// Write the logs of this invocation to this file instead of the shared log files
+	#[arg(long, global = true, conflicts_with = "log_per_run")]
+	pub(crate) log_file: Option<PathBuf>,
+	/// Write the logs of this invocation to a new timestamped file under the data directory
+	#[arg(long, global = true, default_value_t = false)]
+	pub(crate) log_per_run: bool,
+	/// Read the settings from this file instead of `settings.toml` in the config directory
+	#[arg(long, global = true)]
+	pub(crate) settings: Option<PathBuf>,
+	/// Override a setting for this invocation, e.g. `--set log.color=false`. Can be passed several times
+	#[arg(long, global = true, value_name = "KEY=VALUE")]
+	pub(crate) set: Vec<String>,
 }
 
 pub trait Cmd {
@@ -31,11 +119,70 @@ pub trait Cmd {
 
 impl Cmd for App {
 	fn run(self) -> anyhow::Result<()> {
-		Logger::setup(self.no_color)?;
+		let loaded = organize_core::settings::load(self.settings.as_deref(), &self.set);
+		let settings = loaded.as_ref().cloned().unwrap_or_default();
+		let no_color = self.no_color || !settings.log.color;
+		let log_filter = match self.log_filter == LogFilter::default() {
+			true => settings.log.filter.parse()?,
+			false => self.log_filter.clone(),
+		};
+		let log_file = match (&self.log_file, self.log_per_run || settings.log.per_run) {
+			(Some(path), _) => Some(path.clone()),
+			(None, true) => Some(Logger::run_log_path()?),
+			(None, false) => None,
+		};
+		let migrated = layout::migrate();
+		Logger::setup(no_color, self.verbose, &log_filter, log_file.as_deref())?;
+		match migrated {
+			Ok(moved) => {
+				for (from, to) in moved {
+					log::info!("moved {} to {}", from.display(), to.display());
+				}
+			}
+			Err(e) => log::warn!("could not move organize's files to their new locations: {:?}", e),
+		}
+		if let Err(e) = loaded {
+			// broken settings can still be fixed with `organize settings`
+			match self.command {
+				Command::Settings(_) => log::warn!("{:?}", e),
+				_ => return Err(e),
+			}
+		}
+		if no_color {
+			colored::control::set_override(false);
+		}
+		if self.background {
+			if let Err(e) = priority::lower_priority() {
+				log::warn!("could not lower the process priority: {:?}", e);
+			}
+		}
+		confirm::assume_yes(self.yes);
+		version::set_strict(self.strict);
 		match self.command {
 			Command::Run(cmd) => cmd.build()?.run(),
 			Command::Watch(cmd) => cmd.build()?.run(),
 			Command::Edit(edit) => edit.run(),
+			Command::WhyNot(why_not) => why_not.run(),
+			Command::DiffConfig(diff) => diff.run(),
+			Command::Migrate(migrate) => migrate.run(),
+			Command::Render(render) => render.run(),
+			Command::Match(r#match) => r#match.run(),
+			Command::History(history) => history.run(),
+			Command::PurgeTrash(purge) => purge.run(),
+			Command::Pause(pause) => pause.run(),
+			Command::Resume(resume) => resume.run(),
+			Command::File(files) => files.run(),
+			Command::Integrate(integrate) => integrate.run(),
+			Command::Launcher(launcher) => launcher.run(),
+			Command::Suggest(suggest) => suggest.run(),
+			Command::Paths(paths) => paths.run(),
+			Command::Settings(mut cmd) => {
+				cmd.path = self.settings;
+				cmd.overrides = self.set;
+				cmd.run()
+			}
+			Command::Test(test) => test.run(),
+			Command::Bench(bench) => bench.run(),
 		}
 	}
 }
@@ -0,0 +1,21 @@
+use anyhow::Result;
+use clap::Parser;
+
+use organize_core::layout;
+
+use crate::cmd::Cmd;
+
+/// Prints where organize keeps its config, settings, journal, caches and logs
+#[derive(Parser, Debug)]
+pub struct Paths;
+
+impl Cmd for Paths {
+	fn run(self) -> Result<()> {
+		let paths = layout::all()?;
+		let width = paths.iter().map(|(name, _)| name.len()).max().unwrap_or_default();
+		for (name, path) in paths {
+			println!("{:width$}  {}", name, path.display(), width = width);
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,26 @@
+use anyhow::Result;
+use clap::Parser;
+
+use organize_core::control;
+
+use crate::cmd::Cmd;
+
+/// Pauses the running `run`s and `watch`es before the next file they handle, until `organize resume`
+#[derive(Parser, Debug)]
+pub struct Pause;
+
+impl Cmd for Pause {
+	fn run(self) -> Result<()> {
+		control::pause()
+	}
+}
+
+/// Lets paused `run`s and `watch`es carry on
+#[derive(Parser, Debug)]
+pub struct Resume;
+
+impl Cmd for Resume {
+	fn run(self) -> Result<()> {
+		control::resume()
+	}
+}
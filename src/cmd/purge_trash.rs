@@ -0,0 +1,33 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+
+use organize_core::config::Config;
+
+use crate::cmd::Cmd;
+
+/// Prunes the trash according to the `[trash]` section of the config, e.g. from a daily cron job
+#[derive(Parser, Debug)]
+pub struct PurgeTrash {
+	#[arg(long, short = 'c')]
+	config: Option<PathBuf>,
+}
+
+impl Cmd for PurgeTrash {
+	fn run(self) -> Result<()> {
+		let config = Config::parse(match self.config {
+			Some(config) => config,
+			None => Config::path()?,
+		})?;
+		let _definitions = config.enter();
+		let trash = config
+			.trash
+			.as_ref()
+			.ok_or_else(|| anyhow!("there is no `[trash]` section in {}", config.path.display()))?;
+		for path in trash.purge()? {
+			log::info!("(purge) {}", path.display());
+		}
+		Ok(())
+	}
+}
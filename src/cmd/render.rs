@@ -0,0 +1,31 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+
+use organize_core::string::render;
+
+use crate::cmd::Cmd;
+
+/// Prints what a template expands to for a file, without running any rule
+#[derive(Parser, Debug)]
+pub struct Render {
+	/// the template, e.g. `~/Documents/{extension.to_uppercase}/{filename}`
+	#[arg(long, short = 't')]
+	template: String,
+	/// the file the template is rendered for
+	#[arg(long, short = 'p')]
+	path: PathBuf,
+}
+
+impl Cmd for Render {
+	fn run(self) -> Result<()> {
+		let path = self
+			.path
+			.canonicalize()
+			.with_context(|| format!("could not find {}", self.path.display()))?;
+		let rendered = render(&self.template, &path)?;
+		println!("{}", rendered.to_string_lossy());
+		Ok(())
+	}
+}
@@ -1,9 +1,29 @@
-use std::path::PathBuf;
+use std::{
+	collections::HashSet,
+	path::{Path, PathBuf},
+	sync::Mutex,
+	time::{Duration, Instant},
+};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use clap::Parser;
+use rayon::prelude::*;
 
-use organize_core::{config::Config, file::File};
+use organize_core::{
+	capacity::{self, Shortage},
+	config::{defer, filters::parse_size, options::recursive::Recursive, Config},
+	corrections, elevation, events,
+	file::File,
+	journal,
+	memory::{Budget, Chunks},
+	messages,
+	mount::Volume,
+	preflight,
+	profile::{self, Stage},
+	report::Report,
+	summary::Summary,
+	thumbnails,
+};
 
 use crate::Cmd;
 
@@ -11,6 +31,39 @@ use crate::Cmd;
 pub struct RunBuilder {
 	#[arg(long, short = 'c')]
 	config: Option<PathBuf>,
+	/// Don't print a summary at the end of the run
+	#[arg(long, short = 'q', default_value_t = false)]
+	quiet: bool,
+	/// Write a report of the run to this file (.html, .md or .json)
+	#[arg(long)]
+	report: Option<PathBuf>,
+	/// Run the rules of the locations inside FROM against TO instead, e.g. `~/Downloads=/media/usb`.
+	/// Only the substituted locations are processed. Can be passed more than once.
+	#[arg(long = "root", value_name = "FROM=TO", value_parser = parse_root)]
+	roots: Vec<(PathBuf, PathBuf)>,
+	/// Start handling the files of a location before it's fully listed once listing it takes more memory than this (e.g. `512MiB`)
+	#[arg(long, value_parser = |s: &str| parse_size(s))]
+	memory_budget: Option<u64>,
+	/// Handle the files of each location in chunks of this many as they're listed
+	#[arg(long)]
+	chunk_size: Option<usize>,
+	/// Write the journal every this many actions, logging the progress, instead of after each of them.
+	/// An interruption loses what the last batch didn't write yet, which `history undo` can't reverse then.
+	#[arg(long, default_value_t = 1)]
+	checkpoint_every: usize,
+	/// Time how long walking, rendering templates and each filter and action of every rule take, and print where the time went
+	#[arg(long)]
+	profile: bool,
+	/// Start even if some locations can't be read or some destinations can't be written to
+	#[arg(long)]
+	skip_preflight: bool,
+}
+
+fn parse_root(s: &str) -> Result<(PathBuf, PathBuf), String> {
+	match s.split_once('=') {
+		Some((from, to)) if !from.is_empty() && !to.is_empty() => Ok((PathBuf::from(from), PathBuf::from(to))),
+		_ => Err(format!("expected FROM=TO, found {}", s)),
+	}
 }
 
 impl RunBuilder {
@@ -25,17 +78,79 @@ impl RunBuilder {
 		if self.config.is_none() {
 			self = self.config(None)?;
 		}
-		Ok(Run {
-			config: Config::parse(self.config.unwrap()).unwrap(),
-		})
+		let mut config = Config::parse(self.config.unwrap()).unwrap();
+		if !self.roots.is_empty() {
+			config = config.with_roots(&self.roots)?;
+		}
+		let mut run = Run::new(config);
+		run.quiet = self.quiet;
+		run.report = self.report;
+		run.profile = self.profile;
+		run.checkpoint_every = self.checkpoint_every;
+		run.skip_preflight = self.skip_preflight;
+		run.budget = Budget {
+			bytes: self.memory_budget,
+			chunk_size: self.chunk_size,
+		};
+		Ok(run)
 	}
 }
 
 pub struct Run {
 	pub(crate) config: Config,
+	/// where the files handled during this run ended up, so that files that were chained into a location
+	/// that hasn't been walked yet aren't processed twice
+	done: Mutex<HashSet<PathBuf>>,
+	/// the filesystems that didn't have room for the files sent to them, which were left in place
+	shortages: Mutex<Vec<Shortage>>,
+	quiet: bool,
+	report: Option<PathBuf>,
+	budget: Budget,
+	checkpoint_every: usize,
+	profile: bool,
+	skip_preflight: bool,
 }
 
 impl Run {
+	fn start_with_summary(self) -> Result<()> {
+		if self.quiet && self.report.is_none() && !self.profile {
+			return self.start();
+		}
+		let (config, quiet, report, profile) = (self.config.clone(), self.quiet, self.report.clone(), self.profile);
+		let start = Instant::now();
+		events::start();
+		if profile {
+			profile::start();
+		}
+		let result = self.start();
+		let events = events::finish();
+		let summary = Summary::new(&events, &config, start.elapsed());
+		if !quiet {
+			println!("{}", summary);
+		}
+		if profile {
+			println!("{}", profile::finish(&config));
+		}
+		if let Some(path) = report {
+			Report::new(&events, &summary).write(path)?;
+		}
+		result
+	}
+
+	pub(crate) fn new(config: Config) -> Self {
+		Self {
+			config,
+			done: Mutex::new(HashSet::new()),
+			shortages: Mutex::new(Vec::new()),
+			quiet: false,
+			report: None,
+			budget: Budget::default(),
+			checkpoint_every: 1,
+			profile: false,
+			skip_preflight: false,
+		}
+	}
+
 	#[allow(dead_code)]
 	pub fn builder() -> RunBuilder {
 		RunBuilder::default()
@@ -43,23 +158,149 @@ impl Run {
 }
 
 impl Cmd for Run {
-	fn run(self) -> Result<()> {
-		self.start()
+	fn run(mut self) -> Result<()> {
+		let _definitions = self.config.enter();
+		if !self.skip_preflight {
+			let problems = preflight::check(&self.config);
+			for problem in problems.iter() {
+				log::error!("{}", problem);
+			}
+			if !problems.is_empty() {
+				bail!(
+					"found {} problem(s) before starting, nothing was done (pass --skip-preflight to run anyway)",
+					problems.len()
+				);
+			}
+		}
+		journal::enable(self.config.journal.clone());
+		let deferred = self.config.deferred();
+		if !deferred.is_empty() {
+			defer::record(&self.config, &deferred);
+			self.config = self.config.without(&deferred.iter().map(|(i, _)| *i).collect::<Vec<_>>());
+		}
+		corrections::check();
+		thumbnails::check();
+		messages::set(&self.config.messages);
+		elevation::set(self.config.elevation.as_ref());
+		journal::checkpoint_every(self.checkpoint_every);
+		let trash = self.config.trash.clone();
+		let result = self.start_with_summary();
+		journal::flush();
+		if let Some(trash) = trash {
+			if let Err(e) = trash.purge() {
+				log::error!("could not prune the trash: {:?}", e);
+			}
+		}
+		result
 	}
 }
 
 impl Run {
 	pub(crate) fn start(self) -> Result<()> {
-		self.config.path_to_rules.iter().for_each(|(path, _)| {
-			let recursive = self.config.path_to_recursive.get(path).unwrap();
-			let walker = recursive.to_walker(path);
-			walker.into_iter().filter_map(|e| e.ok()).for_each(|entry| {
-				if entry.path().is_file() {
-					let file = File::new(entry.path(), &self.config, false);
-					file.act(&self.config.path_to_rules);
-				}
-			});
-		});
+		let stages = self.config.stages()?;
+		if stages.len() == 1 {
+			self.config
+				.scan_roots()
+				.par_iter()
+				.for_each(|(path, recursive)| self.walk(path, recursive));
+		} else {
+			// every stage needs to see what the previous ones left behind, so they each walk their locations separately
+			for stage in stages {
+				let mut run = Run::new(self.config.restrict(&stage));
+				run.budget = self.budget;
+				run.config
+					.scan_roots()
+					.par_iter()
+					.for_each(|(path, recursive)| run.walk(path, recursive));
+				self.shortages.lock().unwrap().append(&mut run.shortages.lock().unwrap());
+			}
+		}
+		Volume::mounted().iter().for_each(|volume| self.on_mount(volume));
+		let shortages = self.shortages.into_inner().unwrap_or_else(|e| e.into_inner());
+		if !shortages.is_empty() {
+			bail!(
+				"{} file(s) were left in place because {} didn't have enough space for them:\n{}",
+				shortages.iter().map(|shortage| shortage.files.len()).sum::<usize>(),
+				match shortages.len() {
+					1 => "a filesystem".to_string(),
+					n => format!("{} filesystems", n),
+				},
+				shortages
+					.iter()
+					.map(|shortage| format!("  {}", shortage))
+					.collect::<Vec<_>>()
+					.join("\n")
+			);
+		}
 		Ok(())
 	}
+
+	/// Runs the rules that are triggered by `volume` with its mount point as their location
+	pub(crate) fn on_mount(&self, volume: &Volume) {
+		for config in self.config.on_mount(volume) {
+			log::info!("running rules for {} ({})", volume.label, volume.mount_point.display());
+			let run = Run::new(config);
+			run.scan(&volume.mount_point);
+			self.shortages.lock().unwrap().append(&mut run.shortages.lock().unwrap());
+		}
+	}
+
+	/// Processes every file inside a single location
+	pub(crate) fn scan<T: AsRef<Path>>(&self, path: T) {
+		let path = path.as_ref();
+		let recursive = self.config.path_to_recursive.get(path).unwrap();
+		self.walk(path, recursive);
+	}
+
+	/// Walks `path` once, dispatching each file to every rule whose location contains it
+	pub(crate) fn walk(&self, path: &Path, recursive: &Recursive) {
+		let mut stale = self.config.stale(path);
+		let (start, mut handling) = (Instant::now(), Duration::ZERO);
+		let mut chunks = Chunks::new(self.budget);
+		// directories are listed after their contents, so that they're handled once their files were
+		let dirs = self.config.targets_dirs();
+		let walker = recursive.to_walker(path).contents_first(dirs);
+		for entry in walker
+			.into_iter()
+			.filter_map(|e| e.ok())
+			.filter(|entry| !stale.contains(entry))
+			.filter(|entry| dirs || !entry.file_type().is_dir())
+		{
+			let streaming = chunks.is_streaming();
+			if let Some(chunk) = chunks.push(entry.into_path()) {
+				if !streaming && chunks.is_streaming() {
+					log::info!(
+						"listing {} went over the memory budget, handling its files as they're listed",
+						path.display()
+					);
+				}
+				handling += self.handle(chunk);
+			}
+		}
+		handling += self.handle(chunks.finish());
+		profile::record(Stage::Walk, start.elapsed().saturating_sub(handling));
+	}
+
+	/// Applies the rules to `paths`, returning how long it took.
+	/// The files that would be copied or moved to a filesystem without room for all of them are left in place.
+	fn handle(&self, paths: Vec<PathBuf>) -> Duration {
+		let start = Instant::now();
+		let paths = paths
+			.into_iter()
+			.filter(|path| (path.is_file() || path.is_dir()) && !self.done.lock().unwrap().contains(path))
+			.collect::<Vec<_>>();
+		let shortages = capacity::check(&self.config, &paths, false);
+		let refused = capacity::refuse(&shortages);
+		self.shortages.lock().unwrap().extend(shortages);
+		for path in paths.into_iter().filter(|path| !refused.contains(path)) {
+			// an earlier file of the batch might have been chained onto it
+			if (path.is_file() || path.is_dir()) && !self.done.lock().unwrap().contains(&path) {
+				let file = File::new(&path, &self.config, false);
+				if let Some(path) = file.act(&self.config.path_to_rules) {
+					self.done.lock().unwrap().insert(path);
+				}
+			}
+		}
+		start.elapsed()
+	}
 }
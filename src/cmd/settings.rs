@@ -0,0 +1,58 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::{Parser, Subcommand};
+use toml::Value;
+
+use organize_core::settings;
+
+use crate::cmd::Cmd;
+
+/// Reads and changes the settings, which are kept apart from the rules in `settings.toml`
+#[derive(Parser, Debug)]
+pub struct Settings {
+	#[command(subcommand)]
+	action: Action,
+	/// the file passed with `--settings`
+	#[arg(skip)]
+	pub(crate) path: Option<PathBuf>,
+	/// the values passed with `--set`
+	#[arg(skip)]
+	pub(crate) overrides: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Action {
+	/// Prints the value in effect of a setting, e.g. `log.color`, or of all of them
+	Get { key: Option<String> },
+	/// Writes a setting to the settings file. Values are read as TOML, or as strings if they aren't valid TOML
+	Set { key: String, value: String },
+	/// Removes a setting from the settings file, so that it's back to its default
+	Unset { key: String },
+}
+
+impl Cmd for Settings {
+	fn run(self) -> Result<()> {
+		let path = self.path.unwrap_or_else(settings::path);
+		match self.action {
+			Action::Get { key } => {
+				let table = settings::merged(&path, &self.overrides)?;
+				let value = match &key {
+					Some(key) => match settings::lookup(&table, key) {
+						Some(value) => value.clone(),
+						None => bail!("`{}` is not set", key),
+					},
+					None => Value::Table(table),
+				};
+				match value {
+					Value::String(str) => println!("{}", str),
+					Value::Table(table) => print!("{}", toml::to_string_pretty(&table).context("could not serialize the settings")?),
+					value => println!("{}", value),
+				}
+				Ok(())
+			}
+			Action::Set { key, value } => settings::write(&path, &key, Some(&value)),
+			Action::Unset { key } => settings::write(&path, &key, None),
+		}
+	}
+}
@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+
+use organize_core::suggest::{self, Thresholds};
+
+use crate::cmd::Cmd;
+
+/// Looks at how a directory you sorted by hand is organized and proposes rules that would sort new files the same way,
+/// as a starting config to edit
+#[derive(Parser, Debug)]
+pub struct Suggest {
+	/// The directory to learn from
+	dir: PathBuf,
+	/// Where the rules should pick up new files from, the analyzed directory itself by default
+	#[arg(long)]
+	from: Option<PathBuf>,
+	/// How many files must follow a pattern for it to become a rule
+	#[arg(long, default_value_t = 3)]
+	min_files: usize,
+	/// The share of the files following a pattern that must be in the same folder, between 0 and 1
+	#[arg(long, default_value_t = 0.8)]
+	min_share: f64,
+	/// Write the config to this file instead of the standard output
+	#[arg(long, short = 'o')]
+	output: Option<PathBuf>,
+}
+
+impl Cmd for Suggest {
+	fn run(self) -> Result<()> {
+		let dir = self
+			.dir
+			.canonicalize()
+			.with_context(|| format!("could not find {}", self.dir.display()))?;
+		if !dir.is_dir() {
+			bail!("{} is not a directory", dir.display());
+		}
+		let thresholds = Thresholds {
+			min_files: self.min_files,
+			min_share: self.min_share,
+		};
+		let suggestions = suggest::suggest(&suggest::files(&dir), &thresholds);
+		if suggestions.is_empty() {
+			log::warn!("no pattern is followed closely enough in {} to suggest a rule", dir.display());
+			return Ok(());
+		}
+		let from = self
+			.from
+			.map(|from| from.canonicalize().unwrap_or(from))
+			.unwrap_or_else(|| dir.clone());
+		let config = suggest::to_config(&suggestions, &from, &dir);
+		match self.output {
+			Some(path) => {
+				std::fs::write(&path, config).with_context(|| format!("could not write {}", path.display()))?;
+				log::info!("wrote {} suggested rules to {}", suggestions.len(), path.display());
+			}
+			None => print!("{}", config),
+		}
+		Ok(())
+	}
+}
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use colored::Colorize;
+
+use organize_core::testing::Case;
+
+use crate::cmd::Cmd;
+
+/// Runs golden-file test cases against temporary trees and shows how the result differs from the expected tree,
+/// so that config changes can be checked in CI before they reach the daemon
+#[derive(Parser, Debug)]
+pub struct Test {
+	/// the cases to run, or directories whose `.toml` files are cases
+	#[arg(required = true)]
+	cases: Vec<PathBuf>,
+}
+
+impl Cmd for Test {
+	fn run(self) -> Result<()> {
+		let mut paths = Vec::new();
+		for path in self.cases {
+			if path.is_dir() {
+				let mut cases = path
+					.read_dir()
+					.with_context(|| format!("could not read {}", path.display()))?
+					.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+					.filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+					.collect::<Vec<_>>();
+				cases.sort();
+				paths.extend(cases);
+			} else {
+				paths.push(path);
+			}
+		}
+		let mut failed = 0;
+		for path in paths.iter() {
+			let result = Case::parse(path).and_then(|case| Ok((case.run()?, case.name)));
+			match result {
+				Ok((report, name)) if report.passed() => println!("{} {}", "ok".green(), name),
+				Ok((report, name)) => {
+					failed += 1;
+					println!("{} {}", "FAILED".red(), name);
+					print!("{}", report);
+				}
+				Err(e) => {
+					failed += 1;
+					println!("{} {}: {:?}", "FAILED".red(), path.display(), e);
+				}
+			}
+		}
+		println!("{} passed, {} failed", paths.len() - failed, failed);
+		if failed > 0 {
+			bail!("{} of {} cases failed", failed, paths.len());
+		}
+		Ok(())
+	}
+}
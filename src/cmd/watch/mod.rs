@@ -0,0 +1,408 @@
+use std::{
+	collections::{HashMap, HashSet},
+	path::{Path, PathBuf},
+	sync::{
+		mpsc::{RecvTimeoutError, Sender},
+		Arc, Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use clap::Parser;
+
+use organize_core::{
+	capacity,
+	config::{defer, filters::parse_duration, Config},
+	corrections, elevation,
+	file::File,
+	index::{self, Snapshot},
+	journal, messages,
+	path::Identity,
+	preflight,
+	register::Register,
+	thumbnails,
+};
+
+use self::{
+	queue::Queue,
+	source::{Deferral, EventSource, FsEvents, Mounts, Polling, Startup, Work, CHECK_INTERVAL},
+};
+use crate::{cmd::run::Run, Cmd};
+
+mod queue;
+mod source;
+
+/// How many files are handled at the same time
+const WORKERS: usize = 4;
+
+#[derive(Parser, Debug)]
+pub struct WatchBuilder {
+	/// The configs to watch, which can be passed several times (e.g. a personal and a work one) to handle them all in one process
+	#[arg(long, short = 'c')]
+	pub config: Vec<PathBuf>,
+	#[arg(long)]
+	cleanup: Option<bool>,
+	#[arg(long)]
+	cleanup_after_reload: Option<bool>,
+	#[arg(long)]
+	delay: Option<u64>,
+	/// On startup, process the files that appeared while organize wasn't watching, if they were modified within this long (`0s` turns it off)
+	#[arg(long, value_parser = |s: &str| parse_duration(s), default_value = "7d")]
+	catch_up: Duration,
+	/// How many new files to keep in memory while the actions can't keep up, the rest wait on disk
+	#[arg(long, default_value_t = 10_000)]
+	max_queued: usize,
+}
+
+impl WatchBuilder {
+	pub fn build(mut self) -> Result<Daemon> {
+		if self.config.is_empty() {
+			self.config.push(Config::path()?);
+		}
+		self.cleanup = Some(self.cleanup.map_or_else(|| true, |v| !v));
+		self.cleanup_after_reload = Some(self.cleanup_after_reload.map_or_else(|| true, |v| !v));
+		self.delay = Some(self.delay.unwrap_or(0));
+
+		let register = Register::default();
+		let mut watches: Vec<Watch> = Vec::new();
+		for path in self.config {
+			if watches.iter().any(|watch| watch.config.path == path) {
+				continue;
+			}
+			let config = Arc::new(Config::parse(path)?);
+			register.insert(config.clone());
+			watches.push(Watch {
+				queue: Arc::new(Queue::new(config.clone(), self.max_queued)),
+				config,
+				register: register.clone(),
+				primary: watches.is_empty(),
+				cleanup: self.cleanup.unwrap(),
+				cleanup_after_reload: self.cleanup_after_reload.unwrap(),
+				delay: Duration::from_secs(self.delay.unwrap()),
+				catch_up: self.catch_up,
+				processed: Arc::new(Mutex::new(HashMap::new())),
+				deferred: Vec::new(),
+			});
+		}
+		Ok(Daemon { watches, register })
+	}
+}
+
+/// Watches every config passed to `watch`, each with its own sources and workers, so that they're reloaded independently
+#[derive(Debug)]
+pub struct Daemon {
+	watches: Vec<Watch>,
+	register: Register,
+}
+
+impl Cmd for Daemon {
+	fn run(self) -> Result<()> {
+		let primary = self.watches[0].config.clone();
+		for watch in self.watches.iter() {
+			// the problems may be fixed while watching, so they don't keep it from starting
+			for problem in preflight::check(&watch.config) {
+				log::warn!("{}", problem);
+			}
+			if !watch.primary {
+				warn_unshared(&primary, &watch.config);
+			}
+		}
+		journal::enable(primary.journal.clone());
+		corrections::check();
+		thumbnails::check();
+		messages::set(&primary.messages);
+		elevation::set(primary.elevation.as_ref());
+		let mut watches = self.watches;
+		if watches.len() == 1 {
+			watches.remove(0).start();
+			return Ok(());
+		}
+		log::info!(
+			"watching {}",
+			self.register
+				.paths()
+				.iter()
+				.map(|path| path.display().to_string())
+				.collect::<Vec<_>>()
+				.join(", ")
+		);
+		let threads = watches
+			.into_iter()
+			.map(|watch| std::thread::spawn(move || watch.start()))
+			.collect::<Vec<_>>();
+		for thread in threads {
+			thread.join().ok();
+		}
+		Ok(())
+	}
+}
+
+/// The journal, messages and elevation are process-wide, so only the first config's are used
+fn warn_unshared(primary: &Config, config: &Config) {
+	let sections = [
+		("journal", primary.journal != config.journal),
+		("messages", primary.messages != config.messages),
+		("elevation", primary.elevation != config.elevation),
+	];
+	for (section, differs) in sections {
+		if differs {
+			log::warn!(
+				"the [{}] of {} is ignored, the one of {} is used for every config",
+				section,
+				config.path.display(),
+				primary.path.display()
+			);
+		}
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct Watch {
+	pub config: Arc<Config>,
+	/// every config being watched, whose locations and constants are shared
+	register: Register,
+	/// whether this is the first config, whose journal, messages and elevation are used for all of them
+	primary: bool,
+	cleanup: bool,
+	cleanup_after_reload: bool,
+	delay: Duration,
+	catch_up: Duration,
+	/// files that have already been processed, and where they were left
+	processed: Arc<Mutex<HashMap<Identity, PathBuf>>>,
+	queue: Arc<Queue>,
+	/// the rules that are waiting for a better time
+	deferred: Vec<usize>,
+}
+
+impl Watch {
+	/// The config without the rules that are waiting for a better time
+	fn active(&self) -> Config {
+		match self.deferred.is_empty() {
+			true => Config::clone(&self.config),
+			false => self.config.without(&self.deferred),
+		}
+	}
+
+	/// Holds back the rules in `deferred`, and catches up on the locations of the ones that don't have to wait anymore
+	fn defer(&mut self, deferred: Vec<(usize, String)>) {
+		let rules: Vec<usize> = deferred.iter().map(|(i, _)| *i).collect();
+		let newly: Vec<(usize, String)> = deferred.into_iter().filter(|(i, _)| !self.deferred.contains(i)).collect();
+		defer::record(&self.config, &newly);
+		let resumed: Vec<usize> = self.deferred.iter().copied().filter(|i| !rules.contains(i)).collect();
+		self.deferred = rules;
+		self.queue.set_config(Arc::new(self.active()));
+		if resumed.is_empty() {
+			return;
+		}
+		for i in resumed.iter() {
+			log::info!("resuming rule {}", self.config.rules[*i].name(*i));
+		}
+		let config = self.config.restrict(&resumed);
+		let run = Run::new(config.clone());
+		for (root, recursive) in config.scan_roots() {
+			run.walk(&root, &recursive);
+		}
+	}
+
+	/// Saves what a location looks like after it was walked, so that what's left in it isn't caught up on after a restart
+	fn save_index(&self, root: &Path) {
+		if let Some(recursive) = self.config.scan_roots().get(root) {
+			if let Err(e) = index::save(root, &Snapshot::scan(root, recursive)) {
+				log::warn!("could not update the index of {}: {:?}", root.display(), e);
+			}
+		}
+	}
+
+	/// Keeps the index up to date with a file that was just processed, so that it isn't caught up on again after a restart
+	fn update_index(config: &Config, paths: &[&Path]) {
+		for path in paths {
+			if let Some(root) = config.scan_roots().keys().find(|root| path.starts_with(root)) {
+				if let Err(e) = index::update(root, path) {
+					log::warn!("could not update the index of {}: {:?}", root.display(), e);
+				}
+			}
+		}
+	}
+
+	fn on_create<T: AsRef<Path>>(&self, config: &Config, path: T) {
+		let path = path.as_ref();
+		let config_parent = config.path.parent().expect("Couldn't find config path");
+		if let Some(parent) = path.parent() {
+			if parent != config_parent && path.is_file() {
+				let identity = Identity::of(path);
+				if let Some(identity) = identity {
+					// claimed right away, so that the other events of the same change skip it
+					match self.processed.lock().unwrap().insert(identity, path.to_path_buf()) {
+						// it's where we left it, e.g. one of our own actions moved it there
+						Some(previous) if previous == path => {
+							log::debug!("{} was already processed, skipping it", path.display());
+							return;
+						}
+						// renamed since, e.g. a finished download, which the rules might apply to now
+						Some(previous) => log::debug!("{} was renamed to {}", previous.display(), path.display()),
+						None => {}
+					}
+				}
+				let file = File::new(path, config, true);
+				let new_path = file.act(&config.path_to_rules);
+				match &new_path {
+					Some(new_path) if new_path != path => Self::update_index(config, &[path, new_path]),
+					_ => Self::update_index(config, &[path]),
+				}
+				let mut processed = self.processed.lock().unwrap();
+				for (identity, path) in [
+					(identity, Some(path.to_path_buf())),
+					(new_path.as_ref().and_then(Identity::of), new_path.clone()),
+				] {
+					if let (Some(identity), Some(path)) = (identity, path) {
+						processed.insert(identity, path);
+					}
+				}
+			}
+		}
+	}
+
+	/// Queues new files, after waiting for `delay` so that they're done being written
+	fn enqueue(&self, paths: Vec<PathBuf>) {
+		match self.delay.is_zero() {
+			true => self.queue.push(paths),
+			false => {
+				let (queue, delay) = (self.queue.clone(), self.delay);
+				std::thread::spawn(move || {
+					std::thread::sleep(delay);
+					queue.push(paths);
+				});
+			}
+		}
+	}
+
+	/// Handles the queued files, taking turns between locations
+	fn spawn_workers(&self) {
+		for _ in 0..WORKERS {
+			let watch = self.clone();
+			std::thread::spawn(move || loop {
+				let batch = watch.queue.pop();
+				let refused = Self::refuse_shortages(&batch);
+				for (path, config) in batch.into_iter().filter(|(path, _)| !refused.contains(path)) {
+					watch.on_create(&config, path);
+				}
+			});
+		}
+	}
+
+	/// The files of `batch` that would be copied or moved to a filesystem without room for all of them, which are left in place
+	fn refuse_shortages(batch: &[(PathBuf, Arc<Config>)]) -> HashSet<PathBuf> {
+		let mut configs: Vec<&Arc<Config>> = Vec::new();
+		for (_, config) in batch {
+			if !configs.iter().any(|other| Arc::ptr_eq(other, config)) {
+				configs.push(config);
+			}
+		}
+		configs
+			.into_iter()
+			.flat_map(|config| {
+				let paths = batch
+					.iter()
+					.filter(|(_, other)| Arc::ptr_eq(other, config))
+					.map(|(path, _)| path.clone())
+					.collect::<Vec<_>>();
+				capacity::refuse(&capacity::check(config, &paths, true))
+			})
+			.collect()
+	}
+
+	fn reload(&mut self, sources: &mut [Box<dyn EventSource>], queue: &Sender<Work>) {
+		match Config::parse(&self.config.path).map(Arc::new) {
+			Ok(new_config) => {
+				self.register.insert(new_config.clone());
+				self.config = new_config;
+				self.deferred.clear();
+				self.queue.set_config(self.config.clone());
+				if self.primary {
+					journal::enable(self.config.journal.clone());
+					messages::set(&self.config.messages);
+					elevation::set(self.config.elevation.as_ref());
+				}
+				log::info!("Reloaded {}", self.config.path.display());
+				for source in sources.iter_mut() {
+					source.start(&self.config, queue);
+				}
+			}
+			Err(e) => log::error!("{:?}", e),
+		}
+	}
+
+	fn handle(&mut self, work: Work, sources: &mut [Box<dyn EventSource>], queue: &Sender<Work>) {
+		match work {
+			Work::Files(paths) => self.enqueue(paths),
+			Work::Scan(root) => {
+				if let Some(recursive) = self.config.scan_roots().get(&root) {
+					Run::new(self.active()).walk(&root, recursive);
+					self.save_index(&root);
+				}
+			}
+			Work::Run => {
+				if let Err(e) = Run::new(self.active()).start() {
+					log::error!("{:?}", e);
+				}
+				for root in self.config.scan_roots().keys() {
+					self.save_index(root);
+				}
+			}
+			Work::Mounted(volume) => Run::new(self.active()).on_mount(&volume),
+			Work::Reload => self.reload(sources, queue),
+			Work::Deferred(deferred) => self.defer(deferred),
+		}
+	}
+
+	/// Carries out the work sent by every source, one piece at a time, ticking the sources that check for work periodically
+	fn start(mut self) {
+		let (tx, rx) = std::sync::mpsc::channel();
+		// the rules that have to wait are known before anything runs
+		let mut sources: Vec<Box<dyn EventSource>> = vec![
+			Box::new(Deferral::default()),
+			Box::new(Startup {
+				cleanup: self.cleanup,
+				cleanup_after_reload: self.cleanup_after_reload,
+				catch_up: self.catch_up,
+				started: false,
+			}),
+			Box::new(FsEvents::default()),
+			Box::new(Polling::default()),
+			Box::new(Mounts::default()),
+		];
+		for source in sources.iter_mut() {
+			source.start(&self.config, &tx);
+		}
+		self.spawn_workers();
+		let mut next_cleanup = Instant::now() + CHECK_INTERVAL;
+
+		loop {
+			let deadline = sources
+				.iter()
+				.filter_map(|source| source.deadline())
+				.fold(next_cleanup, Instant::min);
+			match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+				Ok(work) => self.handle(work, &mut sources, &tx),
+				Err(RecvTimeoutError::Timeout) => {}
+				Err(RecvTimeoutError::Disconnected) => break,
+			}
+			let now = Instant::now();
+			for source in sources.iter_mut() {
+				if source.deadline().is_some_and(|deadline| deadline <= now) {
+					source.tick(&self.config, &tx);
+				}
+			}
+			if now >= next_cleanup {
+				self.processed.lock().unwrap().retain(|_, path| path.exists());
+				match self.queue.depth() {
+					(0, 0) => {}
+					(memory, 0) => log::debug!("{} files waiting", memory),
+					(memory, disk) => log::info!("{} files waiting ({} of them on disk)", memory + disk, disk),
+				}
+				next_cleanup = now + CHECK_INTERVAL;
+			}
+		}
+	}
+}
@@ -0,0 +1,127 @@
+use std::{
+	path::PathBuf,
+	sync::{Arc, Condvar, Mutex},
+};
+
+use organize_core::{
+	config::Config,
+	queue::{spill, FairQueue},
+};
+
+/// New files waiting to be handled by the workers, grouped by location.
+/// At most `capacity` of them are kept in memory, the rest are spilled to disk until there's room for them again,
+/// so that a burst of events that the actions can't keep up with doesn't grow the daemon's memory without bounds.
+#[derive(Debug)]
+pub struct Queue {
+	pending: Mutex<Pending>,
+	ready: Condvar,
+	capacity: usize,
+}
+
+#[derive(Debug)]
+struct Pending {
+	files: FairQueue<PathBuf, (PathBuf, Arc<Config>)>,
+	/// the config new files are handled with
+	config: Arc<Config>,
+	/// how many files are waiting on disk
+	spilled: usize,
+}
+
+impl Pending {
+	fn push(&mut self, path: PathBuf) {
+		let (location, priority) = match self.config.location_of(&path) {
+			Some((location, priority)) => (location.to_path_buf(), priority),
+			None => (PathBuf::new(), 1),
+		};
+		self.files.push(location, priority, (path, self.config.clone()));
+	}
+}
+
+impl Queue {
+	pub fn new(config: Arc<Config>, capacity: usize) -> Self {
+		// files spilled by a previous run that didn't get to them
+		let spilled = spill::len(&config.path).unwrap_or_else(|e| {
+			log::warn!("could not read the files spilled to disk: {:?}", e);
+			0
+		});
+		Self {
+			pending: Mutex::new(Pending {
+				files: FairQueue::default(),
+				config,
+				spilled,
+			}),
+			ready: Condvar::new(),
+			capacity: capacity.max(1),
+		}
+	}
+
+	pub fn set_config(&self, config: Arc<Config>) {
+		self.pending.lock().unwrap().config = config;
+	}
+
+	pub fn push(&self, paths: Vec<PathBuf>) {
+		let mut pending = self.pending.lock().unwrap();
+		let room = self.capacity.saturating_sub(pending.files.len());
+		let mut paths = paths.into_iter();
+		for path in paths.by_ref().take(room) {
+			pending.push(path);
+		}
+		let overflow: Vec<PathBuf> = paths.collect();
+		if !overflow.is_empty() {
+			let config = pending.config.path.clone();
+			match spill::push(&config, &overflow) {
+				Ok(_) => {
+					if pending.spilled == 0 {
+						log::warn!(
+							"more than {} files are waiting, keeping the rest on disk until there's room",
+							self.capacity
+						);
+					}
+					pending.spilled += overflow.len();
+				}
+				Err(e) => {
+					// better to use more memory than to lose files
+					log::error!("could not spill the queue to disk: {:?}", e);
+					for path in overflow {
+						pending.push(path);
+					}
+				}
+			}
+		}
+		self.ready.notify_all();
+	}
+
+	/// The next files to handle, waiting until there are some
+	pub fn pop(&self) -> Vec<(PathBuf, Arc<Config>)> {
+		let mut pending = self.pending.lock().unwrap();
+		loop {
+			if pending.spilled > 0 && pending.files.len() <= self.capacity / 2 {
+				match spill::pop(&pending.config.path.clone(), self.capacity - pending.files.len()) {
+					Ok(paths) => {
+						pending.spilled = pending.spilled.saturating_sub(paths.len());
+						if paths.is_empty() {
+							pending.spilled = 0;
+						}
+						for path in paths {
+							pending.push(path);
+						}
+					}
+					Err(e) => {
+						log::error!("could not read the files spilled to disk: {:?}", e);
+						pending.spilled = 0;
+					}
+				}
+			}
+			if let Some(batch) = pending.files.pop() {
+				return batch;
+			}
+			pending = self.ready.wait(pending).unwrap();
+		}
+	}
+
+	/// How many files are waiting in memory and on disk
+	pub fn depth(&self) -> (usize, usize) {
+		let pending = self.pending.lock().unwrap();
+		(pending.files.len(), pending.spilled)
+	}
+}
@@ -0,0 +1,358 @@
+use std::{
+	collections::{HashMap, HashSet},
+	path::{Path, PathBuf},
+	sync::{mpsc::Sender, Mutex},
+	time::{Duration, Instant},
+};
+
+use lazy_static::lazy_static;
+use notify::{
+	event::{ModifyKind, RenameMode},
+	Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+
+use organize_core::{
+	config::Config,
+	index::{self, Snapshot},
+	mount::{self, Volume},
+};
+
+/// How often watched locations are checked for availability (e.g. network shares that were unmounted), and volumes for new mounts
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Something the daemon has to do, sent by an [`EventSource`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Work {
+	/// run the rules on these new files
+	Files(Vec<PathBuf>),
+	/// walk a whole location, e.g. because it came back after being unavailable
+	Scan(PathBuf),
+	/// run every rule on every location
+	Run,
+	/// run the rules for a volume that was just mounted
+	Mounted(Volume),
+	/// the config file changed
+	Reload,
+	/// the rules that have to wait right now, with the reason why
+	Deferred(Vec<(usize, String)>),
+}
+
+/// Something that finds work for the daemon, either on its own threads or when it's ticked by the scheduler.
+/// Sources only send [`Work`] to the queue, so adding a new trigger doesn't require any change to how the work is carried out.
+pub trait EventSource {
+	/// Sets the source up for the locations in `config`, when the daemon starts and again whenever the config is reloaded
+	fn start(&mut self, config: &Config, queue: &Sender<Work>);
+
+	/// When the source next needs to be ticked, if it checks for work periodically
+	fn deadline(&self) -> Option<Instant> {
+		None
+	}
+
+	fn tick(&mut self, _config: &Config, _queue: &Sender<Work>) {}
+}
+
+lazy_static! {
+	/// where the filesystem of each location was mounted the last time it was available, if not on `/`
+	static ref MOUNTS: Mutex<HashMap<PathBuf, PathBuf>> = Mutex::new(HashMap::new());
+}
+
+/// Whether `path` can be read and the filesystem it's on is mounted.
+/// A share that was unmounted leaves behind the empty directory it was mounted over, which would look like an empty location.
+fn is_available<T: AsRef<Path>>(path: T) -> bool {
+	let path = path.as_ref();
+	if path.read_dir().is_err() {
+		return false;
+	}
+	let mut mounts = MOUNTS.lock().unwrap_or_else(|e| e.into_inner());
+	if mount::is_unmounted(path, mounts.get(path).map(PathBuf::as_path)) {
+		return false;
+	}
+	if let Some(mount_point) = mount::mount_point(path).filter(|mount_point| mount_point.parent().is_some()) {
+		mounts.insert(path.to_path_buf(), mount_point);
+	}
+	true
+}
+
+/// Runs every rule when the daemon starts (and after reloads, if asked to), or otherwise catches up on
+/// the files that appeared while it wasn't running
+pub struct Startup {
+	pub cleanup: bool,
+	pub cleanup_after_reload: bool,
+	/// how far back to look for files that appeared while nothing was watching
+	pub catch_up: Duration,
+	pub started: bool,
+}
+
+impl EventSource for Startup {
+	fn start(&mut self, config: &Config, queue: &Sender<Work>) {
+		let started = std::mem::replace(&mut self.started, true);
+		let cleanup = match started {
+			true => self.cleanup_after_reload,
+			false => self.cleanup,
+		};
+		if cleanup {
+			// the index is saved once the run is done
+			let _ = queue.send(Work::Run);
+			return;
+		}
+		for (root, recursive) in config.scan_roots() {
+			if !is_available(&root) {
+				continue;
+			}
+			let current = Snapshot::scan(&root, &recursive);
+			let result = index::load(&root).and_then(|previous| {
+				// there's nothing to catch up on in a location that was never watched
+				let previous = previous.filter(|_| !started && !self.catch_up.is_zero());
+				if let Some(previous) = previous {
+					let added = previous.added(&current, Some(self.catch_up));
+					if !added.is_empty() {
+						log::info!("catching up on {} files that appeared in {}", added.len(), root.display());
+						let _ = queue.send(Work::Files(added));
+					}
+				}
+				index::save(&root, &current)
+			});
+			if let Err(e) = result {
+				log::warn!("could not update the index of {}: {:?}", root.display(), e);
+			}
+		}
+	}
+}
+
+/// Filesystem events for the locations that use the native strategy, and for the config file.
+/// Locations that become unavailable are paused, and walked again once they come back.
+#[derive(Default)]
+pub struct FsEvents {
+	watcher: Option<RecommendedWatcher>,
+	/// locations that are currently paused because they can't be read
+	unavailable: HashSet<PathBuf>,
+	next: Option<Instant>,
+}
+
+impl FsEvents {
+	fn handler(config: PathBuf, queue: Sender<Work>) -> impl FnMut(notify::Result<Event>) + Send + 'static {
+		move |res: notify::Result<Event>| {
+			let event = match res {
+				Ok(event) => event,
+				Err(_) => return,
+			};
+			let work = match event.kind {
+				EventKind::Create(_) => Work::Files(event.paths),
+				EventKind::Modify(ModifyKind::Name(RenameMode::To | RenameMode::Both)) => {
+					// for `Both`, the first path is where the file came from
+					let to: Vec<PathBuf> = event.paths.into_iter().rev().take(1).collect();
+					match to.iter().any(|p| p == &config) {
+						true => Work::Reload,
+						false => Work::Files(to),
+					}
+				}
+				EventKind::Modify(_) if event.paths.iter().any(|p| p == &config) => Work::Reload,
+				_ => return,
+			};
+			let _ = queue.send(work);
+		}
+	}
+}
+
+impl EventSource for FsEvents {
+	fn start(&mut self, config: &Config, queue: &Sender<Work>) {
+		let mut watcher = match RecommendedWatcher::new(Self::handler(config.path.clone(), queue.clone()), notify::Config::default()) {
+			Ok(watcher) => watcher,
+			Err(e) => {
+				log::error!("could not start watching: {:?}", e);
+				return;
+			}
+		};
+		let polls = config.poll_roots();
+		for (folder, recursive) in config.scan_roots().iter() {
+			if !is_available(folder) {
+				if self.unavailable.insert(folder.clone()) {
+					log::warn!("{} is not available, pausing it until it comes back", folder.display());
+				}
+				continue;
+			}
+			self.unavailable.remove(folder);
+			if polls.contains_key(folder) {
+				continue;
+			}
+			if let Err(e) = watcher.watch(folder, recursive.type_()) {
+				log::error!("could not watch {}: {:?}", folder.display(), e);
+			}
+		}
+		if let Some(parent) = config.path.parent() {
+			if let Err(e) = watcher.watch(parent, RecursiveMode::NonRecursive) {
+				log::error!("could not watch {}: {:?}", parent.display(), e);
+			}
+		}
+		self.watcher = Some(watcher);
+		self.next = Some(Instant::now() + CHECK_INTERVAL);
+	}
+
+	fn deadline(&self) -> Option<Instant> {
+		self.next
+	}
+
+	/// Pauses locations that disappeared and resumes the ones that came back, catching up on what was missed
+	fn tick(&mut self, config: &Config, queue: &Sender<Work>) {
+		self.next = Some(Instant::now() + CHECK_INTERVAL);
+		let polls = config.poll_roots();
+		let mut resumed = Vec::new();
+		for folder in config.scan_roots().keys().filter(|folder| !polls.contains_key(*folder)) {
+			let available = is_available(folder);
+			let paused = self.unavailable.contains(folder);
+			if !available && !paused {
+				log::warn!("{} is no longer available, pausing it until it comes back", folder.display());
+				self.unavailable.insert(folder.clone());
+				if let Some(watcher) = self.watcher.as_mut() {
+					let _ = watcher.unwatch(folder);
+				}
+			} else if available && paused {
+				log::info!("{} is available again, resuming", folder.display());
+				resumed.push(folder.clone());
+			}
+		}
+		if !resumed.is_empty() {
+			self.start(config, queue);
+			for folder in resumed {
+				let _ = queue.send(Work::Scan(folder));
+			}
+		}
+	}
+}
+
+/// Locations that use the `poll` strategy, rescanned every `poll_interval`
+#[derive(Default)]
+pub struct Polling {
+	polls: HashMap<PathBuf, Poll>,
+}
+
+struct Poll {
+	interval: Duration,
+	next: Instant,
+	/// the files found in the last scan, if the location could be read
+	snapshot: Option<Snapshot>,
+}
+
+impl EventSource for Polling {
+	fn start(&mut self, config: &Config, _queue: &Sender<Work>) {
+		let roots = config.scan_roots();
+		self.polls = config
+			.poll_roots()
+			.into_iter()
+			.map(|(folder, interval)| {
+				let poll = Poll {
+					interval,
+					next: Instant::now() + interval,
+					snapshot: is_available(&folder).then(|| Snapshot::scan(&folder, &roots[&folder])),
+				};
+				(folder, poll)
+			})
+			.collect();
+	}
+
+	fn deadline(&self) -> Option<Instant> {
+		self.polls.values().map(|poll| poll.next).min()
+	}
+
+	/// Rescans the locations that are due, and sends the files that appeared since their last scan as new ones
+	fn tick(&mut self, config: &Config, queue: &Sender<Work>) {
+		let now = Instant::now();
+		let roots = config.scan_roots();
+		let mut added = Vec::new();
+		for (folder, poll) in self.polls.iter_mut() {
+			// a location that can't be read would look empty, and then everything in it would look new once it's back
+			if poll.next > now || !is_available(folder) {
+				continue;
+			}
+			poll.next = now + poll.interval;
+			let current = Snapshot::scan(folder, &roots[folder]);
+			match &poll.snapshot {
+				Some(snapshot) if *snapshot == current => continue,
+				Some(snapshot) => added.extend(snapshot.added(&current, None)),
+				// the first scan since the location became available is only a baseline
+				None => {}
+			}
+			if let Err(e) = index::save(folder, &current) {
+				log::warn!("could not update the index of {}: {:?}", folder.display(), e);
+			}
+			poll.snapshot = Some(current);
+		}
+		if !added.is_empty() {
+			let _ = queue.send(Work::Files(added));
+		}
+	}
+}
+
+/// Volumes that were mounted since the last check
+pub struct Mounts {
+	/// volumes that were mounted the last time we checked
+	volumes: HashSet<Volume>,
+	next: Instant,
+}
+
+impl Default for Mounts {
+	fn default() -> Self {
+		Self {
+			volumes: Volume::mounted().into_iter().collect(),
+			next: Instant::now() + CHECK_INTERVAL,
+		}
+	}
+}
+
+impl EventSource for Mounts {
+	fn start(&mut self, _config: &Config, _queue: &Sender<Work>) {}
+
+	fn deadline(&self) -> Option<Instant> {
+		Some(self.next)
+	}
+
+	fn tick(&mut self, _config: &Config, queue: &Sender<Work>) {
+		self.next = Instant::now() + CHECK_INTERVAL;
+		let volumes: HashSet<Volume> = Volume::mounted().into_iter().collect();
+		for volume in volumes.difference(&self.volumes) {
+			let _ = queue.send(Work::Mounted(volume.clone()));
+		}
+		self.volumes = volumes;
+	}
+}
+
+/// Rules held back by their `defer_if`, checked again every [`CHECK_INTERVAL`]
+pub struct Deferral {
+	/// the rules that were waiting at the last check, unknown until the first one
+	deferred: Option<Vec<usize>>,
+	next: Instant,
+}
+
+impl Default for Deferral {
+	fn default() -> Self {
+		Self {
+			deferred: None,
+			next: Instant::now(),
+		}
+	}
+}
+
+impl EventSource for Deferral {
+	fn start(&mut self, config: &Config, queue: &Sender<Work>) {
+		// the rules may be numbered differently after a reload
+		self.deferred = None;
+		self.tick(config, queue);
+	}
+
+	fn deadline(&self) -> Option<Instant> {
+		Some(self.next)
+	}
+
+	fn tick(&mut self, config: &Config, queue: &Sender<Work>) {
+		self.next = Instant::now() + CHECK_INTERVAL;
+		if !config.defers() {
+			return;
+		}
+		let deferred = config.deferred();
+		let rules: Vec<usize> = deferred.iter().map(|(i, _)| *i).collect();
+		if self.deferred.as_ref() != Some(&rules) {
+			self.deferred = Some(rules);
+			let _ = queue.send(Work::Deferred(deferred));
+		}
+	}
+}
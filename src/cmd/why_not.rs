@@ -0,0 +1,46 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use colored::Colorize;
+
+use organize_core::{config::Config, file::File};
+
+use crate::cmd::Cmd;
+
+/// Shows every check a file goes through for a single rule, and which ones it fails
+#[derive(Parser, Debug)]
+pub struct WhyNot {
+	#[arg(long, short = 'c')]
+	config: Option<PathBuf>,
+	/// the id of the rule, or its position in the config if it doesn't have one
+	#[arg(long)]
+	id: String,
+	path: PathBuf,
+}
+
+impl Cmd for WhyNot {
+	fn run(self) -> Result<()> {
+		let config = Config::parse(match &self.config {
+			Some(config) => config.clone(),
+			None => Config::path()?,
+		})?;
+		let rule = config
+			.rules
+			.iter()
+			.position(|rule| rule.id.as_deref() == Some(self.id.as_str()))
+			.or_else(|| self.id.parse::<usize>().ok().filter(|i| *i < config.rules.len()))
+			.ok_or_else(|| anyhow!("there is no rule with id `{}`", self.id))?;
+		let path = self.path.canonicalize().unwrap_or(self.path);
+
+		let file = File::new(&path, &config, false);
+		for gate in file.gates(rule) {
+			let status = match gate.passed {
+				true => "pass".green(),
+				false => "fail".red(),
+			};
+			println!("[{}] {}: {}", status, gate.name.bold(), gate.value);
+		}
+		Ok(())
+	}
+}
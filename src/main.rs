@@ -6,5 +6,6 @@ fn main() {
 	let app: App = App::parse();
 	if let Err(e) = app.run() {
 		log::error!("{:?}", e);
+		std::process::exit(1);
 	}
 }